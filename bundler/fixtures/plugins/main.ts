@@ -0,0 +1,5 @@
+import config from "./config.yaml";
+import { routes } from "virtual:routes";
+
+const version: string = "__VERSION__";
+console.log(config.name, config.port, routes, version);
@@ -0,0 +1,6 @@
+import answer from "data:text/javascript,export%20default%2042;";
+import config from "data:application/json,{\"name\":\"inline\"}";
+
+export default function () {
+  return `${config.name}: ${answer}`;
+}
@@ -0,0 +1,7 @@
+import config from './data/config.json';
+
+async function main(): Promise<string> {
+  return `${config.name}@${config.version}`;
+}
+
+export default main;
@@ -0,0 +1,7 @@
+import { execute } from '../lib.ts';
+
+async function main() {
+  return await execute('snapshot');
+}
+
+export default main;
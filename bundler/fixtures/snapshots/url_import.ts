@@ -0,0 +1,7 @@
+import { greet } from 'https://example.com/greet.js';
+
+async function main(name: string): Promise<string> {
+  return greet(name);
+}
+
+export default main;
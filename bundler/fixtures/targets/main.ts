@@ -0,0 +1,5 @@
+import { storage } from './storage.ts';
+
+export default function main() {
+  return storage();
+}
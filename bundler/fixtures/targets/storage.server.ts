@@ -0,0 +1,3 @@
+export function storage(): string {
+  return `server storage on ${import.meta.target}`;
+}
@@ -0,0 +1,183 @@
+use base64::prelude::*;
+use std::env;
+use std::fs;
+use url::Url;
+
+/// Environment variable holding `token@host` or `user:password@host` entries
+/// separated by `;`.
+pub const AUTH_TOKENS_ENV: &str = "DINO_AUTH_TOKENS";
+
+/// Credentials sent when downloading URL imports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    Bearer(String),
+    Basic { username: String, password: String },
+}
+
+/// A credential applied to hosts matching `pattern` (`example.com`,
+/// `*.example.com` or `example.com:8080`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostAuth {
+    pub pattern: String,
+    pub credential: Credential,
+}
+
+/// Per-host credentials for URL imports.
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    entries: Vec<HostAuth>,
+}
+
+impl Credential {
+    /// Returns the value of the `Authorization` header.
+    pub fn header_value(&self) -> String {
+        match self {
+            Credential::Bearer(token) => format!("Bearer {token}"),
+            Credential::Basic { username, password } => {
+                let encoded = BASE64_STANDARD.encode(format!("{username}:{password}"));
+                format!("Basic {encoded}")
+            }
+        }
+    }
+}
+
+impl AuthConfig {
+    /// Loads credentials from `DINO_AUTH_TOKENS` and `~/.netrc`, the former
+    /// taking precedence.
+    pub fn from_env() -> Self {
+        let mut config = env::var(AUTH_TOKENS_ENV)
+            .map(|v| Self::parse_tokens(&v))
+            .unwrap_or_default();
+        let netrc = dirs::home_dir().and_then(|home| fs::read_to_string(home.join(".netrc")).ok());
+        if let Some(netrc) = netrc {
+            config.entries.extend(Self::parse_netrc(&netrc).entries);
+        }
+        config
+    }
+
+    /// Parses `token@host;user:password@host` entries.
+    pub fn parse_tokens(value: &str) -> Self {
+        let entries = value
+            .split(';')
+            .filter_map(|entry| {
+                let (secret, host) = entry.trim().rsplit_once('@')?;
+                let credential = match secret.split_once(':') {
+                    Some((username, password)) => Credential::Basic {
+                        username: username.into(),
+                        password: password.into(),
+                    },
+                    None => Credential::Bearer(secret.into()),
+                };
+                Some(HostAuth {
+                    pattern: host.into(),
+                    credential,
+                })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Parses the `machine`/`login`/`password` entries of a netrc file.
+    pub fn parse_netrc(content: &str) -> Self {
+        let mut entries = vec![];
+        let mut machine: Option<String> = None;
+        let mut login = None;
+        let mut password = None;
+        let mut flush = |machine: &mut Option<String>,
+                         login: &mut Option<String>,
+                         password: &mut Option<String>| {
+            if let (Some(host), Some(username), Some(password)) =
+                (machine.take(), login.take(), password.take())
+            {
+                entries.push(HostAuth {
+                    pattern: host,
+                    credential: Credential::Basic { username, password },
+                });
+            }
+        };
+
+        let mut tokens = content.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" => {
+                    flush(&mut machine, &mut login, &mut password);
+                    machine = tokens.next().map(String::from);
+                }
+                "default" => flush(&mut machine, &mut login, &mut password),
+                "login" => login = tokens.next().map(String::from),
+                "password" => password = tokens.next().map(String::from),
+                _ => {}
+            }
+        }
+        flush(&mut machine, &mut login, &mut password);
+        Self { entries }
+    }
+
+    /// Adds a credential for hosts matching `pattern`.
+    pub fn with(mut self, pattern: impl Into<String>, credential: Credential) -> Self {
+        self.entries.push(HostAuth {
+            pattern: pattern.into(),
+            credential,
+        });
+        self
+    }
+
+    /// Finds the first credential matching the URL's host.
+    pub fn lookup(&self, url: &str) -> Option<&Credential> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?;
+        let host_port = url.port().map(|port| format!("{host}:{port}"));
+        self.entries
+            .iter()
+            .find(|entry| {
+                let pattern = entry.pattern.as_str();
+                match pattern.strip_prefix("*.") {
+                    Some(domain) => host.ends_with(&format!(".{domain}")),
+                    None => pattern == host || Some(pattern) == host_port.as_deref(),
+                }
+            })
+            .map(|entry| &entry.credential)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_config_should_match_hosts() {
+        let config =
+            AuthConfig::parse_tokens("abc@example.com; user:pw@*.corp.io;x@localhost:8080")
+                .with("deno.land", Credential::Bearer("t".into()));
+
+        assert_eq!(
+            config.lookup("https://example.com/mod.ts"),
+            Some(&Credential::Bearer("abc".into()))
+        );
+        assert_eq!(
+            config
+                .lookup("https://npm.corp.io/a.js")
+                .map(|c| c.header_value()),
+            Some("Basic dXNlcjpwdw==".into())
+        );
+        assert!(config.lookup("https://corp.io/a.js").is_none());
+        assert!(config.lookup("http://localhost:8080/a.js").is_some());
+        assert!(config.lookup("http://localhost/a.js").is_none());
+        assert!(config.lookup("https://deno.land/x/a.ts").is_some());
+    }
+
+    #[test]
+    fn parse_netrc_should_work() {
+        let config = AuthConfig::parse_netrc(
+            "machine example.com login alice password secret\ndefault login anon password x",
+        );
+        assert_eq!(
+            config.lookup("https://example.com/a.js"),
+            Some(&Credential::Basic {
+                username: "alice".into(),
+                password: "secret".into()
+            })
+        );
+        assert_eq!(config.entries.len(), 1);
+    }
+}
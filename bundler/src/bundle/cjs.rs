@@ -0,0 +1,316 @@
+use anyhow::{Result, anyhow};
+use indexmap::IndexSet;
+use swc_common::FileName;
+use swc_common::SourceMap;
+use swc_common::sync::Lrc;
+use swc_ecma_ast::*;
+use swc_ecma_parser::EsSyntax;
+use swc_ecma_parser::Syntax;
+use swc_ecma_parser::parse_file_as_module;
+use swc_ecma_visit::Visit;
+use swc_ecma_visit::VisitMut;
+use swc_ecma_visit::VisitMutWith;
+use swc_ecma_visit::VisitWith;
+
+/// Export set on wrapped modules, so `require()` of a wrapped module returns
+/// its `module.exports` while `require()` of an ES module returns the namespace.
+const CJS_MARKER: &str = "__cjsModule";
+
+/// Placeholder statement replaced with the body of the CommonJS module.
+const BODY: &str = "__cjs_body";
+
+/// What a CommonJS module uses, collected before wrapping it.
+#[derive(Debug, Default)]
+struct Usage {
+    /// Uses `module`, `exports` or `require`.
+    commonjs: bool,
+    /// Specifiers of `require("...")` calls with a string literal.
+    requires: IndexSet<String>,
+    /// Names assigned with `exports.x = ...` or `module.exports = { x }`,
+    /// re-exported as ES named exports.
+    exports: IndexSet<String>,
+}
+
+/// Wraps a CommonJS module into an ES module when it has no import or export
+/// declarations and uses `module`, `exports` or `require`.
+///
+/// `require()` with a string literal becomes a static import, other calls
+/// throw at runtime. The ES module default-exports `module.exports`.
+pub fn wrap(cm: &Lrc<SourceMap>, module: Module) -> Result<Module> {
+    if module.body.iter().any(|item| item.is_module_decl()) {
+        return Ok(module);
+    }
+    let mut usage = Usage::default();
+    module.visit_with(&mut usage);
+    if !usage.commonjs {
+        return Ok(module);
+    }
+
+    let mut wrapper = String::new();
+    let mut deps = vec![];
+    for (i, specifier) in usage.requires.iter().enumerate() {
+        let specifier = serde_json::to_string(specifier)?;
+        wrapper.push_str(&format!("import * as __cjs_dep{i} from {specifier};\n"));
+        deps.push(format!("{specifier}: __cjs_dep{i}"));
+    }
+    wrapper.push_str(&format!(
+        r#"const __cjs_deps = {{ {deps} }};
+function __cjs_require(id) {{
+  const ns = __cjs_deps[id];
+  if (!ns) throw new Error(`Cannot find module '${{id}}'`);
+  return ns.{CJS_MARKER} ? ns.default : ns;
+}}
+const module = {{ exports: {{}} }};
+(function (module, exports, require) {{
+  {BODY};
+}}).call(module.exports, module, module.exports, __cjs_require);
+export const {CJS_MARKER} = true;
+export default module.exports;
+"#,
+        deps = deps.join(", ")
+    ));
+    if !usage.exports.is_empty() {
+        let names: Vec<_> = usage.exports.iter().map(String::as_str).collect();
+        wrapper.push_str(&format!(
+            "export const {{ {} }} = module.exports;\n",
+            names.join(", ")
+        ));
+    }
+
+    let fm = cm.new_source_file(FileName::Anon.into(), wrapper);
+    let mut wrapped = parse_file_as_module(
+        &fm,
+        Syntax::Es(EsSyntax::default()),
+        EsVersion::latest(),
+        None,
+        &mut vec![],
+    )
+    .map_err(|e| anyhow!("failed to wrap CommonJS module: {:?}", e.kind()))?;
+
+    let body = module
+        .body
+        .into_iter()
+        .filter_map(|item| item.stmt())
+        .collect();
+    wrapped.visit_mut_with(&mut Splice { body: Some(body) });
+    Ok(wrapped)
+}
+
+impl Visit for Usage {
+    fn visit_ident(&mut self, ident: &Ident) {
+        if matches!(&*ident.sym, "module" | "exports" | "require") {
+            self.commonjs = true;
+        }
+    }
+
+    fn visit_call_expr(&mut self, call: &CallExpr) {
+        if let Callee::Expr(callee) = &call.callee
+            && let Expr::Ident(ident) = &**callee
+            && ident.sym == *"require"
+            && let [arg] = call.args.as_slice()
+            && let Expr::Lit(Lit::Str(specifier)) = &*arg.expr
+        {
+            self.requires.insert(specifier.value.to_string());
+        }
+        call.visit_children_with(self);
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignExpr) {
+        if let AssignTarget::Simple(SimpleAssignTarget::Member(member)) = &assign.left {
+            match exports_path(member) {
+                // exports.x = ... / module.exports.x = ...
+                Some(ExportsPath::Property(name)) => {
+                    self.exports.insert(name);
+                }
+                // module.exports = { x, y: ... }
+                Some(ExportsPath::Object) => {
+                    if let Expr::Object(object) = &*assign.right {
+                        self.exports
+                            .extend(object.props.iter().filter_map(prop_name));
+                    }
+                }
+                None => {}
+            }
+        }
+        assign.visit_children_with(self);
+    }
+}
+
+enum ExportsPath {
+    Object,
+    Property(String),
+}
+
+fn is_ident(expr: &Expr, name: &str) -> bool {
+    matches!(expr, Expr::Ident(ident) if ident.sym == *name)
+}
+
+fn is_module_exports(expr: &Expr) -> bool {
+    matches!(expr, Expr::Member(MemberExpr { obj, prop: MemberProp::Ident(prop), .. })
+        if is_ident(obj, "module") && prop.sym == *"exports")
+}
+
+fn exports_path(member: &MemberExpr) -> Option<ExportsPath> {
+    let MemberProp::Ident(prop) = &member.prop else {
+        return None;
+    };
+    if is_ident(&member.obj, "module") && prop.sym == *"exports" {
+        return Some(ExportsPath::Object);
+    }
+    if is_ident(&member.obj, "exports") || is_module_exports(&member.obj) {
+        return valid_export(&prop.sym).then(|| ExportsPath::Property(prop.sym.to_string()));
+    }
+    None
+}
+
+fn prop_name(prop: &PropOrSpread) -> Option<String> {
+    let name = match prop.as_prop()?.as_ref() {
+        Prop::Shorthand(ident) => ident.sym.to_string(),
+        Prop::KeyValue(KeyValueProp {
+            key: PropName::Ident(key),
+            ..
+        }) => key.sym.to_string(),
+        Prop::Method(MethodProp {
+            key: PropName::Ident(key),
+            ..
+        }) => key.sym.to_string(),
+        _ => return None,
+    };
+    valid_export(&name).then_some(name)
+}
+
+/// Names that can't be declared with `export const`.
+fn valid_export(name: &str) -> bool {
+    !matches!(name, "default" | "__esModule" | CJS_MARKER) && !is_reserved(name)
+}
+
+fn is_reserved(name: &str) -> bool {
+    matches!(
+        name,
+        "break"
+            | "case"
+            | "catch"
+            | "class"
+            | "const"
+            | "continue"
+            | "debugger"
+            | "delete"
+            | "do"
+            | "else"
+            | "enum"
+            | "export"
+            | "extends"
+            | "false"
+            | "finally"
+            | "for"
+            | "function"
+            | "if"
+            | "import"
+            | "in"
+            | "instanceof"
+            | "new"
+            | "null"
+            | "return"
+            | "super"
+            | "switch"
+            | "this"
+            | "throw"
+            | "true"
+            | "try"
+            | "typeof"
+            | "var"
+            | "void"
+            | "while"
+            | "with"
+            | "yield"
+            | "let"
+            | "static"
+            | "implements"
+            | "interface"
+            | "package"
+            | "private"
+            | "protected"
+            | "public"
+            | "await"
+            | "arguments"
+            | "eval"
+    )
+}
+
+/// Replaces the placeholder statement with the original module body.
+struct Splice {
+    body: Option<Vec<Stmt>>,
+}
+
+impl VisitMut for Splice {
+    fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        let placeholder = stmts.iter().position(
+            |stmt| matches!(stmt, Stmt::Expr(ExprStmt { expr, .. }) if is_ident(expr, BODY)),
+        );
+        match (placeholder, self.body.take()) {
+            (Some(i), Some(body)) => {
+                stmts.splice(i..=i, body);
+            }
+            (_, body) => {
+                self.body = body;
+                stmts.visit_mut_children_with(self);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swc_common::FilePathMapping;
+    use swc_ecma_codegen::Emitter;
+    use swc_ecma_codegen::text_writer::JsWriter;
+
+    fn wrap_source(source: &str) -> Result<String> {
+        let cm: Lrc<SourceMap> = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+        let fm = cm.new_source_file(FileName::Anon.into(), source.to_string());
+        let module = parse_file_as_module(
+            &fm,
+            Syntax::Es(EsSyntax::default()),
+            EsVersion::latest(),
+            None,
+            &mut vec![],
+        )
+        .map_err(|e| anyhow!("{:?}", e.kind()))?;
+        let module = wrap(&cm, module)?;
+
+        let mut buf = vec![];
+        let mut emitter = Emitter {
+            cfg: swc_ecma_codegen::Config::default().with_minify(true),
+            cm: cm.clone(),
+            comments: None,
+            wr: JsWriter::new(cm, "\n", &mut buf, None),
+        };
+        emitter.emit_module(&module)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn wrap_should_convert_commonjs_to_esm() -> Result<()> {
+        let ret = wrap_source(
+            r#"
+            const { join } = require("./path.js");
+            exports.hello = (name) => join("hello", name);
+            module.exports.version = "1.0";
+            "#,
+        )?;
+        assert!(ret.contains(r#"import*as __cjs_dep0 from"./path.js""#));
+        assert!(ret.contains(r#"const{join}=require("./path.js")"#));
+        assert!(ret.contains("export default module.exports"));
+        assert!(ret.contains("export const{hello,version}=module.exports"));
+
+        let ret = wrap_source("module.exports = { a, b: 1, c() {}, default: 2 };")?;
+        assert!(ret.contains("export const{a,b,c}=module.exports"));
+
+        // ES modules and plain scripts are left alone.
+        let source = "export const a = require;";
+        assert_eq!(wrap_source(source)?, "export const a=require;");
+        assert_eq!(wrap_source("console.log(1);")?, "console.log(1);");
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use super::loaders::ModuleLoader;
+use super::modules::ModulePath;
+use super::modules::ModuleSource;
+use anyhow::Result;
+use anyhow::bail;
+use std::collections::HashMap;
+
+/// Core modules that may be imported with a bare specifier (`import fs from "fs"`).
+pub static CORE_MODULES: &[&str] = &["fs", "net", "dns", "http"];
+
+/// Prefix of resolved core module specifiers.
+const CORE_PREFIX: &str = "core:";
+
+/// Prefix of runtime specific modules (`import { format } from "dino:time"`).
+const DINO_PREFIX: &str = "dino:";
+
+/// Core modules backed by the target runtime, mapped to their exported names.
+pub type HostModules = HashMap<String, Vec<String>>;
+
+/// Describes what the runtime executing the bundle supports, so imports it
+/// can't satisfy fail the build instead of the worker.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Runtime name used in error messages.
+    pub runtime: String,
+    /// Core modules backed by host ops.
+    pub core_modules: HostModules,
+    /// Host globals (`print`, `structuredClone`...) available to handlers.
+    pub globals: Vec<String>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            runtime: "target".into(),
+            core_modules: HostModules::default(),
+            globals: vec![],
+        }
+    }
+}
+
+impl Capabilities {
+    /// Checks if `specifier` is a core module provided by the runtime.
+    pub fn supports_module(&self, specifier: &str) -> bool {
+        let name = specifier.trim_start_matches(CORE_PREFIX);
+        self.core_modules.contains_key(name)
+    }
+
+    /// Checks if the runtime provides the global `name`.
+    pub fn supports_global(&self, name: &str) -> bool {
+        self.globals.iter().any(|g| g == name)
+    }
+}
+
+/// Returns the resolved specifier if `specifier` names a core module.
+pub(crate) fn core_specifier(specifier: &str) -> Option<ModulePath> {
+    core_module_name(specifier).map(|name| format!("{CORE_PREFIX}{name}"))
+}
+
+/// Returns the name of the core module `specifier` refers to, if any.
+pub fn core_module_name(specifier: &str) -> Option<&str> {
+    if let Some(name) = specifier
+        .strip_prefix(CORE_PREFIX)
+        .or_else(|| specifier.strip_prefix(DINO_PREFIX))
+    {
+        return Some(name);
+    }
+    CORE_MODULES.contains(&specifier).then_some(specifier)
+}
+
+/// Loader generating shims that forward core modules to the host ops the
+/// runtime installs on `globalThis.__dino_host`.
+pub struct CoreModuleLoader {
+    pub capabilities: Capabilities,
+}
+
+impl ModuleLoader for CoreModuleLoader {
+    fn resolve(&self, _: Option<&str>, specifier: &str) -> Result<ModulePath> {
+        match core_specifier(specifier) {
+            Some(path) => Ok(path),
+            None => bail!(format!("Module not found \"{specifier}\"")),
+        }
+    }
+
+    fn load(&self, specifier: &str) -> Result<ModuleSource> {
+        let name = specifier.trim_start_matches(CORE_PREFIX);
+        let Some(exports) = self.capabilities.core_modules.get(name) else {
+            bail!(format!(
+                "Core module \"{name}\" is unsupported in {} runtime",
+                self.capabilities.runtime
+            ));
+        };
+
+        let mut source = format!("const __host = globalThis.__dino_host[{name:?}];\n");
+        for export in exports {
+            source.push_str(&format!("export const {export} = __host.{export};\n"));
+        }
+        source.push_str("export default __host;\n");
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_loader_should_shim_host_modules() -> Result<()> {
+        let loader = CoreModuleLoader {
+            capabilities: Capabilities {
+                runtime: "dino-server".into(),
+                core_modules: HashMap::from([("fs".into(), vec!["existsSync".into()])]),
+                globals: vec!["print".into()],
+            },
+        };
+        assert!(loader.capabilities.supports_module("core:fs"));
+        assert!(loader.capabilities.supports_global("print"));
+        assert_eq!(core_specifier("fs"), Some("core:fs".into()));
+        assert_eq!(core_specifier("./fs"), None);
+        assert_eq!(core_specifier("dino:time"), Some("core:time".into()));
+
+        let source = loader.load("core:fs")?;
+        assert!(source.contains("export const existsSync = __host.existsSync;"));
+
+        let err = loader.load("core:net").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Core module \"net\" is unsupported in dino-server runtime"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+use super::transpilers::TypeScript;
+use anyhow::Result;
+use anyhow::anyhow;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use swc_common::DUMMY_SP;
+use swc_common::FileName;
+use swc_common::FilePathMapping;
+use swc_common::SourceMap;
+use swc_common::Spanned;
+use swc_common::sync::Lrc;
+use swc_ecma_ast::*;
+use swc_ecma_codegen::Emitter;
+use swc_ecma_codegen::text_writer::JsWriter;
+use swc_ecma_parser::EsSyntax;
+use swc_ecma_parser::Syntax;
+use swc_ecma_parser::TsSyntax;
+use swc_ecma_parser::parse_file_as_module;
+use swc_ecma_visit::VisitMut;
+use swc_ecma_visit::VisitMutWith;
+
+/// Global object holding the hit counters, `{ [file]: { [line]: hits } }`.
+pub const COVERAGE_GLOBAL: &str = "__dino_coverage";
+
+/// Per-module alias of the module's counters.
+const COUNTERS: &str = "__dino_cov";
+
+/// Only local source files are instrumented, dependencies fetched from URLs
+/// or installed into `node_modules` are not part of the project's coverage.
+pub(crate) fn covers(specifier: &str) -> bool {
+    let path = Path::new(specifier);
+    let source = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("js" | "mjs" | "ts" | "tsx")
+    );
+    source && path.is_file() && !path.components().any(|c| c.as_os_str() == "node_modules")
+}
+
+/// Loads a local module with its statements instrumented.
+pub(crate) fn load(specifier: &str) -> Result<String> {
+    let source = fs::read_to_string(specifier)?;
+    let file = std::path::absolute(specifier)?;
+    let code = instrument(&file.to_string_lossy(), &source)?;
+    match specifier.ends_with(".ts") || specifier.ends_with(".tsx") {
+        true => TypeScript::compile(Some(specifier), &code),
+        false => Ok(code),
+    }
+}
+
+/// Adds a hit counter before every statement of a module. Counters are keyed
+/// by the statement's line in the original source, so TypeScript is
+/// instrumented before it's transpiled.
+pub(crate) fn instrument(filename: &str, source: &str) -> Result<String> {
+    let syntax = match Path::new(filename).extension().and_then(|e| e.to_str()) {
+        Some(ext @ ("ts" | "tsx")) => Syntax::Typescript(TsSyntax {
+            tsx: ext == "tsx",
+            decorators: true,
+            ..Default::default()
+        }),
+        _ => Syntax::Es(EsSyntax::default()),
+    };
+    let cm: Lrc<SourceMap> = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+    let fm = cm.new_source_file(FileName::Custom(filename.into()).into(), source.to_string());
+    let mut module = parse_file_as_module(&fm, syntax, EsVersion::latest(), None, &mut vec![])
+        .map_err(|e| anyhow!("failed to parse {filename}: {:?}", e.kind()))?;
+
+    let mut instrumenter = Instrumenter {
+        cm: cm.clone(),
+        lines: BTreeSet::new(),
+    };
+    module.visit_mut_with(&mut instrumenter);
+
+    let mut output = vec![];
+    let mut emitter = Emitter {
+        cfg: swc_ecma_codegen::Config::default(),
+        cm: cm.clone(),
+        comments: None,
+        wr: JsWriter::new(cm.clone(), "\n", &mut output, None),
+    };
+    emitter.emit_module(&module)?;
+
+    // Lines are registered up front so statements that never run report 0.
+    let lines: Vec<_> = instrumenter
+        .lines
+        .iter()
+        .map(|line| format!("\"{line}\":0"))
+        .collect();
+    let header = format!(
+        "const {COUNTERS} = (globalThis.{COVERAGE_GLOBAL} ??= {{}})[{}] ??= {{{}}};\n",
+        serde_json::to_string(filename)?,
+        lines.join(",")
+    );
+    Ok(format!("{header}{}", String::from_utf8(output)?))
+}
+
+struct Instrumenter {
+    cm: Lrc<SourceMap>,
+    lines: BTreeSet<usize>,
+}
+
+impl Instrumenter {
+    /// Returns the counter statement for a statement starting at `span`.
+    fn counter(&mut self, span: swc_common::Span) -> Option<Stmt> {
+        if span.is_dummy() {
+            return None;
+        }
+        let line = self.cm.lookup_char_pos(span.lo).line;
+        self.lines.insert(line);
+        let target = MemberExpr {
+            span: DUMMY_SP,
+            obj: Box::new(Expr::Ident(Ident::new_no_ctxt(COUNTERS.into(), DUMMY_SP))),
+            prop: MemberProp::Computed(ComputedPropName {
+                span: DUMMY_SP,
+                expr: Box::new(Expr::Lit(Lit::Num(Number {
+                    span: DUMMY_SP,
+                    value: line as f64,
+                    raw: None,
+                }))),
+            }),
+        };
+        Some(Stmt::Expr(ExprStmt {
+            span: DUMMY_SP,
+            expr: Box::new(Expr::Update(UpdateExpr {
+                span: DUMMY_SP,
+                op: UpdateOp::PlusPlus,
+                prefix: false,
+                arg: Box::new(Expr::Member(target)),
+            })),
+        }))
+    }
+}
+
+/// Declarations that don't execute anything when reached.
+fn is_static(decl: &Decl) -> bool {
+    match decl {
+        Decl::Fn(_) | Decl::TsInterface(_) | Decl::TsTypeAlias(_) | Decl::TsModule(_) => true,
+        Decl::Var(var) => var.declare,
+        Decl::Class(class) => class.declare,
+        Decl::TsEnum(e) => e.declare,
+        Decl::Using(_) => false,
+    }
+}
+
+impl VisitMut for Instrumenter {
+    fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
+        items.visit_mut_children_with(self);
+        let mut instrumented = Vec::with_capacity(items.len() * 2);
+        for item in items.drain(..) {
+            let counted = match &item {
+                ModuleItem::Stmt(Stmt::Decl(decl)) => !is_static(decl),
+                ModuleItem::Stmt(Stmt::Empty(_)) => false,
+                ModuleItem::Stmt(_) => true,
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => !is_static(&export.decl),
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(_)) => true,
+                ModuleItem::ModuleDecl(_) => false,
+            };
+            if counted && let Some(counter) = self.counter(item.span()) {
+                instrumented.push(ModuleItem::Stmt(counter));
+            }
+            instrumented.push(item);
+        }
+        *items = instrumented;
+    }
+
+    fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        stmts.visit_mut_children_with(self);
+        let mut instrumented = Vec::with_capacity(stmts.len() * 2);
+        for stmt in stmts.drain(..) {
+            let counted = match &stmt {
+                Stmt::Decl(decl) => !is_static(decl),
+                Stmt::Empty(_) => false,
+                _ => true,
+            };
+            if counted && let Some(counter) = self.counter(stmt.span()) {
+                instrumented.push(counter);
+            }
+            instrumented.push(stmt);
+        }
+        *stmts = instrumented;
+    }
+
+    fn visit_mut_stmt(&mut self, stmt: &mut Stmt) {
+        // Single statement bodies become blocks so they get their own counter.
+        // Labeled bodies are kept as-is, `continue label` needs the loop.
+        let bodies = match stmt {
+            Stmt::If(s) => vec![&mut *s.cons]
+                .into_iter()
+                .chain(s.alt.as_deref_mut())
+                .collect(),
+            Stmt::For(s) => vec![&mut *s.body],
+            Stmt::ForIn(s) => vec![&mut *s.body],
+            Stmt::ForOf(s) => vec![&mut *s.body],
+            Stmt::While(s) => vec![&mut *s.body],
+            Stmt::DoWhile(s) => vec![&mut *s.body],
+            _ => vec![],
+        };
+        for body in bodies {
+            if !matches!(body, Stmt::Block(_)) {
+                let inner = std::mem::replace(body, Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
+                *body = Stmt::Block(BlockStmt {
+                    span: inner.span(),
+                    stmts: vec![inner],
+                    ..Default::default()
+                });
+            }
+        }
+        stmt.visit_mut_children_with(self);
+    }
+}
+
+/// Line hits collected from `globalThis.__dino_coverage`, rendered as lcov or
+/// HTML reports.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// Hits per line of each instrumented file.
+    pub files: BTreeMap<String, BTreeMap<usize, u64>>,
+}
+
+impl Coverage {
+    /// Parses the JSON serialized counters of a run.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let mut coverage = Self::default();
+        let files = value
+            .as_object()
+            .ok_or_else(|| anyhow!("coverage must be an object"))?;
+        for (file, lines) in files {
+            let lines = lines
+                .as_object()
+                .ok_or_else(|| anyhow!("coverage of {file} must be an object"))?;
+            let hits = coverage.files.entry(file.clone()).or_default();
+            for (line, count) in lines {
+                hits.insert(line.parse()?, count.as_u64().unwrap_or_default());
+            }
+        }
+        Ok(coverage)
+    }
+
+    /// Adds the hits of another run, such as another test file.
+    pub fn merge(&mut self, other: Coverage) {
+        for (file, lines) in other.files {
+            let hits = self.files.entry(file).or_default();
+            for (line, count) in lines {
+                *hits.entry(line).or_default() += count;
+            }
+        }
+    }
+
+    /// Returns the number of executed and instrumented lines of a file.
+    pub fn summary(&self, file: &str) -> (usize, usize) {
+        let lines = self.files.get(file);
+        let hit = lines.map_or(0, |l| l.values().filter(|c| **c > 0).count());
+        (hit, lines.map_or(0, BTreeMap::len))
+    }
+
+    /// Returns the number of executed and instrumented lines of all files.
+    pub fn total(&self) -> (usize, usize) {
+        self.files.keys().fold((0, 0), |(hit, total), file| {
+            let (h, t) = self.summary(file);
+            (hit + h, total + t)
+        })
+    }
+
+    /// Renders the report in the lcov tracefile format.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (file, lines) in &self.files {
+            let (hit, total) = self.summary(file);
+            let _ = writeln!(out, "TN:\nSF:{file}");
+            for (line, count) in lines {
+                let _ = writeln!(out, "DA:{line},{count}");
+            }
+            let _ = writeln!(out, "LF:{total}\nLH:{hit}\nend_of_record");
+        }
+        out
+    }
+
+    /// Renders a standalone HTML page with a summary table and the sources
+    /// annotated with their hits.
+    pub fn to_html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Coverage</title><style>\
+             body{font-family:sans-serif}td{padding:2px 8px}pre{line-height:1.3}\
+             .hit{background:#dfd}.miss{background:#fdd}.n{color:#999;display:inline-block;width:4em}\
+             </style></head><body>\n",
+        );
+        let (hit, total) = self.total();
+        let _ = writeln!(out, "<h1>Coverage {}</h1>\n<table>", percent(hit, total));
+        for (i, file) in self.files.keys().enumerate() {
+            let (hit, total) = self.summary(file);
+            let _ = writeln!(
+                out,
+                "<tr><td><a href=\"#f{i}\">{}</a></td><td>{hit}/{total}</td><td>{}</td></tr>",
+                escape(file),
+                percent(hit, total)
+            );
+        }
+        out.push_str("</table>\n");
+        for (i, (file, lines)) in self.files.iter().enumerate() {
+            let _ = writeln!(out, "<h2 id=\"f{i}\">{}</h2>\n<pre>", escape(file));
+            // Files that moved since the run are listed without their source.
+            let source = fs::read_to_string(file).unwrap_or_default();
+            for (n, text) in source.lines().enumerate() {
+                let (class, count) = match lines.get(&(n + 1)) {
+                    Some(0) => ("miss", "0".to_string()),
+                    Some(count) => ("hit", count.to_string()),
+                    None => ("", String::new()),
+                };
+                let _ = writeln!(
+                    out,
+                    "<span class=\"{class}\"><span class=\"n\">{} {count}</span>{}</span>",
+                    n + 1,
+                    escape(text)
+                );
+            }
+            out.push_str("</pre>\n");
+        }
+        out.push_str("</body></html>\n");
+        out
+    }
+}
+
+fn percent(hit: usize, total: usize) -> String {
+    match total {
+        0 => "-".into(),
+        _ => format!("{:.1}%", hit as f64 * 100.0 / total as f64),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_should_count_statements_by_line() -> Result<()> {
+        let source = "import { x } from './x.ts';\ninterface A { a: number }\nfunction f(a: A) {\n  if (a.a > 1) return 1;\n  return 2;\n}\nexport const y = f({ a: x });\n";
+        let ret = instrument("/src/main.ts", source)?;
+        assert!(ret.starts_with(
+            "const __dino_cov = (globalThis.__dino_coverage ??= {})[\"/src/main.ts\"] ??= {\"4\":0,\"5\":0,\"7\":0};"
+        ));
+        assert!(ret.contains(
+            "__dino_cov[4]++;\n    if (a.a > 1) {\n        __dino_cov[4]++;\n        return 1;"
+        ));
+        assert!(ret.contains("__dino_cov[7]++;\nexport const y"));
+
+        let mut coverage = Coverage::from_json(r#"{"/src/main.ts":{"4":1,"5":0,"7":1}}"#)?;
+        coverage.merge(Coverage::from_json(r#"{"/src/main.ts":{"5":2}}"#)?);
+        assert_eq!(coverage.total(), (3, 3));
+        assert_eq!(
+            coverage.to_lcov(),
+            "TN:\nSF:/src/main.ts\nDA:4,1\nDA:5,2\nDA:7,1\nLF:3\nLH:3\nend_of_record\n"
+        );
+        assert!(coverage.to_html().contains("100.0%"));
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{Context, Result, bail};
+use std::collections::HashMap;
+use swc_common::FileName;
+use swc_common::FilePathMapping;
+use swc_common::GLOBALS;
+use swc_common::Globals;
+use swc_common::Mark;
+use swc_common::SourceMap;
+use swc_common::SyntaxContext;
+use swc_common::sync::Lrc;
+use swc_ecma_ast::*;
+use swc_ecma_codegen::Emitter;
+use swc_ecma_codegen::text_writer::JsWriter;
+use swc_ecma_parser::EsSyntax;
+use swc_ecma_parser::Syntax;
+use swc_ecma_parser::parse_file_as_expr;
+use swc_ecma_parser::parse_file_as_module;
+use swc_ecma_transforms_base::fixer::fixer;
+use swc_ecma_transforms_base::hygiene::hygiene;
+use swc_ecma_transforms_base::resolver;
+use swc_ecma_transforms_optimization::simplify::dead_branch_remover;
+use swc_ecma_transforms_optimization::simplify::expr_simplifier;
+use swc_ecma_visit::VisitMut;
+use swc_ecma_visit::VisitMutWith;
+
+/// Environment variables with this prefix are added as defines, e.g.
+/// `DINO_DEFINE_FEATURE_X=true` defines `FEATURE_X`.
+pub const DEFINE_ENV_PREFIX: &str = "DINO_DEFINE_";
+
+/// Compile-time constants replaced in the bundle, keyed by a global name or a
+/// dotted path such as `process.env.NODE_ENV`. Values are JS expressions;
+/// anything that doesn't parse as one is used as a string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Defines(HashMap<String, String>);
+
+impl Defines {
+    /// Reads `DINO_DEFINE_*` variables from the environment.
+    pub fn from_env() -> Self {
+        Self(
+            std::env::vars()
+                .filter_map(|(k, v)| Some((k.strip_prefix(DEFINE_ENV_PREFIX)?.to_string(), v)))
+                .collect(),
+        )
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(key.into(), value.into());
+    }
+
+    /// Parses a `KEY=VALUE` pair as given to `--define`.
+    pub fn parse_pair(pair: &str) -> Result<(String, String)> {
+        let (key, value) = pair
+            .split_once('=')
+            .with_context(|| format!("invalid define {pair:?}, expected KEY=VALUE"))?;
+        let valid = !key.is_empty()
+            && key.split('.').all(|part| {
+                part.chars()
+                    .next()
+                    .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+                    && part
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+            });
+        if !valid {
+            bail!("invalid define key {key:?}");
+        }
+        Ok((key.to_string(), value.to_string()))
+    }
+
+    /// Adds `other`, overriding existing keys.
+    pub fn extend(&mut self, other: Defines) {
+        self.0.extend(other.0);
+    }
+
+    /// Returns the defines sorted by key, for a stable cache key.
+    pub fn sorted(&self) -> Vec<(&str, &str)> {
+        let mut pairs: Vec<_> = self
+            .0
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Replaces the defines in a JavaScript module and folds the constant
+    /// conditions, so code behind a disabled flag is removed.
+    pub fn apply(&self, filename: &str, source: &str) -> Result<String> {
+        let roots: Vec<_> = self
+            .0
+            .keys()
+            .map(|k| k.split('.').next().unwrap())
+            .collect();
+        if !roots.iter().any(|root| source.contains(root)) {
+            return Ok(source.to_string());
+        }
+
+        let globals = Globals::default();
+        let cm: Lrc<SourceMap> = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+        let fm = cm.new_source_file(FileName::Custom(filename.into()).into(), source.to_string());
+        let module = parse_file_as_module(
+            &fm,
+            Syntax::Es(EsSyntax::default()),
+            EsVersion::latest(),
+            None,
+            &mut vec![],
+        )
+        .map_err(|e| anyhow::anyhow!("failed to parse {filename}: {:?}", e.kind()))?;
+
+        let mut output = vec![];
+        GLOBALS.set(&globals, || {
+            let unresolved_mark = Mark::new();
+            let top_level_mark = Mark::new();
+
+            let mut program =
+                Program::Module(module).apply(resolver(unresolved_mark, top_level_mark, false));
+            program.visit_mut_with(&mut Replacer {
+                defines: self.exprs(&cm),
+                unresolved: SyntaxContext::empty().apply_mark(unresolved_mark),
+            });
+            let program = program
+                .apply(expr_simplifier(unresolved_mark, Default::default()))
+                .apply(dead_branch_remover(unresolved_mark))
+                .apply(hygiene())
+                .apply(fixer(None));
+
+            let mut emitter = Emitter {
+                cfg: swc_ecma_codegen::Config::default(),
+                cm: cm.clone(),
+                comments: None,
+                wr: JsWriter::new(cm.clone(), "\n", &mut output, None),
+            };
+            emitter.emit_program(&program)
+        })?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    fn exprs(&self, cm: &Lrc<SourceMap>) -> Vec<(Vec<String>, Box<Expr>)> {
+        self.0
+            .iter()
+            .map(|(key, value)| {
+                // Wrapped so the whole value must be one expression, `1.2.3` is a string
+                let fm = cm.new_source_file(FileName::Anon.into(), format!("({value})"));
+                let expr = parse_file_as_expr(
+                    &fm,
+                    Syntax::Es(EsSyntax::default()),
+                    EsVersion::latest(),
+                    None,
+                    &mut vec![],
+                )
+                .map(|expr| match *expr {
+                    Expr::Paren(paren) => paren.expr,
+                    expr => Box::new(expr),
+                })
+                .unwrap_or_else(|_| Box::new(Expr::Lit(Lit::Str(value.as_str().into()))));
+                (key.split('.').map(String::from).collect(), expr)
+            })
+            .collect()
+    }
+}
+
+struct Replacer {
+    defines: Vec<(Vec<String>, Box<Expr>)>,
+    unresolved: SyntaxContext,
+}
+
+impl Replacer {
+    /// Returns the dotted path of an expression rooted at a free (global)
+    /// identifier, e.g. `process.env.NODE_ENV`.
+    fn path(&self, expr: &Expr) -> Option<Vec<String>> {
+        match expr {
+            Expr::Ident(ident) if ident.ctxt == self.unresolved => {
+                Some(vec![ident.sym.to_string()])
+            }
+            Expr::Member(MemberExpr {
+                obj,
+                prop: MemberProp::Ident(prop),
+                ..
+            }) => {
+                let mut path = self.path(obj)?;
+                path.push(prop.sym.to_string());
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl VisitMut for Replacer {
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        if let Some(path) = self.path(expr)
+            && let Some((_, value)) = self.defines.iter().find(|(key, _)| *key == path)
+        {
+            *expr = *value.clone();
+            return;
+        }
+        expr.visit_mut_children_with(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defines_should_strip_disabled_branches() -> Result<()> {
+        let mut defines = Defines::default();
+        defines.insert("FEATURE_X", "false");
+        defines.insert("process.env.NODE_ENV", "\"production\"");
+        defines.insert("VERSION", "1.2.3");
+
+        let source = r#"
+            if (FEATURE_X) { console.log("x"); } else { console.log("no x"); }
+            if (process.env.NODE_ENV !== "production") { console.log("debug"); }
+            function shadow(FEATURE_X) { return FEATURE_X; }
+            export const version = VERSION;
+        "#;
+        let ret = defines.apply("main.js", source)?;
+        assert!(!ret.contains("console.log(\"x\")"));
+        assert!(ret.contains("no x"));
+        assert!(!ret.contains("debug"));
+        assert!(ret.contains("return FEATURE_X"));
+        assert!(ret.contains("\"1.2.3\""));
+
+        assert!(Defines::parse_pair("A.b=1").is_ok());
+        assert!(Defines::parse_pair("1A=1").is_err());
+        assert!(Defines::parse_pair("A").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+use std::fs;
+use swc_common::DUMMY_SP;
+use swc_common::FileName;
+use swc_common::FilePathMapping;
+use swc_common::SourceMap;
+use swc_common::sync::Lrc;
+use swc_ecma_ast::*;
+use swc_ecma_codegen::Emitter;
+use swc_ecma_codegen::text_writer::JsWriter;
+use swc_ecma_parser::Syntax;
+use swc_ecma_parser::TsSyntax;
+use swc_ecma_parser::parse_file_as_module;
+
+/// Generates TypeScript declarations for the exports of a module, e.g. the
+/// handlers of a project entry.
+///
+/// Functions, classes and variables lose their bodies and initializers, and
+/// interfaces, type aliases and enums of the module are kept as they are.
+/// Types imported from other modules aren't followed.
+pub fn emit_declarations(path: &str) -> Result<String> {
+    let source = fs::read_to_string(path)?;
+    declarations(path, &source)
+}
+
+fn declarations(filename: &str, source: &str) -> Result<String> {
+    let cm: Lrc<SourceMap> = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+    let fm = cm.new_source_file(FileName::Custom(filename.into()).into(), source.into());
+    let module = parse_file_as_module(
+        &fm,
+        Syntax::Typescript(TsSyntax {
+            tsx: filename.ends_with(".tsx"),
+            decorators: true,
+            ..Default::default()
+        }),
+        EsVersion::latest(),
+        None,
+        &mut vec![],
+    )
+    .map_err(|e| anyhow!("failed to parse {filename}: {:?}", e.kind()))?;
+
+    // Locals exported with `export { a, b as c }`.
+    let mut exported = HashSet::new();
+    for item in &module.body {
+        if let ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export)) = item
+            && export.src.is_none()
+        {
+            for specifier in &export.specifiers {
+                if let ExportSpecifier::Named(ExportNamedSpecifier {
+                    orig: ModuleExportName::Ident(ident),
+                    ..
+                }) = specifier
+                {
+                    exported.insert(ident.sym.to_string());
+                }
+            }
+        }
+    }
+
+    let body = module
+        .body
+        .into_iter()
+        .filter_map(|item| match item {
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(mut export)) => {
+                export.decl = declare(export.decl)?;
+                Some(ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)))
+            }
+            ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export)) if export.src.is_none() => {
+                Some(ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export)))
+            }
+            ModuleItem::Stmt(Stmt::Decl(decl)) => {
+                let keep = match &decl {
+                    Decl::TsInterface(_) | Decl::TsTypeAlias(_) | Decl::TsEnum(_) => true,
+                    Decl::Fn(f) => exported.contains(&*f.ident.sym),
+                    Decl::Class(c) => exported.contains(&*c.ident.sym),
+                    Decl::Var(v) => v
+                        .decls
+                        .iter()
+                        .any(|d| matches!(&d.name, Pat::Ident(i) if exported.contains(&*i.id.sym))),
+                    _ => false,
+                };
+                keep.then(|| declare(decl))
+                    .flatten()
+                    .map(|decl| ModuleItem::Stmt(Stmt::Decl(decl)))
+            }
+            _ => None,
+        })
+        .collect();
+    let module = Module {
+        span: DUMMY_SP,
+        body,
+        shebang: None,
+    };
+
+    let mut buf = vec![];
+    let mut emitter = Emitter {
+        cfg: swc_ecma_codegen::Config::default(),
+        cm: cm.clone(),
+        comments: None,
+        wr: JsWriter::new(cm, "\n", &mut buf, None),
+    };
+    emitter.emit_module(&module)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Turns a declaration into its ambient (`declare`) form.
+fn declare(decl: Decl) -> Option<Decl> {
+    match decl {
+        Decl::Fn(mut f) => {
+            strip_function(&mut f.function);
+            f.declare = true;
+            Some(Decl::Fn(f))
+        }
+        Decl::Class(mut c) => {
+            strip_class(&mut c.class);
+            c.declare = true;
+            Some(Decl::Class(c))
+        }
+        Decl::Var(var) => {
+            // Functions assigned to variables become function declarations.
+            if let [decl] = var.decls.as_slice()
+                && let Pat::Ident(name) = &decl.name
+                && name.type_ann.is_none()
+                && let Some(function) = decl.init.as_deref().and_then(as_function)
+            {
+                return Some(Decl::Fn(FnDecl {
+                    ident: name.id.clone(),
+                    declare: true,
+                    function: Box::new(function),
+                }));
+            }
+            let decls = var
+                .decls
+                .into_iter()
+                .map(|mut decl| {
+                    if let Pat::Ident(name) = &mut decl.name
+                        && name.type_ann.is_none()
+                    {
+                        name.type_ann = Some(Box::new(TsTypeAnn {
+                            span: DUMMY_SP,
+                            type_ann: Box::new(infer_type(decl.init.as_deref())),
+                        }));
+                    }
+                    decl.init = None;
+                    decl
+                })
+                .collect();
+            Some(Decl::Var(Box::new(VarDecl {
+                declare: true,
+                decls,
+                ..*var
+            })))
+        }
+        Decl::TsInterface(_) | Decl::TsTypeAlias(_) | Decl::TsEnum(_) => Some(decl),
+        _ => None,
+    }
+}
+
+fn as_function(expr: &Expr) -> Option<Function> {
+    match expr {
+        Expr::Fn(f) => {
+            let mut function = (*f.function).clone();
+            strip_function(&mut function);
+            Some(function)
+        }
+        Expr::Arrow(arrow) => {
+            let mut function = Function {
+                params: arrow.params.iter().cloned().map(Param::from).collect(),
+                decorators: vec![],
+                span: DUMMY_SP,
+                ctxt: Default::default(),
+                body: None,
+                is_generator: arrow.is_generator,
+                is_async: arrow.is_async,
+                type_params: arrow.type_params.clone(),
+                return_type: arrow.return_type.clone(),
+            };
+            strip_function(&mut function);
+            Some(function)
+        }
+        Expr::Paren(paren) => as_function(&paren.expr),
+        _ => None,
+    }
+}
+
+fn strip_function(function: &mut Function) {
+    // `async` isn't allowed in declarations, it shows in the return type.
+    if function.is_async && function.return_type.is_none() {
+        function.return_type = Some(Box::new(TsTypeAnn {
+            span: DUMMY_SP,
+            type_ann: Box::new(TsType::TsTypeRef(TsTypeRef {
+                span: DUMMY_SP,
+                type_name: TsEntityName::Ident(Ident::new_no_ctxt("Promise".into(), DUMMY_SP)),
+                type_params: Some(Box::new(TsTypeParamInstantiation {
+                    span: DUMMY_SP,
+                    params: vec![Box::new(infer_type(None))],
+                })),
+            })),
+        }));
+    }
+    function.is_async = false;
+    function.is_generator = false;
+    function.body = None;
+    function.decorators.clear();
+    for param in &mut function.params {
+        param.decorators.clear();
+        strip_default(&mut param.pat);
+    }
+}
+
+/// Initializers aren't allowed in declarations, `a = 1` becomes `a?: number`.
+fn strip_default(pat: &mut Pat) {
+    if let Pat::Assign(assign) = pat {
+        let mut left = *assign.left.clone();
+        if let Pat::Ident(ident) = &mut left {
+            ident.id.optional = true;
+            if ident.type_ann.is_none() {
+                ident.type_ann = Some(Box::new(TsTypeAnn {
+                    span: DUMMY_SP,
+                    type_ann: Box::new(infer_type(Some(&assign.right))),
+                }));
+            }
+        }
+        *pat = left;
+    }
+}
+
+fn strip_class(class: &mut Class) {
+    class.decorators.clear();
+    class.body.retain_mut(|member| match member {
+        ClassMember::Constructor(constructor) => {
+            constructor.body = None;
+            for param in &mut constructor.params {
+                if let ParamOrTsParamProp::Param(param) = param {
+                    param.decorators.clear();
+                    strip_default(&mut param.pat);
+                }
+            }
+            true
+        }
+        ClassMember::Method(method) => {
+            strip_function(&mut method.function);
+            true
+        }
+        ClassMember::ClassProp(prop) => {
+            if prop.type_ann.is_none() {
+                prop.type_ann = Some(Box::new(TsTypeAnn {
+                    span: DUMMY_SP,
+                    type_ann: Box::new(infer_type(prop.value.as_deref())),
+                }));
+            }
+            prop.value = None;
+            prop.decorators.clear();
+            true
+        }
+        ClassMember::TsIndexSignature(_) => true,
+        _ => false,
+    });
+}
+
+/// Type of an unannotated variable, from a literal initializer.
+fn infer_type(init: Option<&Expr>) -> TsType {
+    let kind = match init {
+        Some(Expr::Lit(Lit::Str(_)) | Expr::Tpl(_)) => TsKeywordTypeKind::TsStringKeyword,
+        Some(Expr::Lit(Lit::Num(_))) => TsKeywordTypeKind::TsNumberKeyword,
+        Some(Expr::Lit(Lit::Bool(_))) => TsKeywordTypeKind::TsBooleanKeyword,
+        _ => TsKeywordTypeKind::TsUnknownKeyword,
+    };
+    TsType::TsKeywordType(TsKeywordType {
+        span: DUMMY_SP,
+        kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declarations_should_keep_exported_signatures() -> Result<()> {
+        let ret = declarations(
+            "main.ts",
+            r#"
+            import { helper } from "./helper.ts";
+            interface Greeting { message: string }
+            type Id = string;
+            const secret = "hidden";
+            async function hello(req: Req, verbose = false) {
+                return { status: 200, headers: {}, body: helper(secret) };
+            }
+            const list = async (req: Req): Promise<Resp> => ({ status: 200, headers: {} });
+            export const version = "1.0";
+            export class Counter {
+                count = 0;
+                #hidden = 1;
+                constructor(state: ObjectState) {}
+                async fetch(req: Req): Promise<Resp> { return this.count; }
+            }
+            export { hello, list as listItems };
+            "#,
+        )?;
+        assert_eq!(
+            ret,
+            r#"interface Greeting {
+    message: string;
+}
+type Id = string;
+declare function hello(req: Req, verbose?: boolean): Promise<unknown>;
+declare function list(req: Req): Promise<Resp>;
+export declare const version: string;
+export declare class Counter {
+    count: number;
+    constructor(state: ObjectState);
+    fetch(req: Req): Promise<Resp>;
+}
+export { hello, list as listItems };
+"#
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use swc_common::DUMMY_SP;
+use swc_common::FileName;
+use swc_common::FilePathMapping;
+use swc_common::SourceMap;
+use swc_common::Span;
+use swc_ecma_ast::*;
+use swc_ecma_parser::EsSyntax;
+use swc_ecma_parser::Syntax;
+use swc_ecma_parser::parse_file_as_expr;
+use swc_ecma_visit::VisitMut;
+use swc_ecma_visit::VisitMutWith;
+use url::Url;
+
+/// Name of the per-module object that replaces `import.meta`.
+const IMPORT_META: &str = "__import_meta";
+
+/// `import.meta.resolve()`, resolving against the module URL at runtime.
+/// Bare specifiers can't be resolved once bundled and throw a TypeError.
+const RESOLVE: &str = r#"(specifier) => {
+  const base = __BASE__;
+  if (/^[a-zA-Z][a-zA-Z\d+.-]*:/.test(specifier)) return specifier;
+  if (!/^\.{0,2}\//.test(specifier)) {
+    throw new TypeError(`Cannot resolve bare specifier "${specifier}" from "${base}"`);
+  }
+  const [, origin, path] = /^([a-zA-Z][a-zA-Z\d+.-]*:(?:\/\/[^/]*)?)(.*)$/.exec(base);
+  const joined = specifier.startsWith("/") ? specifier : path.replace(/[^/]*$/, "") + specifier;
+  const segments = [];
+  for (const segment of joined.split("/").slice(1)) {
+    if (segment === "..") segments.pop();
+    else if (segment !== ".") segments.push(segment);
+  }
+  return `${origin}/${segments.join("/")}`;
+}"#;
+
+/// Returns the URL of a loaded module, local files (relative to the current
+/// directory for the entry) become `file://` URLs.
+pub fn module_url(path: &str) -> String {
+    match Url::parse(path) {
+        // Windows paths such as `C:\foo` parse as URLs with a one letter scheme.
+        Ok(url) if url.scheme().len() > 1 => path.to_string(),
+        _ => std::path::absolute(Path::new(path))
+            .ok()
+            .and_then(|path| Url::from_file_path(path).ok())
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| path.to_string()),
+    }
+}
+
+/// Returns the `import.meta` properties of a module: `url`, `main`,
+/// `resolve` and, when bundling for a target, `target`.
+pub fn props(
+    span: Span,
+    url: &str,
+    is_entry: bool,
+    target: Option<&str>,
+) -> Result<Vec<KeyValueProp>> {
+    let str_lit = |value: &str| {
+        Box::new(Expr::Lit(Lit::Str(Str {
+            span,
+            raw: None,
+            value: value.into(),
+        })))
+    };
+    let mut props = vec![
+        KeyValueProp {
+            key: PropName::Ident(IdentName::new("url".into(), span)),
+            value: str_lit(url),
+        },
+        KeyValueProp {
+            key: PropName::Ident(IdentName::new("main".into(), span)),
+            value: Box::new(Expr::Lit(Lit::Bool(Bool {
+                span,
+                value: is_entry,
+            }))),
+        },
+        KeyValueProp {
+            key: PropName::Ident(IdentName::new("resolve".into(), span)),
+            value: resolve_fn(url)?,
+        },
+    ];
+    if let Some(target) = target {
+        props.push(KeyValueProp {
+            key: PropName::Ident(IdentName::new("target".into(), span)),
+            value: str_lit(target),
+        });
+    }
+    Ok(props)
+}
+
+/// Replaces `import.meta` in a module with a module level object.
+///
+/// The bundle runs as a script where `import.meta` is a syntax error, and
+/// swc_bundler's own replacement may drop the object of the entry module.
+pub fn inline(
+    module: &mut Module,
+    props: impl FnOnce() -> Result<Vec<KeyValueProp>>,
+) -> Result<()> {
+    let mut replacer = Replacer { found: false };
+    module.visit_mut_with(&mut replacer);
+    if !replacer.found {
+        return Ok(());
+    }
+    let object = ObjectLit {
+        span: DUMMY_SP,
+        props: props()?
+            .into_iter()
+            .map(|kv| PropOrSpread::Prop(Box::new(Prop::KeyValue(kv))))
+            .collect(),
+    };
+    let decl = VarDecl {
+        kind: VarDeclKind::Const,
+        decls: vec![VarDeclarator {
+            span: DUMMY_SP,
+            name: Pat::Ident(Ident::new_no_ctxt(IMPORT_META.into(), DUMMY_SP).into()),
+            init: Some(Box::new(Expr::Object(object))),
+            definite: false,
+        }],
+        ..Default::default()
+    };
+    module
+        .body
+        .insert(0, ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(decl)))));
+    Ok(())
+}
+
+struct Replacer {
+    found: bool,
+}
+
+impl VisitMut for Replacer {
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        expr.visit_mut_children_with(self);
+        if let Expr::MetaProp(MetaPropExpr {
+            kind: MetaPropKind::ImportMeta,
+            span,
+        }) = expr
+        {
+            *expr = Expr::Ident(Ident::new_no_ctxt(IMPORT_META.into(), *span));
+            self.found = true;
+        }
+    }
+}
+
+/// Builds the `import.meta.resolve` function for a module URL.
+fn resolve_fn(url: &str) -> Result<Box<Expr>> {
+    let source = RESOLVE.replace("__BASE__", &serde_json::to_string(url)?);
+    let cm = SourceMap::new(FilePathMapping::empty());
+    let fm = cm.new_source_file(FileName::Anon.into(), source);
+    parse_file_as_expr(
+        &fm,
+        Syntax::Es(EsSyntax::default()),
+        EsVersion::latest(),
+        None,
+        &mut vec![],
+    )
+    .map_err(|e| anyhow!("failed to build import.meta.resolve: {:?}", e.kind()))
+}
@@ -1,14 +1,23 @@
+use super::auth::AuthConfig;
 use super::modules::ModulePath;
 use super::modules::ModuleSource;
+use super::proxy::ProxyConfig;
+use super::timings;
+use super::timings::Phase;
 use super::transpilers::TypeScript;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
+use base64::prelude::*;
 use colored::*;
 use lazy_static::lazy_static;
 use path_absolutize::*;
+use percent_encoding::percent_decode_str;
 use regex::Regex;
+use serde_json::Value;
+use serde_json::json;
 use sha::sha1::Sha1;
+use sha::sha256::Sha256;
 use sha::utils::Digest;
 use sha::utils::DigestExt;
 use std::env;
@@ -23,7 +32,7 @@ pub trait ModuleLoader {
     fn resolve(&self, base: Option<&str>, specifier: &str) -> Result<ModulePath>;
 }
 
-static EXTENSIONS: &[&str] = &["js", "ts", "json"];
+pub(crate) static EXTENSIONS: &[&str] = &["js", "ts", "json"];
 
 #[derive(Default)]
 pub struct FsModuleLoader;
@@ -95,7 +104,7 @@ impl ModuleLoader for FsModuleLoader {
     fn resolve(&self, base: Option<&str>, specifier: &str) -> Result<ModulePath> {
         // Windows platform full path regex.
         lazy_static! {
-            static ref WINDOWS_REGEX: Regex = Regex::new(r"^[a-zA-Z]:\\").unwrap();
+            static ref WINDOWS_REGEX: Regex = Regex::new(r"^[a-zA-Z]:[\\/]").unwrap();
         }
 
         // Resolve absolute import.
@@ -152,11 +161,54 @@ lazy_static! {
     };
 }
 
+/// Returns the cache location of a remote module (sha1 of the URL).
+pub(crate) fn cache_path(specifier: &str) -> PathBuf {
+    let hash = Sha1::default().digest(specifier.as_bytes()).to_hex();
+    CACHE_DIR.join(hash)
+}
+
+/// Returns the location of the validators (ETag/Last-Modified) of a cached module.
+fn cache_meta_path(module_path: &Path) -> PathBuf {
+    module_path.with_extension("headers.json")
+}
+
+/// HTTP validators stored next to a cached module.
+#[derive(Default)]
+struct CacheMeta {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl CacheMeta {
+    fn read(module_path: &Path) -> Self {
+        let Ok(text) = fs::read_to_string(cache_meta_path(module_path)) else {
+            return Self::default();
+        };
+        let json: Value = serde_json::from_str(&text).unwrap_or_default();
+        let field = |name: &str| json[name].as_str().map(String::from);
+        Self {
+            etag: field("etag"),
+            last_modified: field("last_modified"),
+        }
+    }
+
+    fn write(&self, module_path: &Path) -> Result<()> {
+        let json = json!({ "etag": self.etag, "last_modified": self.last_modified });
+        fs::write(cache_meta_path(module_path), json.to_string())?;
+        Ok(())
+    }
+}
+
 #[derive(Default)]
 /// Loader supporting URL imports.
 pub struct UrlModuleLoader {
-    // Ignores the cache and re-downloads the dependency.
+    // Revalidates cached dependencies with conditional requests (ETag or
+    // Last-Modified) and re-downloads the ones that changed.
     pub skip_cache: bool,
+    // Credentials sent to matching hosts.
+    pub auth: AuthConfig,
+    // Proxy used for downloads.
+    pub proxy: ProxyConfig,
 }
 
 impl ModuleLoader for UrlModuleLoader {
@@ -167,14 +219,14 @@ impl ModuleLoader for UrlModuleLoader {
         }
 
         // 2. Check if the requester is a valid URL.
-        if let Some(base) = base {
-            if let Ok(base) = Url::parse(base) {
-                let options = Url::options();
-                let url = options.base_url(Some(&base));
-                let url = url.parse(specifier)?;
-
-                return Ok(url.as_str().to_string());
-            }
+        if let Some(base) = base
+            && let Ok(base) = Url::parse(base)
+        {
+            let options = Url::options();
+            let url = options.base_url(Some(&base));
+            let url = url.parse(specifier)?;
+
+            return Ok(url.as_str().to_string());
         }
 
         // Possibly unreachable error.
@@ -182,40 +234,263 @@ impl ModuleLoader for UrlModuleLoader {
     }
 
     fn load(&self, specifier: &str) -> Result<ModuleSource> {
+        let (url, integrity) = split_integrity(specifier);
+        let source = self.fetch(url, integrity)?;
+
+        // Use a preprocessor if necessary.
+        let is_ts = Url::parse(url).is_ok_and(|url| url.path().ends_with(".ts"));
+        match is_ts {
+            true => TypeScript::compile(Some(url), &source),
+            false => Ok(source),
+        }
+    }
+}
+
+impl UrlModuleLoader {
+    /// Returns the raw source of `url`, from the cache when possible. Sources
+    /// are cached untranspiled so checksum pins can be verified on every read.
+    pub(crate) fn fetch(&self, url: &str, integrity: Option<&str>) -> Result<ModuleSource> {
         // Create the cache directory.
         if fs::create_dir_all(CACHE_DIR.as_path()).is_err() {
             bail!("Failed to create module caching directory");
         }
 
-        // Hash URL using sha1.
-        let hash = Sha1::default().digest(specifier.as_bytes()).to_hex();
-        let module_path = CACHE_DIR.join(hash);
+        let module_path = cache_path(url);
 
-        if !self.skip_cache {
-            // Check cache, and load file.
-            if module_path.is_file() {
-                let source = fs::read_to_string(&module_path).unwrap();
-                return Ok(source);
-            }
+        // Check cache, and load file.
+        let cached = match module_path.is_file() {
+            true => Some(fs::read_to_string(&module_path)?),
+            false => None,
+        };
+        if let (Some(source), Some(expected)) = (&cached, integrity) {
+            verify_integrity(url, source, expected)?;
+        }
+        if let Some(source) = &cached
+            && !self.skip_cache
+        {
+            return Ok(source.clone());
         }
 
-        println!("{} {}", "Downloading".green(), specifier);
+        // Revalidate the cached copy instead of downloading it again.
+        let mut request = self.proxy.agent_for(url)?.get(url);
+        if let Some(credential) = self.auth.lookup(url) {
+            request = request.set("Authorization", &credential.header_value());
+        }
+        if cached.is_some() {
+            let meta = CacheMeta::read(&module_path);
+            if let Some(etag) = &meta.etag {
+                request = request.set("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &meta.last_modified {
+                request = request.set("If-Modified-Since", last_modified);
+            }
+            println!("{} {}", "Revalidating".green(), url);
+        } else {
+            println!("{} {}", "Downloading".green(), url);
+        }
 
         // Download file and, save it to cache.
-        let source = match ureq::get(specifier).call()?.into_string() {
-            Ok(source) => source,
-            Err(_) => bail!(format!("Module not found \"{specifier}\"")),
-        };
+        let response =
+            timings::timed(Phase::Fetch, || request.call().map_err(anyhow::Error::from))?;
+        if let Some(source) = cached
+            && response.status() == 304
+        {
+            return Ok(source);
+        }
 
-        // Use a preprocessor if necessary.
-        let source = if specifier.ends_with(".ts") {
-            TypeScript::compile(Some(specifier), &source)?
-        } else {
-            source
+        let meta = CacheMeta {
+            etag: response.header("ETag").map(String::from),
+            last_modified: response.header("Last-Modified").map(String::from),
+        };
+        let source = match timings::timed(Phase::Fetch, || response.into_string()) {
+            Ok(source) => source,
+            Err(_) => bail!(format!("Module not found \"{url}\"")),
         };
+        if let Some(expected) = integrity {
+            verify_integrity(url, &source, expected)?;
+        }
 
         fs::write(&module_path, &source)?;
+        meta.write(&module_path)?;
 
         Ok(source)
     }
 }
+
+/// Loader supporting inline `data:` URL imports.
+#[derive(Default)]
+pub struct DataModuleLoader;
+
+impl DataModuleLoader {
+    /// Splits a data URL into its media type and decoded payload.
+    fn decode(&self, specifier: &str) -> Result<(String, String)> {
+        let Some((header, payload)) = specifier
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(','))
+        else {
+            bail!(format!("Invalid data URL \"{specifier}\""));
+        };
+
+        let (media_type, is_base64) = match header.strip_suffix(";base64") {
+            Some(media_type) => (media_type, true),
+            None => (header, false),
+        };
+        // Drop parameters like `;charset=utf-8`.
+        let media_type = media_type.split(';').next().unwrap_or_default().trim();
+
+        let bytes = match is_base64 {
+            true => BASE64_STANDARD.decode(percent_decode_str(payload).collect::<Vec<_>>())?,
+            false => percent_decode_str(payload).collect(),
+        };
+        Ok((media_type.to_lowercase(), String::from_utf8(bytes)?))
+    }
+}
+
+impl ModuleLoader for DataModuleLoader {
+    fn resolve(&self, _: Option<&str>, specifier: &str) -> Result<ModulePath> {
+        // Data URLs are self-contained, there is nothing to resolve.
+        Ok(specifier.into())
+    }
+
+    fn load(&self, specifier: &str) -> Result<ModuleSource> {
+        let (media_type, source) = self.decode(specifier)?;
+        match media_type.as_str() {
+            "" | "text/javascript" | "application/javascript" => Ok(source),
+            "application/typescript" | "text/typescript" => {
+                TypeScript::compile(Some(specifier), &source)
+            }
+            "application/json" => {
+                // Make sure the payload is valid JSON before inlining it.
+                serde_json::from_str::<Value>(&source)?;
+                Ok(format!("export default {source};"))
+            }
+            _ => bail!(format!("Unsupported data URL media type \"{media_type}\"")),
+        }
+    }
+}
+
+/// Splits a `#sha256=<hex>` checksum pin off a URL specifier.
+pub(crate) fn split_integrity(specifier: &str) -> (&str, Option<&str>) {
+    match specifier.split_once("#sha256=") {
+        Some((url, hash)) => (url, Some(hash)),
+        None => (specifier, None),
+    }
+}
+
+/// Returns the hex sha256 of a module source.
+pub(crate) fn sha256_hex(source: &str) -> String {
+    Sha256::default().digest(source.as_bytes()).to_hex()
+}
+
+/// Fails when the sha256 of `source` doesn't match the pinned checksum.
+fn verify_integrity(url: &str, source: &str, expected: &str) -> Result<()> {
+    let actual = sha256_hex(source);
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("Integrity check failed for \"{url}\": expected sha256 {expected}, got {actual}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Serves a single module with an ETag, answering 304 when it matches.
+    fn serve(requests: usize) -> (String, Arc<Mutex<Vec<u16>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/mod.js", listener.local_addr().unwrap());
+        let statuses = Arc::new(Mutex::new(vec![]));
+        let sent = statuses.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut matched = false;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    matched |= line.eq_ignore_ascii_case("if-none-match: \"v1\"");
+                }
+                let resp = if matched {
+                    sent.lock().unwrap().push(304);
+                    "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nContent-Length: 0\r\n\r\n"
+                        .to_string()
+                } else {
+                    sent.lock().unwrap().push(200);
+                    let body = "export default 1;";
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                stream.write_all(resp.as_bytes()).unwrap();
+            }
+        });
+        (url, statuses)
+    }
+
+    #[test]
+    fn url_loader_should_revalidate_with_etag() -> Result<()> {
+        let (url, statuses) = serve(2);
+        // The URL contains an ephemeral port, a reused port would find the
+        // cached copy of an earlier run and skip the first download.
+        let cached = cache_path(&url);
+        let _ = fs::remove_file(cache_meta_path(&cached));
+        let _ = fs::remove_file(&cached);
+
+        let loader = |skip_cache| UrlModuleLoader {
+            skip_cache,
+            ..Default::default()
+        };
+        let source = loader(false).load(&url)?;
+        assert_eq!(source, "export default 1;");
+        // Served from cache without a request.
+        let source = loader(false).load(&url)?;
+        assert_eq!(source, "export default 1;");
+        // Revalidated with a conditional request.
+        let source = loader(true).load(&url)?;
+        assert_eq!(source, "export default 1;");
+
+        assert_eq!(*statuses.lock().unwrap(), [200, 304]);
+        Ok(())
+    }
+
+    #[test]
+    fn data_loader_should_work() -> Result<()> {
+        let loader = DataModuleLoader;
+        assert_eq!(
+            loader.load("data:text/javascript,export%20default%201;")?,
+            "export default 1;"
+        );
+        assert_eq!(
+            loader.load("data:application/json;base64,eyJhIjoxfQ==")?,
+            r#"export default {"a":1};"#
+        );
+        assert!(loader.load("data:application/json,{oops").is_err());
+        assert!(loader.load("data:image/png;base64,AAAA").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn url_loader_should_verify_integrity() -> Result<()> {
+        let url = "https://example.com/pinned.js";
+        let source = "export const pinned = true;";
+        fs::create_dir_all(CACHE_DIR.as_path())?;
+        fs::write(cache_path(url), source)?;
+        let hash = Sha256::default().digest(source.as_bytes()).to_hex();
+
+        let loader = UrlModuleLoader::default();
+        assert_eq!(loader.load(&format!("{url}#sha256={hash}"))?, source);
+        assert_eq!(loader.load(url)?, source);
+        let err = loader
+            .load(&format!("{url}#sha256={}", "0".repeat(64)))
+            .unwrap_err();
+        assert!(err.to_string().contains("Integrity check failed"));
+        Ok(())
+    }
+}
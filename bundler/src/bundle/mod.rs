@@ -1,14 +1,43 @@
-mod loaders;
+mod auth;
+mod cjs;
+mod core_modules;
+mod coverage;
+mod defines;
+mod dts;
+mod import_meta;
+pub(crate) mod loaders;
 mod modules;
+mod node_compat;
+mod plugins;
+mod proxy;
+mod registry;
+mod timings;
 mod transpilers;
 
+pub use auth::{AUTH_TOKENS_ENV, AuthConfig, Credential};
+pub use core_modules::{CORE_MODULES, Capabilities, HostModules, core_module_name};
+pub use coverage::{COVERAGE_GLOBAL, Coverage};
+pub use defines::{DEFINE_ENV_PREFIX, Defines};
+pub use dts::emit_declarations;
+pub use loaders::CACHE_DIR;
+pub use modules::ImportMap;
+pub use node_compat::{NodeCompat, NodeShim};
+pub use plugins::{Plugin, Plugins};
+pub use proxy::ProxyConfig;
+pub use registry::{
+    DENO_LAND_CDN_ENV, DENO_LAND_PREFIX, DenoLandUrl, JSR_PREFIX, JSR_URL_ENV, JsrSpecifier,
+};
+pub use timings::{Phase, Timings};
+
 use anyhow::Error;
 use anyhow::Result;
-use modules::ImportMap;
 use modules::load_import;
 use modules::resolve_import;
+use modules::resolve_target_variant;
+use registry::Registry;
 use std::collections::HashMap;
 use std::path::Path;
+use std::time::Instant;
 use swc_bundler::Bundler;
 use swc_bundler::Config;
 use swc_bundler::Load;
@@ -38,9 +67,91 @@ pub struct Options {
     pub minify: bool,
     pub import_map: Option<ImportMap>,
     pub module_type: ModuleType,
+    /// Credentials for URL imports, loaded from the environment by default.
+    pub auth: AuthConfig,
+    /// Proxy for URL imports, read from HTTP(S)_PROXY/NO_PROXY by default.
+    pub proxy: ProxyConfig,
+    /// Capability manifest of the target runtime, importing a core module it
+    /// doesn't provide fails the build.
+    pub capabilities: Capabilities,
+    /// Compile-time constants, code behind constant false conditions is
+    /// removed. Includes `DINO_DEFINE_*` from the environment by default.
+    pub defines: Defines,
+    /// Runtime target such as "server" or "dev". Local imports of `foo.ts`
+    /// pick `foo.<target>.ts` when it exists, and `import.meta.target` is
+    /// set to it.
+    pub target: Option<String>,
+    /// How `node:` built-ins (and their bare names) are provided.
+    pub node_compat: NodeCompat,
+    /// Custom resolve, load and transform hooks, run before the built-in
+    /// loaders.
+    pub plugins: Plugins,
+    /// Instrument local modules with line hit counters, collected in
+    /// `globalThis.__dino_coverage` and reported with `Coverage`.
+    pub coverage: bool,
 }
 
 pub fn run_bundle(entry: &str, options: &Options) -> Result<String> {
+    let mut source = bundle(entry, options)?;
+
+    if !options.minify {
+        // Decorate output with the following messages.
+        let messages = [
+            format!("// Dune v{}\n", env!("CARGO_PKG_VERSION")),
+            "// It's not recommended to edit this code manually since it's generated by `dune bundle`\n\n".into()
+        ];
+        messages.iter().rev().for_each(|msg| {
+            source.insert_str(0, msg);
+        });
+    }
+
+    Ok(source)
+}
+
+/// Same as `run_bundle`, but also reports per-phase and per-module durations.
+pub fn run_bundle_with_timings(entry: &str, options: &Options) -> Result<(String, Timings)> {
+    let (ret, timings) = timings::collect(|| run_bundle(entry, options));
+    Ok((ret?, timings))
+}
+
+/// Bundles the entry into readable (non-minified) code without the banner,
+/// which keeps the output stable for snapshot tests.
+pub fn bundle_to_string_pretty(entry: &str, options: &Options) -> Result<String> {
+    let options = Options {
+        skip_cache: options.skip_cache,
+        minify: false,
+        import_map: options.import_map.clone(),
+        module_type: match options.module_type {
+            ModuleType::Es => ModuleType::Es,
+            ModuleType::Iife => ModuleType::Iife,
+        },
+        auth: options.auth.clone(),
+        proxy: options.proxy.clone(),
+        capabilities: options.capabilities.clone(),
+        defines: options.defines.clone(),
+        target: options.target.clone(),
+        node_compat: options.node_compat.clone(),
+        plugins: options.plugins.clone(),
+        coverage: options.coverage,
+    };
+    bundle(entry, &options)
+}
+
+/// Downloads a remote module (or reads it from the cache) and returns its
+/// source with its sha256, the checksum pinned with `#sha256=` in imports.
+pub fn fetch_remote(url: &str, options: &Options) -> Result<(String, String)> {
+    let loader = loaders::UrlModuleLoader {
+        skip_cache: options.skip_cache,
+        auth: options.auth.clone(),
+        proxy: options.proxy.clone(),
+    };
+    let (url, integrity) = loaders::split_integrity(url);
+    let source = loader.fetch(url, integrity)?;
+    let checksum = loaders::sha256_hex(&source);
+    Ok((source, checksum))
+}
+
+fn bundle(entry: &str, options: &Options) -> Result<String> {
     // Create SWC globals and an LRC sourcemap.
     let globals = Globals::default();
     let cm = Lrc::new(SourceMap::new(FilePathMapping::empty()));
@@ -58,14 +169,20 @@ pub fn run_bundle(entry: &str, options: &Options) -> Result<String> {
         Loader {
             cm: cm.clone(),
             options,
+            entry,
+        },
+        Resolver {
+            options,
+            registry: Registry::default(),
         },
-        Resolver { options },
         Config {
             require: false,
             module: module_type,
             ..Default::default()
         },
-        Box::new(Hook),
+        Box::new(Hook {
+            target: options.target.clone(),
+        }),
     );
 
     // Create bundle entries.
@@ -92,29 +209,17 @@ pub fn run_bundle(entry: &str, options: &Options) -> Result<String> {
             wr: Box::new(JsWriter::new(cm, "\n", &mut buf, None)),
         };
 
-        emitter.emit_module(&bundle.module)?;
+        timings::timed(Phase::Emit, || emitter.emit_module(&bundle.module))?;
     }
 
     // Build source from bytes.
-    let mut source = String::from_utf8(buf).unwrap();
-
-    if !options.minify {
-        // Decorate output with the following messages.
-        let messages = [
-            format!("// Dune v{}\n", env!("CARGO_PKG_VERSION")),
-            "// It's not recommended to edit this code manually since it's generated by `dune bundle`\n\n".into()
-        ];
-        messages.iter().rev().for_each(|msg| {
-            source.insert_str(0, msg);
-        });
-    }
-
-    Ok(source)
+    Ok(String::from_utf8(buf).unwrap())
 }
 
 struct Loader<'s> {
     cm: Lrc<SourceMap>,
     options: &'s Options,
+    entry: &'s str,
 }
 
 impl Load for Loader<'_> {
@@ -126,26 +231,56 @@ impl Load for Loader<'_> {
         };
 
         // Try load the module's source-code.
-        let source = load_import(&specifier, self.options.skip_cache)?;
-        let path = FileName::Real(specifier.into());
+        let start = Instant::now();
+        let plugins = &self.options.plugins;
+        let source = match plugins.load(&specifier)? {
+            Some(source) => source,
+            None if self.options.coverage && coverage::covers(&specifier) => {
+                coverage::load(&specifier)?
+            }
+            None => load_import(&specifier, self.options)?,
+        };
+        let mut source = plugins.transform(&specifier, source)?;
+        if !self.options.defines.is_empty() {
+            source = self.options.defines.apply(&specifier, &source)?;
+        }
+        let path = FileName::Real(specifier.as_str().into());
         let fm = self.cm.new_source_file(path.into(), source);
 
         let handler =
             Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(self.cm.clone()));
 
         // Parse JavaScript source into an SWC module.
-        let module = match parse_file_as_module(
-            &fm,
-            Syntax::Es(EsSyntax::default()),
-            EsVersion::latest(),
-            None,
-            &mut vec![],
-        )
-        .map_err(|e| e.into_diagnostic(&handler).emit())
-        {
+        let module = match timings::timed(Phase::Parse, || {
+            parse_file_as_module(
+                &fm,
+                Syntax::Es(EsSyntax::default()),
+                EsVersion::latest(),
+                None,
+                &mut vec![],
+            )
+        }) {
             Ok(module) => module,
-            Err(_) => std::process::exit(1),
+            Err(e) => {
+                let pos = self.cm.lookup_char_pos(swc_common::Spanned::span(&e).lo);
+                let message = format!(
+                    "{specifier}:{}:{}: {}",
+                    pos.line,
+                    pos.col_display + 1,
+                    e.kind().msg()
+                );
+                e.into_diagnostic(&handler).emit();
+                anyhow::bail!("failed to parse {message}");
+            }
         };
+        let mut module = cjs::wrap(&self.cm, module)?;
+        let span = module.span;
+        import_meta::inline(&mut module, || {
+            let url = import_meta::module_url(&specifier);
+            let is_entry = specifier == self.entry;
+            import_meta::props(span, &url, is_entry, self.options.target.as_deref())
+        })?;
+        timings::record_module(&fm.name.to_string(), start.elapsed());
 
         Ok(ModuleData {
             fm,
@@ -157,6 +292,7 @@ impl Load for Loader<'_> {
 
 struct Resolver<'a> {
     options: &'a Options,
+    registry: Registry,
 }
 
 impl Resolve for Resolver<'_> {
@@ -168,21 +304,34 @@ impl Resolve for Resolver<'_> {
         };
 
         // Try resolve the specifier.
+        let path = timings::timed(Phase::Resolve, || {
+            if let Some(path) = self.options.plugins.resolve(specifier, base)? {
+                return Ok(path);
+            }
+            // Import maps take precedence over bare Node.js built-in names.
+            let mapped = (self.options.import_map.as_ref())
+                .is_some_and(|map| map.lookup(specifier).is_some());
+            if !mapped && let Some(path) = self.options.node_compat.builtin_specifier(specifier) {
+                return Ok(path);
+            }
+            let path = resolve_import(base, specifier, self.options, &self.registry)?;
+            let variant = self
+                .options
+                .target
+                .as_deref()
+                .and_then(|target| resolve_target_variant(&path, target));
+            anyhow::Ok(variant.unwrap_or(path))
+        })?;
         Ok(Resolution {
-            filename: FileName::Real(
-                Path::new(&resolve_import(
-                    base,
-                    specifier,
-                    self.options.import_map.clone(),
-                )?)
-                .to_path_buf(),
-            ),
+            filename: FileName::Real(Path::new(&path).to_path_buf()),
             slug: None,
         })
     }
 }
 
-struct Hook;
+struct Hook {
+    target: Option<String>,
+}
 
 impl swc_bundler::Hook for Hook {
     fn get_import_meta_props(
@@ -190,36 +339,8 @@ impl swc_bundler::Hook for Hook {
         span: Span,
         module: &ModuleRecord,
     ) -> Result<Vec<KeyValueProp>, Error> {
-        // Get filename as string.
-        let file_name = module.file_name.to_string();
-        let file_name = resolve_import(None, &file_name, None)?;
-
-        // Compute .main and .url properties.
-        Ok(vec![
-            KeyValueProp {
-                key: PropName::Ident(IdentName::new("url".into(), span)),
-                value: Box::new(Expr::Lit(Lit::Str(Str {
-                    span,
-                    raw: None,
-                    value: file_name.into(),
-                }))),
-            },
-            KeyValueProp {
-                key: PropName::Ident(IdentName::new("main".into(), span)),
-                value: Box::new(if module.is_entry {
-                    Expr::Member(MemberExpr {
-                        span,
-                        obj: Box::new(Expr::MetaProp(MetaPropExpr {
-                            span,
-                            kind: MetaPropKind::ImportMeta,
-                        })),
-                        prop: MemberProp::Ident(IdentName::new("main".into(), span)),
-                    })
-                } else {
-                    Expr::Lit(Lit::Bool(Bool { span, value: false }))
-                }),
-            },
-        ])
+        let url = import_meta::module_url(&module.file_name.to_string());
+        import_meta::props(span, &url, module.is_entry, self.target.as_deref())
     }
 }
 
@@ -230,6 +351,14 @@ impl Default for Options {
             minify: true,
             import_map: None,
             module_type: ModuleType::Iife,
+            auth: AuthConfig::from_env(),
+            proxy: ProxyConfig::from_env(),
+            capabilities: Capabilities::default(),
+            defines: Defines::from_env(),
+            target: None,
+            node_compat: NodeCompat::default(),
+            plugins: Plugins::default(),
+            coverage: false,
         }
     }
 }
@@ -6,7 +6,11 @@ use regex::Regex;
 use serde_json::Value;
 use url::Url;
 
-use super::loaders::{FsModuleLoader, ModuleLoader, UrlModuleLoader};
+use super::Options;
+use super::core_modules::{CoreModuleLoader, core_specifier};
+use super::loaders::{DataModuleLoader, EXTENSIONS, FsModuleLoader, ModuleLoader, UrlModuleLoader};
+use super::node_compat::{NODE_PREFIX, NodeModuleLoader};
+use super::registry::Registry;
 
 pub type ModulePath = String;
 pub type ModuleSource = String;
@@ -21,20 +25,31 @@ pub struct ImportMap {
 
 lazy_static! {
     // Windows absolute path regex validator.
-    static ref WINDOWS_REGEX: Regex = Regex::new(r"^[a-zA-Z]:\\").unwrap();
+    static ref WINDOWS_REGEX: Regex = Regex::new(r"^[a-zA-Z]:[\\/]").unwrap();
     // URL regex validator (string begins with http:// or https://).
     static ref URL_REGEX: Regex = Regex::new(r"^(http|https)://").unwrap();
 }
 
 /// Loads an import using the appropriate loader.
-pub fn load_import(specifier: &str, skip_cache: bool) -> Result<ModuleSource> {
+pub fn load_import(specifier: &str, options: &Options) -> Result<ModuleSource> {
     // Look the params and choose a loader.
     let loader: Box<dyn ModuleLoader> = match (
         WINDOWS_REGEX.is_match(specifier),
         Url::parse(specifier).is_ok(),
     ) {
         (true, _) => Box::new(FsModuleLoader),
-        (_, true) => Box::new(UrlModuleLoader { skip_cache }),
+        _ if specifier.starts_with(NODE_PREFIX) => Box::new(NodeModuleLoader {
+            compat: options.node_compat.clone(),
+        }),
+        _ if core_specifier(specifier).is_some() => Box::new(CoreModuleLoader {
+            capabilities: options.capabilities.clone(),
+        }),
+        _ if specifier.starts_with("data:") => Box::new(DataModuleLoader),
+        (_, true) => Box::new(UrlModuleLoader {
+            skip_cache: options.skip_cache,
+            auth: options.auth.clone(),
+            proxy: options.proxy.clone(),
+        }),
         _ => Box::new(FsModuleLoader),
     };
 
@@ -46,16 +61,31 @@ pub fn load_import(specifier: &str, skip_cache: bool) -> Result<ModuleSource> {
 pub fn resolve_import(
     base: Option<&str>,
     specifier: &str,
-    import_map: Option<ImportMap>,
+    options: &Options,
+    registry: &Registry,
 ) -> Result<ModulePath> {
     // Use import-maps if available.
-    let specifier = match import_map {
+    let specifier = match &options.import_map {
         Some(map) => map.lookup(specifier).unwrap_or_else(|| specifier.into()),
         None => specifier.into(),
     };
 
+    // Registry specifiers and version ranges resolve to a concrete URL.
+    if let Some(url) = registry.resolve(&specifier, options)? {
+        return Ok(url);
+    }
+
     // Look the params and choose a loader.
     let loader: Box<dyn ModuleLoader> = {
+        if specifier.starts_with("data:") {
+            return DataModuleLoader.resolve(base, &specifier);
+        }
+        if specifier.starts_with(NODE_PREFIX) {
+            return Ok(specifier);
+        }
+        if let Some(path) = core_specifier(&specifier) {
+            return Ok(path);
+        }
         let is_url_import = URL_REGEX.is_match(&specifier)
             || match base {
                 Some(base) => URL_REGEX.is_match(base),
@@ -72,6 +102,31 @@ pub fn resolve_import(
     loader.resolve(base, &specifier)
 }
 
+/// Picks the `<name>.<target>.<ext>` variant of a resolved local module when
+/// it exists, e.g. `db.ts` resolves to `db.server.ts` for the "server" target.
+pub fn resolve_target_variant(path: &str, target: &str) -> Option<ModulePath> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let candidates: Vec<_> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => vec![format!("{stem}.{target}.{ext}")],
+        None => {
+            let name = path.file_name()?.to_str()?;
+            EXTENSIONS
+                .iter()
+                .map(|ext| format!("{name}.{target}.{ext}"))
+                .collect()
+        }
+    };
+    candidates
+        .into_iter()
+        .map(|name| path.with_file_name(name))
+        .find(|variant| variant.is_file())
+        .map(|variant| variant.to_string_lossy().to_string())
+}
+
 impl ImportMap {
     /// Creates an ImportMap from JSON text.
     pub fn parse_from_json(text: &str) -> Result<ImportMap> {
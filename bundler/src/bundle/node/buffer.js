@@ -0,0 +1,217 @@
+// Subset of `node:buffer`: a `Buffer` built on Uint8Array with utf8, hex,
+// base64, base64url and latin1 encodings.
+const BASE64 = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+function utf8Encode(str) {
+  const bytes = [];
+  for (let i = 0; i < str.length; i++) {
+    let code = str.charCodeAt(i);
+    if (code >= 0xd800 && code <= 0xdbff && i + 1 < str.length) {
+      const next = str.charCodeAt(i + 1);
+      if (next >= 0xdc00 && next <= 0xdfff) {
+        code = 0x10000 + ((code - 0xd800) << 10) + (next - 0xdc00);
+        i++;
+      }
+    }
+    if (code >= 0xd800 && code <= 0xdfff) code = 0xfffd;
+    if (code < 0x80) bytes.push(code);
+    else if (code < 0x800) bytes.push(0xc0 | (code >> 6), 0x80 | (code & 0x3f));
+    else if (code < 0x10000) bytes.push(0xe0 | (code >> 12), 0x80 | ((code >> 6) & 0x3f), 0x80 | (code & 0x3f));
+    else
+      bytes.push(
+        0xf0 | (code >> 18),
+        0x80 | ((code >> 12) & 0x3f),
+        0x80 | ((code >> 6) & 0x3f),
+        0x80 | (code & 0x3f),
+      );
+  }
+  return bytes;
+}
+
+function utf8Decode(bytes) {
+  let out = "";
+  for (let i = 0; i < bytes.length; ) {
+    const b = bytes[i];
+    const need = b < 0x80 ? 0 : b >= 0xf0 && b < 0xf8 ? 3 : b >= 0xe0 ? 2 : b >= 0xc0 ? 1 : -1;
+    let code = need === 0 ? b : need === 1 ? b & 0x1f : need === 2 ? b & 0x0f : b & 0x07;
+    let valid = need >= 0 && i + need < bytes.length;
+    for (let j = 1; valid && j <= need; j++) {
+      if ((bytes[i + j] & 0xc0) !== 0x80) valid = false;
+      else code = (code << 6) | (bytes[i + j] & 0x3f);
+    }
+    if (!valid) {
+      out += "�";
+      i++;
+      continue;
+    }
+    out += String.fromCodePoint(code);
+    i += need + 1;
+  }
+  return out;
+}
+
+function base64Encode(bytes, url) {
+  let out = "";
+  for (let i = 0; i < bytes.length; i += 3) {
+    const n = (bytes[i] << 16) | ((bytes[i + 1] ?? 0) << 8) | (bytes[i + 2] ?? 0);
+    out += BASE64[(n >> 18) & 63] + BASE64[(n >> 12) & 63];
+    out += i + 1 < bytes.length ? BASE64[(n >> 6) & 63] : "=";
+    out += i + 2 < bytes.length ? BASE64[n & 63] : "=";
+  }
+  return url ? out.replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "") : out;
+}
+
+function base64Decode(str) {
+  const clean = str.replace(/-/g, "+").replace(/_/g, "/").replace(/[^A-Za-z0-9+/]/g, "");
+  const bytes = [];
+  let bits = 0;
+  let value = 0;
+  for (const c of clean) {
+    value = (value << 6) | BASE64.indexOf(c);
+    bits += 6;
+    if (bits >= 8) {
+      bits -= 8;
+      bytes.push((value >> bits) & 0xff);
+    }
+  }
+  return bytes;
+}
+
+function encode(str, encoding = "utf8") {
+  switch (encoding.toLowerCase()) {
+    case "utf8":
+    case "utf-8":
+      return utf8Encode(str);
+    case "hex": {
+      const bytes = [];
+      for (let i = 0; i + 1 < str.length; i += 2) {
+        const byte = parseInt(str.slice(i, i + 2), 16);
+        if (Number.isNaN(byte)) break;
+        bytes.push(byte);
+      }
+      return bytes;
+    }
+    case "base64":
+    case "base64url":
+      return base64Decode(str);
+    case "latin1":
+    case "binary":
+    case "ascii":
+      return Array.from(str, (c) => c.charCodeAt(0) & 0xff);
+    default:
+      throw new TypeError(`Unknown encoding: ${encoding}`);
+  }
+}
+
+export class Buffer extends Uint8Array {
+  static from(value, encodingOrOffset, length) {
+    if (typeof value === "string") return new Buffer(encode(value, encodingOrOffset));
+    if (value instanceof ArrayBuffer) return new Buffer(value, encodingOrOffset ?? 0, length);
+    if (ArrayBuffer.isView(value)) {
+      return new Buffer(new Uint8Array(value.buffer, value.byteOffset, value.byteLength));
+    }
+    if (Array.isArray(value)) return new Buffer(value);
+    throw new TypeError("The first argument must be a string, Buffer, ArrayBuffer or Array");
+  }
+
+  static alloc(size, fill = 0, encoding) {
+    const buf = new Buffer(size);
+    if (fill !== 0) buf.fill(fill, 0, size, encoding);
+    return buf;
+  }
+
+  static allocUnsafe(size) {
+    return new Buffer(size);
+  }
+
+  static isBuffer(value) {
+    return value instanceof Buffer;
+  }
+
+  static isEncoding(encoding) {
+    return ["utf8", "utf-8", "hex", "base64", "base64url", "latin1", "binary", "ascii"].includes(
+      String(encoding).toLowerCase(),
+    );
+  }
+
+  static byteLength(value, encoding) {
+    return typeof value === "string" ? encode(value, encoding).length : value.byteLength;
+  }
+
+  static concat(list, totalLength) {
+    const total = totalLength ?? list.reduce((n, b) => n + b.length, 0);
+    const buf = Buffer.alloc(total);
+    let offset = 0;
+    for (const b of list) {
+      if (offset >= total) break;
+      buf.set(b.subarray(0, total - offset), offset);
+      offset += b.length;
+    }
+    return buf;
+  }
+
+  static compare(a, b) {
+    return a.compare(b);
+  }
+
+  fill(value, offset = 0, end = this.length, encoding) {
+    if (typeof value === "string") {
+      const bytes = encode(value, encoding);
+      for (let i = offset; i < end && bytes.length; i++) this[i] = bytes[(i - offset) % bytes.length];
+      return this;
+    }
+    return super.fill(value, offset, end);
+  }
+
+  toString(encoding = "utf8", start = 0, end = this.length) {
+    const bytes = this.subarray(start, end);
+    switch (encoding.toLowerCase()) {
+      case "utf8":
+      case "utf-8":
+        return utf8Decode(bytes);
+      case "hex":
+        return Array.from(bytes, (b) => b.toString(16).padStart(2, "0")).join("");
+      case "base64":
+        return base64Encode(bytes, false);
+      case "base64url":
+        return base64Encode(bytes, true);
+      case "latin1":
+      case "binary":
+        return String.fromCharCode(...bytes);
+      case "ascii":
+        return String.fromCharCode(...Array.from(bytes, (b) => b & 0x7f));
+      default:
+        throw new TypeError(`Unknown encoding: ${encoding}`);
+    }
+  }
+
+  toJSON() {
+    return { type: "Buffer", data: Array.from(this) };
+  }
+
+  equals(other) {
+    return this.compare(other) === 0;
+  }
+
+  compare(other) {
+    const n = Math.min(this.length, other.length);
+    for (let i = 0; i < n; i++) {
+      if (this[i] !== other[i]) return this[i] < other[i] ? -1 : 1;
+    }
+    return Math.sign(this.length - other.length);
+  }
+
+  write(str, offset = 0, encoding = "utf8") {
+    const bytes = encode(str, encoding).slice(0, this.length - offset);
+    this.set(bytes, offset);
+    return bytes.length;
+  }
+
+  slice(start, end) {
+    return this.subarray(start, end);
+  }
+}
+
+export const constants = { MAX_LENGTH: 2 ** 32 - 1 };
+
+export default { Buffer, constants };
@@ -0,0 +1,111 @@
+// POSIX subset of `node:path`.
+export const sep = "/";
+export const delimiter = ":";
+
+function normalizeSegments(path, absolute) {
+  const out = [];
+  for (const segment of path.split("/")) {
+    if (segment === "" || segment === ".") continue;
+    if (segment === "..") {
+      if (out.length && out[out.length - 1] !== "..") out.pop();
+      else if (!absolute) out.push("..");
+    } else {
+      out.push(segment);
+    }
+  }
+  return out;
+}
+
+export function isAbsolute(path) {
+  return path.startsWith("/");
+}
+
+export function normalize(path) {
+  if (path === "") return ".";
+  const absolute = isAbsolute(path);
+  const trailing = path.endsWith("/");
+  let ret = normalizeSegments(path, absolute).join("/");
+  if (ret === "" && !absolute) ret = ".";
+  if (ret !== "" && trailing) ret += "/";
+  return absolute ? `/${ret}` : ret;
+}
+
+export function join(...paths) {
+  const joined = paths.filter((p) => p !== "").join("/");
+  return joined === "" ? "." : normalize(joined);
+}
+
+export function resolve(...paths) {
+  let resolved = "";
+  for (let i = paths.length - 1; i >= 0 && !isAbsolute(resolved); i--) {
+    if (paths[i]) resolved = resolved ? `${paths[i]}/${resolved}` : paths[i];
+  }
+  if (!isAbsolute(resolved)) resolved = `/${resolved}`;
+  return `/${normalizeSegments(resolved, true).join("/")}`;
+}
+
+export function relative(from, to) {
+  const a = normalizeSegments(resolve(from), true);
+  const b = normalizeSegments(resolve(to), true);
+  let i = 0;
+  while (i < a.length && i < b.length && a[i] === b[i]) i++;
+  return [...a.slice(i).map(() => ".."), ...b.slice(i)].join("/");
+}
+
+export function dirname(path) {
+  const trimmed = path.length > 1 ? path.replace(/\/+$/, "") : path;
+  const i = trimmed.lastIndexOf("/");
+  if (i === -1) return ".";
+  if (i === 0) return "/";
+  return trimmed.slice(0, i);
+}
+
+export function basename(path, ext) {
+  const trimmed = path.length > 1 ? path.replace(/\/+$/, "") : path;
+  let base = trimmed.slice(trimmed.lastIndexOf("/") + 1);
+  if (ext && base !== ext && base.endsWith(ext)) base = base.slice(0, -ext.length);
+  return base;
+}
+
+export function extname(path) {
+  const base = basename(path);
+  const i = base.lastIndexOf(".");
+  return i <= 0 ? "" : base.slice(i);
+}
+
+export function parse(path) {
+  const base = basename(path);
+  const ext = extname(path);
+  const dir = path.includes("/") ? dirname(path) : "";
+  return {
+    root: isAbsolute(path) ? "/" : "",
+    dir,
+    base,
+    ext,
+    name: ext ? base.slice(0, -ext.length) : base,
+  };
+}
+
+export function format({ root = "", dir, base, name = "", ext = "" }) {
+  const file = base ?? `${name}${ext}`;
+  const prefix = dir ?? root;
+  if (!prefix) return file;
+  return prefix === root ? `${prefix}${file}` : `${prefix}/${file}`;
+}
+
+export const posix = {
+  sep,
+  delimiter,
+  isAbsolute,
+  normalize,
+  join,
+  resolve,
+  relative,
+  dirname,
+  basename,
+  extname,
+  parse,
+  format,
+};
+
+export default posix;
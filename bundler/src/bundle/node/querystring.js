@@ -0,0 +1,56 @@
+// `node:querystring`.
+export function escape(str) {
+  return encodeURIComponent(str);
+}
+
+export function unescape(str) {
+  try {
+    return decodeURIComponent(str.replace(/\+/g, " "));
+  } catch {
+    return str;
+  }
+}
+
+export function parse(str, sep = "&", eq = "=") {
+  const ret = {};
+  if (typeof str !== "string" || str === "") return ret;
+  for (const pair of str.split(sep)) {
+    if (pair === "") continue;
+    const i = pair.indexOf(eq);
+    const key = unescape(i === -1 ? pair : pair.slice(0, i));
+    const value = i === -1 ? "" : unescape(pair.slice(i + eq.length));
+    if (!Object.prototype.hasOwnProperty.call(ret, key)) ret[key] = value;
+    else if (Array.isArray(ret[key])) ret[key].push(value);
+    else ret[key] = [ret[key], value];
+  }
+  return ret;
+}
+
+function stringifyValue(value) {
+  switch (typeof value) {
+    case "string":
+      return value;
+    case "number":
+      return Number.isFinite(value) ? String(value) : "";
+    case "bigint":
+    case "boolean":
+      return String(value);
+    default:
+      return "";
+  }
+}
+
+export function stringify(obj, sep = "&", eq = "=") {
+  if (obj === null || typeof obj !== "object") return "";
+  const parts = [];
+  for (const [key, value] of Object.entries(obj)) {
+    const values = Array.isArray(value) ? value : [value];
+    for (const v of values) parts.push(`${escape(key)}${eq}${escape(stringifyValue(v))}`);
+  }
+  return parts.join(sep);
+}
+
+export const decode = parse;
+export const encode = stringify;
+
+export default { escape, unescape, parse, stringify, decode, encode };
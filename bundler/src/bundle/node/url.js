@@ -0,0 +1,254 @@
+// Subset of `node:url`: a WHATWG-like `URL` for hierarchical URLs,
+// `URLSearchParams` and the file URL helpers.
+const DEFAULT_PORTS = { "http:": "80", "https:": "443", "ws:": "80", "wss:": "443", "ftp:": "21" };
+const SPECIAL = new Set(["http:", "https:", "ws:", "wss:", "ftp:", "file:"]);
+const URL_RE =
+  /^([a-zA-Z][a-zA-Z\d+.-]*:)(?:\/\/(?:([^:@/?#]*)(?::([^@/?#]*))?@)?(\[[^\]]*\]|[^:/?#]*)(?::(\d*))?)?([^?#]*)(\?[^#]*)?(#.*)?$/;
+
+function removeDotSegments(path) {
+  const out = [];
+  const segments = path.split("/");
+  for (let i = 0; i < segments.length; i++) {
+    const segment = segments[i];
+    const last = i === segments.length - 1;
+    if (segment === "..") {
+      if (out.length > 1) out.pop();
+      if (last) out.push("");
+    } else if (segment === ".") {
+      if (last) out.push("");
+    } else {
+      out.push(segment);
+    }
+  }
+  return out.join("/");
+}
+
+function resolveRelative(input, base) {
+  if (input.startsWith("//")) return `${base.protocol}${input}`;
+  if (input.startsWith("/")) return `${base.protocol}//${base.host}${input}`;
+  if (input === "") return base.href.replace(/#.*$/, "");
+  if (input.startsWith("?")) return `${base.origin === "null" ? base.protocol : base.origin}${base.pathname}${input}`;
+  if (input.startsWith("#")) return `${base.href.replace(/#.*$/, "")}${input}`;
+  const dir = base.pathname.replace(/[^/]*$/, "");
+  return `${base.protocol}//${base.host}${dir}${input}`;
+}
+
+export class URLSearchParams {
+  #list = [];
+  #url = null;
+
+  constructor(init = "") {
+    if (typeof init === "string") {
+      this.#parse(init);
+    } else if (init instanceof URLSearchParams) {
+      this.#list = [...init];
+    } else if (Array.isArray(init)) {
+      this.#list = init.map(([k, v]) => [String(k), String(v)]);
+    } else if (init && typeof init === "object") {
+      this.#list = Object.entries(init).map(([k, v]) => [k, String(v)]);
+    }
+  }
+
+  #parse(query) {
+    this.#list = [];
+    for (const pair of query.replace(/^\?/, "").split("&")) {
+      if (pair === "") continue;
+      const i = pair.indexOf("=");
+      const decode = (s) => decodeURIComponent(s.replace(/\+/g, " "));
+      this.#list.push(i === -1 ? [decode(pair), ""] : [decode(pair.slice(0, i)), decode(pair.slice(i + 1))]);
+    }
+  }
+
+  #update() {
+    if (this.#url) this.#url._setSearch(this.toString());
+  }
+
+  static _attach(params, url) {
+    params.#url = url;
+  }
+
+  static _reset(params, query) {
+    params.#parse(query);
+  }
+
+  get size() {
+    return this.#list.length;
+  }
+  append(name, value) {
+    this.#list.push([String(name), String(value)]);
+    this.#update();
+  }
+  delete(name) {
+    this.#list = this.#list.filter(([k]) => k !== name);
+    this.#update();
+  }
+  get(name) {
+    const entry = this.#list.find(([k]) => k === name);
+    return entry ? entry[1] : null;
+  }
+  getAll(name) {
+    return this.#list.filter(([k]) => k === name).map(([, v]) => v);
+  }
+  has(name) {
+    return this.#list.some(([k]) => k === name);
+  }
+  set(name, value) {
+    const i = this.#list.findIndex(([k]) => k === name);
+    if (i === -1) {
+      this.#list.push([String(name), String(value)]);
+    } else {
+      this.#list[i][1] = String(value);
+      this.#list = this.#list.filter(([k], j) => k !== name || j <= i);
+    }
+    this.#update();
+  }
+  sort() {
+    this.#list.sort(([a], [b]) => (a < b ? -1 : a > b ? 1 : 0));
+    this.#update();
+  }
+  forEach(callback, thisArg) {
+    for (const [k, v] of this.#list) callback.call(thisArg, v, k, this);
+  }
+  keys() {
+    return this.#list.map(([k]) => k)[Symbol.iterator]();
+  }
+  values() {
+    return this.#list.map(([, v]) => v)[Symbol.iterator]();
+  }
+  entries() {
+    return this.#list.map(([k, v]) => [k, v])[Symbol.iterator]();
+  }
+  [Symbol.iterator]() {
+    return this.entries();
+  }
+  toString() {
+    const encode = (s) => encodeURIComponent(s).replace(/%20/g, "+");
+    return this.#list.map(([k, v]) => `${encode(k)}=${encode(v)}`).join("&");
+  }
+}
+
+export class URL {
+  #parts;
+  #searchParams;
+
+  constructor(input, base) {
+    input = String(input).trim();
+    let m = URL_RE.exec(input);
+    if (!m && base !== undefined) {
+      const baseUrl = base instanceof URL ? base : new URL(base);
+      m = URL_RE.exec(resolveRelative(input, baseUrl));
+    }
+    if (!m) throw new TypeError(`Invalid URL: ${input}`);
+    const protocol = m[1].toLowerCase();
+    const special = SPECIAL.has(protocol);
+    let pathname = m[6] || "";
+    if (m[4] !== undefined || special) pathname = removeDotSegments(pathname || "/");
+    if (special && !pathname.startsWith("/")) pathname = `/${pathname}`;
+    const port = m[5] && m[5] !== DEFAULT_PORTS[protocol] ? m[5] : "";
+    this.#parts = {
+      protocol,
+      username: m[2] || "",
+      password: m[3] || "",
+      hostname: (m[4] || "").toLowerCase(),
+      port,
+      pathname,
+      search: m[7] && m[7] !== "?" ? m[7] : "",
+      hash: m[8] && m[8] !== "#" ? m[8] : "",
+      opaque: m[4] === undefined && !special,
+    };
+    this.#searchParams = new URLSearchParams(this.#parts.search);
+    URLSearchParams._attach(this.#searchParams, this);
+  }
+
+  static canParse(input, base) {
+    try {
+      new URL(input, base);
+      return true;
+    } catch {
+      return false;
+    }
+  }
+
+  _setSearch(query) {
+    this.#parts.search = query ? `?${query}` : "";
+  }
+
+  get protocol() {
+    return this.#parts.protocol;
+  }
+  get username() {
+    return this.#parts.username;
+  }
+  get password() {
+    return this.#parts.password;
+  }
+  get hostname() {
+    return this.#parts.hostname;
+  }
+  set hostname(value) {
+    this.#parts.hostname = String(value).toLowerCase();
+  }
+  get port() {
+    return this.#parts.port;
+  }
+  set port(value) {
+    value = String(value);
+    this.#parts.port = value === DEFAULT_PORTS[this.protocol] ? "" : value;
+  }
+  get host() {
+    return this.port ? `${this.hostname}:${this.port}` : this.hostname;
+  }
+  get origin() {
+    return DEFAULT_PORTS[this.protocol] !== undefined ? `${this.protocol}//${this.host}` : "null";
+  }
+  get pathname() {
+    return this.#parts.pathname;
+  }
+  set pathname(value) {
+    value = String(value);
+    this.#parts.pathname = value.startsWith("/") ? value : `/${value}`;
+  }
+  get search() {
+    return this.#parts.search;
+  }
+  set search(value) {
+    value = String(value).replace(/^\?/, "");
+    this._setSearch(value);
+    URLSearchParams._reset(this.#searchParams, value);
+  }
+  get searchParams() {
+    return this.#searchParams;
+  }
+  get hash() {
+    return this.#parts.hash;
+  }
+  set hash(value) {
+    value = String(value).replace(/^#/, "");
+    this.#parts.hash = value ? `#${value}` : "";
+  }
+  get href() {
+    const { protocol, username, password, pathname, search, hash, opaque } = this.#parts;
+    if (opaque) return `${protocol}${pathname}${search}${hash}`;
+    const auth = username ? `${username}${password ? `:${password}` : ""}@` : "";
+    return `${protocol}//${auth}${this.host}${pathname}${search}${hash}`;
+  }
+  toString() {
+    return this.href;
+  }
+  toJSON() {
+    return this.href;
+  }
+}
+
+export function fileURLToPath(url) {
+  url = url instanceof URL ? url : new URL(url);
+  if (url.protocol !== "file:") throw new TypeError("The URL must be of scheme file");
+  return decodeURIComponent(url.pathname);
+}
+
+export function pathToFileURL(path) {
+  const encoded = String(path).split("/").map(encodeURIComponent).join("/");
+  return new URL(`file://${encoded.startsWith("/") ? "" : "/"}${encoded}`);
+}
+
+export default { URL, URLSearchParams, fileURLToPath, pathToFileURL };
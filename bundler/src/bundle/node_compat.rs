@@ -0,0 +1,149 @@
+use super::core_modules::CORE_MODULES;
+use super::loaders::ModuleLoader;
+use super::modules::ModulePath;
+use super::modules::ModuleSource;
+use anyhow::Result;
+use anyhow::bail;
+use std::collections::HashMap;
+
+/// Prefix of Node.js built-in specifiers (`import { join } from "node:path"`).
+pub(crate) const NODE_PREFIX: &str = "node:";
+
+/// JavaScript implementations of Node.js built-ins shipped with the bundler.
+static POLYFILLS: &[(&str, &str)] = &[
+    ("buffer", include_str!("node/buffer.js")),
+    ("path", include_str!("node/path.js")),
+    ("querystring", include_str!("node/querystring.js")),
+    ("url", include_str!("node/url.js")),
+];
+
+/// How a Node.js built-in module is provided to the bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeShim {
+    /// Forwarded to a core module of the runtime, e.g. `node:fs` to `fs`.
+    Core(String),
+    /// Bundled JavaScript implementation, available for buffer, path,
+    /// querystring and url.
+    Polyfill,
+}
+
+/// Maps Node.js built-ins to shims. Importing a built-in that isn't listed
+/// fails the build with the list of supported ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeCompat {
+    modules: HashMap<String, NodeShim>,
+}
+
+impl Default for NodeCompat {
+    fn default() -> Self {
+        let mut compat = Self::none();
+        for name in ["fs", "dns", "crypto"] {
+            compat.insert(name, NodeShim::Core(name.into()));
+        }
+        for (name, _) in POLYFILLS {
+            compat.insert(*name, NodeShim::Polyfill);
+        }
+        compat
+    }
+}
+
+impl NodeCompat {
+    /// No Node.js built-ins at all.
+    pub fn none() -> Self {
+        Self {
+            modules: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, shim: NodeShim) {
+        self.modules.insert(name.into(), shim);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<NodeShim> {
+        self.modules.remove(name)
+    }
+
+    /// Returns the supported built-ins, sorted by name.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.modules.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the shim for a `node:` specifier or a bare built-in name.
+    pub fn shim(&self, specifier: &str) -> Option<&NodeShim> {
+        self.modules
+            .get(specifier.strip_prefix(NODE_PREFIX).unwrap_or(specifier))
+    }
+
+    /// Returns the resolved specifier if `specifier` names a Node.js built-in,
+    /// either prefixed with `node:` or bare (`require("path")`). Bare names
+    /// of core modules keep resolving to the core module.
+    pub(crate) fn builtin_specifier(&self, specifier: &str) -> Option<ModulePath> {
+        if specifier.starts_with(NODE_PREFIX) {
+            return Some(specifier.into());
+        }
+        (self.modules.contains_key(specifier) && !CORE_MODULES.contains(&specifier))
+            .then(|| format!("{NODE_PREFIX}{specifier}"))
+    }
+}
+
+/// Loader generating the shims configured in `NodeCompat`.
+pub struct NodeModuleLoader {
+    pub compat: NodeCompat,
+}
+
+impl ModuleLoader for NodeModuleLoader {
+    fn resolve(&self, _: Option<&str>, specifier: &str) -> Result<ModulePath> {
+        match self.compat.builtin_specifier(specifier) {
+            Some(path) => Ok(path),
+            None => bail!(format!("Module not found \"{specifier}\"")),
+        }
+    }
+
+    fn load(&self, specifier: &str) -> Result<ModuleSource> {
+        let name = specifier.trim_start_matches(NODE_PREFIX);
+        match self.compat.modules.get(name) {
+            Some(NodeShim::Core(core)) => Ok(format!(
+                "export * from \"core:{core}\";\nexport {{ default }} from \"core:{core}\";\n"
+            )),
+            Some(NodeShim::Polyfill) => match POLYFILLS.iter().find(|(n, _)| *n == name) {
+                Some((_, source)) => Ok(source.to_string()),
+                None => bail!(format!("No polyfill for Node.js built-in \"{name}\"")),
+            },
+            None => bail!(format!(
+                "Node.js built-in \"{name}\" is not supported, supported built-ins: {}",
+                self.compat.names().join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_loader_should_load_configured_shims() -> Result<()> {
+        let mut compat = NodeCompat::default();
+        compat.remove("dns");
+        let loader = NodeModuleLoader { compat };
+
+        assert_eq!(loader.resolve(None, "node:path")?, "node:path");
+        assert_eq!(loader.resolve(None, "querystring")?, "node:querystring");
+        // Bare core module names stay with the core module loader.
+        assert!(loader.compat.builtin_specifier("fs").is_none());
+        assert!(loader.compat.builtin_specifier("lodash").is_none());
+
+        let source = loader.load("node:fs")?;
+        assert!(source.contains("export * from \"core:fs\";"));
+        assert!(loader.load("node:path")?.contains("export function join"));
+
+        let err = loader.load("node:dns").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Node.js built-in \"dns\" is not supported, supported built-ins: buffer, crypto, fs, path, querystring, url"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use super::modules::ModulePath;
+use super::modules::ModuleSource;
+use super::transpilers::TypeScript;
+use anyhow::Context;
+use anyhow::Result;
+use std::fmt;
+use std::sync::Arc;
+
+/// Hooks into module resolution, loading and transformation, run before the
+/// built-in loaders in the order the plugins were added.
+///
+/// Every hook defaults to doing nothing, so a plugin only implements the ones
+/// it needs and decides itself which specifiers or paths it handles.
+pub trait Plugin: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Resolves `specifier` imported from `importer`, `None` for the entry.
+    /// The returned path may be virtual (e.g. `virtual:routes`) when an
+    /// `on_load` hook provides its source.
+    fn on_resolve(&self, _specifier: &str, _importer: Option<&str>) -> Result<Option<ModulePath>> {
+        Ok(None)
+    }
+
+    /// Provides the JavaScript source of a resolved path. Sources of `.ts`
+    /// paths are transpiled like files on disk.
+    fn on_load(&self, _path: &str) -> Result<Option<ModuleSource>> {
+        Ok(None)
+    }
+
+    /// Rewrites the JavaScript source of a loaded module. Transforms of all
+    /// plugins are applied in turn.
+    fn on_transform(&self, _path: &str, _source: &str) -> Result<Option<ModuleSource>> {
+        Ok(None)
+    }
+}
+
+/// Plugins of a bundle, see `Plugin`.
+#[derive(Debug, Clone, Default)]
+pub struct Plugins(Vec<Arc<dyn Plugin>>);
+
+impl Plugins {
+    pub fn push(&mut self, plugin: impl Plugin + 'static) {
+        self.0.push(Arc::new(plugin));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the path of the first plugin resolving `specifier`.
+    pub(crate) fn resolve(
+        &self,
+        specifier: &str,
+        importer: Option<&str>,
+    ) -> Result<Option<ModulePath>> {
+        for plugin in &self.0 {
+            let path = plugin.on_resolve(specifier, importer).with_context(|| {
+                format!(
+                    "plugin \"{}\" failed to resolve \"{specifier}\"",
+                    plugin.name()
+                )
+            })?;
+            if path.is_some() {
+                return Ok(path);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the source of the first plugin loading `path`.
+    pub(crate) fn load(&self, path: &str) -> Result<Option<ModuleSource>> {
+        for plugin in &self.0 {
+            let source = plugin.on_load(path).with_context(|| {
+                format!("plugin \"{}\" failed to load \"{path}\"", plugin.name())
+            })?;
+            if let Some(source) = source {
+                return match path.ends_with(".ts") {
+                    true => TypeScript::compile(Some(path), &source).map(Some),
+                    false => Ok(Some(source)),
+                };
+            }
+        }
+        Ok(None)
+    }
+
+    /// Passes `source` through the transform of every plugin.
+    pub(crate) fn transform(&self, path: &str, mut source: ModuleSource) -> Result<ModuleSource> {
+        for plugin in &self.0 {
+            let transformed = plugin.on_transform(path, &source).with_context(|| {
+                format!(
+                    "plugin \"{}\" failed to transform \"{path}\"",
+                    plugin.name()
+                )
+            })?;
+            if let Some(transformed) = transformed {
+                source = transformed;
+            }
+        }
+        Ok(source)
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use std::env;
+use url::Url;
+
+/// Proxy settings for module downloads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    /// Hosts bypassing the proxy, `*` disables it for every host.
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Reads `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` (or their lowercase
+    /// variants).
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            env::var(name)
+                .or_else(|_| env::var(name.to_lowercase()))
+                .ok()
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            http: var("HTTP_PROXY"),
+            https: var("HTTPS_PROXY"),
+            no_proxy: var("NO_PROXY")
+                .map(|v| Self::parse_no_proxy(&v))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Uses the same proxy for every scheme.
+    pub fn new(proxy: impl Into<String>) -> Self {
+        let proxy = proxy.into();
+        Self {
+            http: Some(proxy.clone()),
+            https: Some(proxy),
+            no_proxy: vec![],
+        }
+    }
+
+    fn parse_no_proxy(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+
+    /// Returns the proxy to use for `url`, if any.
+    pub fn proxy_for(&self, url: &str) -> Option<&str> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        let bypass = self.no_proxy.iter().any(|entry| {
+            let entry = entry.split(':').next().unwrap_or_default();
+            let domain = entry.trim_start_matches("*.").trim_start_matches('.');
+            entry == "*" || host == domain || host.ends_with(&format!(".{domain}"))
+        });
+        if bypass {
+            return None;
+        }
+        match url.scheme() {
+            "https" => self.https.as_deref().or(self.http.as_deref()),
+            _ => self.http.as_deref(),
+        }
+    }
+
+    /// Builds an agent routing requests for `url` through the proxy.
+    pub fn agent_for(&self, url: &str) -> Result<ureq::Agent> {
+        let mut builder = ureq::AgentBuilder::new();
+        if let Some(proxy) = self.proxy_for(url) {
+            builder = builder.proxy(ureq::Proxy::new(proxy)?);
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_for_should_respect_no_proxy() {
+        let config = ProxyConfig {
+            http: Some("http://proxy:3128".into()),
+            https: None,
+            no_proxy: ProxyConfig::parse_no_proxy("localhost, .internal.io,example.com:443"),
+        };
+        assert_eq!(
+            config.proxy_for("https://deno.land/x/mod.ts"),
+            Some("http://proxy:3128")
+        );
+        assert_eq!(config.proxy_for("http://localhost:8080/a.js"), None);
+        assert_eq!(config.proxy_for("https://git.internal.io/a.js"), None);
+        assert_eq!(config.proxy_for("https://example.com/a.js"), None);
+        assert_eq!(config.proxy_for("https://sub.example.com/a.js"), None);
+
+        let config = ProxyConfig {
+            no_proxy: vec!["*".into()],
+            ..ProxyConfig::new("http://proxy:3128")
+        };
+        assert_eq!(config.proxy_for("https://deno.land/x/mod.ts"), None);
+    }
+}
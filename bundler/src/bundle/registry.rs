@@ -0,0 +1,331 @@
+use super::Options;
+use anyhow::{Context, Result, anyhow, bail};
+use semver::{Version, VersionReq};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+/// Prefix of JSR package specifiers (`jsr:@std/path@1.0.8/join`).
+pub const JSR_PREFIX: &str = "jsr:";
+
+/// Overrides the JSR registry, e.g. for a mirror.
+pub const JSR_URL_ENV: &str = "DINO_JSR_URL";
+
+const JSR_REGISTRY: &str = "https://jsr.io";
+
+/// Prefix of deno.land/x module URLs (`https://deno.land/x/oak@^12/mod.ts`).
+pub const DENO_LAND_PREFIX: &str = "https://deno.land/x/";
+
+/// Overrides where deno.land/x version lists are fetched from.
+pub const DENO_LAND_CDN_ENV: &str = "DINO_DENO_LAND_CDN_URL";
+
+const DENO_LAND_CDN: &str = "https://cdn.deno.land";
+
+/// A parsed `jsr:@scope/name[@version][/subpath]` specifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsrSpecifier {
+    pub scope: String,
+    pub name: String,
+    /// Exact version or a version prefix such as `1` or `1.2`, the latest
+    /// version when absent.
+    pub version: Option<String>,
+    /// Export of the package, `None` for the main export.
+    pub subpath: Option<String>,
+}
+
+impl JsrSpecifier {
+    pub fn parse(specifier: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid JSR specifier \"{specifier}\", expected jsr:@scope/name");
+        let rest = specifier
+            .strip_prefix(JSR_PREFIX)
+            .map(|s| s.strip_prefix('/').unwrap_or(s))
+            .and_then(|s| s.strip_prefix('@'))
+            .ok_or_else(invalid)?;
+        let (scope, rest) = rest.split_once('/').ok_or_else(invalid)?;
+        let (package, subpath) = match rest.split_once('/') {
+            Some((package, subpath)) => (package, Some(subpath.to_string())),
+            None => (rest, None),
+        };
+        let (name, version) = match package.split_once('@') {
+            Some((name, version)) => (name, Some(version.to_string())),
+            None => (package, None),
+        };
+        if scope.is_empty() || name.is_empty() || version.as_deref() == Some("") {
+            return Err(invalid());
+        }
+        Ok(Self {
+            scope: scope.into(),
+            name: name.into(),
+            version,
+            subpath: subpath.filter(|s| !s.is_empty()),
+        })
+    }
+
+    /// Package name, e.g. `@std/path`.
+    pub fn package(&self) -> String {
+        format!("@{}/{}", self.scope, self.name)
+    }
+
+    /// Package name with the export, e.g. `@std/path/join`, used as the
+    /// import map key.
+    pub fn import_name(&self) -> String {
+        match &self.subpath {
+            Some(subpath) => format!("{}/{subpath}", self.package()),
+            None => self.package(),
+        }
+    }
+
+    /// Resolves to the URL of the exported module, picking the newest
+    /// non-yanked version matching `version`, an exact version, a version
+    /// prefix or a semver range such as `^1.2.3`.
+    pub fn resolve(&self, options: &Options) -> Result<String> {
+        let registry = env::var(JSR_URL_ENV).unwrap_or_else(|_| JSR_REGISTRY.into());
+        let registry = registry.trim_end_matches('/');
+        let base = format!("{registry}/{}", self.package());
+
+        let meta = fetch_json(&format!("{base}/meta.json"), options)?;
+        let version = self.pick_version(&meta)?;
+
+        let meta = fetch_json(&format!("{base}/{version}_meta.json"), options)?;
+        let export = match &self.subpath {
+            Some(subpath) => format!("./{subpath}"),
+            None => ".".into(),
+        };
+        let path = meta["exports"][&export]
+            .as_str()
+            .with_context(|| format!("{}@{version} has no export \"{export}\"", self.package()))?;
+        Ok(format!(
+            "{base}/{version}/{}",
+            path.trim_start_matches("./")
+        ))
+    }
+
+    fn pick_version(&self, meta: &Value) -> Result<String> {
+        let versions = meta["versions"]
+            .as_object()
+            .with_context(|| format!("invalid metadata for {}", self.package()))?;
+        let versions = versions
+            .iter()
+            .filter(|(_, info)| !info["yanked"].as_bool().unwrap_or(false))
+            .map(|(version, _)| version.as_str());
+        pick_version(&self.package(), self.version.as_deref(), versions)
+    }
+}
+
+/// A deno.land/x URL, `https://deno.land/x/name[@version]/path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenoLandUrl {
+    pub name: String,
+    pub version: Option<String>,
+    pub path: String,
+}
+
+impl DenoLandUrl {
+    /// Returns `None` for URLs outside deno.land/x.
+    pub fn parse(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix(DENO_LAND_PREFIX)?;
+        let (module, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let (name, version) = match module.split_once('@') {
+            Some((name, version)) => (name, Some(version.to_string())),
+            None => (module, None),
+        };
+        (!name.is_empty()).then(|| Self {
+            name: name.into(),
+            version: version.filter(|v| !v.is_empty()),
+            path: path.into(),
+        })
+    }
+
+    /// Whether the version is a range that needs the registry, exact
+    /// versions and unversioned URLs are fetched as is.
+    pub fn is_range(&self) -> bool {
+        self.version
+            .as_deref()
+            .is_some_and(|v| exact_version(v).is_none())
+    }
+
+    /// Resolves the version range to the newest matching published version.
+    pub fn resolve(&self, options: &Options) -> Result<String> {
+        let cdn = env::var(DENO_LAND_CDN_ENV).unwrap_or_else(|_| DENO_LAND_CDN.into());
+        let cdn = cdn.trim_end_matches('/');
+        let meta = fetch_json(&format!("{cdn}/{}/meta/versions.json", self.name), options)?;
+        let versions = meta["versions"]
+            .as_array()
+            .with_context(|| format!("invalid metadata for {}", self.name))?
+            .iter()
+            .filter_map(Value::as_str);
+        let version = pick_version(&self.name, self.version.as_deref(), versions)?;
+        Ok(format!(
+            "{DENO_LAND_PREFIX}{}@{version}/{}",
+            self.name, self.path
+        ))
+    }
+}
+
+/// Resolves `jsr:` specifiers and deno.land/x version ranges to concrete
+/// URLs, asking each registry once per bundle.
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    resolved: Mutex<HashMap<String, String>>,
+}
+
+impl Registry {
+    /// Returns `None` for specifiers that don't need a registry.
+    pub fn resolve(&self, specifier: &str, options: &Options) -> Result<Option<String>> {
+        if specifier.starts_with(JSR_PREFIX) {
+            let spec = JsrSpecifier::parse(specifier)?;
+            return self.cached(specifier, || spec.resolve(options)).map(Some);
+        }
+        match DenoLandUrl::parse(specifier).filter(DenoLandUrl::is_range) {
+            Some(url) => self.cached(specifier, || url.resolve(options)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn cached(&self, specifier: &str, resolve: impl FnOnce() -> Result<String>) -> Result<String> {
+        if let Some(url) = self.resolved.lock().unwrap().get(specifier) {
+            return Ok(url.clone());
+        }
+        let url = resolve().with_context(|| format!("failed to resolve \"{specifier}\""))?;
+        (self.resolved.lock().unwrap()).insert(specifier.into(), url.clone());
+        Ok(url)
+    }
+}
+
+/// Picks the newest of `versions` matching `wanted`, which is an exact
+/// version, a version prefix (`1`, `1.2`) or a semver range (`^1.2`,
+/// `~1.2.3`, `>=1 <3`). Without `wanted`, prereleases are skipped.
+/// Versions may carry a `v` prefix as on deno.land/x.
+fn pick_version<'a>(
+    package: &str,
+    wanted: Option<&str>,
+    versions: impl Iterator<Item = &'a str>,
+) -> Result<String> {
+    let matches: Box<dyn Fn(&str) -> bool> = match wanted {
+        None => Box::new(|version| !version.contains('-')),
+        Some(wanted) if wanted.chars().all(|c| c.is_ascii_digit() || c == '.') => {
+            let wanted = wanted.to_string();
+            Box::new(move |version| {
+                let version = version.trim_start_matches('v');
+                version == wanted || version.starts_with(&format!("{wanted}."))
+            })
+        }
+        Some(wanted) => {
+            let trimmed = wanted.trim_start_matches('v');
+            match exact_version(trimmed) {
+                Some(exact) => {
+                    Box::new(move |version| exact_version(version) == Some(exact.clone()))
+                }
+                None => {
+                    let req = VersionReq::parse(trimmed)
+                        .with_context(|| format!("invalid version range \"{wanted}\""))?;
+                    Box::new(move |version| exact_version(version).is_some_and(|v| req.matches(&v)))
+                }
+            }
+        }
+    };
+    versions
+        .filter(|version| matches(version))
+        .max_by_key(|version| version_key(version))
+        .map(str::to_string)
+        .with_context(|| match wanted {
+            Some(version) => format!("{package} has no version matching {version}"),
+            None => format!("{package} has no published version"),
+        })
+}
+
+fn exact_version(version: &str) -> Option<Version> {
+    Version::parse(version.trim_start_matches('v')).ok()
+}
+
+/// Numeric `major.minor.patch` used to order versions, a prerelease sorts
+/// before its release.
+fn version_key(version: &str) -> (Vec<u64>, bool) {
+    let version = version.trim_start_matches('v');
+    let release = version.split(['-', '+']).next().unwrap_or_default();
+    let numbers = release
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    (numbers, !version.contains('-'))
+}
+
+fn fetch_json(url: &str, options: &Options) -> Result<Value> {
+    let response = options
+        .proxy
+        .agent_for(url)?
+        .get(url)
+        .call()
+        .map_err(|e| anyhow!("failed to fetch {url}: {e}"))?;
+    let text = response.into_string()?;
+    match serde_json::from_str(&text) {
+        Ok(value) => Ok(value),
+        Err(e) => bail!("invalid JSON from {url}: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn jsr_specifier_should_parse_and_pick_versions() -> Result<()> {
+        let spec = JsrSpecifier::parse("jsr:@std/path@1/join")?;
+        assert_eq!(spec.package(), "@std/path");
+        assert_eq!(spec.import_name(), "@std/path/join");
+        assert_eq!(spec.version.as_deref(), Some("1"));
+        assert!(JsrSpecifier::parse("jsr:std/path").is_err());
+        assert!(JsrSpecifier::parse("jsr:@std/path@").is_err());
+
+        let meta = json!({
+            "versions": {
+                "0.9.0": {},
+                "1.2.0": {},
+                "1.10.0": {},
+                "1.11.0": { "yanked": true },
+                "2.0.0-rc.1": {},
+            }
+        });
+        assert_eq!(spec.pick_version(&meta)?, "1.10.0");
+        let latest = JsrSpecifier::parse("jsr:@std/path")?;
+        assert_eq!(latest.pick_version(&meta)?, "1.10.0");
+        let missing = JsrSpecifier::parse("jsr:@std/path@3")?;
+        assert!(missing.pick_version(&meta).is_err());
+        let range = JsrSpecifier::parse("jsr:/@std/path@^1.2.0")?;
+        assert_eq!(range.pick_version(&meta)?, "1.10.0");
+        let range = JsrSpecifier::parse("jsr:@std/path@~1.2")?;
+        assert_eq!(range.pick_version(&meta)?, "1.2.0");
+        Ok(())
+    }
+
+    #[test]
+    fn deno_land_url_should_parse_and_pick_versions() -> Result<()> {
+        let url = DenoLandUrl::parse("https://deno.land/x/oak@^12.1/mod.ts").unwrap();
+        assert_eq!(url.name, "oak");
+        assert_eq!(url.path, "mod.ts");
+        assert!(url.is_range());
+        assert!(
+            !DenoLandUrl::parse("https://deno.land/x/oak@v12.6.1/mod.ts")
+                .unwrap()
+                .is_range()
+        );
+        assert!(
+            !DenoLandUrl::parse("https://deno.land/x/oak/mod.ts")
+                .unwrap()
+                .is_range()
+        );
+        assert!(DenoLandUrl::parse("https://deno.land/std@0.200.0/path/mod.ts").is_none());
+
+        let versions = ["v11.1.0", "v12.0.0", "v12.6.1", "v13.0.0", "v13.0.0-rc1"];
+        let pick = |wanted| pick_version("oak", Some(wanted), versions.into_iter());
+        assert_eq!(pick("^12.1")?, "v12.6.1");
+        assert_eq!(pick("v12.0.0")?, "v12.0.0");
+        assert_eq!(pick(">=11, <13")?, "v12.6.1");
+        assert_eq!(pick("13")?, "v13.0.0");
+        assert!(pick("^14").is_err());
+        assert!(pick("not a range").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Phases of the bundle pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Resolve,
+    Fetch,
+    Transpile,
+    Parse,
+    Emit,
+}
+
+/// Durations collected while bundling.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    pub phases: BTreeMap<Phase, Duration>,
+    /// Load cost per module (fetch + transpile + parse).
+    pub modules: Vec<(String, Duration)>,
+    pub total: Duration,
+}
+
+thread_local! {
+    // The bundler runs on a single thread, so a thread-local collector lets
+    // loaders record timings without threading a handle through every call.
+    static CURRENT: RefCell<Option<Timings>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` while collecting timings on the current thread.
+pub(crate) fn collect<T>(f: impl FnOnce() -> T) -> (T, Timings) {
+    CURRENT.with(|c| *c.borrow_mut() = Some(Timings::default()));
+    let start = Instant::now();
+    let ret = f();
+    let mut timings = CURRENT.with(|c| c.borrow_mut().take()).unwrap_or_default();
+    timings.total = start.elapsed();
+    (ret, timings)
+}
+
+/// Runs `f` and adds its duration to `phase` if timings are being collected.
+pub(crate) fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let ret = f();
+    let elapsed = start.elapsed();
+    CURRENT.with(|c| {
+        if let Some(timings) = c.borrow_mut().as_mut() {
+            *timings.phases.entry(phase).or_default() += elapsed;
+        }
+    });
+    ret
+}
+
+/// Records the load cost of a single module.
+pub(crate) fn record_module(name: &str, elapsed: Duration) {
+    CURRENT.with(|c| {
+        if let Some(timings) = c.borrow_mut().as_mut() {
+            timings.modules.push((name.to_string(), elapsed));
+        }
+    });
+}
+
+impl Timings {
+    /// Returns the `n` most expensive modules.
+    pub fn slowest_modules(&self, n: usize) -> Vec<(String, Duration)> {
+        let mut modules = self.modules.clone();
+        modules.sort_by_key(|m| Reverse(m.1));
+        modules.truncate(n);
+        modules
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::Resolve => "resolve",
+            Phase::Fetch => "fetch",
+            Phase::Transpile => "transpile",
+            Phase::Parse => "parse",
+            Phase::Emit => "emit",
+        };
+        f.pad(name)
+    }
+}
@@ -1,8 +1,20 @@
+use super::loaders::CACHE_DIR;
+use super::timings;
+use super::timings::Phase;
 use anyhow::Result;
 use anyhow::bail;
 use base64::prelude::*;
 use lazy_static::lazy_static;
 use regex::Regex;
+use sha::sha1::Sha1;
+use sha::utils::Digest;
+use sha::utils::DigestExt;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
 use swc_common::BytePos;
 use swc_common::FileName;
 use swc_common::FilePathMapping;
@@ -29,13 +41,100 @@ use swc_ecma_transforms_typescript::strip;
 
 lazy_static! {
     static ref PRAGMA_REGEX: Regex = Regex::new(r"@jsx\s+([^\s]+)").unwrap();
+    // Transpiled output keyed by sha1(options + filename + source), shared by
+    // all bundles built in this process and backed by `CACHE_DIR/transpiled`.
+    static ref TRANSPILE_CACHE: Mutex<MemoryCache> =
+        Mutex::new(MemoryCache::new(TRANSPILE_CACHE_BYTES));
 }
 
+/// Upper bound of the transpiled output kept in memory. Bundles are also
+/// built by the long-running server, so the oldest entries are dropped past
+/// it and read back from disk when they are needed again.
+const TRANSPILE_CACHE_BYTES: usize = 64 << 20;
+
+/// In-memory transpile results, evicted oldest first once their total size
+/// exceeds `limit` bytes.
+struct MemoryCache {
+    entries: HashMap<String, String>,
+    order: VecDeque<String>,
+    bytes: usize,
+    limit: usize,
+}
+
+impl MemoryCache {
+    fn new(limit: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            bytes: 0,
+            limit,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&String> {
+        self.entries.get(key)
+    }
+
+    fn insert(&mut self, key: String, output: String) {
+        self.bytes += output.len();
+        match self.entries.insert(key.clone(), output) {
+            Some(old) => self.bytes -= old.len(),
+            None => self.order.push_back(key),
+        }
+        while self.bytes > self.limit {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(output) = self.entries.remove(&oldest) {
+                self.bytes -= output.len();
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn remove(&mut self, key: &str) {
+        if let Some(output) = self.entries.remove(key) {
+            self.bytes -= output.len();
+            self.order.retain(|k| k != key);
+        }
+    }
+}
+
+/// Part of the cache key standing for the compiler and its options, so
+/// results of another dino version or configuration are never reused.
+const TRANSPILE_OPTIONS: &str = concat!(env!("CARGO_PKG_VERSION"), ";tsx;decorators");
+
 pub struct TypeScript;
 
 impl TypeScript {
-    /// Compiles TypeScript code into JavaScript.
+    /// Compiles TypeScript code into JavaScript, reusing previous results for
+    /// identical inputs from memory or from the on-disk cache.
     pub fn compile(filename: Option<&str>, source: &str) -> Result<String> {
+        let input = format!(
+            "{TRANSPILE_OPTIONS}\0{}\0{source}",
+            filename.unwrap_or_default()
+        );
+        let key = Sha1::default().digest(input.as_bytes()).to_hex();
+
+        if let Some(output) = TRANSPILE_CACHE.lock().unwrap().get(&key) {
+            return Ok(output.clone());
+        }
+        let path = transpile_cache_path(&key);
+        let output = match fs::read_to_string(&path) {
+            Ok(output) => output,
+            Err(_) => {
+                let output =
+                    timings::timed(Phase::Transpile, || Self::transpile(filename, source))?;
+                // The cache is an optimization, failing to write it is fine.
+                let _ = write_atomically(&path, &output);
+                output
+            }
+        };
+        TRANSPILE_CACHE.lock().unwrap().insert(key, output.clone());
+        Ok(output)
+    }
+
+    fn transpile(filename: Option<&str>, source: &str) -> Result<String> {
         let globals = Globals::default();
         let cm: Lrc<SourceMap> = Lrc::new(SourceMap::new(FilePathMapping::empty()));
         let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
@@ -119,6 +218,20 @@ impl TypeScript {
     }
 }
 
+fn transpile_cache_path(key: &str) -> PathBuf {
+    CACHE_DIR.join("transpiled").join(format!("{key}.js"))
+}
+
+/// Writes through a temporary file, so concurrent builds never read a
+/// partially written entry.
+fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
 /// Returns the string (JSON) representation of the source-map.
 fn source_map_to_string(cm: Lrc<SourceMap>, mappings: &[(BytePos, LineCol)]) -> String {
     let mut buffer = Vec::new();
@@ -126,3 +239,51 @@ fn source_map_to_string(cm: Lrc<SourceMap>, mappings: &[(BytePos, LineCol)]) ->
     source_map.to_writer(&mut buffer).unwrap();
     String::from_utf8_lossy(&buffer).to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_should_reuse_cached_output_from_disk() -> Result<()> {
+        let source = "export const cached: number = 42;";
+        let filename = "transpile-cache-test.ts";
+        let output = TypeScript::compile(Some(filename), source)?;
+        assert!(output.starts_with("export const cached = 42;"));
+
+        let input = format!("{TRANSPILE_OPTIONS}\0{filename}\0{source}");
+        let key = Sha1::default().digest(input.as_bytes()).to_hex();
+        let path = transpile_cache_path(&key);
+        assert_eq!(fs::read_to_string(&path)?, output);
+
+        // Served from disk once the in-memory entry is gone.
+        TRANSPILE_CACHE.lock().unwrap().remove(&key);
+        fs::write(&path, "export const cached = 0;")?;
+        assert_eq!(
+            TypeScript::compile(Some(filename), source)?,
+            "export const cached = 0;"
+        );
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn memory_cache_should_evict_oldest_entries() {
+        let mut cache = MemoryCache::new(10);
+        cache.insert("a".into(), "aaaa".into());
+        cache.insert("b".into(), "bbbb".into());
+        assert_eq!(cache.bytes, 8);
+        // Replacing an entry doesn't count it twice.
+        cache.insert("b".into(), "bbb".into());
+        assert_eq!(cache.bytes, 7);
+        cache.insert("c".into(), "cccc".into());
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.get("b").map(String::as_str), Some("bbb"));
+        assert_eq!(cache.get("c").map(String::as_str), Some("cccc"));
+        assert_eq!(cache.bytes, 7);
+        // An entry larger than the limit isn't kept at all.
+        cache.insert("d".into(), "d".repeat(11));
+        assert!(cache.get("d").is_none());
+        assert_eq!(cache.bytes, 0);
+    }
+}
@@ -1,6 +1,13 @@
 mod bundle;
 
-pub use bundle::{Options, run_bundle};
+pub use bundle::{
+    AUTH_TOKENS_ENV, AuthConfig, CACHE_DIR, CORE_MODULES, COVERAGE_GLOBAL, Capabilities, Coverage,
+    Credential, DEFINE_ENV_PREFIX, DENO_LAND_CDN_ENV, DENO_LAND_PREFIX, Defines, DenoLandUrl,
+    HostModules, ImportMap, JSR_PREFIX, JSR_URL_ENV, JsrSpecifier, NodeCompat, NodeShim, Options,
+    Phase, Plugin, Plugins, ProxyConfig, Timings, bundle_to_string_pretty, core_module_name,
+    emit_declarations, fetch_remote, run_bundle, run_bundle_with_timings,
+};
+pub use swc_bundler::ModuleType;
 
 #[cfg(test)]
 mod tests {
@@ -16,4 +23,118 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn bundle_with_timings_should_work() -> Result<()> {
+        let (ret, timings) = run_bundle_with_timings("fixtures/main.ts", &Default::default())?;
+        assert_eq!(ret, run_bundle("fixtures/main.ts", &Default::default())?);
+        assert_eq!(timings.modules.len(), 2);
+        for phase in [Phase::Resolve, Phase::Parse, Phase::Emit] {
+            assert!(timings.phases.contains_key(&phase));
+        }
+        assert!(timings.total >= timings.phases[&Phase::Emit]);
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_should_pick_target_variants() -> Result<()> {
+        let options = Options {
+            target: Some("server".into()),
+            ..Default::default()
+        };
+        let ret = run_bundle("fixtures/targets/main.ts", &options)?;
+        assert!(ret.contains("server storage"));
+        assert!(ret.contains("target:\"server\""));
+
+        let ret = run_bundle("fixtures/targets/main.ts", &Default::default())?;
+        assert!(ret.contains("\"memory storage\""));
+        assert!(!ret.contains("target:"));
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn on_resolve(&self, specifier: &str, _: Option<&str>) -> Result<Option<String>> {
+            Ok((specifier == "virtual:routes").then(|| specifier.into()))
+        }
+
+        fn on_load(&self, path: &str) -> Result<Option<String>> {
+            if path == "virtual:routes" {
+                return Ok(Some("export const routes = [\"/\", \"/about\"];".into()));
+            }
+            if !path.ends_with(".yaml") {
+                return Ok(None);
+            }
+            let fields: Vec<_> = std::fs::read_to_string(path)?
+                .lines()
+                .filter_map(|line| line.split_once(": "))
+                .map(|(key, value)| format!("{key}: {value:?}"))
+                .collect();
+            Ok(Some(format!("export default {{ {} }};", fields.join(", "))))
+        }
+
+        fn on_transform(&self, _: &str, source: &str) -> Result<Option<String>> {
+            Ok(Some(source.replace("__VERSION__", "1.0.0")))
+        }
+    }
+
+    #[test]
+    fn bundle_should_run_plugins() -> Result<()> {
+        let mut options = Options::default();
+        options.plugins.push(TestPlugin);
+        let ret = run_bundle("fixtures/plugins/main.ts", &options)?;
+        assert!(ret.contains("name:\"demo\",port:\"8080\""));
+        assert!(ret.contains("[\"/\",\"/about\"]"));
+        assert!(ret.contains("\"1.0.0\""));
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_should_instrument_local_modules() -> Result<()> {
+        let options = Options {
+            coverage: true,
+            ..Default::default()
+        };
+        let ret = run_bundle("fixtures/main.ts", &options)?;
+        for file in ["main.ts", "lib.ts"] {
+            let path = std::path::absolute("fixtures")?.join(file);
+            assert!(ret.contains(&format!("[{:?}]", path.to_string_lossy())));
+        }
+        assert!(ret.contains("globalThis.__dino_coverage??={}"));
+        assert!(!run_bundle("fixtures/main.ts", &Default::default())?.contains(COVERAGE_GLOBAL));
+        Ok(())
+    }
+
+    /// Serves the URL imports of the snapshot fixtures, so they build without
+    /// network access and without touching the module cache.
+    #[derive(Debug)]
+    struct RemoteFixtures;
+
+    impl Plugin for RemoteFixtures {
+        fn name(&self) -> &str {
+            "remote-fixtures"
+        }
+
+        fn on_load(&self, path: &str) -> Result<Option<String>> {
+            Ok((path == "https://example.com/greet.js")
+                .then(|| "export function greet(name) { return `Hello ${name}!`; }".into()))
+        }
+    }
+
+    #[test]
+    fn bundle_snapshots_should_match() -> Result<()> {
+        let mut options = Options::default();
+        options.plugins.push(RemoteFixtures);
+        insta::glob!("../fixtures/snapshots", "*.ts", |path| {
+            let ret = bundle_to_string_pretty(path.to_str().unwrap(), &options).unwrap();
+            insta::assert_snapshot!(ret);
+        });
+        Ok(())
+    }
 }
@@ -39,7 +39,10 @@ pub(crate) fn process_from_js(input: DeriveInput) -> TokenStream {
     quote! {
         impl #merged rquickjs::FromJs<'js> for #ident #generics {
             fn from_js(_ctx: &rquickjs::Ctx<'js>, v: rquickjs::Value<'js>) -> rquickjs::Result<Self> {
-                let obj = v.into_object().unwrap();
+                let from = v.type_name();
+                let obj = v
+                    .into_object()
+                    .ok_or_else(|| rquickjs::Error::new_from_js(from, stringify!(#ident)))?;
 
                 #(#code)*
 
@@ -0,0 +1,210 @@
+//! 端到端压测：在进程内启动服务器，用多个客户端线程并发请求一个简单的 handler，
+//! 统计吞吐和延迟，并和 `benches/baseline.json` 中的基线比较，退化超过容差时失败。
+//!
+//! `cargo bench -p dino-server --bench load` 运行，可以用环境变量调整：
+//! - `DINO_BENCH_REQUESTS`：请求总数，默认 10000
+//! - `DINO_BENCH_CONCURRENCY`：客户端线程数，默认 16
+//! - `DINO_BENCH_WORKERS`：worker 线程数，默认 4
+//! - `DINO_BENCH_UPDATE=1`：用本次结果覆盖基线，修改调度逻辑后在同一台机器上重新生成
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::TcpListener,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use dino_server::{
+    ProjectConfig, ServerOptions, SwappableAppRouter, TenantRouter, WorkerSettings,
+    start_server_with,
+};
+use serde::{Deserialize, Serialize};
+
+const HOST: &str = "127.0.0.1";
+const BASELINE: &str = "benches/baseline.json";
+const WARMUP: usize = 200;
+
+const CODE: &str = r#"
+(function(){
+    async function hello(req) {
+        return { status: 200, headers: { "content-type": "text/plain" }, body: `hello ${req.params.id}` };
+    }
+    return { hello };
+})();
+"#;
+
+const CONFIG: &str = r#"
+name: bench
+routes:
+  /hello/{id}:
+    - method: GET
+      handler: hello
+"#;
+
+/// 压测结果，同时也是基线文件的格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Report {
+    /// 每秒请求数
+    throughput: f64,
+    p50_ms: f64,
+    p99_ms: f64,
+    /// 比较时允许的相对退化，如 0.5 表示吞吐可以低 50%、延迟可以高 50%，p99 在共享机器上波动较大
+    #[serde(default = "default_tolerance")]
+    tolerance: f64,
+}
+
+fn default_tolerance() -> f64 {
+    0.5
+}
+
+fn main() -> Result<()> {
+    // 只有 `cargo bench` 会传入 `--bench`，`cargo test --benches` 时只检查能否编译运行
+    if !env::args().any(|arg| arg == "--bench") {
+        return Ok(());
+    }
+    let requests = env_or("DINO_BENCH_REQUESTS", 10_000);
+    let concurrency = env_or("DINO_BENCH_CONCURRENCY", 16).max(1);
+    let workers = env_or("DINO_BENCH_WORKERS", 4).max(1);
+
+    let port = start(workers)?;
+    let url = |i: usize| format!("http://{HOST}:{port}/hello/{i}");
+    let agent = ureq::agent();
+    for i in 0..WARMUP {
+        agent.get(&url(i)).call()?;
+    }
+
+    let start = Instant::now();
+    let mut latencies = thread::scope(|s| {
+        let handles: Vec<_> = (0..concurrency)
+            .map(|c| {
+                let url = &url;
+                s.spawn(move || {
+                    // 每个线程一个 agent，复用 keep-alive 连接
+                    let agent = ureq::agent();
+                    let mut latencies = Vec::with_capacity(requests / concurrency + 1);
+                    for i in (c..requests).step_by(concurrency) {
+                        let start = Instant::now();
+                        let body = agent.get(&url(i)).call()?.into_string()?;
+                        latencies.push(start.elapsed());
+                        if body != format!("hello {i}") {
+                            bail!("unexpected body: {body}");
+                        }
+                    }
+                    Ok(latencies)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("client thread panicked"))
+            .collect::<Result<Vec<_>>>()
+    })?
+    .concat();
+    let elapsed = start.elapsed();
+    latencies.sort();
+
+    let report = Report {
+        throughput: requests as f64 / elapsed.as_secs_f64(),
+        p50_ms: ms(percentile(&latencies, 0.50)),
+        p99_ms: ms(percentile(&latencies, 0.99)),
+        tolerance: default_tolerance(),
+    };
+    println!(
+        "{requests} requests, {concurrency} clients, {workers} workers: {:.0} req/s, p50 {:.3}ms, p99 {:.3}ms",
+        report.throughput, report.p50_ms, report.p99_ms
+    );
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(BASELINE);
+    if env::var("DINO_BENCH_UPDATE").is_ok_and(|v| v == "1") {
+        fs::write(&path, serde_json::to_string_pretty(&report)? + "\n")?;
+        println!("Baseline written to {}", path.display());
+        return Ok(());
+    }
+    let baseline: Report = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    let regressions = compare(&report, &baseline);
+    if !regressions.is_empty() {
+        bail!("performance regressed: {}", regressions.join("; "));
+    }
+    println!("Within {:.0}% of the baseline", baseline.tolerance * 100.0);
+    Ok(())
+}
+
+/// 在后台线程启动服务器，等端口可以访问后返回
+fn start(workers: usize) -> Result<u16> {
+    let port = TcpListener::bind((HOST, 0))?.local_addr()?.port();
+    let config: ProjectConfig = serde_yaml::from_str(CONFIG)?;
+    let routers = vec![TenantRouter::new(
+        HOST.to_string(),
+        SwappableAppRouter::try_new(CODE, config.routes)?,
+    )];
+    let settings = WorkerSettings {
+        min_workers: workers,
+        max_workers: workers,
+        ..Default::default()
+    };
+    let options = ServerOptions {
+        workers: HashMap::from([(HOST.to_string(), settings)]),
+        ..Default::default()
+    };
+    thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("failed to create runtime");
+        if let Err(e) = rt.block_on(start_server_with(port, routers, options)) {
+            eprintln!("Server error: {e:#}");
+        }
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while ureq::get(&format!("http://{HOST}:{port}/hello/0"))
+        .call()
+        .is_err()
+    {
+        if Instant::now() > deadline {
+            bail!("server didn't start on port {port}");
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    Ok(port)
+}
+
+/// 返回超出基线容差的指标
+fn compare(report: &Report, baseline: &Report) -> Vec<String> {
+    let tolerance = baseline.tolerance;
+    let mut regressions = vec![];
+    if report.throughput < baseline.throughput * (1.0 - tolerance) {
+        regressions.push(format!(
+            "throughput {:.0} req/s < baseline {:.0} req/s",
+            report.throughput, baseline.throughput
+        ));
+    }
+    for (name, value, base) in [
+        ("p50", report.p50_ms, baseline.p50_ms),
+        ("p99", report.p99_ms, baseline.p99_ms),
+    ] {
+        if value > base * (1.0 + tolerance) {
+            regressions.push(format!("{name} {value:.3}ms > baseline {base:.3}ms"));
+        }
+    }
+    regressions
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index]
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn env_or(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use dino_server::{ReloadOptions, ServerConfig, ServerOptions, start_server_with};
+
+// cargo run --example reload -- server.yml
+// 修改磁盘上的代码后执行 `kill -HUP <pid>` 或 `echo reload | nc -U /tmp/dino.sock`
+#[tokio::main]
+async fn main() -> Result<()> {
+    let path: PathBuf = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "server.yml".into())
+        .into();
+    let config = ServerConfig::load(&path)?;
+    config.logging.init()?;
+    let options = ServerOptions {
+        reload: Some(ReloadOptions {
+            server_config: path,
+            control_socket: Some("/tmp/dino.sock".into()),
+        }),
+        workers: config.worker_settings(),
+        #[cfg(feature = "tls")]
+        tls: config.tls.clone(),
+        unknown_host: config.unknown_host.clone(),
+        ..Default::default()
+    };
+    start_server_with(8888, config.tenant_routers()?, options).await?;
+
+    Ok(())
+}
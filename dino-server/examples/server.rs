@@ -1,14 +1,10 @@
 use anyhow::Result;
 use dino_server::{ProjectConfig, SwappableAppRouter, TenantRouter, start_server};
-use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{Layer as _, fmt::Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let layer = Layer::new().with_filter(LevelFilter::INFO);
-    tracing_subscriber::registry().with(layer).init();
-
     let config: ProjectConfig = ProjectConfig::load("./fixtures/config.yml")?;
+    config.logging.init()?;
 
     let code = r#"
     (function(){
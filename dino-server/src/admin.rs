@@ -0,0 +1,725 @@
+use std::{collections::HashMap, net::SocketAddr, path::Path};
+
+use anyhow::{Context, Result};
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{FromRequestParts, Path as UrlPath, Query, State, rejection::PathRejection},
+    http::{HeaderMap, Method, Uri, header::CONTENT_TYPE, request::Parts, uri::InvalidUri},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::{
+    AppState, AuditAction, AuditEvent, AuditQuery, ProjectConfig, ProjectRoutes, UsageQuery,
+    UsageReport, error::AppError, invoke,
+};
+
+/// 管理接口的路径前缀
+pub const ADMIN_PREFIX: &str = "/_admin";
+
+/// 角色，按权限从小到大排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// 只读：查看 tenant 和审计日志
+    Viewer,
+    /// 可以部署和重启所管辖的 tenant
+    Deployer,
+    /// 不受 tenant 限制，可以执行所有操作
+    Admin,
+}
+
+/// 管理接口的 API token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// token 的名字，会作为审计日志中的操作者
+    pub name: String,
+    pub token: String,
+    pub role: Role,
+    /// 可以管理的 tenant，为空表示全部
+    #[serde(default)]
+    pub tenants: Vec<String>,
+}
+
+/// 管理接口配置，未配置时管理接口不可用
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+}
+
+impl AdminConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path).context("Failed to read admin config file")?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+
+    /// 校验 `Authorization: Bearer <token>`，并检查角色和 tenant 范围
+    pub fn authorize(
+        &self,
+        headers: &HeaderMap,
+        role: Role,
+        tenant: Option<&str>,
+    ) -> Result<&ApiToken, AppError> {
+        let token = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::Unauthorized("missing bearer token".into()))?;
+        let api_token = self
+            .tokens
+            .iter()
+            .find(|t| constant_time_eq(t.token.as_bytes(), token.as_bytes()))
+            .ok_or_else(|| AppError::Unauthorized("invalid token".into()))?;
+
+        if api_token.role < role {
+            return Err(AppError::Forbidden(format!(
+                "{} requires the {role:?} role",
+                api_token.name
+            )));
+        }
+        if let Some(tenant) = tenant
+            && !api_token.can_access(tenant)
+        {
+            return Err(AppError::Forbidden(format!(
+                "{} has no access to tenant {tenant}",
+                api_token.name
+            )));
+        }
+        Ok(api_token)
+    }
+}
+
+impl ApiToken {
+    pub fn can_access(&self, tenant: &str) -> bool {
+        self.role == Role::Admin
+            || self.tenants.is_empty()
+            || self.tenants.iter().any(|t| t.eq_ignore_ascii_case(tenant))
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 在单独的端口上提供管理接口，此时服务端口不再提供 `/_admin`。
+/// 返回的 listener 和 router 由 `shutdown::serve` 与服务端口一起提供服务并在退出时排空
+pub(crate) async fn bind(state: AppState, addr: SocketAddr) -> Result<(TcpListener, Router)> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Admin API listening on: {}", listener.local_addr()?);
+    let app = Router::new().nest(ADMIN_PREFIX, router()).with_state(state);
+    Ok((listener, app))
+}
+
+/// 路径中的 `{host}`，host 不区分大小写，转为小写后与 tenant 比较
+struct TenantHost(String);
+
+impl<S: Send + Sync> FromRequestParts<S> for TenantHost {
+    type Rejection = PathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let UrlPath(params) =
+            UrlPath::<HashMap<String, String>>::from_request_parts(parts, state).await?;
+        let host = params.get("host").map(|h| h.to_ascii_lowercase());
+        Ok(Self(host.unwrap_or_default()))
+    }
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    let router = Router::new()
+        .route("/tenants", get(list_tenants))
+        .route(
+            "/tenants/{host}",
+            get(tenant_status)
+                .post(add_tenant)
+                .put(deploy_tenant)
+                .delete(remove_tenant),
+        )
+        .route("/tenants/{host}/reload", post(reload_tenant))
+        .route("/tenants/{host}/restart", post(restart_tenant))
+        .route("/tenants/{host}/versions", get(list_versions))
+        .route("/tenants/{host}/previews", get(list_previews))
+        .route(
+            "/tenants/{host}/previews/{name}",
+            axum::routing::put(deploy_preview).delete(remove_preview),
+        )
+        .route("/tenants/{host}/rollback", post(rollback_tenant))
+        .route("/tenants/{host}/invoke", post(invoke_tenant))
+        .route("/tenants/{host}/inspect", get(inspect_tenant))
+        .route("/tenants/{host}/routes", get(list_routes))
+        .route("/reload", post(reload_server))
+        .route("/audit", get(query_audit))
+        .route("/usage", get(query_usage));
+    #[cfg(feature = "build")]
+    let router = router.route("/tenants/{host}/source", axum::routing::put(build_tenant));
+    #[cfg(feature = "git")]
+    let router = router
+        .route("/tenants/{host}/git", axum::routing::put(git_deploy))
+        .route("/tenants/{host}/git/webhook", post(git_webhook));
+    router
+}
+
+fn admin_config(state: &AppState) -> Result<&AdminConfig, AppError> {
+    state
+        .admin
+        .as_deref()
+        .ok_or_else(|| AppError::HostNotFound(ADMIN_PREFIX.into()))
+}
+
+async fn list_tenants(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Viewer, None)?;
+    let tenants: Vec<_> = state
+        .tenants()
+        .into_iter()
+        .filter(|t| token.can_access(&t.host))
+        .collect();
+    Ok(Json(json!({ "tenants": tenants })))
+}
+
+async fn tenant_status(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    admin_config(&state)?.authorize(&headers, Role::Viewer, Some(&host))?;
+    let status = state
+        .tenants()
+        .into_iter()
+        .find(|t| t.host == host)
+        .ok_or_else(|| AppError::HostNotFound(host))?;
+    Ok(Json(json!(status)))
+}
+
+#[derive(Debug, Deserialize)]
+struct DeployBody {
+    /// 打包后的代码
+    code: String,
+    /// config.yml 的内容
+    config: String,
+}
+
+async fn deploy_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    Json(body): Json<DeployBody>,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    let config: ProjectConfig = body
+        .config
+        .parse()
+        .context("invalid config")
+        .map_err(AppError::BadRequest)?;
+    state.swap_with_config(&host, body.code, &config, &token.name)?;
+    Ok(Json(json!({ "host": host, "status": "deployed" })))
+}
+
+/// 重新读取服务器配置，只重启发生变化的 tenant
+async fn reload_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Admin, None)?;
+    let actor = token.name.clone();
+    let changed = tokio::task::spawn_blocking(move || state.reload(&actor))
+        .await
+        .context("reload task failed")?
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(json!({ "changed": changed })))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReloadBody {
+    /// 打包后的代码
+    code: String,
+    routes: ProjectRoutes,
+}
+
+/// 热更新代码和路由并重启 worker，不修改 bindings
+async fn reload_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    Json(body): Json<ReloadBody>,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    if !state.routers.contains_key(&host) {
+        return Err(AppError::HostNotFound(host));
+    }
+    state.swap(&host, body.code, body.routes, &token.name)?;
+    Ok(Json(json!({ "host": host, "status": "reloaded" })))
+}
+
+/// 上传源码包（tar），在服务器上打包后部署，打包失败时以 422 返回错误信息
+#[cfg(feature = "build")]
+async fn build_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    let build = tokio::task::spawn_blocking(move || crate::build_source(&body))
+        .await
+        .context("build task failed")?;
+    let build = match build {
+        Ok(build) => build,
+        Err(e) => return Ok(build_failed(&host, &e)),
+    };
+    state.swap_with_config(&host, build.code, &build.config, &token.name)?;
+    Ok(Json(json!({ "host": host, "status": "deployed" })).into_response())
+}
+
+#[cfg(feature = "build")]
+fn build_failed(host: &str, e: &anyhow::Error) -> Response {
+    let diagnostics: Vec<_> = e.chain().map(ToString::to_string).collect();
+    let body = json!({ "host": host, "status": "failed", "diagnostics": diagnostics });
+    (axum::http::StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
+}
+
+/// 拉取 git 仓库并在服务器上打包部署，之后仓库的 push webhook 会触发重新部署
+#[cfg(feature = "git")]
+async fn git_deploy(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    Json(source): Json<crate::GitSource>,
+) -> Result<Response, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    if !state.routers.contains_key(&host) {
+        return Err(AppError::HostNotFound(host));
+    }
+    let actor = token.name.clone();
+    let (task_state, task_host) = (state.clone(), host.clone());
+    let deployed =
+        tokio::task::spawn_blocking(move || task_state.deploy_git(&task_host, source, &actor))
+            .await
+            .context("build task failed")?;
+    match deployed {
+        Ok(commit) => Ok(
+            Json(json!({ "host": host, "status": "deployed", "commit": commit })).into_response(),
+        ),
+        Err(e) => Ok(build_failed(&host, &e)),
+    }
+}
+
+/// push webhook，以 `X-Hub-Signature-256` 签名代替 token 认证，
+/// 推送的是部署的 ref 时在后台重新部署
+#[cfg(feature = "git")]
+async fn git_webhook(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, AppError> {
+    admin_config(&state)?;
+    let source = state
+        .git_source(&host)
+        .ok_or_else(|| AppError::HostNotFound(host.clone()))?;
+    let signature = headers
+        .get("x-hub-signature-256")
+        .and_then(|v| v.to_str().ok());
+    let tracked = crate::git::check_push(&source, &body, signature)
+        .map_err(|e| AppError::Unauthorized(e.to_string()))?;
+    if !tracked {
+        return Ok(Json(json!({ "host": host, "status": "ignored" })));
+    }
+    let task_host = host.clone();
+    tokio::task::spawn_blocking(
+        move || match state.deploy_git(&task_host, source, "webhook") {
+            Ok(commit) => tracing::info!("Deployed {task_host} from git at {commit}"),
+            Err(e) => tracing::error!("Git deploy of {task_host} failed: {e:#}"),
+        },
+    );
+    Ok(Json(json!({ "host": host, "status": "deploying" })))
+}
+
+async fn add_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    Json(body): Json<DeployBody>,
+) -> Result<Json<Value>, AppError> {
+    // 新增 tenant 会占用服务器资源，只有 admin 可以操作
+    let token = admin_config(&state)?.authorize(&headers, Role::Admin, Some(&host))?;
+    let config: ProjectConfig = body
+        .config
+        .parse()
+        .context("invalid config")
+        .map_err(AppError::BadRequest)?;
+    state
+        .add_tenant_with_config(&host, body.code, &config, &token.name)
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(json!({ "host": host, "status": "added" })))
+}
+
+async fn remove_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Admin, Some(&host))?;
+    state.remove_tenant(&host, &token.name)?;
+    Ok(Json(json!({ "host": host, "status": "removed" })))
+}
+
+async fn list_previews(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    admin_config(&state)?.authorize(&headers, Role::Viewer, Some(&host))?;
+    Ok(Json(
+        json!({ "tenant": host, "previews": state.previews(&host) }),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviewBody {
+    code: String,
+    config: String,
+    /// 保留的秒数，缺省为一天
+    #[serde(default)]
+    ttl: Option<u64>,
+}
+
+/// 预览环境属于 tenant，有 tenant 的 deployer 权限即可创建
+async fn deploy_preview(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    UrlPath((_, name)): UrlPath<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<PreviewBody>,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    let config: ProjectConfig = body
+        .config
+        .parse()
+        .context("invalid config")
+        .map_err(AppError::BadRequest)?;
+    let ttl = body.ttl.map(std::time::Duration::from_secs);
+    let preview = state
+        .deploy_preview(&host, &name, body.code, config, ttl, &token.name)
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(json!(preview)))
+}
+
+async fn remove_preview(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    UrlPath((_, name)): UrlPath<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    state
+        .remove_preview(&host, &name, &token.name)
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(
+        json!({ "tenant": host, "preview": name, "status": "removed" }),
+    ))
+}
+
+async fn list_versions(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    admin_config(&state)?.authorize(&headers, Role::Viewer, Some(&host))?;
+    let (current, versions) = state
+        .versions(&host)
+        .ok_or_else(|| AppError::HostNotFound(host.clone()))?;
+    Ok(Json(
+        json!({ "host": host, "current": current, "versions": versions }),
+    ))
+}
+
+/// 当前生效的路由表
+async fn list_routes(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    admin_config(&state)?.authorize(&headers, Role::Viewer, Some(&host))?;
+    let router = state
+        .routers
+        .get(&host)
+        .ok_or_else(|| AppError::HostNotFound(host.clone()))?
+        .load();
+    Ok(Json(json!({
+        "host": host,
+        "generation": router.generation,
+        "routes": &*router.table,
+    })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RollbackBody {
+    /// 要切换到的版本，缺省为上一个版本
+    version: Option<u64>,
+}
+
+async fn rollback_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    body: Option<Json<RollbackBody>>,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    let Json(body) = body.unwrap_or_default();
+    let version = state
+        .rollback(&host, body.version, &token.name)
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(
+        json!({ "host": host, "status": "rolled back", "version": version }),
+    ))
+}
+
+async fn restart_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let token = admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    state.audit(
+        AuditEvent::new(&host, &token.name, AuditAction::Admin).with_detail("restart worker"),
+    );
+    state.restart_worker(&host, &token.name)?;
+    Ok(Json(json!({ "host": host, "status": "restarted" })))
+}
+
+/// worker 的运行时状态，只包含名字和统计，不包含 tenant 的数据
+async fn inspect_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    admin_config(&state)?.authorize(&headers, Role::Viewer, Some(&host))?;
+    if !state.routers.contains_key(&host) {
+        return Err(AppError::HostNotFound(host));
+    }
+    let task_host = host.clone();
+    let inspection = tokio::task::spawn_blocking(move || state.inspect(&task_host))
+        .await
+        .context("inspect task failed")??;
+    Ok(Json(json!({ "host": host, "worker": inspection })))
+}
+
+#[derive(Debug, Deserialize)]
+struct InvokeBody {
+    #[serde(default = "default_method")]
+    method: String,
+    /// 路径，可以带 query string
+    path: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// 调用 tenant 的路由，以 NDJSON 流式返回 handler 的输出和结果
+async fn invoke_tenant(
+    State(state): State<AppState>,
+    TenantHost(host): TenantHost,
+    headers: HeaderMap,
+    Json(body): Json<InvokeBody>,
+) -> Result<Response, AppError> {
+    admin_config(&state)?.authorize(&headers, Role::Deployer, Some(&host))?;
+    let method = Method::from_bytes(body.method.to_uppercase().as_bytes())
+        .map_err(|e| AppError::BadRequest(e.into()))?;
+    let uri: Uri = body
+        .path
+        .parse()
+        .map_err(|e: InvalidUri| AppError::BadRequest(e.into()))?;
+    let body = body.body.map(Bytes::from).unwrap_or_default();
+    let stream = invoke::stream(state, host, method, uri, body);
+    Ok(([(CONTENT_TYPE, "application/x-ndjson")], stream).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditParams {
+    tenant: Option<String>,
+    action: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn query_audit(
+    State(state): State<AppState>,
+    Query(params): Query<AuditParams>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let token =
+        admin_config(&state)?.authorize(&headers, Role::Viewer, params.tenant.as_deref())?;
+    let Some(audit) = &state.audit else {
+        return Ok(Json(json!({ "events": [] })));
+    };
+    let query = AuditQuery {
+        tenant: params.tenant,
+        action: params
+            .action
+            .map(|a| a.parse())
+            .transpose()
+            .map_err(AppError::BadRequest)?,
+        limit: None,
+    };
+    let mut events: Vec<_> = audit
+        .query(&query)?
+        .into_iter()
+        .filter(|e| token.can_access(&e.tenant))
+        .collect();
+    if let Some(limit) = params.limit {
+        events.drain(..events.len().saturating_sub(limit));
+    }
+    Ok(Json(json!({ "events": events })))
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageParams {
+    tenant: Option<String>,
+    /// RFC 3339 格式的时间
+    from: Option<String>,
+    to: Option<String>,
+    /// `json`（默认）或 `csv`
+    format: Option<String>,
+}
+
+async fn query_usage(
+    State(state): State<AppState>,
+    Query(params): Query<UsageParams>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let token =
+        admin_config(&state)?.authorize(&headers, Role::Viewer, params.tenant.as_deref())?;
+    let time = |s: Option<String>| {
+        s.map(|s| {
+            DateTime::parse_from_rfc3339(&s)
+                .map(|t| t.to_utc())
+                .map_err(|e| AppError::BadRequest(anyhow::anyhow!("invalid time {s}: {e}")))
+        })
+        .transpose()
+    };
+    let query = UsageQuery {
+        tenant: params.tenant,
+        from: time(params.from)?,
+        to: time(params.to)?,
+    };
+    let reports: Vec<_> = match &state.usage {
+        Some(usage) => usage
+            .query(&query)?
+            .into_iter()
+            .filter(|r| token.can_access(&r.tenant))
+            .collect(),
+        None => vec![],
+    };
+    match params.format.as_deref() {
+        None | Some("json") => Ok(Json(json!({ "reports": reports })).into_response()),
+        Some("csv") => Ok((
+            [(CONTENT_TYPE, "text/csv; charset=utf-8")],
+            UsageReport::to_csv(&reports),
+        )
+            .into_response()),
+        Some(format) => Err(AppError::BadRequest(anyhow::anyhow!(
+            "unknown format {format}, expected json or csv"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorize_should_check_role_and_tenant() {
+        let config: AdminConfig = serde_yaml::from_str(
+            r#"
+tokens:
+  - { name: ops, token: t-admin, role: admin }
+  - { name: ci, token: t-ci, role: deployer, tenants: [a.com] }
+  - { name: dash, token: t-view, role: viewer }
+"#,
+        )
+        .unwrap();
+        let headers = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("authorization", format!("Bearer {token}").parse().unwrap());
+            headers
+        };
+
+        let ok = |token, role, tenant| config.authorize(&headers(token), role, tenant).is_ok();
+        assert!(ok("t-admin", Role::Admin, Some("b.com")));
+        assert!(ok("t-ci", Role::Deployer, Some("a.com")));
+        assert!(!ok("t-ci", Role::Deployer, Some("b.com")));
+        assert!(!ok("t-view", Role::Deployer, None));
+        assert!(ok("t-view", Role::Viewer, Some("b.com")));
+
+        let err = config.authorize(&HeaderMap::new(), Role::Viewer, None);
+        assert!(matches!(err, Err(AppError::Unauthorized(_))));
+        let err = config.authorize(&headers("nope"), Role::Viewer, None);
+        assert!(matches!(err, Err(AppError::Unauthorized(_))));
+    }
+
+    #[tokio::test]
+    async fn reload_should_swap_code_and_routes() -> Result<()> {
+        let code = |body: &str| {
+            format!(
+                r#"(function(){{ async function hello() {{ return {{ status: 200, headers: {{}}, body: "{body}" }}; }} return {{ hello }}; }})();"#
+            )
+        };
+        let routes: ProjectRoutes =
+            serde_yaml::from_str("/hello:\n  - method: GET\n    handler: hello\n")?;
+        let router = crate::SwappableAppRouter::try_new(code("v1"), routes)?;
+        let mut state =
+            AppState::with_routers(dashmap::DashMap::from_iter([("a.com".to_string(), router)]));
+        state.admin = Some(std::sync::Arc::new(serde_yaml::from_str(
+            "tokens: [{ name: ci, token: t-ci, role: deployer, tenants: [a.com] }]",
+        )?));
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer t-ci".parse()?);
+
+        let body = || -> Result<ReloadBody> {
+            Ok(serde_json::from_value(json!({
+                "code": code("v2"),
+                "routes": { "/hi": [{ "method": "GET", "handler": "hello" }] },
+            }))?)
+        };
+        let reload = |host: &str| {
+            reload_tenant(
+                State(state.clone()),
+                TenantHost(host.to_string()),
+                headers.clone(),
+                Json(body().unwrap()),
+            )
+        };
+        let Json(status) = reload("a.com").await?;
+        assert_eq!(status["status"], "reloaded");
+        let req = crate::engine::Req::builder()
+            .method("GET")
+            .url("/hi")
+            .build();
+        let resp = state.send("a.com".into(), "hello", req)?;
+        assert_eq!(resp.text(), Some("v2"));
+        assert!(
+            state
+                .routers
+                .get("a.com")
+                .unwrap()
+                .load()
+                .match_it(Method::GET, "/hello")
+                .is_err()
+        );
+
+        let err = reload("b.com").await;
+        assert!(matches!(err, Err(AppError::Forbidden(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Result, bail};
+use axum::{
+    body::Body,
+    http::{
+        Response, StatusCode, Uri,
+        header::{CONTENT_TYPE, LOCATION},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+
+/// 不属于任何 tenant 的 host 的响应，在读取请求 body 之前返回
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnknownHost {
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub body: String,
+}
+
+fn default_status() -> u16 {
+    404
+}
+
+impl UnknownHost {
+    fn response(&self) -> Response<Body> {
+        let mut resp = Response::new(Body::from(self.body.clone()));
+        *resp.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::NOT_FOUND);
+        if !self.body.is_empty() {
+            resp.headers_mut()
+                .insert(CONTENT_TYPE, "text/plain; charset=utf-8".parse().unwrap());
+        }
+        resp
+    }
+}
+
+impl AppState {
+    /// 设置 tenant 的别名 host，请求别名时 301 重定向到 tenant 的 host。
+    /// 别名不能是 tenant 或其他 tenant 的别名
+    pub fn set_aliases(&self, host: &str, aliases: &[String]) -> Result<()> {
+        let aliases: Vec<_> = aliases.iter().map(|a| a.to_ascii_lowercase()).collect();
+        for alias in &aliases {
+            if alias == host || self.routers.contains_key(alias) {
+                bail!("alias {alias} of {host} is a tenant");
+            }
+            if let Some(other) = self.aliases.get(alias)
+                && *other != host
+            {
+                bail!("alias {alias} of {host} already belongs to {}", *other);
+            }
+        }
+        self.aliases.retain(|_, canonical| canonical != host);
+        for alias in aliases {
+            self.aliases.insert(alias, host.to_string());
+        }
+        Ok(())
+    }
+
+    /// tenant 的别名，按名称排序
+    pub fn aliases(&self, host: &str) -> Vec<String> {
+        let mut aliases: Vec<_> = self
+            .aliases
+            .iter()
+            .filter(|a| a.value() == host)
+            .map(|a| a.key().clone())
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// 请求的 host 是别名时返回重定向，不属于任何 tenant 且配置了 `unknown_host` 时返回
+    /// 该响应，否则返回 None 继续处理。`port` 为 Host 头中的 `:port`
+    pub(crate) fn check_host(&self, host: &str, port: &str, uri: &Uri) -> Option<Response<Body>> {
+        let host = host.to_ascii_lowercase();
+        if self.routers.contains_key(&host) {
+            return None;
+        }
+        if let Some(canonical) = self.aliases.get(&host) {
+            let path = uri.path_and_query().map_or("/", |p| p.as_str());
+            // 不带 scheme，客户端沿用当前请求的 scheme
+            let location = format!("//{}{port}{path}", *canonical);
+            let mut resp = Response::new(Body::empty());
+            *resp.status_mut() = StatusCode::MOVED_PERMANENTLY;
+            if let Ok(location) = location.parse() {
+                resp.headers_mut().insert(LOCATION, location);
+            }
+            return Some(resp);
+        }
+        self.unknown_host.as_ref().map(UnknownHost::response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::SwappableAppRouter;
+
+    #[test]
+    fn aliases_should_redirect_and_unknown_hosts_should_be_rejected() -> Result<()> {
+        let router = || SwappableAppRouter::try_new("", Default::default());
+        let mut state = AppState::with_routers(DashMap::from_iter([
+            ("a.com".to_string(), router()?),
+            ("b.com".to_string(), router()?),
+        ]));
+        state.set_aliases("a.com", &["www.a.com".into(), "A.net".into()])?;
+        assert_eq!(state.aliases("a.com"), ["a.net", "www.a.com"]);
+        assert!(state.set_aliases("b.com", &["a.com".into()]).is_err());
+        assert!(state.set_aliases("b.com", &["a.net".into()]).is_err());
+
+        let uri: Uri = "/x?y=1".parse()?;
+        assert!(state.check_host("a.com", "", &uri).is_none());
+        assert!(state.check_host("A.Com", "", &uri).is_none());
+        let resp = state.check_host("WWW.a.com", ":8080", &uri).unwrap();
+        assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(resp.headers()[LOCATION], "//a.com:8080/x?y=1");
+        assert!(state.check_host("evil.com", "", &uri).is_none());
+
+        state.unknown_host = Some(serde_yaml::from_str("{ status: 421, body: nope }")?);
+        let resp = state.check_host("evil.com", "", &uri).unwrap();
+        assert_eq!(resp.status(), StatusCode::MISDIRECTED_REQUEST);
+        assert!(state.check_host("B.COM", "", &uri).is_none());
+
+        // tenant 的 host 同样以小写保存
+        state.add_tenant("Api.Example.com", "", Default::default(), "test")?;
+        assert!(state.check_host("api.example.com", "", &uri).is_none());
+        assert!(state.check_host("API.example.COM", "", &uri).is_none());
+        assert!(
+            state
+                .add_tenant("api.example.com", "", Default::default(), "test")
+                .is_err()
+        );
+        state.remove_tenant("API.EXAMPLE.COM", "test")?;
+        let resp = state.check_host("api.example.com", "", &uri).unwrap();
+        assert_eq!(resp.status(), StatusCode::MISDIRECTED_REQUEST);
+
+        // 替换别名时去掉旧的
+        state.set_aliases("a.com", &["a.org".into()])?;
+        assert_eq!(state.aliases("a.com"), ["a.org"]);
+        state.remove_tenant("a.com", "test")?;
+        assert!(state.aliases("a.com").is_empty());
+        Ok(())
+    }
+}
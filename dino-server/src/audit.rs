@@ -0,0 +1,189 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 审计事件的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// tenant 代码被替换
+    CodeSwap,
+    /// 路由配置发生变化
+    ConfigChange,
+    /// worker 重启
+    WorkerRestart,
+    /// 通过管理接口执行的操作
+    Admin,
+}
+
+/// 一条审计记录：谁在什么时候对哪个 tenant 做了什么，before/after 为变更前后的 hash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub at: String,
+    pub tenant: String,
+    pub actor: String,
+    pub action: AuditAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// 查询条件，字段为 None 时不过滤
+#[derive(Debug, Default, Clone)]
+pub struct AuditQuery {
+    pub tenant: Option<String>,
+    pub action: Option<AuditAction>,
+    /// 只返回最近的 n 条
+    pub limit: Option<usize>,
+}
+
+/// 只追加的审计日志，以 JSON Lines 格式持久化
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl AuditEvent {
+    pub fn new(tenant: impl Into<String>, actor: impl Into<String>, action: AuditAction) -> Self {
+        Self {
+            at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            tenant: tenant.into(),
+            actor: actor.into(),
+            action,
+            before: None,
+            after: None,
+            detail: None,
+        }
+    }
+
+    pub fn with_change(mut self, before: Option<String>, after: Option<String>) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+impl AuditLog {
+    pub fn try_new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open audit log {}", path.display()))?;
+        Ok(Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn append(&self, event: &AuditEvent) -> Result<()> {
+        let line = serde_json::to_string(event)?;
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{line}")?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>> {
+        Self::query_file(&self.path, query)
+    }
+
+    /// 直接读取日志文件，不需要服务在运行
+    pub fn query_file(path: impl AsRef<Path>, query: &AuditQuery) -> Result<Vec<AuditEvent>> {
+        let file = File::open(path.as_ref())?;
+        let mut events = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: AuditEvent = serde_json::from_str(&line)?;
+            let matched = query.tenant.as_ref().is_none_or(|t| *t == event.tenant)
+                && query.action.is_none_or(|a| a == event.action);
+            if matched {
+                events.push(event);
+            }
+        }
+        if let Some(limit) = query.limit {
+            events.drain(..events.len().saturating_sub(limit));
+        }
+        Ok(events)
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::Value::String(s.replace('-', "_")))
+            .with_context(|| format!("unknown audit action: {s}"))
+    }
+}
+
+/// 代码或配置的短 hash，用于审计记录
+pub(crate) fn short_hash(content: &str) -> String {
+    let mut hash = blake3::hash(content.as_bytes()).to_string();
+    hash.truncate(16);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audit_log_should_append_and_query() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("dino-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = AuditLog::try_new(&path)?;
+        log.append(
+            &AuditEvent::new("a.com", "alice", AuditAction::CodeSwap)
+                .with_change(Some("old".into()), Some("new".into())),
+        )?;
+        log.append(&AuditEvent::new("b.com", "bob", AuditAction::WorkerRestart))?;
+        log.append(&AuditEvent::new(
+            "a.com",
+            "alice",
+            AuditAction::WorkerRestart,
+        ))?;
+
+        let all = log.query(&AuditQuery::default())?;
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].before.as_deref(), Some("old"));
+
+        let query = AuditQuery {
+            tenant: Some("a.com".into()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let events = log.query(&query)?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, AuditAction::WorkerRestart);
+
+        let query = AuditQuery {
+            action: Some("worker-restart".parse()?),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&query)?.len(), 2);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
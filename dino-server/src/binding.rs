@@ -0,0 +1,187 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use anyhow::{Context, Result};
+use axum::{
+    body::Bytes,
+    extract::Query,
+    http::{HeaderMap, Method, Uri},
+};
+use indexmap::IndexMap;
+use rquickjs::{Ctx, Exception, Function};
+use serde::Deserialize;
+
+use crate::{AppState, ProjectConfig, engine::Resp};
+
+/// tenant 的服务绑定：可以调用的其他 tenant，以及允许调用自己的 tenant
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bindings {
+    /// 绑定名 -> 目标 tenant 的 host
+    pub targets: IndexMap<String, String>,
+    /// 允许通过绑定调用本 tenant 的 host
+    pub allowed_callers: Vec<String>,
+}
+
+/// JS 中 `bindings.NAME.fetch(req)` 的请求
+#[derive(Debug, Deserialize)]
+struct BindingRequest {
+    #[serde(default = "default_method")]
+    method: String,
+    /// 目标 tenant 中的路径，可以带 query string
+    #[serde(default = "default_url")]
+    url: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+thread_local! {
+    // worker 线程所属的 tenant，`bindings.X.fetch` 以它作为调用方
+    static CALLER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// `bindings` 对象，属性名即绑定名
+pub(crate) const BINDINGS: &str = r#"
+globalThis.bindings = new Proxy({}, {
+  get(_, name) {
+    return {
+      fetch: async (req = {}) => JSON.parse(__dino_binding_fetch(String(name), JSON.stringify(req))),
+    };
+  },
+});
+"#;
+
+impl Bindings {
+    pub fn from_config(config: &ProjectConfig) -> Self {
+        Self {
+            targets: config.bindings.clone(),
+            allowed_callers: config.allowed_callers.clone(),
+        }
+    }
+}
+
+impl AppState {
+    /// 设置 tenant 的服务绑定，立即生效
+    pub fn set_bindings(&self, host: &str, bindings: Bindings) {
+        self.bindings.insert(host.to_string(), bindings);
+    }
+
+    /// 通过 `caller` 的绑定 `name` 调用另一个 tenant，目标 tenant 必须允许 `caller` 调用
+    pub fn call_binding(
+        &self,
+        caller: &str,
+        name: &str,
+        method: Method,
+        uri: &Uri,
+        body: Bytes,
+    ) -> Result<Resp> {
+        let target = self
+            .bindings
+            .get(caller)
+            .and_then(|b| b.targets.get(name).cloned())
+            .with_context(|| format!("binding not found: {name}"))?;
+        // 目标只有一个 worker 时调用自己会死锁
+        anyhow::ensure!(
+            target != caller,
+            "binding {name} points to the caller itself"
+        );
+        let allowed = self
+            .bindings
+            .get(&target)
+            .is_some_and(|b| b.allowed_callers.iter().any(|h| h == caller));
+        anyhow::ensure!(
+            allowed,
+            "tenant {target} does not allow calls from {caller}"
+        );
+
+        let Query(query) = Query::<HashMap<String, String>>::try_from_uri(uri)?;
+        self.dispatch(target, method, uri, query, &HeaderMap::new(), body)
+    }
+}
+
+/// 当前 worker 线程所属的 tenant
+pub(crate) fn caller() -> Option<String> {
+    CALLER.with(|c| c.borrow().clone())
+}
+
+/// 在 worker 线程启动时调用
+pub(crate) fn set_caller(host: &str) {
+    CALLER.with(|c| *c.borrow_mut() = Some(host.to_string()));
+}
+
+pub(crate) fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.globals().set(
+        "__dino_binding_fetch",
+        Function::new(ctx.clone(), binding_fetch)?,
+    )?;
+    ctx.eval::<(), _>(BINDINGS)
+}
+
+fn binding_fetch(ctx: Ctx, name: String, req: String) -> rquickjs::Result<String> {
+    let ret = CALLER.with(|c| {
+        let c = c.borrow();
+        let caller = c.as_ref().context("bindings are not available here")?;
+        let state = AppState::get_current().context("server is not running")?;
+        let req: BindingRequest = serde_json::from_str(&req)?;
+        let method = Method::from_bytes(req.method.to_uppercase().as_bytes())?;
+        let uri: Uri = req.url.parse()?;
+        let body = req.body.map(Bytes::from).unwrap_or_default();
+        let resp = state.call_binding(caller, &name, method, &uri, body)?;
+        Ok::<_, anyhow::Error>(serde_json::to_string(&resp)?)
+    });
+    ret.map_err(|e| Exception::throw_message(&ctx, &format!("{e:#}")))
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_url() -> String {
+    "/".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::SwappableAppRouter;
+
+    #[test]
+    fn call_binding_should_check_allowed_callers() -> Result<()> {
+        let code = "(function(){ async function hello(req){ return { status: 200, headers: {}, body: req.query.name }; } return { hello }; })();";
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: auth\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?;
+        let routers = DashMap::new();
+        routers.insert(
+            "auth.internal".to_string(),
+            SwappableAppRouter::try_new(code, config.routes)?,
+        );
+        let state = AppState::with_routers(routers);
+        let caller = Bindings {
+            targets: IndexMap::from([("AUTH".to_string(), "auth.internal".to_string())]),
+            allowed_callers: vec![],
+        };
+        state.set_bindings("shop.com", caller.clone());
+        state.set_bindings("other.com", caller);
+
+        let uri: Uri = "/hello?name=dino".parse()?;
+        let call = |caller| state.call_binding(caller, "AUTH", Method::GET, &uri, Bytes::new());
+        assert!(call("shop.com").is_err());
+
+        state.set_bindings(
+            "auth.internal",
+            Bindings {
+                allowed_callers: vec!["shop.com".into()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(call("shop.com")?.text(), Some("dino"));
+        assert!(call("other.com").is_err());
+        assert!(
+            state
+                .call_binding("shop.com", "NOPE", Method::GET, &uri, Bytes::new())
+                .is_err()
+        );
+        Ok(())
+    }
+}
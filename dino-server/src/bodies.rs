@@ -0,0 +1,142 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use axum::http::{HeaderMap, header::CONTENT_TYPE};
+use serde::de::IgnoredAny;
+use tracing::warn;
+
+use crate::AppState;
+
+/// 每种问题的第 1 个以及之后每这么多个记录一条带有 body 片段的日志
+const SAMPLE_EVERY: u64 = 100;
+/// 日志中 body 片段的最大字节数
+const SAMPLE_BYTES: usize = 64;
+
+/// 客户端发来的有问题的请求 body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BodyProblem {
+    /// 超过 body 大小上限（或解压后超过上限），请求被拒绝
+    TooLarge,
+    /// 声明为文本或 JSON，但不是合法的 UTF-8
+    InvalidUtf8,
+    /// 声明为 JSON，但无法解析
+    InvalidJson,
+}
+
+/// tenant 累计收到的有问题的 body 数量和字节数
+#[derive(Debug, Default)]
+pub(crate) struct BodyStats {
+    pub too_large: AtomicU64,
+    pub invalid_utf8: AtomicU64,
+    pub invalid_json: AtomicU64,
+    /// 有问题的 body 的字节数，超过上限的 body 按 Content-Length 计算
+    pub bytes: AtomicU64,
+}
+
+impl BodyProblem {
+    fn as_str(self) -> &'static str {
+        match self {
+            BodyProblem::TooLarge => "too large",
+            BodyProblem::InvalidUtf8 => "not valid UTF-8",
+            BodyProblem::InvalidJson => "not valid JSON",
+        }
+    }
+}
+
+/// 按 Content-Type 检查 body：JSON 必须能够解析，文本必须是 UTF-8。
+/// 只用于统计，body 仍然原样交给 handler
+pub(crate) fn check_body(headers: &HeaderMap, body: &[u8]) -> Option<BodyProblem> {
+    if body.is_empty() {
+        return None;
+    }
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let json = mime == "application/json" || mime.ends_with("+json");
+    if !json && !mime.starts_with("text/") {
+        return None;
+    }
+    if std::str::from_utf8(body).is_err() {
+        return Some(BodyProblem::InvalidUtf8);
+    }
+    if json && serde_json::from_slice::<IgnoredAny>(body).is_err() {
+        return Some(BodyProblem::InvalidJson);
+    }
+    None
+}
+
+impl AppState {
+    /// 记录 tenant 收到的有问题的 body，按 `SAMPLE_EVERY` 抽样记录日志。
+    /// 不属于任何 tenant 的 host 不记录
+    pub(crate) fn record_body(&self, host: &str, problem: BodyProblem, len: u64, sample: &[u8]) {
+        if !self.routers.contains_key(host) {
+            return;
+        }
+        let stats = self.body_stats(host);
+        let counter = match problem {
+            BodyProblem::TooLarge => &stats.too_large,
+            BodyProblem::InvalidUtf8 => &stats.invalid_utf8,
+            BodyProblem::InvalidJson => &stats.invalid_json,
+        };
+        let count = counter.fetch_add(1, Ordering::Relaxed) + 1;
+        stats.bytes.fetch_add(len, Ordering::Relaxed);
+        if count % SAMPLE_EVERY == 1 {
+            let sample = String::from_utf8_lossy(&sample[..sample.len().min(SAMPLE_BYTES)]);
+            warn!(
+                "Request body of {host} is {} ({len} bytes, {count} so far): {sample:?}",
+                problem.as_str()
+            );
+        }
+    }
+
+    pub(crate) fn body_stats(&self, host: &str) -> Arc<BodyStats> {
+        self.body_stats.entry(host.to_string()).or_default().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::SwappableAppRouter;
+
+    #[test]
+    fn bodies_should_be_checked_and_counted() -> anyhow::Result<()> {
+        let mut headers = HeaderMap::new();
+        assert_eq!(check_body(&headers, b"\xff"), None);
+        headers.insert(CONTENT_TYPE, "application/json; charset=utf-8".parse()?);
+        assert_eq!(check_body(&headers, br#"{"a": 1}"#), None);
+        assert_eq!(check_body(&headers, b""), None);
+        assert_eq!(
+            check_body(&headers, b"{\"a\": "),
+            Some(BodyProblem::InvalidJson)
+        );
+        assert_eq!(
+            check_body(&headers, b"\xff"),
+            Some(BodyProblem::InvalidUtf8)
+        );
+        headers.insert(CONTENT_TYPE, "text/plain".parse()?);
+        assert_eq!(check_body(&headers, b"{"), None);
+        headers.insert(CONTENT_TYPE, "image/png".parse()?);
+        assert_eq!(check_body(&headers, b"\xff"), None);
+
+        let router = SwappableAppRouter::try_new("", Default::default())?;
+        let state = AppState::with_routers(DashMap::from_iter([("a.com".to_string(), router)]));
+        state.record_body("a.com", BodyProblem::InvalidJson, 6, b"{\"a\": ");
+        state.record_body("a.com", BodyProblem::TooLarge, 1024, b"");
+        state.record_body("evil.com", BodyProblem::TooLarge, 1024, b"");
+        let stats = state.body_stats("a.com");
+        assert_eq!(stats.invalid_json.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.too_large.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.bytes.load(Ordering::Relaxed), 1030);
+        assert!(!state.body_stats.contains_key("evil.com"));
+        Ok(())
+    }
+}
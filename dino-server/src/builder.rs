@@ -0,0 +1,254 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use bundler::{AuthConfig, Capabilities, Defines, Options, Plugin, core_module_name, run_bundle};
+use tar::{Archive, EntryType};
+
+use crate::{HOST_GLOBALS, ProjectConfig, host_modules};
+
+/// 源码包中的项目配置文件
+const CONFIG_FILE: &str = "config.yml";
+/// 打包目标，与 `dino build` 一致，`foo.ts` 存在 `foo.server.ts` 时使用后者
+const SERVER_TARGET: &str = "server";
+/// 解包后的文件总大小上限
+pub(crate) const MAX_SOURCE_SIZE: u64 = 32 << 20;
+
+/// 在服务器上打包的结果
+#[derive(Debug)]
+pub struct SourceBuild {
+    pub code: String,
+    pub config: ProjectConfig,
+}
+
+/// 把 tenant 上传的源码包（tar，根目录下有 config.yml 和入口文件）解包到临时目录并打包。
+///
+/// 源码包只能包含普通文件和目录，路径不能是绝对路径或包含 `..`；打包时只能导入项目内的文件
+/// 和核心模块，不读取服务器环境变量中的 defines 和 URL 导入凭据
+pub fn build_source(archive: &[u8]) -> Result<SourceBuild> {
+    let dir = TempDir::new()?;
+    unpack(archive, &dir.0)?;
+    build_dir(&dir.0)
+}
+
+/// 打包 `root` 下的项目，`root` 中不能有符号链接
+pub(crate) fn build_dir(root: &Path) -> Result<SourceBuild> {
+    let config = ProjectConfig::load(root.join(CONFIG_FILE))
+        .with_context(|| format!("invalid {CONFIG_FILE}"))?;
+    let entry = root.join(&config.entry);
+    ensure!(
+        is_inside(root, &entry) && entry.is_file(),
+        "entry file not found: {}",
+        config.entry
+    );
+
+    let mut options = Options {
+        auth: AuthConfig::default(),
+        defines: Defines::default(),
+        capabilities: Capabilities {
+            runtime: "dino-server".into(),
+            core_modules: host_modules(),
+            globals: HOST_GLOBALS.iter().map(|g| g.to_string()).collect(),
+        },
+        target: Some(SERVER_TARGET.into()),
+        ..Default::default()
+    };
+    options.plugins.push(Sandbox {
+        root: root.to_path_buf(),
+    });
+    let code = run_bundle(&entry.to_string_lossy(), &options)?;
+    Ok(SourceBuild { code, config })
+}
+
+fn unpack(archive: &[u8], dir: &Path) -> Result<()> {
+    let mut size = 0;
+    for entry in Archive::new(archive).entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        ensure!(
+            path.components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir)),
+            "invalid path in source archive: {}",
+            path.display()
+        );
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Directory => {}
+            EntryType::XGlobalHeader => continue,
+            kind => bail!(
+                "{} in source archive is not a file or directory ({kind:?})",
+                path.display()
+            ),
+        }
+        size += entry.size();
+        ensure!(
+            size <= MAX_SOURCE_SIZE,
+            "source archive is larger than {} MiB",
+            MAX_SOURCE_SIZE >> 20
+        );
+        entry.unpack_in(dir)?;
+    }
+    Ok(())
+}
+
+/// 拒绝导入项目目录之外的文件和 URL：URL 导入会让服务器代替 tenant 请求任意地址，
+/// 需要远程依赖的项目应当在本地打包后部署
+#[derive(Debug)]
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Plugin for Sandbox {
+    fn name(&self) -> &str {
+        "sandbox"
+    }
+
+    fn on_resolve(&self, specifier: &str, importer: Option<&str>) -> Result<Option<String>> {
+        let Some(importer) = importer else {
+            return Ok(None);
+        };
+        if core_module_name(specifier).is_some() {
+            return Ok(None);
+        }
+        // URL、`file:` 等带 scheme 的导入
+        let has_scheme = specifier
+            .split_once(':')
+            .is_some_and(|(scheme, _)| scheme.len() > 1 && !scheme.contains('/'));
+        if has_scheme {
+            bail!("{specifier} can't be imported in server builds");
+        }
+        let base = Path::new(importer).parent().unwrap_or(&self.root);
+        ensure!(
+            is_inside(&self.root, &base.join(specifier)),
+            "{specifier} is outside the project"
+        );
+        Ok(None)
+    }
+}
+
+/// 按路径的字面值判断，解包时已经拒绝了符号链接
+fn is_inside(root: &Path, path: &Path) -> bool {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            c => normalized.push(c),
+        }
+    }
+    normalized.starts_with(root)
+}
+
+/// 打包结束后删除的临时目录
+pub(crate) struct TempDir(pub(crate) PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("dino-build-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(files: &[(&str, &str)]) -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(EntryType::Regular);
+            // `append_data` 会拒绝 `..`，直接写入路径以构造恶意的源码包
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, content.as_bytes())?;
+        }
+        Ok(builder.into_inner()?)
+    }
+
+    const CONFIG: &str =
+        "name: demo\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n";
+
+    #[test]
+    fn build_source_should_bundle_project() -> Result<()> {
+        let source = archive(&[
+            ("config.yml", CONFIG),
+            ("lib.ts", "export const greeting: string = 'hello';"),
+            (
+                "main.ts",
+                "import { greeting } from './lib.ts';\nexport async function hello() { return { status: 200, headers: {}, body: greeting }; }",
+            ),
+        ])?;
+        let build = build_source(&source)?;
+        assert_eq!(build.config.name, "demo");
+        assert!(build.code.contains("\"hello\""));
+        Ok(())
+    }
+
+    #[test]
+    fn build_source_should_reject_broken_js() -> Result<()> {
+        let source = archive(&[
+            ("config.yml", CONFIG),
+            ("lib.js", "export const greeting = ;"),
+            (
+                "main.ts",
+                "import { greeting } from './lib.js';\nexport async function hello() { return { status: 200, headers: {}, body: greeting }; }",
+            ),
+        ])?;
+        let err = format!("{:#}", build_source(&source).unwrap_err());
+        assert!(
+            err.contains("failed to parse") && err.contains("lib.js:1:"),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn build_source_should_stay_in_project() -> Result<()> {
+        let escape = archive(&[("../config.yml", CONFIG)])?;
+        let err = build_source(&escape).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid path in source archive: ../config.yml"
+        );
+
+        let outside = archive(&[
+            ("config.yml", CONFIG),
+            ("main.ts", "import '../../etc/secret.ts';"),
+        ])?;
+        let err = format!("{:#}", build_source(&outside).unwrap_err());
+        assert!(
+            err.contains("../../etc/secret.ts is outside the project"),
+            "{err}"
+        );
+
+        for url in ["https://169.254.169.254/latest.js", "file:///etc/secret.ts"] {
+            let remote = archive(&[
+                ("config.yml", CONFIG),
+                ("main.ts", &format!("import '{url}';")),
+            ])?;
+            let err = format!("{:#}", build_source(&remote).unwrap_err());
+            assert!(
+                err.contains(&format!("{url} can't be imported in server builds")),
+                "{err}"
+            );
+        }
+
+        let missing = archive(&[("config.yml", CONFIG)])?;
+        let err = build_source(&missing).unwrap_err();
+        assert_eq!(err.to_string(), "entry file not found: main.ts");
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, ensure};
+use rquickjs::{Ctx, Exception, Function};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, binding};
+
+/// 每个 tenant 缓存的默认大小上限
+pub const DEFAULT_CACHE_BYTES: usize = 64 << 20;
+
+/// 类似 Cache API 的 `caches`，只缓存 GET 请求，key 为请求的 url
+pub(crate) const CACHES: &str = r#"
+globalThis.caches = (() => {
+  const key = (req) => {
+    if (typeof req === "string") return req;
+    if (req.method && req.method.toUpperCase() !== "GET") return null;
+    return String(req.url);
+  };
+  const open = (name) => ({
+    async match(req) {
+      const k = key(req);
+      if (k === null) return undefined;
+      const resp = __dino_cache_match(name, k);
+      return resp === undefined ? undefined : JSON.parse(resp);
+    },
+    async put(req, resp) {
+      const k = key(req);
+      if (k === null) throw new TypeError("only GET requests can be cached");
+      __dino_cache_put(name, k, JSON.stringify(resp));
+    },
+    async delete(req) {
+      const k = key(req);
+      return k === null ? false : __dino_cache_delete(name, k);
+    },
+  });
+  return { default: open("default"), open: async (name) => open(String(name)) };
+})();
+"#;
+
+/// 缓存的响应，`match` 返回时加上 `age` 和 `x-dino-cache` 头
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CachedResponse {
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// 各 tenant 的响应缓存，保存在内存中，超出大小上限时淘汰最久未使用的条目
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseCache {
+    max_bytes: usize,
+    tenants: Arc<Mutex<HashMap<String, TenantCache>>>,
+}
+
+#[derive(Debug, Default)]
+struct TenantCache {
+    /// (cache 名, url) -> 条目
+    entries: HashMap<(String, String), Entry>,
+    bytes: usize,
+    /// 每次访问加 1，用于找出最久未使用的条目
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    resp: CachedResponse,
+    stored: Instant,
+    /// `max-age` 或 `s-maxage`，没有时不过期
+    ttl: Option<Duration>,
+    /// 过期后仍然可以返回的时长，来自 `stale-while-revalidate`
+    stale: Duration,
+    size: usize,
+    used: u64,
+}
+
+/// `put` 时从 `cache-control` 解析出的缓存策略
+#[derive(Debug, PartialEq, Eq)]
+struct Policy {
+    ttl: Option<Duration>,
+    stale: Duration,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_BYTES)
+    }
+}
+
+impl ResponseCache {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            tenants: Default::default(),
+        }
+    }
+
+    /// 新鲜的条目带 `x-dino-cache: hit`，过期但在 `stale-while-revalidate` 内的带
+    /// `x-dino-cache: stale`，handler 据此在返回旧响应的同时重新生成
+    pub(crate) fn get(&self, tenant: &str, name: &str, url: &str) -> Option<CachedResponse> {
+        self.get_at(tenant, name, url, Instant::now())
+    }
+
+    fn get_at(&self, tenant: &str, name: &str, url: &str, now: Instant) -> Option<CachedResponse> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let cache = tenants.get_mut(tenant)?;
+        let key = (name.to_string(), url.to_string());
+        let entry = cache.entries.get(&key)?;
+        let age = now.saturating_duration_since(entry.stored);
+        let state = match entry.ttl {
+            Some(ttl) if age > ttl + entry.stale => {
+                cache.remove(&key);
+                return None;
+            }
+            Some(ttl) if age > ttl => "stale",
+            _ => "hit",
+        };
+        cache.clock += 1;
+        let clock = cache.clock;
+        let entry = cache.entries.get_mut(&key)?;
+        entry.used = clock;
+        let mut resp = entry.resp.clone();
+        resp.headers.insert("age".into(), age.as_secs().to_string());
+        resp.headers.insert("x-dino-cache".into(), state.into());
+        Some(resp)
+    }
+
+    /// 保存响应，`cache-control` 为 `no-store` 或 `private` 的响应不保存，返回是否保存
+    pub(crate) fn put(
+        &self,
+        tenant: &str,
+        name: &str,
+        url: &str,
+        resp: CachedResponse,
+    ) -> Result<bool> {
+        let Some(policy) = Policy::parse(&resp.headers) else {
+            return Ok(false);
+        };
+        let size = url.len()
+            + resp.body.as_ref().map_or(0, String::len)
+            + resp
+                .headers
+                .iter()
+                .map(|(k, v)| k.len() + v.len())
+                .sum::<usize>();
+        ensure!(
+            size <= self.max_bytes,
+            "response of {size} bytes is larger than the cache ({} bytes)",
+            self.max_bytes
+        );
+
+        let mut tenants = self.tenants.lock().unwrap();
+        let cache = tenants.entry(tenant.to_string()).or_default();
+        let key = (name.to_string(), url.to_string());
+        cache.remove(&key);
+        while cache.bytes + size > self.max_bytes {
+            let oldest = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(k, _)| k.clone())
+                .context("cache is empty")?;
+            cache.remove(&oldest);
+        }
+        cache.clock += 1;
+        cache.bytes += size;
+        let entry = Entry {
+            resp,
+            stored: Instant::now(),
+            ttl: policy.ttl,
+            stale: policy.stale,
+            size,
+            used: cache.clock,
+        };
+        cache.entries.insert(key, entry);
+        Ok(true)
+    }
+
+    pub(crate) fn delete(&self, tenant: &str, name: &str, url: &str) -> bool {
+        let mut tenants = self.tenants.lock().unwrap();
+        let key = (name.to_string(), url.to_string());
+        tenants
+            .get_mut(tenant)
+            .is_some_and(|cache| cache.remove(&key))
+    }
+
+    /// 删除 tenant 的所有缓存
+    pub(crate) fn clear(&self, tenant: &str) {
+        self.tenants.lock().unwrap().remove(tenant);
+    }
+}
+
+impl TenantCache {
+    fn remove(&mut self, key: &(String, String)) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.bytes -= entry.size;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Policy {
+    /// 返回 None 表示不能缓存
+    fn parse(headers: &HashMap<String, String>) -> Option<Self> {
+        let mut policy = Policy {
+            ttl: None,
+            stale: Duration::ZERO,
+        };
+        let Some((_, value)) = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("cache-control"))
+        else {
+            return Some(policy);
+        };
+        let mut max_age = None;
+        for directive in value.split(',').map(str::trim) {
+            let (name, arg) = directive.split_once('=').unwrap_or((directive, ""));
+            let secs = arg.trim_matches('"').parse().ok().map(Duration::from_secs);
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" | "private" => return None,
+                "s-maxage" => policy.ttl = secs.or(policy.ttl),
+                "max-age" => max_age = secs,
+                "stale-while-revalidate" => policy.stale = secs.unwrap_or_default(),
+                _ => {}
+            }
+        }
+        // s-maxage 优先于 max-age
+        policy.ttl = policy.ttl.or(max_age);
+        Some(policy)
+    }
+}
+
+pub(crate) fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    let global = ctx.globals();
+    global.set(
+        "__dino_cache_match",
+        Function::new(ctx.clone(), cache_match)?,
+    )?;
+    global.set("__dino_cache_put", Function::new(ctx.clone(), cache_put)?)?;
+    global.set(
+        "__dino_cache_delete",
+        Function::new(ctx.clone(), cache_delete)?,
+    )?;
+    ctx.eval::<(), _>(CACHES)
+}
+
+/// 以当前 worker 所属的 tenant 访问缓存
+fn with_cache<T>(
+    ctx: &Ctx,
+    f: impl FnOnce(&ResponseCache, &str) -> Result<T>,
+) -> rquickjs::Result<T> {
+    let ret = (|| {
+        let tenant = binding::caller().context("caches are not available here")?;
+        let state = AppState::get_current().context("server is not running")?;
+        f(&state.cache, &tenant)
+    })();
+    ret.map_err(|e| Exception::throw_message(ctx, &format!("{e:#}")))
+}
+
+fn cache_match(ctx: Ctx, name: String, url: String) -> rquickjs::Result<Option<String>> {
+    with_cache(&ctx, |cache, tenant| {
+        cache
+            .get(tenant, &name, &url)
+            .map(|resp| serde_json::to_string(&resp))
+            .transpose()
+            .map_err(Into::into)
+    })
+}
+
+fn cache_put(ctx: Ctx, name: String, url: String, resp: String) -> rquickjs::Result<bool> {
+    with_cache(&ctx, |cache, tenant| {
+        let resp: CachedResponse = serde_json::from_str(&resp)?;
+        cache.put(tenant, &name, &url, resp)
+    })
+}
+
+fn cache_delete(ctx: Ctx, name: String, url: String) -> rquickjs::Result<bool> {
+    with_cache(&ctx, |cache, tenant| Ok(cache.delete(tenant, &name, &url)))
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resp(cache_control: &str, body: &str) -> CachedResponse {
+        CachedResponse {
+            status: 200,
+            headers: HashMap::from([("Cache-Control".into(), cache_control.into())]),
+            body: Some(body.into()),
+        }
+    }
+
+    #[test]
+    fn policy_should_parse_cache_control() {
+        let parse = |v: &str| Policy::parse(&resp(v, "").headers);
+        let secs = Duration::from_secs;
+        assert_eq!(parse("no-store"), None);
+        assert_eq!(parse("Private, max-age=10"), None);
+        assert_eq!(
+            parse("max-age=10, s-maxage=60, stale-while-revalidate=30"),
+            Some(Policy {
+                ttl: Some(secs(60)),
+                stale: secs(30)
+            })
+        );
+        assert_eq!(
+            Policy::parse(&HashMap::new()),
+            Some(Policy {
+                ttl: None,
+                stale: Duration::ZERO
+            })
+        );
+    }
+
+    #[test]
+    fn cache_should_serve_fresh_then_stale_entries() -> Result<()> {
+        let cache = ResponseCache::default();
+        let url = "https://a.com/x";
+        assert!(cache.put(
+            "a.com",
+            "default",
+            url,
+            resp("max-age=10, stale-while-revalidate=5", "v1")
+        )?);
+        assert!(!cache.put("a.com", "default", url, resp("no-store", "v2"))?);
+
+        let now = Instant::now();
+        let hit = cache.get_at("a.com", "default", url, now).unwrap();
+        assert_eq!(hit.body.as_deref(), Some("v1"));
+        assert_eq!(hit.headers["x-dino-cache"], "hit");
+        let stale = cache.get_at("a.com", "default", url, now + Duration::from_secs(12));
+        assert_eq!(stale.unwrap().headers["x-dino-cache"], "stale");
+        assert_eq!(stale_age(&cache, url, now + Duration::from_secs(12)), "12");
+        assert!(
+            cache
+                .get_at("a.com", "default", url, now + Duration::from_secs(16))
+                .is_none()
+        );
+        // 过期的条目已被删除
+        assert!(cache.get_at("a.com", "default", url, now).is_none());
+
+        // tenant 和 cache 名互相隔离
+        cache.put("a.com", "default", url, resp("", "v3"))?;
+        assert!(cache.get("b.com", "default", url).is_none());
+        assert!(cache.get("a.com", "other", url).is_none());
+        assert!(cache.delete("a.com", "default", url));
+        assert!(!cache.delete("a.com", "default", url));
+        Ok(())
+    }
+
+    fn stale_age(cache: &ResponseCache, url: &str, at: Instant) -> String {
+        let mut resp = cache.get_at("a.com", "default", url, at).unwrap();
+        resp.headers.remove("age").unwrap()
+    }
+
+    #[test]
+    fn cache_should_evict_least_recently_used() -> Result<()> {
+        let entry = |body: &str| resp("", body);
+        // 每个条目 1 + 13 + 100 字节
+        let cache = ResponseCache::new(300);
+        let body = "x".repeat(100);
+        cache.put("a.com", "default", "a", entry(&body))?;
+        cache.put("a.com", "default", "b", entry(&body))?;
+        cache.get("a.com", "default", "a").unwrap();
+        cache.put("a.com", "default", "c", entry(&body))?;
+        assert!(cache.get("a.com", "default", "a").is_some());
+        assert!(cache.get("a.com", "default", "b").is_none());
+        assert!(cache.get("a.com", "default", "c").is_some());
+
+        let err = cache
+            .put("a.com", "default", "d", entry(&"x".repeat(300)))
+            .unwrap_err();
+        assert!(err.to_string().contains("larger than the cache"));
+        cache.clear("a.com");
+        assert!(cache.get("a.com", "default", "a").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use std::{cell::RefCell, fmt};
+
+use rquickjs::{Ctx, Exception, Function, Object};
+use serde::{Deserialize, Serialize};
+
+/// tenant 可以使用的运行时能力，被拒绝的能力对应的 host op 在创建 worker 时
+/// 替换为抛出异常的函数。`kv` 是 `objects` 的别名，键值存储由有状态对象的存储提供；
+/// 运行时没有读取环境变量或访问数据库的 host API，配置中出现 `env`、`db` 时报错，
+/// 而不是静默接受一个不起作用的限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum Capability {
+    /// `fetch` 访问外部网络
+    Fetch,
+    /// `fs` 核心模块读写服务器上的文件
+    Fs,
+    /// `dns` 核心模块
+    Dns,
+    /// `caches` 响应缓存
+    Cache,
+    /// `objects` 有状态对象及其存储
+    Objects,
+}
+
+/// tenant 的能力声明，`allow` 为 None 时允许除 `deny` 之外的所有能力
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Capabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow: Option<Vec<Capability>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<Capability>,
+}
+
+thread_local! {
+    // worker 线程所属 tenant 的能力，创建引擎时读取
+    static CURRENT: RefCell<Capabilities> = RefCell::new(Capabilities::default());
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [Self::Fetch, Self::Fs, Self::Dns, Self::Cache, Self::Objects];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fetch => "fetch",
+            Self::Fs => "fs",
+            Self::Dns => "dns",
+            Self::Cache => "cache",
+            Self::Objects => "objects",
+        }
+    }
+
+    /// 核心模块对应的能力，没有副作用的模块（time、crypto）总是可用
+    pub(crate) fn of_module(name: &str) -> Option<Self> {
+        match name {
+            "fs" => Some(Self::Fs),
+            "dns" => Some(Self::Dns),
+            _ => None,
+        }
+    }
+
+    /// 安装在全局对象上的 host op，核心模块的 op 由 `host::install` 处理
+    #[cfg(feature = "server")]
+    fn global_ops(self) -> &'static [&'static str] {
+        match self {
+            Self::Fetch => &["__dino_fetch"],
+            Self::Cache => &[
+                "__dino_cache_match",
+                "__dino_cache_put",
+                "__dino_cache_delete",
+            ],
+            Self::Objects => &[
+                "__dino_object_fetch",
+                "__dino_storage_get",
+                "__dino_storage_put",
+                "__dino_storage_delete",
+            ],
+            // 核心模块之外，`serveFile` 也会读取服务器上的文件
+            Self::Fs => &["serveFile"],
+            Self::Dns => &[],
+        }
+    }
+}
+
+impl TryFrom<String> for Capability {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if name == "kv" {
+            return Ok(Self::Objects);
+        }
+        if let Some(capability) = Self::ALL.into_iter().find(|c| c.name() == name) {
+            return Ok(capability);
+        }
+        let names: Vec<_> = Self::ALL.iter().map(|c| c.name()).collect();
+        match name.as_str() {
+            "env" | "db" => Err(format!(
+                "{name} is not a capability of dino-server, the runtime has no {name} host API"
+            )),
+            _ => Err(format!(
+                "unknown capability {name}, expected one of: {}, kv",
+                names.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Capabilities {
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.allow.as_ref().is_none_or(|a| a.contains(&capability))
+            && !self.deny.contains(&capability)
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        Capability::ALL.into_iter().all(|c| self.allows(c))
+    }
+}
+
+/// 设置当前线程创建的 worker 的能力
+#[cfg(feature = "server")]
+pub(crate) fn set_current(capabilities: Capabilities) {
+    CURRENT.with(|c| *c.borrow_mut() = capabilities);
+}
+
+/// 当前线程是否允许使用 `capability`
+pub(crate) fn allowed(capability: Capability) -> bool {
+    CURRENT.with(|c| c.borrow().allows(capability))
+}
+
+/// 把 `target` 上的 `names` 替换为抛出异常的函数
+pub(crate) fn deny<'js>(
+    ctx: &Ctx<'js>,
+    target: &Object<'js>,
+    capability: Capability,
+    names: &[&str],
+) -> rquickjs::Result<()> {
+    for name in names {
+        let stub = Function::new(ctx.clone(), move |ctx: Ctx<'js>| -> rquickjs::Result<()> {
+            let msg = format!("{capability} is not allowed for this tenant");
+            Err(Exception::throw_message(&ctx, &msg))
+        })?;
+        target.set(*name, stub)?;
+    }
+    Ok(())
+}
+
+/// 替换当前线程不允许的全局 host op，在各模块安装之后调用
+#[cfg(feature = "server")]
+pub(crate) fn restrict(ctx: &Ctx) -> rquickjs::Result<()> {
+    let global = ctx.globals();
+    for capability in Capability::ALL {
+        if !allowed(capability) {
+            deny(ctx, &global, capability, capability.global_ops())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_should_combine_allow_and_deny() -> anyhow::Result<()> {
+        let caps: Capabilities = serde_yaml::from_str("deny: [fs]")?;
+        assert!(!caps.allows(Capability::Fs));
+        assert!(caps.allows(Capability::Fetch));
+
+        let caps: Capabilities = serde_yaml::from_str("{ allow: [fetch, fs], deny: [fs] }")?;
+        assert!(caps.allows(Capability::Fetch));
+        assert!(!caps.allows(Capability::Fs));
+        assert!(!caps.allows(Capability::Cache));
+        assert!(!caps.is_unrestricted());
+        assert!(Capabilities::default().is_unrestricted());
+
+        assert!(serde_yaml::from_str::<Capabilities>("deny: [net]").is_err());
+
+        // kv 是 objects 的别名，env 和 db 没有对应的 host API
+        let caps: Capabilities = serde_yaml::from_str("deny: [kv]")?;
+        assert_eq!(caps.deny, [Capability::Objects]);
+        for name in ["env", "db"] {
+            let err = serde_yaml::from_str::<Capabilities>(&format!("deny: [{name}]")).unwrap_err();
+            let expected = format!("no {name} host API");
+            assert!(err.to_string().contains(&expected), "{err}");
+        }
+        Ok(())
+    }
+}
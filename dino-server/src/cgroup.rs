@@ -0,0 +1,90 @@
+//! 用 cgroup v2 限制 tenant 的 CPU。
+//!
+//! worker 是 dino-server 进程中的线程，所以每个 tenant 对应服务器所在 cgroup 下的一个
+//! threaded 子 cgroup，worker 线程启动时加入其中。memory 控制器不支持 threaded 模式，
+//! 内存仍然只能按进程整体限制。
+
+/// `cpu.max` 的周期（微秒）
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// `cpu.max` 的内容，`percent` 为 100 时可以使用一个 core
+fn cpu_max(percent: u32) -> String {
+    let quota = CPU_PERIOD_US * u64::from(percent.max(1)) / 100;
+    format!("{quota} {CPU_PERIOD_US}")
+}
+
+/// tenant 的 cgroup 目录名，host 中只保留字母、数字、`.` 和 `-`
+fn group_name(host: &str) -> String {
+    let host: String = host
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("dino-{host}")
+}
+
+/// 从 `/proc/self/cgroup` 中取出 cgroup v2 的路径
+fn own_path(content: &str) -> Option<&str> {
+    content.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+/// 把当前线程加入 tenant 的 cgroup，并按 `percent` 设置 CPU 上限，失败只打印警告。
+/// tenant 删除后保留 cgroup，重新添加时复用
+#[cfg(target_os = "linux")]
+pub(crate) fn join(host: &str, percent: u32) {
+    if let Err(e) = try_join(host, percent) {
+        tracing::warn!("Failed to limit cpu of {host} with cgroup: {e:#}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn try_join(host: &str, percent: u32) -> anyhow::Result<()> {
+    use std::{fs, path::Path};
+
+    use anyhow::Context;
+
+    let content = fs::read_to_string("/proc/self/cgroup")?;
+    let own = own_path(&content).context("cgroup v2 is not mounted")?;
+    let parent = Path::new("/sys/fs/cgroup").join(own.trim_start_matches('/'));
+    let dir = parent.join(group_name(host));
+    if !dir.exists() {
+        fs::write(parent.join("cgroup.subtree_control"), "+cpu")
+            .context("failed to enable the cpu controller")?;
+        // 并发启动的 worker 可能已经创建了目录
+        if let Err(e) = fs::create_dir(&dir)
+            && e.kind() != std::io::ErrorKind::AlreadyExists
+        {
+            return Err(e).with_context(|| format!("failed to create {}", dir.display()));
+        }
+        fs::write(dir.join("cgroup.type"), "threaded")?;
+    }
+    fs::write(dir.join("cpu.max"), cpu_max(percent))?;
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) };
+    fs::write(dir.join("cgroup.threads"), tid.to_string())?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn join(host: &str, _percent: u32) {
+    tracing::warn!("Limiting cpu of {host} with cgroup is only supported on linux");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cgroup_should_format_limits_and_paths() {
+        assert_eq!(cpu_max(100), "100000 100000");
+        assert_eq!(cpu_max(50), "50000 100000");
+        assert_eq!(cpu_max(0), "1000 100000");
+        assert_eq!(group_name("a.com:8080"), "dino-a.com_8080");
+        assert_eq!(
+            own_path("1:name=systemd:/x\n0::/system.slice/dino.service\n"),
+            Some("/system.slice/dino.service")
+        );
+        assert_eq!(own_path("1:cpu:/x\n"), None);
+    }
+}
@@ -1,23 +1,239 @@
-use std::path::Path;
+#[cfg(feature = "server")]
+use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fmt,
+    marker::PhantomData,
+    path::{Component, Path},
+    str::FromStr,
+};
 
-use anyhow::{Context, Result};
-use axum::http::Method;
+use anyhow::{Context, Result, bail};
+use chrono_tz::Tz;
+use http::Method;
 use indexmap::IndexMap;
-use serde::{Deserialize, Deserializer};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{
+        Error, MapAccess, SeqAccess, Visitor,
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+    },
+};
+use serde_yaml::Value;
 
-#[derive(Debug, Deserialize)]
+#[cfg(feature = "tls")]
+use crate::TlsOptions;
+#[cfg(feature = "server")]
+use crate::{Bindings, SwappableAppRouter, TenantRouter, UnknownHost, WorkerSettings};
+use crate::{LogConfig, contract::Contracts};
+
+/// 当前的配置文件版本，旧版本可以用 `dino upgrade` 迁移
+pub const CONFIG_VERSION: u32 = 1;
+
+/// 项目的 config.yml，用 `str::parse` 解析时会校验整个配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProjectConfig {
+    /// 缺省为 0，即引入版本号之前的配置
+    #[serde(default)]
+    pub version: u32,
     pub name: String,
+    /// 打包入口，相对于项目根目录
+    #[serde(default = "default_entry")]
+    pub entry: String,
+    /// 值为方法列表的是路由，值为带 `routes` 的对象的是分组，见 `RouteGroup`
+    #[serde(deserialize_with = "deserialize_routes")]
     pub routes: ProjectRoutes,
+    #[serde(default)]
+    pub logging: LogConfig,
+    /// 服务绑定：名字 -> 其他 tenant 的 host，JS 中通过 `bindings.NAME.fetch(req)` 调用
+    #[serde(default, deserialize_with = "unique_keys")]
+    pub bindings: IndexMap<String, String>,
+    /// 允许通过服务绑定调用本项目的 tenant
+    #[serde(default)]
+    pub allowed_callers: Vec<String>,
+    /// 定时任务：名字 -> cron 表达式和 handler，可以用 `dino cron run <name>` 在本地执行一次
+    #[serde(default, deserialize_with = "unique_keys")]
+    pub crons: IndexMap<String, CronJob>,
+    /// IANA 时区，如 `Asia/Shanghai`，是 `dino:time` 的默认时区和定时任务触发时间的时区，
+    /// 不设置时为 UTC，不受服务器时区影响
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
+    /// `dino deploy` 的默认目标，命令行参数和环境变量优先
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deploy: Option<DeployConfig>,
+    /// 路径结尾的 `/` 和大小写的匹配方式
+    #[serde(default, skip_serializing_if = "RoutingOptions::is_default")]
+    pub routing: RoutingOptions,
+    /// 每个 JS worker 可以使用的内存（MB），超过时 handler 以 500 结束，
+    /// 设置后覆盖服务器配置中的 worker 设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit_mb: Option<usize>,
+}
+
+/// 请求路径与路由只差结尾的 `/` 或大小写时的处理方式，默认都按不同的路径处理
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoutingOptions {
+    pub trailing_slash: TrailingSlash,
+    /// 路由中固定的部分不区分 ASCII 字母的大小写，参数保留请求中的原文
+    pub case_insensitive: bool,
+}
+
+/// 请求路径结尾的 `/` 与路由不一致时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlash {
+    /// `/foo` 和 `/foo/` 是不同的路径
+    #[default]
+    Strict,
+    /// 用 308 重定向到声明的路径
+    Redirect,
+    /// 直接交给声明的路径的路由
+    Ignore,
+}
+
+impl RoutingOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// config.yml 会随代码上传到服务器，所以这里只写 token 所在的环境变量，不写 token 本身
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeployConfig {
+    /// 服务器 URL
+    pub server: Option<String>,
+    /// 部署到的 tenant host
+    pub host: Option<String>,
+    /// `dino login` 保存的 profile
+    pub profile: Option<String>,
+    /// 保存管理接口 token 的环境变量名
+    pub token_env: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CronJob {
+    /// cron 表达式，如 `*/5 * * * *`
+    pub schedule: String,
+    pub handler: String,
 }
 
 pub type ProjectRoutes = IndexMap<String, Vec<ProjectRoute>>;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProjectRoute {
-    #[serde(deserialize_with = "deserialize_method")]
+    /// 不区分大小写
+    #[serde(
+        deserialize_with = "deserialize_method",
+        serialize_with = "serialize_method"
+    )]
     pub method: Method,
     pub handler: String,
+    /// 响应 body 的 JSON Schema，dev 模式下校验 handler 的返回值
+    #[serde(default)]
+    pub response: Option<serde_json::Value>,
+    /// 调度优先级，worker 空闲时优先处理高优先级的请求
+    #[serde(default)]
+    pub priority: Priority,
+    /// 路由类型，`sse` 和 `websocket` 的 method 必须为 GET，`stream` 可以用任意 method
+    #[serde(default, rename = "type")]
+    pub kind: RouteKind,
+    /// 请求 body 的接收方式，`streaming` 时直接写入磁盘，不读入内存
+    #[serde(default)]
+    pub upload: UploadMode,
+    /// 路由所属的 API 版本，如 `[v1, v2]`。请求用 `/v1` 这样的路径前缀或 `Accept` 头
+    /// 选择版本，同一个 method 可以为不同版本声明不同的 handler；不指定版本的请求
+    /// 交给没有声明版本的路由，没有时交给最后声明的版本
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+/// 路由类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteKind {
+    /// 普通请求，handler 返回一个响应
+    #[default]
+    Http,
+    /// Server-Sent Events，handler 返回事件的（异步）迭代器
+    Sse,
+    /// WebSocket，连接的事件都交给 handler 处理，见 `rooms`
+    WebSocket,
+    /// 流式请求和响应，body 逐块在客户端和 handler 之间传递，不读入内存，见 `pipe`
+    Stream,
+}
+
+impl RouteKind {
+    /// SSE 和 WebSocket 路由只能用 GET
+    pub(crate) fn get_only(self) -> bool {
+        matches!(self, RouteKind::Sse | RouteKind::WebSocket)
+    }
+}
+
+/// 请求 body 的接收方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadMode {
+    /// 读入内存后放在 `req.body` 中
+    #[default]
+    Buffered,
+    /// 写入 tenant 的上传目录，handler 从 `req.upload.path` 读取文件，
+    /// 接收过程中以 `req.event` 为 `progress` 调用 handler 报告进度
+    Streaming,
+}
+
+/// 路由的调度优先级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// 健康检查、UI 调用等需要快速响应的路由
+    Interactive,
+    #[default]
+    Normal,
+    /// 报表、导出等耗时较长的路由，最多占用 worker 数减一个线程
+    Batch,
+}
+
+/// 服务器配置，声明每个 tenant 的代码和配置文件在磁盘上的位置，
+/// 收到 SIGHUP、控制 socket 的 reload 命令或管理接口的 `POST /reload` 时会重新读取
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub tenants: Vec<TenantSource>,
+    #[serde(default)]
+    pub logging: LogConfig,
+    /// 为 None 时只提供 HTTP
+    #[cfg(feature = "tls")]
+    #[serde(default)]
+    pub tls: Option<TlsOptions>,
+    /// 不属于任何 tenant 的 host 的响应，为 None 时按找不到路由处理
+    #[serde(default)]
+    pub unknown_host: Option<UnknownHost>,
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantSource {
+    /// host 不区分大小写，读取时转为小写
+    #[serde(deserialize_with = "deserialize_host")]
+    pub host: String,
+    /// 打包后的代码，相对路径基于服务器配置文件所在目录
+    pub code: PathBuf,
+    /// 项目的 config.yml
+    pub config: PathBuf,
+    /// 别名 host，请求别名时 301 重定向到 `host`。别名只能在服务器配置中声明，
+    /// tenant 上传的 config.yml 不能占用其他 host
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// worker 线程的 `cpus`、`nice` 和 `capabilities`
+    #[serde(default, flatten)]
+    pub worker: WorkerSettings,
+}
+
+fn default_entry() -> String {
+    "main.ts".to_string()
 }
 
 fn deserialize_method<'de, D>(deserializer: D) -> Result<Method, D::Error>
@@ -39,10 +255,552 @@ where
     }
 }
 
+#[cfg(feature = "server")]
+fn deserialize_host<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(String::deserialize(deserializer)?.to_ascii_lowercase())
+}
+
+pub(crate) fn serialize_method<S: Serializer>(
+    method: &Method,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(method.as_str())
+}
+
+/// 拒绝重复的 key，YAML 中重复的 key 会静默覆盖前面的值
+fn unique_keys<'de, D, V>(deserializer: D) -> Result<IndexMap<String, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    struct UniqueKeys<V>(PhantomData<V>);
+
+    impl<'de, V: Deserialize<'de>> Visitor<'de> for UniqueKeys<V> {
+        type Value = IndexMap<String, V>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut ret = IndexMap::new();
+            while let Some((key, value)) = map.next_entry::<String, V>()? {
+                if ret.contains_key(&key) {
+                    return Err(A::Error::custom(format!("duplicate key `{key}`")));
+                }
+                ret.insert(key, value);
+            }
+            Ok(ret)
+        }
+    }
+
+    deserializer.deserialize_map(UniqueKeys(PhantomData))
+}
+
+/// `routes` 中的一项
+enum RouteNode {
+    Methods(Vec<ProjectRoute>),
+    Group(RouteGroup),
+}
+
+/// 路由分组，`routes` 中的 key 是分组的路径前缀，分组内的路由和分组都以它为前缀，
+/// 例如 `/api/v1` 分组内的 `/users` 为 `/api/v1/users`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteGroup {
+    /// 分组内没有设置 `priority` 的路由和分组使用的优先级
+    #[serde(default)]
+    priority: Option<Priority>,
+    /// 分组内没有设置 `upload` 的路由和分组使用的接收方式
+    #[serde(default)]
+    upload: Option<UploadMode>,
+    /// 分组内没有设置 `versions` 的路由和分组使用的版本
+    #[serde(default)]
+    versions: Option<Vec<String>>,
+    /// 分组的默认值要在反序列化前合并，先保留为 YAML
+    #[serde(deserialize_with = "unique_keys")]
+    routes: IndexMap<String, Value>,
+}
+
+impl<'de> Deserialize<'de> for RouteNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NodeVisitor;
+
+        impl<'de> Visitor<'de> for NodeVisitor {
+            type Value = RouteNode;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a list of methods or a route group")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<RouteNode, A::Error> {
+                Vec::deserialize(SeqAccessDeserializer::new(seq)).map(RouteNode::Methods)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<RouteNode, A::Error> {
+                RouteGroup::deserialize(MapAccessDeserializer::new(map)).map(RouteNode::Group)
+            }
+        }
+
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+/// 展开路由分组，返回以完整路径为 key 的路由表
+fn deserialize_routes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ProjectRoutes, D::Error> {
+    let nodes: IndexMap<String, RouteNode> = unique_keys(deserializer)?;
+    let mut routes = ProjectRoutes::new();
+    for (path, node) in nodes {
+        flatten(path, node, &mut routes).map_err(D::Error::custom)?;
+    }
+    Ok(routes)
+}
+
+fn flatten(path: String, node: RouteNode, routes: &mut ProjectRoutes) -> Result<(), String> {
+    let group = match node {
+        RouteNode::Methods(methods) => {
+            if routes.insert(path.clone(), methods).is_some() {
+                return Err(format!("duplicate route `{path}`"));
+            }
+            return Ok(());
+        }
+        RouteNode::Group(group) => group,
+    };
+    if !path.starts_with('/') {
+        return Err(format!("route group `{path}` must start with `/`"));
+    }
+    let defaults = [
+        ("priority", group.priority.map(serde_yaml::to_value)),
+        ("upload", group.upload.map(serde_yaml::to_value)),
+        ("versions", group.versions.map(serde_yaml::to_value)),
+    ];
+    for (child, mut value) in group.routes {
+        if !child.starts_with('/') {
+            return Err(format!(
+                "route `{child}` in group `{path}` must start with `/`"
+            ));
+        }
+        let full = match (path.trim_end_matches('/'), child.as_str()) {
+            ("", child) => child.to_string(),
+            (prefix, "/") => prefix.to_string(),
+            (prefix, child) => format!("{prefix}{child}"),
+        };
+        // 默认值写入子路由的每个方法，或者子分组本身
+        let targets: Vec<_> = match &mut value {
+            Value::Sequence(methods) => methods
+                .iter_mut()
+                .filter_map(Value::as_mapping_mut)
+                .collect(),
+            Value::Mapping(group) => vec![group],
+            _ => vec![],
+        };
+        for target in targets {
+            for (key, default) in &defaults {
+                if let Some(Ok(default)) = default
+                    && !target.contains_key(*key)
+                {
+                    target.insert((*key).into(), default.clone());
+                }
+            }
+        }
+        let node = serde_yaml::from_value(value).map_err(|e| format!("route `{full}`: {e}"))?;
+        flatten(full, node, routes)?;
+    }
+    Ok(())
+}
+
+/// 版本为 `version` 时 `path` 的路由路径，如 `/v1/users/{id}`
+pub(crate) fn versioned_path(version: &str, path: &str) -> String {
+    match path {
+        "/" => format!("/{version}"),
+        path => format!("/{version}{path}"),
+    }
+}
+
+/// 配置校验发现的问题，`path` 指向出问题的字段，如 `routes./api[0].method`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl FromStr for ProjectConfig {
+    type Err = anyhow::Error;
+
+    /// 解析并校验配置，校验失败时列出所有问题
+    fn from_str(s: &str) -> Result<Self> {
+        let config: ProjectConfig = serde_yaml::from_str(s)?;
+        let issues: Vec<_> = config.validate().iter().map(|i| i.to_string()).collect();
+        if !issues.is_empty() {
+            bail!("invalid config:\n  {}", issues.join("\n  "));
+        }
+        Ok(config)
+    }
+}
+
 impl ProjectConfig {
     pub fn load(path: impl AsRef<Path>) -> Result<Self> {
         let config = std::fs::read_to_string(path).context("Failed to read config file")?;
-        let config: ProjectConfig = serde_yaml::from_str(&config)?;
+        config.parse()
+    }
+
+    /// 检查反序列化无法发现的问题，这些问题会导致构建路由、部署或运行时出错
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = vec![];
+        let mut issue = |path: String, message: String| issues.push(ConfigIssue { path, message });
+        if self.version > CONFIG_VERSION {
+            let message = format!("unsupported version, the latest is {CONFIG_VERSION}");
+            issue("version".into(), message);
+        }
+        if self.name.trim().is_empty() {
+            issue("name".into(), "must not be empty".into());
+        }
+        let entry = Path::new(&self.entry);
+        if self.entry.is_empty()
+            || entry.is_absolute()
+            || entry.components().any(|c| c == Component::ParentDir)
+        {
+            issue(
+                "entry".into(),
+                "must be a relative path inside the project".into(),
+            );
+        }
+
+        let mut router = matchit::Router::new();
+        for (path, routes) in &self.routes {
+            let at = format!("routes.{path}");
+            if !path.starts_with('/') {
+                issue(at.clone(), "must start with `/`".into());
+            } else if path.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                issue(at.clone(), "must not contain whitespace".into());
+            } else if let Err(e) = router.insert(path.as_str(), ()) {
+                issue(at.clone(), e.to_string());
+            }
+            if routes.is_empty() {
+                issue(at.clone(), "must declare at least one method".into());
+            }
+            // 没有声明版本的路由记为 None，同一个 method 的每个版本只能有一个 handler
+            let mut methods = HashSet::new();
+            let mut versions = HashSet::new();
+            for (i, route) in routes.iter().enumerate() {
+                let at = format!("{at}[{i}]");
+                if route.versions.is_empty() && !methods.insert((&route.method, None)) {
+                    issue(
+                        format!("{at}.method"),
+                        format!("duplicate method {}", route.method),
+                    );
+                }
+                for version in &route.versions {
+                    if version.is_empty()
+                        || !version
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                    {
+                        let message =
+                            format!("invalid version `{version}`, use letters, digits, `-` or `_`");
+                        issue(format!("{at}.versions"), message);
+                        continue;
+                    }
+                    if !methods.insert((&route.method, Some(version))) {
+                        let message =
+                            format!("duplicate method {} for version {version}", route.method);
+                        issue(format!("{at}.versions"), message);
+                    }
+                    if path.starts_with('/')
+                        && versions.insert(version)
+                        && let Err(e) = router.insert(versioned_path(version, path), ())
+                    {
+                        issue(format!("{at}.versions"), e.to_string());
+                    }
+                }
+                if route.handler.is_empty() {
+                    issue(format!("{at}.handler"), "must not be empty".into());
+                }
+                if route.kind.get_only() && route.method != Method::GET {
+                    issue(
+                        format!("{at}.method"),
+                        format!("{:?} routes must use GET", route.kind),
+                    );
+                }
+                if route.kind != RouteKind::Http && route.upload != UploadMode::Buffered {
+                    let message = format!("{:?} routes can't stream uploads", route.kind);
+                    issue(format!("{at}.upload"), message);
+                }
+            }
+        }
+        if let Err(e) = Contracts::from_routes(&self.routes) {
+            issue("routes".into(), e.to_string());
+        }
+
+        for (name, host) in &self.bindings {
+            if host.is_empty() {
+                issue(format!("bindings.{name}"), "host must not be empty".into());
+            }
+        }
+        for (name, job) in &self.crons {
+            if !matches!(job.schedule.split_whitespace().count(), 5 | 6) {
+                let message = "must have 5 fields (or 6 with seconds)".into();
+                issue(format!("crons.{name}.schedule"), message);
+            }
+            if job.handler.is_empty() {
+                issue(format!("crons.{name}.handler"), "must not be empty".into());
+            }
+        }
+        if let Some(deploy) = &self.deploy {
+            if let Some(server) = &deploy.server
+                && !(server.starts_with("http://") || server.starts_with("https://"))
+            {
+                issue("deploy.server".into(), "must be an http(s) URL".into());
+            }
+            if deploy.host.as_ref().is_some_and(|h| h.is_empty()) {
+                issue("deploy.host".into(), "must not be empty".into());
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServerConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read server config {}", path.display()))?;
+        let mut config: ServerConfig = serde_yaml::from_str(&content)?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        for tenant in &mut config.tenants {
+            tenant.code = base.join(&tenant.code);
+            tenant.config = base.join(&tenant.config);
+        }
         Ok(config)
     }
 }
+
+#[cfg(feature = "server")]
+impl TenantSource {
+    /// 从磁盘读取代码和项目配置
+    pub fn read(&self) -> Result<(String, ProjectConfig)> {
+        let code = std::fs::read_to_string(&self.code)
+            .with_context(|| format!("Failed to read {}", self.code.display()))?;
+        let config = ProjectConfig::load(&self.config)?;
+        Ok((code, config))
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServerConfig {
+    /// 按 host 的 worker 线程设置，用于 `ServerOptions::workers`
+    pub fn worker_settings(&self) -> HashMap<String, WorkerSettings> {
+        self.tenants
+            .iter()
+            .map(|t| (t.host.clone(), t.worker.clone()))
+            .collect()
+    }
+
+    /// 读取所有 tenant，用于启动服务器
+    pub fn tenant_routers(&self) -> Result<Vec<TenantRouter>> {
+        self.tenants
+            .iter()
+            .map(|tenant| {
+                let (code, config) = tenant.read()?;
+                let bindings = Bindings::from_config(&config);
+                let (timezone, routing) = (config.timezone, config.routing);
+                let memory_limit = config.memory_limit_mb;
+                let router = SwappableAppRouter::try_new(code, config.routes)?;
+                Ok(TenantRouter::new(tenant.host.clone(), router)
+                    .with_bindings(bindings)
+                    .with_timezone(timezone)
+                    .with_memory_limit(memory_limit)
+                    .with_routing(routing)
+                    .with_aliases(tenant.aliases.clone()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SwappableAppRouter;
+
+    /// 固定种子的 xorshift，失败时可以复现
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.next(items.len())]
+        }
+    }
+
+    const METHODS: &[&str] = &[
+        "GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "CONNECT", "TRACE",
+    ];
+
+    #[test]
+    fn config_fixtures_should_validate() -> Result<()> {
+        let config = ProjectConfig::load("fixtures/configs/full.yml")?;
+        assert_eq!(config.routes["/api/users/{id}"][1].method, Method::DELETE);
+        assert_eq!(config.routes["/chat"][0].kind, RouteKind::WebSocket);
+        assert_eq!(config.timezone, Some(chrono_tz::Asia::Shanghai));
+        assert_eq!(config.routing.trailing_slash, TrailingSlash::Redirect);
+        assert_eq!(config.memory_limit_mb, Some(64));
+        let deploy = config.deploy.as_ref().unwrap();
+        assert_eq!(deploy.host.as_deref(), Some("full.example.com"));
+        // 序列化后再解析得到相同的模型
+        let yaml = serde_yaml::to_string(&config)?;
+        assert_eq!(yaml.parse::<ProjectConfig>()?, config);
+
+        let content = std::fs::read_to_string("fixtures/configs/invalid.yml")?;
+        let config: ProjectConfig = serde_yaml::from_str(&content)?;
+        let paths: Vec<_> = config.validate().into_iter().map(|i| i.path).collect();
+        assert_eq!(
+            paths,
+            [
+                "version",
+                "name",
+                "entry",
+                "routes.api",
+                "routes./users/{id}[0].handler",
+                "routes./users/{id}[1].method",
+                "routes./users/{name}",
+                "routes./events[0].method",
+                "routes./empty",
+                "bindings.AUTH",
+                "crons.cleanup.schedule",
+                "deploy.server",
+            ]
+        );
+        let err = content.parse::<ProjectConfig>().unwrap_err().to_string();
+        assert!(err.contains("entry: must be a relative path inside the project"));
+
+        let err = ProjectConfig::load("fixtures/configs/duplicate.yml").unwrap_err();
+        assert!(format!("{err:#}").contains("duplicate key `/a`"));
+        Ok(())
+    }
+
+    #[test]
+    fn route_groups_should_flatten() -> Result<()> {
+        let groups = ProjectConfig::load("fixtures/configs/groups.yml")?;
+        let flat = ProjectConfig::load("fixtures/configs/flat.yml")?;
+        assert_eq!(groups, flat);
+
+        let parse = |routes: &str| format!("name: t\nroutes:\n{routes}").parse::<ProjectConfig>();
+        let err = parse("  /a:\n    routes:\n      /b: []\n  /a/b: []\n").unwrap_err();
+        assert!(format!("{err:#}").contains("duplicate route `/a/b`"));
+        let err = parse("  /a:\n    routes:\n      b: []\n").unwrap_err();
+        assert!(format!("{err:#}").contains("route `b` in group `/a` must start with `/`"));
+        let err = parse("  /a:\n    route: {}\n").unwrap_err();
+        assert!(format!("{err:#}").contains("unknown field `route`"));
+        let err =
+            parse("  /a:\n    routes:\n      /b:\n        - method: FETCH\n          handler: b\n")
+                .unwrap_err();
+        assert!(format!("{err:#}").contains("route `/a/b`: "));
+
+        let config = parse(
+            "  /api:\n    versions: [v1]\n    routes:\n      /a:\n        - method: GET\n          handler: a\n",
+        )?;
+        assert_eq!(config.routes["/api/a"][0].versions, ["v1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn route_versions_should_validate() -> Result<()> {
+        let issues = |routes: &str| -> Result<Vec<String>> {
+            let config: ProjectConfig =
+                serde_yaml::from_str(&format!("name: t\nroutes:\n{routes}"))?;
+            Ok(config
+                .validate()
+                .into_iter()
+                .map(|i| i.to_string())
+                .collect())
+        };
+        let route = |handler: &str, versions: &str| {
+            format!("    - method: GET\n      handler: {handler}\n      versions: {versions}\n")
+        };
+        let ok = format!("  /a:\n{}{}", route("a1", "[v1]"), route("a2", "[v2]"));
+        assert!(issues(&ok)?.is_empty());
+        let duplicate = format!("  /a:\n{}{}", route("a1", "[v1, v2]"), route("a2", "[v2]"));
+        assert_eq!(
+            issues(&duplicate)?,
+            ["routes./a[1].versions: duplicate method GET for version v2"]
+        );
+        let invalid = format!("  /a:\n{}", route("a", "[v/1]"));
+        assert_eq!(
+            issues(&invalid)?,
+            ["routes./a[0].versions: invalid version `v/1`, use letters, digits, `-` or `_`"]
+        );
+        let conflict = format!(
+            "  /a:\n{}  /v1/a:\n{}",
+            route("a", "[v1]"),
+            route("b", "[]")
+        );
+        assert_eq!(issues(&conflict)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn config_parsing_should_hold_properties() -> Result<()> {
+        let mut rng = Rng(0x5eed);
+        let segments = &[
+            "api", "{id}", "{name}", "{*rest}", "v1", "a.b", "%20", "{", "}", "{{x}}", "", " ",
+            "ü", ":id", "*",
+        ];
+        for _ in 0..500 {
+            // 方法名不区分大小写
+            let method = rng.pick(METHODS);
+            let cased: String = method
+                .chars()
+                .map(|c| match rng.next(2) {
+                    0 => c.to_ascii_lowercase(),
+                    _ => c,
+                })
+                .collect();
+
+            let mut yaml = "name: prop\nroutes:\n".to_string();
+            let mut paths = vec![];
+            for _ in 0..1 + rng.next(4) {
+                let path: Vec<_> = (0..rng.next(4)).map(|_| rng.pick(segments)).collect();
+                let path = format!("/{}", path.join("/"));
+                yaml.push_str(&format!(
+                    "  {}:\n    - method: {cased}\n      handler: h{}\n",
+                    serde_json::to_string(&path)?,
+                    paths.len()
+                ));
+                paths.push(path);
+            }
+
+            let unique = paths.iter().collect::<HashSet<_>>().len() == paths.len();
+            match yaml.parse::<ProjectConfig>() {
+                Ok(config) => {
+                    assert!(unique, "duplicate keys accepted: {yaml}");
+                    assert!(config.routes.values().flatten().all(|r| r.method == method));
+                    // 通过校验的配置一定能构建路由，并且序列化后不变
+                    SwappableAppRouter::try_new("", config.routes.clone())?;
+                    let yaml = serde_yaml::to_string(&config)?;
+                    assert_eq!(yaml.parse::<ProjectConfig>()?, config);
+                }
+                Err(e) if !unique => assert!(format!("{e:#}").contains("duplicate key")),
+                Err(_) => {
+                    let config: ProjectConfig = serde_yaml::from_str(&yaml)?;
+                    assert!(!config.validate().is_empty());
+                }
+            }
+        }
+        Ok(())
+    }
+}
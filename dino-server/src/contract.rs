@@ -0,0 +1,131 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, anyhow, bail};
+use jsonschema::Validator;
+use serde_json::Value;
+
+use crate::{config::ProjectRoutes, engine::Resp};
+
+/// 路由声明的响应契约，按 handler 保存编译后的 JSON Schema
+#[derive(Debug, Clone, Default)]
+pub struct Contracts(HashMap<String, Arc<Validator>>);
+
+impl Contracts {
+    /// 编译路由中的 `response` schema，同一个 handler 不能声明不同的 schema
+    pub fn from_routes(routes: &ProjectRoutes) -> Result<Self> {
+        let mut schemas: HashMap<&str, &Value> = HashMap::new();
+        for (path, methods) in routes {
+            for route in methods {
+                let Some(schema) = &route.response else {
+                    continue;
+                };
+                match schemas.insert(&route.handler, schema) {
+                    Some(other) if other != schema => bail!(
+                        "handler {} declares different response schemas, the one of {} {path} differs",
+                        route.handler,
+                        route.method
+                    ),
+                    _ => {}
+                }
+            }
+        }
+        let contracts = schemas
+            .into_iter()
+            .map(|(handler, schema)| {
+                let validator = jsonschema::validator_for(schema)
+                    .map_err(|e| anyhow!("invalid response schema of handler {handler}: {e}"))?;
+                Ok((handler.to_string(), Arc::new(validator)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(contracts))
+    }
+
+    /// 检查 handler 的返回值，返回所有违反契约的地方
+    ///
+    /// 所有响应的状态码都必须合法；声明了 schema 的 handler，body 按 JSON 解析后
+    /// 校验，没有 body 时按 null 校验
+    pub fn check(&self, handler: &str, resp: &Resp) -> Vec<String> {
+        let mut violations = vec![];
+        if !(100..=599).contains(&resp.status) {
+            violations.push(format!("status {} is not a valid HTTP status", resp.status));
+        }
+        let Some(validator) = self.0.get(handler) else {
+            return violations;
+        };
+        let body = match resp.body.as_deref().map(serde_json::from_slice) {
+            None => Value::Null,
+            Some(Ok(body)) => body,
+            Some(Err(e)) => {
+                violations.push(format!("body is not valid JSON: {e}"));
+                return violations;
+            }
+        };
+        violations.extend(validator.iter_errors(&body).map(|e| {
+            let path = e.instance_path.to_string();
+            match path.is_empty() {
+                true => format!("body: {e}"),
+                false => format!("body{path}: {e}"),
+            }
+        }));
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProjectConfig;
+
+    fn resp(status: u16, body: Option<&str>) -> Resp {
+        Resp {
+            status,
+            headers: HashMap::new(),
+            body: body.map(Into::into),
+            file: None,
+            compress: None,
+        }
+    }
+
+    #[test]
+    fn contracts_should_check_responses() -> Result<()> {
+        let config: ProjectConfig = serde_yaml::from_str(
+            r#"
+name: test
+routes:
+  /users/{id}:
+    - method: GET
+      handler: getUser
+      response:
+        type: object
+        required: [id, name]
+        properties:
+          id: { type: integer }
+          name: { type: string }
+  /health:
+    - method: GET
+      handler: health
+"#,
+        )?;
+        let contracts = Contracts::from_routes(&config.routes)?;
+
+        let ok = resp(200, Some(r#"{"id": 1, "name": "alice"}"#));
+        assert!(contracts.check("getUser", &ok).is_empty());
+        assert_eq!(
+            contracts.check("getUser", &resp(200, Some(r#"{"id": "1"}"#))),
+            [
+                r#"body/id: "1" is not of type "integer""#,
+                r#"body: "name" is a required property"#,
+            ]
+        );
+        assert_eq!(
+            contracts.check("getUser", &resp(200, Some("oops"))),
+            ["body is not valid JSON: expected value at line 1 column 1"]
+        );
+        assert!(contracts.check("health", &resp(204, None)).is_empty());
+        assert_eq!(
+            contracts.check("health", &resp(0, None)),
+            ["status 0 is not a valid HTTP status"]
+        );
+        Ok(())
+    }
+}
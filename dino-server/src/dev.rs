@@ -0,0 +1,126 @@
+use axum::{Json, extract::State};
+use axum_extra::extract::Host;
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::{AppState, audit::short_hash, error::AppError};
+
+/// dev server 的状态接口，显示正在运行的代码、最近一次失败的构建和文件监听的状态
+pub const DEV_STATUS_PATH: &str = "/_dino/status";
+
+/// 文件变化后重新构建失败，服务器继续使用上一次成功的代码
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildFailure {
+    pub error: String,
+    pub at: String,
+}
+
+/// dev server 文件监听任务的状态，任务退出后自动重启
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WatcherHealth {
+    pub running: bool,
+    pub restarts: u32,
+    /// 最近一次退出的原因
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DevStatus {
+    host: String,
+    /// 正在运行的代码的 hash
+    code: String,
+    /// 为 null 时最近一次构建成功
+    build_error: Option<BuildFailure>,
+    /// 为 null 时没有监听文件，例如 `dino run --no-watch`
+    watcher: Option<WatcherHealth>,
+}
+
+impl AppState {
+    /// 记录构建失败，`error` 为 None 时表示构建成功，清除之前的错误
+    pub fn set_build_error(&self, host: &str, error: Option<String>) {
+        match error {
+            Some(error) => {
+                let at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+                self.build_errors
+                    .insert(host.to_string(), BuildFailure { error, at });
+            }
+            None => {
+                self.build_errors.remove(host);
+            }
+        }
+    }
+
+    pub fn build_error(&self, host: &str) -> Option<BuildFailure> {
+        self.build_errors.get(host).map(|e| e.clone())
+    }
+
+    pub fn set_watcher_health(&self, host: &str, health: WatcherHealth) {
+        self.watchers.insert(host.to_string(), health);
+    }
+
+    pub fn watcher_health(&self, host: &str) -> Option<WatcherHealth> {
+        self.watchers.get(host).map(|h| h.clone())
+    }
+}
+
+pub(crate) async fn status(
+    State(state): State<AppState>,
+    Host(mut host): Host,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let _ = host.split_off(host.find(':').unwrap_or(host.len()));
+    host.make_ascii_lowercase();
+    let router = state
+        .routers
+        .get(&host)
+        .ok_or_else(|| AppError::HostNotFound(host.clone()))?
+        .load();
+    let status = DevStatus {
+        code: short_hash(&router.code),
+        build_error: state.build_error(&host),
+        watcher: state.watcher_health(&host),
+        host,
+    };
+    Ok(Json(serde_json::to_value(status)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{ProjectRoutes, SwappableAppRouter};
+
+    #[tokio::test]
+    async fn status_should_show_build_errors() -> anyhow::Result<()> {
+        let code = "(function(){ return {}; })();";
+        let router = SwappableAppRouter::try_new(code, ProjectRoutes::new())?;
+        let state = AppState::with_routers(DashMap::from_iter([("localhost".to_string(), router)]));
+        let get = async || {
+            let Json(ret) = status(State(state.clone()), Host("LocalHost:3000".into())).await?;
+            Ok::<_, AppError>(ret)
+        };
+        let ret = get().await?;
+        assert_eq!(ret["code"], short_hash(code));
+        assert!(ret["build_error"].is_null());
+
+        state.set_build_error("localhost", Some("unexpected token".into()));
+        assert_eq!(get().await?["build_error"]["error"], "unexpected token");
+        state.set_build_error("localhost", None);
+        assert!(get().await?["build_error"].is_null());
+
+        assert!(get().await?["watcher"].is_null());
+        let health = WatcherHealth {
+            running: true,
+            restarts: 1,
+            last_error: Some("watch error".into()),
+        };
+        state.set_watcher_health("localhost", health);
+        let ret = get().await?;
+        assert_eq!(ret["watcher"]["restarts"], 1);
+        assert_eq!(ret["watcher"]["last_error"], "watch error");
+
+        let ret = status(State(state.clone()), Host("other".into())).await;
+        assert!(matches!(ret, Err(AppError::HostNotFound(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use dashmap::DashMap;
+use rquickjs::{Ctx, Exception, Function};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{AppState, binding};
+
+/// 响应 body 的大小上限
+const MAX_RESPONSE_BODY: u64 = 10 << 20;
+
+/// `fetch(url, init)`，返回的响应带有 `ok`、`text()` 和 `json()`，
+/// `init.timeout` 为单个请求的超时毫秒数，不超过服务器设置的超时时间
+pub(crate) const FETCH: &str = r#"
+globalThis.fetch = async (input, init = {}) => {
+  const req = typeof input === "string" ? { url: input } : { ...input };
+  Object.assign(req, init);
+  const resp = JSON.parse(__dino_fetch(JSON.stringify({
+    url: String(req.url),
+    method: req.method ?? "GET",
+    headers: req.headers ?? {},
+    body: req.body ?? null,
+    timeout: req.timeout ?? null,
+  })));
+  return {
+    ...resp,
+    ok: resp.status >= 200 && resp.status < 300,
+    text: async () => resp.body,
+    json: async () => JSON.parse(resp.body),
+  };
+};
+"#;
+
+/// 出站请求的限制，所有 tenant 共享一个连接池
+#[derive(Debug, Clone)]
+pub struct EgressOptions {
+    /// 每个 tenant 同时进行的请求数上限，超过时 `fetch` 直接报错
+    pub max_concurrent: usize,
+    /// 连接池中每个目标保留的空闲连接数
+    pub max_idle_per_host: usize,
+    /// 单个请求的超时时间，包括连接和读取响应
+    pub timeout: Duration,
+    /// 同一个目标连续失败这么多次后熔断
+    pub breaker_threshold: u32,
+    /// 熔断后拒绝请求的时长，之后放行请求，成功则恢复，失败则再次熔断
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for EgressOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 16,
+            max_idle_per_host: 8,
+            timeout: Duration::from_secs(30),
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// 共享的 HTTP 客户端，记录每个 tenant 的并发和流量，按目标熔断
+#[derive(Debug, Clone)]
+pub(crate) struct Egress {
+    agent: ureq::Agent,
+    options: EgressOptions,
+    tenants: Arc<DashMap<String, Arc<TenantEgress>>>,
+    /// 按 `host:port` 记录的熔断状态
+    breakers: Arc<DashMap<String, Breaker>>,
+}
+
+/// tenant 的出站请求统计
+#[derive(Debug, Default)]
+pub(crate) struct TenantEgress {
+    in_flight: AtomicUsize,
+    /// 发送的请求 body 字节数
+    pub(crate) sent: AtomicU64,
+    /// 接收的响应 body 字节数
+    pub(crate) received: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FetchRequest {
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+    /// 毫秒
+    #[serde(default)]
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct FetchResponse {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+/// 请求结束时释放 tenant 的并发名额
+struct InFlight(Arc<TenantEgress>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for Egress {
+    fn default() -> Self {
+        Self::new(EgressOptions::default())
+    }
+}
+
+impl Egress {
+    pub(crate) fn new(options: EgressOptions) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(options.timeout)
+            .max_idle_connections_per_host(options.max_idle_per_host)
+            .build();
+        Self {
+            agent,
+            options,
+            tenants: Default::default(),
+            breakers: Default::default(),
+        }
+    }
+
+    pub(crate) fn stats(&self, host: &str) -> Arc<TenantEgress> {
+        self.tenants.entry(host.to_string()).or_default().clone()
+    }
+
+    /// 以 `tenant` 的名义发送请求，4xx/5xx 也作为响应返回
+    fn fetch(&self, tenant: &str, req: FetchRequest) -> Result<FetchResponse> {
+        let uri: http::Uri = req
+            .url
+            .parse()
+            .with_context(|| format!("invalid url {}", req.url))?;
+        let port = match uri.scheme_str() {
+            Some("http") => 80,
+            Some("https") => 443,
+            scheme => bail!("unsupported scheme {}", scheme.unwrap_or_default()),
+        };
+        let dest = format!(
+            "{}:{}",
+            uri.host().unwrap_or_default(),
+            uri.port_u16().unwrap_or(port)
+        );
+        self.check_breaker(&dest)?;
+
+        let stats = self.stats(tenant);
+        let in_flight = stats.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        let _guard = InFlight(stats.clone());
+        if in_flight > self.options.max_concurrent {
+            bail!(
+                "too many concurrent fetches, the limit is {}",
+                self.options.max_concurrent
+            );
+        }
+
+        let mut request = self.agent.request(&req.method.to_uppercase(), &req.url);
+        if let Some(ms) = req.timeout {
+            request = request.timeout(Duration::from_millis(ms).min(self.options.timeout));
+        }
+        for (name, value) in &req.headers {
+            request = request.set(name, value);
+        }
+        let body = req.body.unwrap_or_default();
+        stats.sent.fetch_add(body.len() as u64, Ordering::Relaxed);
+        let ret = match body.is_empty() {
+            true => request.call(),
+            false => request.send_string(&body),
+        };
+        let resp = match ret {
+            Ok(resp) | Err(ureq::Error::Status(_, resp)) => resp,
+            Err(e) => {
+                self.record(&dest, false);
+                return Err(e).with_context(|| format!("fetch {} failed", req.url));
+            }
+        };
+        self.record(&dest, resp.status() < 500);
+
+        let status = resp.status();
+        let headers = resp
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), resp.header(&name)?.to_string())))
+            .collect();
+        let mut body = vec![];
+        resp.into_reader()
+            .take(MAX_RESPONSE_BODY + 1)
+            .read_to_end(&mut body)?;
+        stats
+            .received
+            .fetch_add(body.len() as u64, Ordering::Relaxed);
+        ensure!(
+            body.len() as u64 <= MAX_RESPONSE_BODY,
+            "response body of {} is larger than {MAX_RESPONSE_BODY} bytes",
+            req.url
+        );
+        let body = String::from_utf8(body).context("response body is not valid UTF-8")?;
+        Ok(FetchResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    /// 熔断期间直接拒绝请求
+    fn check_breaker(&self, dest: &str) -> Result<()> {
+        if let Some(breaker) = self.breakers.get(dest)
+            && let Some(until) = breaker.open_until
+            && until > Instant::now()
+        {
+            bail!("circuit breaker for {dest} is open, retry later");
+        }
+        Ok(())
+    }
+
+    /// 连接失败、超时和 5xx 计为失败，连续失败达到阈值时熔断
+    fn record(&self, dest: &str, ok: bool) {
+        if ok {
+            self.breakers.remove(dest);
+            return;
+        }
+        let mut breaker = self.breakers.entry(dest.to_string()).or_default();
+        breaker.failures += 1;
+        if breaker.failures >= self.options.breaker_threshold {
+            if breaker
+                .open_until
+                .is_none_or(|until| until <= Instant::now())
+            {
+                warn!(
+                    "Circuit breaker for {dest} opened after {} failures",
+                    breaker.failures
+                );
+            }
+            breaker.open_until = Some(Instant::now() + self.options.breaker_cooldown);
+        }
+    }
+}
+
+pub(crate) fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.globals()
+        .set("__dino_fetch", Function::new(ctx.clone(), fetch)?)?;
+    ctx.eval::<(), _>(FETCH)
+}
+
+fn fetch(ctx: Ctx, req: String) -> rquickjs::Result<String> {
+    let ret = (|| {
+        let tenant = binding::caller().context("fetch is not available here")?;
+        let state = AppState::get_current().context("server is not running")?;
+        let req: FetchRequest = serde_json::from_str(&req)?;
+        let resp = state.egress.fetch(&tenant, req)?;
+        Ok::<_, anyhow::Error>(serde_json::to_string(&resp)?)
+    })();
+    ret.map_err(|e| Exception::throw_message(&ctx, &format!("{e:#}")))
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        net::{SocketAddr, TcpListener},
+        thread,
+    };
+
+    use super::*;
+
+    /// 对每个连接返回固定响应的 HTTP 服务器
+    fn serve(status: u16, body: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for mut conn in listener.incoming().flatten() {
+                let mut buf = [0; 4096];
+                let _ = conn.read(&mut buf);
+                let _ = write!(
+                    conn,
+                    "HTTP/1.1 {status} X\r\nx-test: 1\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        addr
+    }
+
+    fn post(url: String) -> FetchRequest {
+        FetchRequest {
+            url,
+            method: "POST".into(),
+            headers: HashMap::new(),
+            body: Some("ping".into()),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn fetch_should_account_bytes_and_limit_concurrency() -> Result<()> {
+        let addr = serve(200, "pong!");
+        let egress = Egress::default();
+        let resp = egress.fetch("a.com", post(format!("http://{addr}/x")))?;
+        assert_eq!((resp.status, resp.body.as_str()), (200, "pong!"));
+        assert_eq!(resp.headers["x-test"], "1");
+        let stats = egress.stats("a.com");
+        assert_eq!(stats.sent.load(Ordering::Relaxed), 4);
+        assert_eq!(stats.received.load(Ordering::Relaxed), 5);
+        assert_eq!(stats.in_flight.load(Ordering::Relaxed), 0);
+        assert_eq!(egress.stats("b.com").received.load(Ordering::Relaxed), 0);
+
+        let egress = Egress::new(EgressOptions {
+            max_concurrent: 0,
+            ..Default::default()
+        });
+        let err = egress
+            .fetch("a.com", post(format!("http://{addr}/x")))
+            .unwrap_err();
+        assert!(err.to_string().contains("too many concurrent fetches"));
+        assert_eq!(egress.stats("a.com").in_flight.load(Ordering::Relaxed), 0);
+
+        let err = egress
+            .fetch("a.com", post("ftp://example.com/x".into()))
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported scheme"));
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_should_open_breaker_after_failures() -> Result<()> {
+        let failing = serve(503, "down");
+        let egress = Egress::new(EgressOptions {
+            breaker_threshold: 2,
+            breaker_cooldown: Duration::from_millis(200),
+            ..Default::default()
+        });
+        let url = format!("http://{failing}/");
+        // 5xx 仍然作为响应返回，但计为失败
+        assert_eq!(egress.fetch("a.com", post(url.clone()))?.status, 503);
+        assert_eq!(egress.fetch("a.com", post(url.clone()))?.status, 503);
+        let err = egress.fetch("b.com", post(url.clone())).unwrap_err();
+        assert!(err.to_string().contains("circuit breaker"));
+
+        // 其他目标不受影响
+        let ok = serve(200, "ok");
+        assert_eq!(
+            egress.fetch("a.com", post(format!("http://{ok}/")))?.status,
+            200
+        );
+
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(egress.fetch("a.com", post(url.clone()))?.status, 503);
+        assert!(egress.fetch("a.com", post(url)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_should_honor_request_timeout() -> Result<()> {
+        // 接受连接但从不响应
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        thread::spawn(move || {
+            let _conns: Vec<_> = listener.incoming().collect();
+        });
+        let egress = Egress::default();
+        let req = FetchRequest {
+            timeout: Some(200),
+            ..post(format!("http://{addr}/slow"))
+        };
+        let start = Instant::now();
+        assert!(egress.fetch("a.com", req).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, header},
+};
+use flate2::{
+    Compression,
+    read::{GzDecoder, ZlibDecoder},
+    write::GzEncoder,
+};
+
+use crate::error::AppError;
+
+/// 解压后的请求 body 上限，与 axum 默认的请求 body 上限一致
+pub(crate) const MAX_DECODED_BODY: usize = 2 << 20;
+
+/// 小于该大小的响应 body 不压缩
+pub(crate) const MIN_COMPRESS_SIZE: usize = 1024;
+
+/// 响应的压缩编码，按优先级排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Br,
+    Gzip,
+}
+
+impl Encoding {
+    pub(crate) const ALL: [Encoding; 2] = [Encoding::Br, Encoding::Gzip];
+
+    /// `Content-Encoding` 的值
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Encoding::Br => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// 预压缩文件的扩展名
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Encoding::Br => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    /// 客户端的 `Accept-Encoding` 是否接受该编码，`q=0` 表示不接受
+    pub(crate) fn accepted(self, headers: &HeaderMap) -> bool {
+        let mut wildcard = false;
+        let items = headers
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+        for item in items {
+            let mut parts = item.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let q = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if name.eq_ignore_ascii_case(self.name()) {
+                return q > 0.0;
+            }
+            if name == "*" {
+                wildcard = q > 0.0;
+            }
+        }
+        wildcard
+    }
+
+    /// 客户端接受的优先级最高的编码
+    pub(crate) fn negotiate(headers: &HeaderMap) -> Option<Encoding> {
+        Self::ALL.into_iter().find(|e| e.accepted(headers))
+    }
+
+    pub(crate) fn encode(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Br => {
+                let mut out = vec![];
+                // quality 5 在压缩率和 CPU 之间折中，适合动态响应
+                let mut encoder = brotli::CompressorWriter::new(&mut out, 4096, 5, 22);
+                encoder.write_all(data)?;
+                drop(encoder);
+                Ok(out)
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+}
+
+/// 值得压缩的 Content-Type，没有 Content-Type 的字符串 body 也压缩
+pub(crate) fn compressible(content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return true;
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+        )
+}
+
+/// 按 `Content-Encoding` 解压请求 body，支持 gzip、deflate 和 br，
+/// 多个编码按声明的相反顺序解压
+pub(crate) fn decode_body(
+    headers: &HeaderMap,
+    body: Bytes,
+    limit: usize,
+) -> Result<Bytes, AppError> {
+    let encodings: Vec<String> = headers
+        .get_all(header::CONTENT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_ascii_lowercase())
+        .filter(|v| !v.is_empty() && v != "identity")
+        .collect();
+    encodings.iter().rev().try_fold(body, |body, encoding| {
+        let reader: Box<dyn Read + '_> = match encoding.as_str() {
+            "gzip" | "x-gzip" => Box::new(GzDecoder::new(&body[..])),
+            "deflate" => Box::new(ZlibDecoder::new(&body[..])),
+            "br" => Box::new(brotli::Decompressor::new(&body[..], 4096)),
+            _ => {
+                return Err(AppError::UnsupportedMediaType(format!(
+                    "content encoding {encoding}"
+                )));
+            }
+        };
+        let mut decoded = vec![];
+        reader
+            .take((limit as u64).saturating_add(1))
+            .read_to_end(&mut decoded)
+            .map_err(|e| AppError::BadRequest(anyhow::anyhow!("invalid {encoding} body: {e}")))?;
+        if decoded.len() > limit {
+            return Err(AppError::PayloadTooLarge(format!(
+                "decompressed body is larger than {limit} bytes"
+            )));
+        }
+        Ok(Bytes::from(decoded))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        Encoding::Gzip.encode(data).unwrap()
+    }
+
+    fn br(data: &[u8]) -> Vec<u8> {
+        Encoding::Br.encode(data).unwrap()
+    }
+
+    fn headers(encoding: &str) -> HeaderMap {
+        HeaderMap::from_iter([(header::CONTENT_ENCODING, encoding.parse().unwrap())])
+    }
+
+    #[test]
+    fn decode_body_should_decompress() {
+        let body = b"hello dino".repeat(10);
+        let decode = |encoding: &str, data: Vec<u8>| {
+            decode_body(&headers(encoding), data.into(), 1024).unwrap()
+        };
+        assert_eq!(decode("gzip", gzip(&body)), body);
+        assert_eq!(decode("BR", br(&body)), body);
+        // 先 gzip 再 br
+        assert_eq!(decode("gzip, br", br(&gzip(&body))), body);
+        assert_eq!(decode("identity", body.clone()), body);
+        assert_eq!(
+            decode_body(&HeaderMap::new(), body.clone().into(), 1024).unwrap(),
+            body
+        );
+    }
+
+    #[test]
+    fn decode_body_should_reject_bad_bodies() {
+        let bomb = gzip(&vec![0; 4096]);
+        let err = decode_body(&headers("gzip"), bomb.into(), 1024).unwrap_err();
+        assert!(matches!(err, AppError::PayloadTooLarge(_)));
+        let err = decode_body(&headers("zstd"), "x".into(), 1024).unwrap_err();
+        assert!(matches!(err, AppError::UnsupportedMediaType(_)));
+        let err = decode_body(&headers("gzip"), "not gzip".into(), 1024).unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(_)));
+    }
+
+    #[test]
+    fn encoding_should_follow_accept_encoding() {
+        let accept = |v: &str| {
+            let headers = HeaderMap::from_iter([(header::ACCEPT_ENCODING, v.parse().unwrap())]);
+            Encoding::negotiate(&headers)
+        };
+        assert_eq!(accept("gzip, deflate, br"), Some(Encoding::Br));
+        assert_eq!(accept("gzip, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(accept("*"), Some(Encoding::Br));
+        assert_eq!(accept("*, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(accept("identity"), None);
+        assert_eq!(Encoding::negotiate(&HeaderMap::new()), None);
+
+        assert!(compressible(None));
+        assert!(compressible(Some("text/html; charset=utf-8")));
+        assert!(compressible(Some("application/ld+json")));
+        assert!(!compressible(Some("image/png")));
+    }
+}
@@ -0,0 +1,567 @@
+//! JS 引擎抽象，handler 通过 `Engine` 执行，默认使用 QuickJS 解释器
+//!
+//! 其他后端（如基于 deno_core 的 V8）实现 `Engine` 并通过 feature 替换 `JsWorker`
+
+mod payload;
+mod quickjs;
+
+use std::{cell::RefCell, collections::HashMap, time::Duration};
+
+use anyhow::Result;
+#[cfg(feature = "server")]
+use axum::{body::Body, response::Response};
+use bytes::Bytes;
+use dino_macros::{FromJs, IntoJs};
+// 派生的 IntoJs 实现需要同名 trait 在作用域中
+use rquickjs::IntoJs;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+#[cfg(feature = "server")]
+use crate::invoke::{self, LogSender};
+use crate::{host, replay::Replay};
+
+pub use payload::Payload;
+pub use quickjs::QuickJs;
+
+/// 服务器、worker 池和命令行使用的引擎
+pub type JsWorker = QuickJs;
+
+/// 一个 JS 引擎实例，加载打包后的模块并执行其中的 handler
+///
+/// 实例只在创建它的线程中使用，不要求 `Send`
+pub trait Engine: Sized {
+    /// 引擎名称，用于日志
+    const NAME: &'static str;
+
+    /// 求值打包后的模块，模块返回的对象作为 `handlers`，同时安装 host 模块和全局函数
+    fn try_new(module: &str) -> Result<Self> {
+        Self::try_new_with(module, Limits::default())
+    }
+
+    /// 同 `try_new`，`limits` 在求值模块之前设置到 runtime 上
+    fn try_new_with(module: &str, limits: Limits) -> Result<Self>;
+
+    /// 执行 handler，同时返回把请求转换为 JS 对象所用的时间
+    fn call_timed(&self, name: &str, req: Req) -> Result<(Resp, Duration)>;
+
+    /// 执行返回事件迭代器的 handler（SSE 路由），每个事件以 JSON 交给 `emit`，
+    /// `emit` 返回 false 时（如客户端已断开）提前结束迭代
+    fn call_stream(&self, name: &str, req: Req, emit: &mut dyn FnMut(String) -> bool)
+    -> Result<()>;
+
+    /// 执行流式路由的 handler：`req.body` 为 ReadableStream 形式的对象，由 `body` 逐块读取；
+    /// 响应的 status 和 headers 交给 `head`，body 为（异步）可迭代对象时每块交给 `emit`，
+    /// `head` 或 `emit` 返回 false 时（如客户端已断开）提前结束迭代
+    fn call_pipe(
+        &self,
+        name: &str,
+        req: Req,
+        body: BodyReader,
+        head: &mut dyn FnMut(Resp) -> bool,
+        emit: &mut dyn FnMut(Bytes) -> bool,
+    ) -> Result<()>;
+
+    /// 在全局环境中执行代码并返回结果的字符串形式，Promise 会等待其完成
+    fn eval(&self, code: &str) -> Result<String>;
+
+    /// 以事件触发 handler，按 `Trigger::kind` 选择对象形式 handler 的方法。
+    /// handler 没有返回值时视为 200，返回非响应对象时序列化为 JSON body
+    fn trigger(&self, name: &str, event: Trigger) -> Result<Resp>;
+
+    /// 运行时的状态：导出的 handler、全局属性、内存和待执行的任务
+    fn inspect(&self) -> Result<Inspection>;
+
+    /// 上次执行是否因为分配的内存超过 `Limits::memory` 而失败，同时清除该标记
+    fn take_out_of_memory(&self) -> bool;
+
+    /// 在对象线程中调用 `class` 的 `id` 实例，请求和响应都是 JSON
+    #[cfg(feature = "server")]
+    fn call_object(&self, class: &str, id: &str, req: &str) -> Result<String>;
+
+    fn run(&self, name: &str, req: Req) -> Result<Resp> {
+        self.run_with_replay(name, req, None)
+    }
+
+    /// 执行 handler，`replay` 存在时 `Math.random` 使用其种子、`Date.now` 冻结在其时间，
+    /// 同样的 replay 和请求会得到同样的结果
+    fn run_with_replay(&self, name: &str, req: Req, replay: Option<Replay>) -> Result<Resp> {
+        self.run_timed(name, req, replay).map(|(resp, _)| resp)
+    }
+
+    fn run_timed(&self, name: &str, req: Req, replay: Option<Replay>) -> Result<(Resp, Duration)> {
+        host::set_replay(replay);
+        let ret = self.call_timed(name, req);
+        host::set_replay(None);
+        ret
+    }
+}
+
+/// 创建 runtime 时设置的限制，模块的初始化代码同样受其约束
+#[derive(Default)]
+pub struct Limits {
+    /// 执行 JS 的过程中定期调用，返回 true 时中断正在执行的 JS，以不可捕获的错误结束
+    pub interrupt: Option<Box<dyn FnMut() -> bool + Send>>,
+    /// runtime 可以分配的内存字节数，超过时正在执行的 JS 以错误结束，0 表示不限制
+    pub memory: usize,
+}
+
+#[derive(Debug, Clone, TypedBuilder, IntoJs, Serialize, Deserialize)]
+pub struct Req {
+    #[builder(default)]
+    pub headers: HashMap<String, String>,
+    #[builder(default)]
+    pub query: HashMap<String, String>,
+    #[builder(default)]
+    pub params: HashMap<String, String>,
+    /// UTF-8 文本为字符串，其他内容为 `Uint8Array`
+    #[builder(default, setter(transform = |body: Option<impl Into<Payload>>| body.map(Into::into)))]
+    pub body: Option<Payload>,
+    #[builder(setter(into))]
+    pub url: String,
+    #[builder(setter(into))]
+    pub method: String,
+    /// WebSocket 路由的连接 id，见 `rooms`
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ws: Option<String>,
+    /// WebSocket 事件：`open`、`message` 或 `close`；流式上传的进度为 `progress`
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    /// 流式上传路由接收到的文件
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload: Option<Upload>,
+}
+
+/// 流式上传的文件，接收完成前 `received` 是已写入的字节数
+#[derive(Debug, Clone, IntoJs, Serialize, Deserialize)]
+pub struct Upload {
+    pub path: String,
+    pub received: u64,
+    /// 请求的 `Content-Length`，chunked 请求没有
+    pub total: Option<u64>,
+}
+
+/// 不经过 HTTP 触发 handler 的事件，JS 中为带 `type` 字段的对象。
+///
+/// handler 为函数时直接以事件调用；为对象时调用与 `type` 同名的方法，
+/// 这样同一个导出可以用 `fetch` 处理 HTTP 请求、用 `scheduled` 等处理后台触发
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Trigger {
+    Scheduled(ScheduleEvent),
+    Queue(QueueEvent),
+    Webhook(WebhookEvent),
+}
+
+/// 定时任务触发时的事件
+#[derive(Debug, Clone, IntoJs, Serialize, Deserialize)]
+pub struct ScheduleEvent {
+    /// config.yml 中 `crons` 的名字
+    pub name: String,
+    /// cron 表达式
+    pub schedule: String,
+    /// 本次计划执行的时间，RFC 3339 格式，项目设置了 `timezone` 时带有该时区的偏移
+    pub time: String,
+}
+
+/// 队列投递一条消息时的事件
+#[derive(Debug, Clone, IntoJs, Serialize, Deserialize)]
+pub struct QueueEvent {
+    pub queue: String,
+    /// 消息 id，重试时不变
+    pub id: String,
+    pub body: Option<String>,
+    /// 第几次投递，从 1 开始
+    pub attempt: u32,
+}
+
+/// 外部服务推送的 webhook 事件，签名由服务器在触发前校验
+#[derive(Debug, Clone, IntoJs, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// 推送方，如 `github`
+    pub source: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+impl Trigger {
+    /// 事件的 `type`，也是对象形式的 handler 上被调用的方法名
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Scheduled(_) => "scheduled",
+            Self::Queue(_) => "queue",
+            Self::Webhook(_) => "webhook",
+        }
+    }
+}
+
+impl<'js> IntoJs<'js> for Trigger {
+    fn into_js(self, ctx: &rquickjs::Ctx<'js>) -> rquickjs::Result<rquickjs::Value<'js>> {
+        let kind = self.kind();
+        let v = match self {
+            Self::Scheduled(e) => e.into_js(ctx)?,
+            Self::Queue(e) => e.into_js(ctx)?,
+            Self::Webhook(e) => e.into_js(ctx)?,
+        };
+        if let Some(obj) = v.as_object() {
+            obj.set("type", kind)?;
+        }
+        Ok(v)
+    }
+}
+
+/// worker 的运行时状态，用于排查线上 tenant 的问题
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Inspection {
+    pub engine: String,
+    /// 模块导出的 handler，按名称排序
+    pub handlers: Vec<String>,
+    /// 全局对象上的属性，不含 JS 内置的全局属性，按名称排序
+    pub globals: Vec<String>,
+    pub memory: MemoryStats,
+    /// 是否有等待执行的 Promise 任务，handler 返回后仍有任务通常是漏了 await
+    pub pending_jobs: bool,
+}
+
+/// JS 堆的内存统计，大小的单位为字节
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// 分配器分配的总大小
+    pub malloc_size: u64,
+    /// 运行时使用的大小
+    pub used_size: u64,
+    pub objects: u64,
+    pub strings: u64,
+    pub functions: u64,
+    pub arrays: u64,
+    /// ArrayBuffer 等二进制对象的大小
+    pub binary_size: u64,
+}
+
+#[derive(Debug, FromJs, Serialize)]
+#[allow(unused)]
+pub struct Resp {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    /// 字符串、`Uint8Array` 或 `ArrayBuffer`
+    pub body: Option<Payload>,
+    /// `serveFile(path)` 返回的文件，服务器读取文件内容作为 body，支持 Range 请求
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// 为 false 时不压缩响应，也不使用预压缩的文件，用于已经压缩过的内容
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>,
+}
+
+impl Resp {
+    /// body 为 UTF-8 文本时返回文本
+    pub fn text(&self) -> Option<&str> {
+        self.body.as_ref().and_then(Payload::as_str)
+    }
+}
+
+/// `dispatch(name, req)` 在当前 worker 中直接调用同一 tenant 的另一个 handler，不经过 HTTP
+const DISPATCH: &str = r#"
+globalThis.dispatch = async function dispatch(name, req = {}) {
+  const handler = globalThis.handlers[name];
+  const fetch = typeof handler === "function" ? handler : handler?.fetch?.bind(handler);
+  if (typeof fetch !== "function") {
+    throw new Error(`handler not found: ${name}`);
+  }
+  return await fetch({ headers: {}, query: {}, params: {}, url: "", method: "GET", ...req });
+};
+"#;
+
+/// `console.log/info/warn/error/debug`，参数以空格连接，非字符串的参数序列化为 JSON
+const CONSOLE: &str = r#"
+globalThis.console = (() => {
+  const emit = globalThis.__dino_console;
+  const format = (args) =>
+    args
+      .map((arg) => {
+        if (typeof arg === "string") return arg;
+        if (arg instanceof Error) return arg.stack ? `${arg}\n${arg.stack}` : String(arg);
+        try {
+          return JSON.stringify(arg) ?? String(arg);
+        } catch {
+          return String(arg);
+        }
+      })
+      .join(" ");
+  const log = (level) => (...args) => emit(level, format(args));
+  return {
+    log: log("info"),
+    info: log("info"),
+    warn: log("warn"),
+    error: log("error"),
+    debug: log("debug"),
+  };
+})();
+"#;
+
+/// 事件触发：函数形式的 handler 直接调用，对象形式的 handler 调用与事件 `type` 同名的方法
+const TRIGGER: &str = r#"
+globalThis.__dino_trigger = async function (name, event) {
+  const handler = globalThis.handlers[name];
+  let ret;
+  if (typeof handler === "function") {
+    ret = await handler(event);
+  } else if (typeof handler?.[event.type] === "function") {
+    ret = await handler[event.type](event);
+  } else {
+    throw new Error(`handler ${name} can't handle ${event.type} events`);
+  }
+  if (ret === undefined || ret === null) {
+    return { status: 200, headers: {} };
+  }
+  if (typeof ret === "object" && typeof ret.status === "number") {
+    return { headers: {}, ...ret };
+  }
+  const body = typeof ret === "string" ? ret : JSON.stringify(ret);
+  return { status: 200, headers: {}, body };
+};
+"#;
+
+/// `serveFile(path, init)` 返回一个由服务器读取文件的响应，`init` 可以设置 status、headers 和 compress
+const SERVE_FILE: &str = r#"
+globalThis.serveFile = function serveFile(path, init = {}) {
+  return {
+    status: init.status ?? 200,
+    headers: { ...init.headers },
+    file: String(path),
+    compress: init.compress,
+  };
+};
+"#;
+
+/// SSE handler 的事件迭代：handler 可以是 async generator，也可以返回（异步）可迭代对象。
+/// 每个事件为字符串或 `{ data, id, event, retry }`，非字符串的 data 序列化为 JSON。
+/// 迭代期间 worker 只处理这一个流，因此当前迭代器保存在全局变量中
+const STREAM: &str = r#"
+globalThis.__dino_stream_open = async function (name, req) {
+  const handler = globalThis.handlers[name];
+  if (typeof handler !== "function") {
+    throw new Error(`handler not found: ${name}`);
+  }
+  const events = await handler(req);
+  const iter = events?.[Symbol.asyncIterator]?.() ?? events?.[Symbol.iterator]?.();
+  if (!iter) {
+    throw new Error(`handler ${name} must return an iterator of events`);
+  }
+  globalThis.__dino_stream = iter;
+};
+globalThis.__dino_stream_next = async function () {
+  const { value, done } = await globalThis.__dino_stream.next();
+  if (done) {
+    globalThis.__dino_stream = undefined;
+    return null;
+  }
+  const event = value !== null && typeof value === "object" && "data" in value ? { ...value } : { data: value };
+  if (typeof event.data !== "string") event.data = JSON.stringify(event.data);
+  if (event.id !== undefined) event.id = String(event.id);
+  return JSON.stringify(event);
+};
+globalThis.__dino_stream_close = async function () {
+  const iter = globalThis.__dino_stream;
+  globalThis.__dino_stream = undefined;
+  await iter?.return?.();
+};
+"#;
+
+/// 流式路由：`req.body` 是可以 `getReader().read()` 或 `for await` 逐块读取的对象，
+/// handler 返回的 body 可以是字符串，也可以是字符串块的（异步）可迭代对象，
+/// 比如直接返回 `req.body`。迭代期间 worker 只处理这一个请求
+const PIPE: &str = r#"
+globalThis.__dino_pipe_open = async function (name, req) {
+  const handler = globalThis.handlers[name];
+  const fetch = typeof handler === "function" ? handler : handler?.fetch?.bind(handler);
+  if (typeof fetch !== "function") {
+    throw new Error(`handler not found: ${name}`);
+  }
+  const read = async () => {
+    const value = __dino_body_read();
+    return value == null ? { done: true, value: undefined } : { done: false, value };
+  };
+  req.body = {
+    getReader: () => ({ read, releaseLock() {}, cancel: async () => {} }),
+    [Symbol.asyncIterator]: () => ({ next: read }),
+    async text() {
+      let text = "";
+      for await (const chunk of this) {
+        if (typeof chunk !== "string") {
+          throw new TypeError("request body is not valid UTF-8");
+        }
+        text += chunk;
+      }
+      return text;
+    },
+    async json() {
+      return JSON.parse(await this.text());
+    },
+  };
+  const resp = (await fetch(req)) ?? {};
+  let body = resp.body ?? null;
+  const whole = typeof body === "string" || ArrayBuffer.isView(body) || body instanceof ArrayBuffer;
+  if (body !== null && !whole) {
+    const iter = body[Symbol.asyncIterator]?.() ?? body[Symbol.iterator]?.();
+    if (!iter) {
+      throw new Error(`handler ${name} must return a string or an iterable of chunks as body`);
+    }
+    globalThis.__dino_pipe = iter;
+    body = null;
+  }
+  return { status: resp.status ?? 200, headers: { ...resp.headers }, body };
+};
+globalThis.__dino_pipe_next = async function () {
+  const iter = globalThis.__dino_pipe;
+  if (!iter) return null;
+  const { value, done } = await iter.next();
+  if (done) {
+    globalThis.__dino_pipe = undefined;
+    return null;
+  }
+  if (typeof value !== "string" && !(value instanceof Uint8Array) && !(value instanceof ArrayBuffer)) {
+    throw new TypeError("body chunks must be strings, Uint8Array or ArrayBuffer");
+  }
+  return value;
+};
+globalThis.__dino_pipe_close = async function () {
+  const iter = globalThis.__dino_pipe;
+  globalThis.__dino_pipe = undefined;
+  await iter?.return?.();
+};
+"#;
+
+/// 流式路由的请求 body，每次返回一块原始字节，None 表示结束
+pub type BodyReader = Box<dyn FnMut() -> Result<Option<Bytes>>>;
+
+/// 交给 JS 的请求 body 块
+pub(crate) enum BodyChunk {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// 把字节块转换为交给 JS 的块：body 是 UTF-8 文本时为字符串，跨块的字符留到下一块；
+/// 遇到不是 UTF-8 的内容后，其余部分都原样以 `Uint8Array` 交给 JS
+struct BodyChunks {
+    read: BodyReader,
+    pending: Vec<u8>,
+    binary: bool,
+}
+
+impl BodyChunks {
+    fn next(&mut self) -> Result<Option<BodyChunk>> {
+        loop {
+            let Some(chunk) = (self.read)()? else {
+                // 结尾是不完整的 UTF-8 字符
+                let rest = std::mem::take(&mut self.pending);
+                return Ok((!rest.is_empty()).then_some(BodyChunk::Binary(rest)));
+            };
+            if self.binary {
+                return Ok(Some(BodyChunk::Binary(chunk.to_vec())));
+            }
+            self.pending.extend_from_slice(&chunk);
+            let valid = match std::str::from_utf8(&self.pending) {
+                Ok(s) => s.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => {
+                    self.binary = true;
+                    let pending = std::mem::take(&mut self.pending);
+                    return Ok(Some(BodyChunk::Binary(pending)));
+                }
+            };
+            if valid == 0 {
+                continue;
+            }
+            let rest = self.pending.split_off(valid);
+            let text = String::from_utf8(std::mem::replace(&mut self.pending, rest))?;
+            return Ok(Some(BodyChunk::Text(text)));
+        }
+    }
+}
+
+thread_local! {
+    // 当前流式请求的 body，`__dino_body_read` 从中读取
+    static BODY: RefCell<Option<BodyChunks>> = const { RefCell::new(None) };
+}
+
+/// 设置当前线程的流式请求 body，传 None 清除
+pub(crate) fn set_body(body: Option<BodyReader>) {
+    let body = body.map(|read| BodyChunks {
+        read,
+        pending: vec![],
+        binary: false,
+    });
+    BODY.with(|b| *b.borrow_mut() = body);
+}
+
+/// 读取当前流式请求 body 的下一块，不在流式请求中时视为空 body
+pub(crate) fn read_body() -> Result<Option<BodyChunk>> {
+    BODY.with(|b| match b.borrow_mut().as_mut() {
+        Some(body) => body.next(),
+        None => Ok(None),
+    })
+}
+
+#[cfg(feature = "server")]
+thread_local! {
+    // `dino invoke --tail` 时 handler 的输出同时发送给调用方
+    static LOG: RefCell<Option<LogSender>> = const { RefCell::new(None) };
+}
+
+fn print(msg: String) {
+    forward(&msg);
+    println!("{msg}");
+}
+
+/// `console` 的输出写入 tracing，target 为 `console`。worker 执行请求时日志挂在请求的
+/// span 下，带有 tenant 的 host 和请求 id
+fn console(level: String, msg: String) {
+    forward(&msg);
+    match level.as_str() {
+        "error" => tracing::error!(target: "console", "{msg}"),
+        "warn" => tracing::warn!(target: "console", "{msg}"),
+        "debug" => tracing::debug!(target: "console", "{msg}"),
+        _ => tracing::info!(target: "console", "{msg}"),
+    }
+}
+
+/// 把 handler 的输出转发给 `dino invoke --tail` 和调试会话
+fn forward(msg: &str) {
+    #[cfg(feature = "server")]
+    LOG.with(|log| {
+        if let Some(log) = log.borrow().as_ref() {
+            let _ = invoke::emit(log, msg.to_string());
+        }
+    });
+    #[cfg(feature = "server")]
+    crate::inspector::emit(msg);
+    #[cfg(not(feature = "server"))]
+    let _ = msg;
+}
+
+/// 设置当前线程的输出接收方，传 None 清除
+#[cfg(feature = "server")]
+pub(crate) fn set_log(log: Option<LogSender>) {
+    LOG.with(|l| *l.borrow_mut() = log);
+}
+
+#[cfg(feature = "server")]
+impl TryFrom<Resp> for Response {
+    type Error = anyhow::Error;
+
+    /// status 和 headers 来自 handler，不合法时返回错误
+    fn try_from(res: Resp) -> Result<Self> {
+        let mut builder = Response::builder().status(res.status);
+        for (k, v) in res.headers {
+            builder = builder.header(k, v);
+        }
+        let body = res
+            .body
+            .map_or_else(Body::empty, |b| Body::from(b.into_bytes()));
+        Ok(builder.body(body)?)
+    }
+}
@@ -0,0 +1,162 @@
+use std::{fmt, ops::Deref};
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use rquickjs::{ArrayBuffer, Ctx, FromJs, IntoJs, TypedArray, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 请求和响应的 body。
+///
+/// 传给 JS 时，UTF-8 文本为字符串，其他内容为 `Uint8Array`；handler 返回的 body
+/// 可以是字符串、`Uint8Array` 或 `ArrayBuffer`。序列化为 JSON 时文本为字符串，
+/// 其他内容为 `{"base64": "..."}`
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Payload(Vec<u8>);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Text(String),
+    Binary { base64: String },
+}
+
+impl Payload {
+    /// body 为 UTF-8 文本时返回文本
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Deref for Payload {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Payload {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Some(s) => fmt::Debug::fmt(s, f),
+            None => write!(f, "<{} bytes>", self.0.len()),
+        }
+    }
+}
+
+impl From<String> for Payload {
+    fn from(s: String) -> Self {
+        Self(s.into_bytes())
+    }
+}
+
+impl From<&str> for Payload {
+    fn from(s: &str) -> Self {
+        Self(s.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Payload {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for Payload {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl PartialEq<str> for Payload {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for Payload {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self.as_str() {
+            Some(s) => Repr::Text(s.to_string()),
+            None => Repr::Binary {
+                base64: BASE64_STANDARD.encode(&self.0),
+            },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(s) => Ok(s.into()),
+            Repr::Binary { base64 } => BASE64_STANDARD
+                .decode(base64)
+                .map(Self)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl<'js> IntoJs<'js> for Payload {
+    fn into_js(self, ctx: &Ctx<'js>) -> rquickjs::Result<Value<'js>> {
+        match String::from_utf8(self.0) {
+            Ok(s) => s.into_js(ctx),
+            Err(e) => TypedArray::<u8>::new(ctx.clone(), e.into_bytes())?.into_js(ctx),
+        }
+    }
+}
+
+impl<'js> FromJs<'js> for Payload {
+    fn from_js(ctx: &Ctx<'js>, v: Value<'js>) -> rquickjs::Result<Self> {
+        if v.is_string() {
+            return Ok(String::from_js(ctx, v)?.into());
+        }
+        let bytes = if let Ok(array) = TypedArray::<u8>::from_value(v.clone()) {
+            array.as_bytes().map(<[u8]>::to_vec)
+        } else if let Some(buffer) = ArrayBuffer::from_value(v.clone()) {
+            buffer.as_bytes().map(<[u8]>::to_vec)
+        } else {
+            return Err(rquickjs::Error::new_from_js(
+                v.type_name(),
+                "string, Uint8Array or ArrayBuffer",
+            ));
+        };
+        bytes
+            .map(Self)
+            .ok_or_else(|| rquickjs::Error::new_from_js("detached buffer", "body"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_should_serialize_text_and_binary() -> anyhow::Result<()> {
+        let text = Payload::from("hi");
+        assert_eq!(serde_json::to_string(&text)?, r#""hi""#);
+        let binary = Payload::from(vec![0xff, 0x00]);
+        let json = serde_json::to_string(&binary)?;
+        assert_eq!(json, r#"{"base64":"/wA="}"#);
+        assert_eq!(serde_json::from_str::<Payload>(&json)?, binary);
+        assert_eq!(serde_json::from_str::<Payload>(r#""hi""#)?, text);
+        assert_eq!(binary.as_str(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,877 @@
+use std::{
+    cell::Cell,
+    ptr,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use rquickjs::{
+    CatchResultExt, Context, Ctx, Exception, Function, IntoJs, Object, Promise, Runtime,
+    TypedArray, Value,
+    allocator::{Allocator, RustAllocator},
+    function::This,
+};
+
+use super::{
+    BodyChunk, BodyReader, CONSOLE, DISPATCH, Engine, Inspection, Limits, MemoryStats, PIPE,
+    Payload, Req, Resp, SERVE_FILE, STREAM, TRIGGER, Trigger, console, print, read_body, set_body,
+};
+use crate::host;
+#[cfg(feature = "server")]
+use crate::{binding, cache, capability, egress, object, rooms};
+
+/// 基于 rquickjs 的解释器后端，每个实例有独立的 runtime
+#[allow(unused)]
+pub struct QuickJs {
+    rt: Runtime,
+    ctx: Context,
+    /// 分配的内存超过上限，由 `Metered` 设置
+    out_of_memory: Rc<Cell<bool>>,
+}
+
+/// 统计 runtime 已分配的内存，超过上限的分配直接失败并记录下来，
+/// 这样可以区分超过内存上限和 JS 抛出的其他错误
+struct Metered {
+    used: usize,
+    limit: usize,
+    exceeded: Rc<Cell<bool>>,
+}
+
+impl Metered {
+    /// 再分配 `size` 字节是否会超过上限，超过时记录下来
+    fn exceeds(&mut self, size: usize) -> bool {
+        let exceeds = self.used.saturating_add(size) > self.limit;
+        if exceeds {
+            self.exceeded.set(true);
+        }
+        exceeds
+    }
+
+    fn track(&mut self, ptr: *mut u8) -> *mut u8 {
+        if !ptr.is_null() {
+            self.used += unsafe { RustAllocator::usable_size(ptr) };
+        }
+        ptr
+    }
+}
+
+unsafe impl Allocator for Metered {
+    fn alloc(&mut self, size: usize) -> *mut u8 {
+        if self.exceeds(size) {
+            return ptr::null_mut();
+        }
+        let ptr = RustAllocator.alloc(size);
+        self.track(ptr)
+    }
+
+    fn calloc(&mut self, count: usize, size: usize) -> *mut u8 {
+        if self.exceeds(count.saturating_mul(size)) {
+            return ptr::null_mut();
+        }
+        let ptr = RustAllocator.calloc(count, size);
+        self.track(ptr)
+    }
+
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        unsafe {
+            self.used -= RustAllocator::usable_size(ptr);
+            RustAllocator.dealloc(ptr);
+        }
+    }
+
+    unsafe fn realloc(&mut self, ptr: *mut u8, new_size: usize) -> *mut u8 {
+        let old = unsafe { RustAllocator::usable_size(ptr) };
+        if self.exceeds(new_size.saturating_sub(old)) {
+            return ptr::null_mut();
+        }
+        let ptr = unsafe { RustAllocator.realloc(ptr, new_size) };
+        if !ptr.is_null() {
+            self.used -= old;
+        }
+        self.track(ptr)
+    }
+
+    unsafe fn usable_size(ptr: *mut u8) -> usize {
+        unsafe { RustAllocator::usable_size(ptr) }
+    }
+}
+
+impl Engine for QuickJs {
+    const NAME: &'static str = "quickjs";
+
+    fn try_new_with(module: &str, limits: Limits) -> Result<Self> {
+        let out_of_memory = Rc::new(Cell::new(false));
+        let rt = match limits.memory {
+            0 => Runtime::new()?,
+            limit => Runtime::new_with_alloc(Metered {
+                used: 0,
+                limit,
+                exceeded: out_of_memory.clone(),
+            })?,
+        };
+        if let Some(interrupt) = limits.interrupt {
+            rt.set_interrupt_handler(Some(interrupt));
+        }
+        let ctx = Context::full(&rt)?;
+
+        ctx.with(|ctx| {
+            let global = ctx.globals();
+            // 核心模块的 shim 在模块求值时就会读取 host op，需要先安装
+            host::install(&ctx)?;
+            global.set("__dino_console", Function::new(ctx.clone(), console)?)?;
+            ctx.eval::<(), _>(CONSOLE)?;
+            let ret: Object = ctx.eval(module)?;
+            global.set("handlers", ret)?;
+            ctx.eval::<(), _>(DISPATCH)?;
+            ctx.eval::<(), _>(SERVE_FILE)?;
+            ctx.eval::<(), _>(STREAM)?;
+            ctx.eval::<(), _>(PIPE)?;
+            ctx.eval::<(), _>(TRIGGER)?;
+            // 服务绑定、fetch、caches 和对象依赖服务器的 AppState
+            #[cfg(feature = "server")]
+            {
+                binding::install(&ctx)?;
+                egress::install(&ctx)?;
+                cache::install(&ctx)?;
+                object::install(&ctx)?;
+                rooms::install(&ctx)?;
+                capability::restrict(&ctx)?;
+            }
+
+            let func = Function::new(ctx.clone(), print)?.with_name("print")?;
+            global.set("print", func)?;
+            global.set("__dino_body_read", Function::new(ctx.clone(), body_read)?)?;
+
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        Ok(Self {
+            rt,
+            ctx,
+            out_of_memory,
+        })
+    }
+
+    fn call_timed(&self, name: &str, req: Req) -> Result<(Resp, Duration)> {
+        self.ctx.with(|ctx| {
+            let global = ctx.globals();
+            let handlers: Object = global.get("handlers")?;
+
+            let handler: Value = handlers.get(name)?;
+            let start = Instant::now();
+            let req = req.into_js(&ctx)?;
+            let serialize = start.elapsed();
+            // 对象形式的 handler 用 `fetch` 方法处理 HTTP 请求
+            let v: Promise = match handler.as_object() {
+                Some(obj) if !handler.is_function() => {
+                    let fetch: Function = obj.get("fetch")?;
+                    fetch.call((This(obj.clone()), req))
+                }
+                _ => Function::from_value(handler)?.call((req,)),
+            }
+            .catch(&ctx)
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+            // 异常带上 JS 的调用栈，handler 被中断时可以看到卡在哪里
+            let resp = v
+                .finish::<Resp>()
+                .catch(&ctx)
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+            Ok::<_, anyhow::Error>((resp, serialize))
+        })
+    }
+
+    fn inspect(&self) -> Result<Inspection> {
+        // 新建一个空的 context 得到内置的全局属性
+        let builtins = Context::full(&self.rt)?.with(global_names)?;
+        let (handlers, globals) = self.ctx.with(|ctx| {
+            let handlers: Object = ctx.globals().get("handlers")?;
+            let mut handlers = handlers.keys().collect::<rquickjs::Result<Vec<String>>>()?;
+            handlers.sort();
+            let mut globals = global_names(ctx)?;
+            globals.retain(|name| !builtins.contains(name));
+            Ok::<_, anyhow::Error>((handlers, globals))
+        })?;
+        let usage = self.rt.memory_usage();
+        let size = |n: i64| n.max(0) as u64;
+        Ok(Inspection {
+            engine: Self::NAME.to_string(),
+            handlers,
+            globals,
+            memory: MemoryStats {
+                malloc_size: size(usage.malloc_size),
+                used_size: size(usage.memory_used_size),
+                objects: size(usage.obj_count),
+                strings: size(usage.str_count),
+                functions: size(usage.js_func_count),
+                arrays: size(usage.array_count),
+                binary_size: size(usage.binary_object_size),
+            },
+            pending_jobs: self.rt.is_job_pending(),
+        })
+    }
+
+    fn take_out_of_memory(&self) -> bool {
+        self.out_of_memory.replace(false)
+    }
+
+    #[cfg(feature = "server")]
+    fn call_object(&self, class: &str, id: &str, req: &str) -> Result<String> {
+        self.ctx.with(|ctx| {
+            let fun: Function = ctx.globals().get("__dino_object_call")?;
+            let v: Promise = fun.call((class, id, req))?;
+            v.finish::<String>()
+                .catch(&ctx)
+                .map_err(|e| anyhow::anyhow!("{e}"))
+        })
+    }
+
+    fn call_stream(
+        &self,
+        name: &str,
+        req: Req,
+        emit: &mut dyn FnMut(String) -> bool,
+    ) -> Result<()> {
+        self.ctx.with(|ctx| {
+            let global = ctx.globals();
+            let await_fn = |name: &str, args| -> Result<Option<String>> {
+                let fun: Function = global.get(name)?;
+                let v: Promise = fun.call(args)?;
+                v.finish::<Option<String>>()
+                    .catch(&ctx)
+                    .map_err(|e| anyhow::anyhow!("{e}"))
+            };
+
+            let open: Function = global.get("__dino_stream_open")?;
+            let v: Promise = open.call((name, req.into_js(&ctx)?))?;
+            v.finish::<()>()
+                .catch(&ctx)
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+            while let Some(event) = await_fn("__dino_stream_next", ())? {
+                if !emit(event) {
+                    await_fn("__dino_stream_close", ())?;
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn call_pipe(
+        &self,
+        name: &str,
+        req: Req,
+        body: BodyReader,
+        head: &mut dyn FnMut(Resp) -> bool,
+        emit: &mut dyn FnMut(Bytes) -> bool,
+    ) -> Result<()> {
+        set_body(Some(body));
+        let ret = self.ctx.with(|ctx| {
+            let global = ctx.globals();
+            let await_fn = |name: &str| -> Result<Option<Payload>> {
+                let fun: Function = global.get(name)?;
+                let v: Promise = fun.call(())?;
+                v.finish::<Option<Payload>>()
+                    .catch(&ctx)
+                    .map_err(|e| anyhow::anyhow!("{e}"))
+            };
+
+            let open: Function = global.get("__dino_pipe_open")?;
+            let v: Promise = open.call((name, req.into_js(&ctx)?))?;
+            let resp = v
+                .finish::<Resp>()
+                .catch(&ctx)
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+            if !head(resp) {
+                await_fn("__dino_pipe_close")?;
+                return Ok(());
+            }
+            while let Some(chunk) = await_fn("__dino_pipe_next")? {
+                if !emit(chunk.into_bytes().into()) {
+                    await_fn("__dino_pipe_close")?;
+                    break;
+                }
+            }
+            Ok(())
+        });
+        set_body(None);
+        ret
+    }
+
+    fn trigger(&self, name: &str, event: Trigger) -> Result<Resp> {
+        self.ctx.with(|ctx| {
+            let fun: Function = ctx.globals().get("__dino_trigger")?;
+            let v: Promise = fun.call((name, event))?;
+            v.finish::<Resp>()
+                .catch(&ctx)
+                .map_err(|e| anyhow::anyhow!("{e}"))
+        })
+    }
+
+    fn eval(&self, code: &str) -> Result<String> {
+        self.ctx.with(|ctx| {
+            let v: Value = ctx
+                .eval(code)
+                .catch(&ctx)
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+            let v = match v.as_promise() {
+                Some(p) => p
+                    .finish::<Value>()
+                    .catch(&ctx)
+                    .map_err(|e| anyhow::anyhow!("{e}"))?,
+                None => v,
+            };
+
+            if v.is_undefined() {
+                return Ok("undefined".to_string());
+            }
+            if v.is_function() {
+                return Ok("[Function]".to_string());
+            }
+            let ret = ctx
+                .json_stringify(v)?
+                .map(|s| s.to_string())
+                .transpose()?
+                .unwrap_or_else(|| "undefined".to_string());
+            Ok(ret)
+        })
+    }
+}
+
+/// 全局对象自身的属性名，按名称排序
+fn body_read(ctx: Ctx) -> rquickjs::Result<Value> {
+    let chunk = read_body().map_err(|e| Exception::throw_message(&ctx, &format!("{e:#}")))?;
+    match chunk {
+        Some(BodyChunk::Text(text)) => text.into_js(&ctx),
+        Some(BodyChunk::Binary(bytes)) => TypedArray::<u8>::new(ctx.clone(), bytes)?.into_js(&ctx),
+        None => Ok(Value::new_null(ctx)),
+    }
+}
+
+fn global_names(ctx: rquickjs::Ctx) -> rquickjs::Result<Vec<String>> {
+    let mut names: Vec<String> = ctx.eval("Object.getOwnPropertyNames(globalThis)")?;
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::Replay;
+    use std::collections::HashMap;
+
+    #[test]
+    fn js_worker_should_run() {
+        let code = r#"
+         (function(){
+         async function hello(req){
+             return {
+                 status:200,
+                 headers:{
+                     "content-type":"application/json"
+                 },
+                 body: JSON.stringify(req),
+             };
+         }
+         return{hello:hello};
+     })();
+     "#;
+        let req = Req::builder()
+            .method("GET")
+            .url("https://www.baidu.com")
+            .headers(HashMap::new())
+            .build();
+
+        let worker = QuickJs::try_new(code).unwrap();
+        let resp = worker.run("hello", req).unwrap();
+        println!("{:?}", resp);
+        assert_eq!(resp.status, 200);
+    }
+
+    #[test]
+    fn js_worker_should_dispatch_to_other_handlers() {
+        let code = r#"
+        (function(){
+            async function auth(req) {
+                return { status: req.headers.token === "t" ? 200 : 401, headers: {} };
+            }
+            async function hello(req) {
+                const ret = await dispatch("auth", { headers: req.headers });
+                if (ret.status !== 200) return ret;
+                return { status: 200, headers: {}, body: `hello ${req.params.name}` };
+            }
+            return { auth, hello };
+        })();
+        "#;
+        let worker = QuickJs::try_new(code).unwrap();
+        let req = |token: &str| {
+            Req::builder()
+                .method("GET")
+                .url("/hello/dino")
+                .headers(HashMap::from([("token".to_string(), token.to_string())]))
+                .params(HashMap::from([("name".to_string(), "dino".to_string())]))
+                .build()
+        };
+        let resp = worker.run("hello", req("t")).unwrap();
+        assert_eq!(resp.text(), Some("hello dino"));
+        assert_eq!(worker.run("hello", req("x")).unwrap().status, 401);
+        assert!(worker.eval("dispatch('nope')").is_err());
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn js_worker_should_deny_capabilities() -> Result<()> {
+        use crate::{Capabilities, Capability};
+
+        let code = r#"
+        (function(){
+            const ok = (body) => ({ status: 200, headers: {}, body: String(body) });
+            async function read() { return ok(__dino_host.fs.existsSync("/")); }
+            async function get() { return await fetch("http://127.0.0.1:9"); }
+            async function now() { return ok(__dino_host.time.now() > 0); }
+            async function file() { return serveFile("index.html"); }
+            return { read, get, now, file };
+        })();
+        "#;
+        capability::set_current(Capabilities {
+            allow: None,
+            deny: vec![Capability::Fs, Capability::Fetch],
+        });
+        let worker = QuickJs::try_new(code);
+        capability::set_current(Capabilities::default());
+        let worker = worker?;
+
+        let req = || Req::builder().method("GET").url("/").build();
+        let err = worker.run("read", req()).unwrap_err();
+        assert!(format!("{err:#}").contains("fs is not allowed for this tenant"));
+        let err = worker.run("get", req()).unwrap_err();
+        assert!(format!("{err:#}").contains("fetch is not allowed for this tenant"));
+        let err = worker.run("file", req()).unwrap_err();
+        assert!(format!("{err:#}").contains("fs is not allowed for this tenant"));
+        assert_eq!(worker.run("now", req())?.text(), Some("true"));
+
+        let worker = QuickJs::try_new(code)?;
+        assert_eq!(worker.run("read", req())?.text(), Some("true"));
+        Ok(())
+    }
+
+    #[test]
+    fn js_worker_should_pass_binary_bodies() -> Result<()> {
+        let code = r#"
+        (function(){
+            async function invert(req) {
+                const body = req.body.map((b) => 255 - b);
+                return { status: 200, headers: {}, body };
+            }
+            async function buffer(req) {
+                return { status: 200, headers: {}, body: new Uint8Array([1, 2]).buffer };
+            }
+            return { invert, buffer };
+        })();
+        "#;
+        let worker = QuickJs::try_new(code)?;
+        let req = |body: Vec<u8>| {
+            Req::builder()
+                .method("POST")
+                .url("/")
+                .body(Some(body))
+                .build()
+        };
+        let resp = worker.run("invert", req(vec![0xff, 0xfe]))?;
+        assert_eq!(resp.body.as_deref(), Some(&[0, 1][..]));
+        let resp = worker.run("buffer", req(vec![]))?;
+        assert_eq!(resp.body.as_deref(), Some(&[1, 2][..]));
+        Ok(())
+    }
+
+    #[test]
+    fn js_worker_should_trigger_handlers_by_event_type() -> anyhow::Result<()> {
+        use crate::engine::{QueueEvent, ScheduleEvent, WebhookEvent};
+
+        let code = r#"
+        (function(){
+            async function cleanup(event) {
+                return { job: event.name, type: event.type };
+            }
+            const app = {
+                async fetch(req) { return { status: 200, headers: {}, body: `http ${req.url}` }; },
+                async queue(event) { this.last = event.body; },
+                async webhook(event) { return { status: 202, body: event.headers["x-event"] }; },
+            };
+            return { cleanup, app };
+        })();
+        "#;
+        let worker = QuickJs::try_new(code)?;
+        let schedule = Trigger::Scheduled(ScheduleEvent {
+            name: "cleanup".into(),
+            schedule: "0 * * * *".into(),
+            time: "2025-01-01T00:00:00Z".into(),
+        });
+        let resp = worker.trigger("cleanup", schedule.clone())?;
+        assert_eq!(resp.text(), Some(r#"{"job":"cleanup","type":"scheduled"}"#));
+
+        // 同一个对象同时处理 HTTP 请求和后台事件
+        let req = Req::builder().method("GET").url("/app").build();
+        assert_eq!(worker.run("app", req)?.text(), Some("http /app"));
+        let queue = Trigger::Queue(QueueEvent {
+            queue: "mail".into(),
+            id: "m1".into(),
+            body: Some("hi".into()),
+            attempt: 1,
+        });
+        let resp = worker.trigger("app", queue)?;
+        assert_eq!((resp.status, resp.body), (200, None));
+        assert_eq!(worker.eval("handlers.app.last")?, r#""hi""#);
+        let webhook = Trigger::Webhook(WebhookEvent {
+            source: "github".into(),
+            headers: HashMap::from([("x-event".to_string(), "push".to_string())]),
+            body: None,
+        });
+        let resp = worker.trigger("app", webhook)?;
+        assert_eq!((resp.status, resp.text()), (202, Some("push")));
+
+        let err = worker.trigger("app", schedule).unwrap_err();
+        assert!(err.to_string().contains("can't handle scheduled events"));
+        Ok(())
+    }
+
+    #[test]
+    fn js_worker_should_provide_host_modules() {
+        let code = "(function(){ return {}; })();";
+        let worker = QuickJs::try_new(code).unwrap();
+        assert_eq!(
+            worker
+                .eval("__dino_host.fs.existsSync('Cargo.toml')")
+                .unwrap(),
+            "true"
+        );
+        assert_eq!(
+            worker
+                .eval("__dino_host.dns.lookup('localhost').length > 0")
+                .unwrap(),
+            "true"
+        );
+        assert!(worker.eval("__dino_host.fs.readFileSync('nope')").is_err());
+        let uuid = worker.eval("__dino_host.crypto.randomUUID()").unwrap();
+        let uuid = uuid.trim_matches('"');
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!("89ab".contains(&uuid[19..20]));
+    }
+
+    #[test]
+    fn js_worker_should_provide_web_globals() {
+        let code = "(function(){ return {}; })();";
+        let worker = QuickJs::try_new(code).unwrap();
+        let ret = worker
+            .eval(
+                r#"
+                const a = { m: new Map([["k", new Set([1, 2])]]), d: new Date(0), b: new Uint8Array([1, 2]) };
+                a.self = a;
+                const b = structuredClone(a);
+                b.b[0] = 9;
+                [b !== a, b.self === b, b.m.get("k").has(2), b.d.getTime(), a.b[0], b.b instanceof Uint8Array]
+                "#,
+            )
+            .unwrap();
+        assert_eq!(ret, "[true,true,true,0,1,true]");
+        assert!(worker.eval("structuredClone({ f() {} })").is_err());
+
+        let ret = worker
+            .eval(
+                r#"
+                (async () => {
+                    const order = [];
+                    queueMicrotask(() => order.push("micro"));
+                    order.push("sync");
+                    await null;
+                    return order;
+                })()
+                "#,
+            )
+            .unwrap();
+        assert_eq!(ret, r#"["sync","micro"]"#);
+    }
+
+    #[test]
+    fn js_worker_should_provide_time_module() {
+        let code = "(function(){ return {}; })();";
+        let worker = QuickJs::try_new(code).unwrap();
+        let eval = |code: &str| {
+            worker.eval(&format!(
+                "(() => {{ const t = __dino_host.time; return {code}; }})()"
+            ))
+        };
+        assert_eq!(
+            eval("t.format(0, '%Y-%m-%d %H:%M', 'Asia/Shanghai')").unwrap(),
+            r#""1970-01-01 08:00""#
+        );
+        assert_eq!(eval("t.offset(0, 'America/New_York')").unwrap(), "-300");
+        assert_eq!(
+            eval("t.parse('2024-03-01 12:00', '%Y-%m-%d %H:%M', 'Europe/Paris')").unwrap(),
+            eval("t.parse('2024-03-01T11:00:00Z')").unwrap()
+        );
+        assert_eq!(eval("t.parts(0).weekday").unwrap(), "4");
+        assert!(eval("t.format(0, '%Y', 'Mars/Olympus')").is_err());
+
+        // 不指定时区时使用 tenant 的时区
+        crate::set_timezone(Some(chrono_tz::Asia::Tokyo));
+        assert_eq!(eval("t.format(0, '%H:%M')").unwrap(), r#""09:00""#);
+        crate::set_timezone(None);
+        assert_eq!(eval("t.format(0, '%H:%M')").unwrap(), r#""00:00""#);
+    }
+
+    #[test]
+    fn js_worker_should_replay_deterministically() {
+        let code = r#"
+        (function(){
+            async function rand(req){
+                return { status: 200, headers: {}, body: `${Math.random()} ${Date.now()}` };
+            }
+            return{rand:rand};
+        })();
+        "#;
+        let worker = QuickJs::try_new(code).unwrap();
+        let req = || Req::builder().method("GET").url("/").build();
+        let replay = Replay {
+            seed: 42,
+            now: 1_700_000_000_000.0,
+        };
+        let a = worker.run_with_replay("rand", req(), Some(replay)).unwrap();
+        let b = worker.run_with_replay("rand", req(), Some(replay)).unwrap();
+        let c = worker.run("rand", req()).unwrap();
+        assert_eq!(a.body, b.body);
+        assert!(a.text().unwrap().ends_with(" 1700000000000"));
+        assert_ne!(b.body, c.body);
+    }
+
+    #[test]
+    fn js_worker_eval_should_work() {
+        let code = r#"
+        (function(){
+            async function hello(req){
+                return { status: 200, headers: {}, body: req.method };
+            }
+            return{hello:hello};
+        })();
+        "#;
+        let worker = QuickJs::try_new(code).unwrap();
+        assert_eq!(worker.eval("1 + 1").unwrap(), "2");
+        assert_eq!(worker.eval("let a = {x: 1}; a").unwrap(), r#"{"x":1}"#);
+        assert_eq!(worker.eval("handlers.hello").unwrap(), "[Function]");
+        assert_eq!(
+            worker.eval("handlers.hello({method: 'GET'})").unwrap(),
+            r#"{"status":200,"headers":{},"body":"GET"}"#
+        );
+        assert!(worker.eval("throw new Error('boom')").is_err());
+    }
+
+    #[test]
+    fn js_worker_should_inspect_runtime() -> anyhow::Result<()> {
+        let code = r#"
+        (function(){
+            async function hello() { return { status: 200, headers: {}, body: "ok" }; }
+            const app = { async fetch() { return hello(); } };
+            return { hello, app };
+        })();
+        "#;
+        let worker = QuickJs::try_new(code)?;
+        worker.eval("globalThis.leaked = new Array(1000).fill('x'); undefined")?;
+        let inspection = worker.inspect()?;
+        assert_eq!(inspection.engine, "quickjs");
+        assert_eq!(inspection.handlers, ["app", "hello"]);
+        // 内置的全局属性不列出，dino 安装的和 handler 留下的都列出
+        assert!(inspection.globals.iter().any(|g| g == "leaked"));
+        assert!(inspection.globals.iter().any(|g| g == "dispatch"));
+        assert!(!inspection.globals.iter().any(|g| g == "Object"));
+        assert!(inspection.memory.used_size > 0 && inspection.memory.arrays > 0);
+        assert!(!inspection.pending_jobs);
+        Ok(())
+    }
+
+    #[test]
+    fn js_worker_should_stream_events() {
+        let code = r#"
+        (function(){
+            let closed = false;
+            async function* ticks(req) {
+                try {
+                    const from = Number(req.headers["last-event-id"] ?? 0);
+                    for (let i = from + 1; i <= from + 3; i++) {
+                        yield { id: i, event: "tick", data: { i } };
+                    }
+                } finally {
+                    closed = true;
+                }
+            }
+            function list() {
+                return ["a", "b"];
+            }
+            return { ticks, list, closed: () => closed };
+        })();
+        "#;
+        let worker = QuickJs::try_new(code).unwrap();
+        let req = |last: Option<&str>| {
+            let headers = last
+                .map(|id| HashMap::from([("last-event-id".to_string(), id.to_string())]))
+                .unwrap_or_default();
+            Req::builder()
+                .method("GET")
+                .url("/")
+                .headers(headers)
+                .build()
+        };
+        let mut events = vec![];
+        worker
+            .call_stream("ticks", req(Some("5")), &mut |e| {
+                events.push(e);
+                true
+            })
+            .unwrap();
+        assert_eq!(
+            events,
+            [6, 7, 8].map(|i| format!(r#"{{"id":"{i}","event":"tick","data":"{{\"i\":{i}}}"}}"#))
+        );
+
+        // 接收方返回 false 时提前结束，迭代器的 finally 会执行
+        let mut events = vec![];
+        worker
+            .call_stream("ticks", req(None), &mut |e| {
+                events.push(e);
+                false
+            })
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(worker.eval("handlers.closed()").unwrap(), "true");
+
+        let mut events = vec![];
+        worker
+            .call_stream("list", req(None), &mut |e| {
+                events.push(e);
+                true
+            })
+            .unwrap();
+        assert_eq!(events, [r#"{"data":"a"}"#, r#"{"data":"b"}"#]);
+        assert!(
+            worker
+                .call_stream("nope", req(None), &mut |_| true)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn js_worker_should_pipe_bodies() -> anyhow::Result<()> {
+        let code = r#"
+        (function(){
+            async function upper(req) {
+                async function* chunks() {
+                    for await (const chunk of req.body) yield chunk.toUpperCase();
+                }
+                return { status: 201, headers: { "x-a": "1" }, body: chunks() };
+            }
+            async function count(req) {
+                const reader = req.body.getReader();
+                let n = 0;
+                while (!(await reader.read()).done) n++;
+                return { status: 200, headers: {}, body: String(n) };
+            }
+            async function types(req) {
+                async function* chunks() {
+                    for await (const chunk of req.body) {
+                        yield typeof chunk === "string" ? chunk : new Uint8Array(chunk).buffer;
+                    }
+                    yield new Uint8Array([0xfe]);
+                }
+                return { status: 200, headers: {}, body: chunks() };
+            }
+            async function text(req) {
+                return { status: 200, headers: {}, body: await req.body.text() };
+            }
+            async function number() {
+                return { status: 200, headers: {}, body: [1][Symbol.iterator]() };
+            }
+            return { upper, count, types, text, number };
+        })();
+        "#;
+        let worker = QuickJs::try_new(code)?;
+        let body = |chunks: &[&[u8]]| -> BodyReader {
+            let mut chunks = chunks
+                .iter()
+                .map(|c| Bytes::copy_from_slice(c))
+                .collect::<Vec<_>>()
+                .into_iter();
+            Box::new(move || Ok(chunks.next()))
+        };
+        let req = || Req::builder().url("/").method("POST").build();
+        let pipe = |name: &str, chunks: &[&[u8]]| -> anyhow::Result<(Option<Resp>, Vec<Bytes>)> {
+            let (mut head, mut out) = (None, vec![]);
+            worker.call_pipe(
+                name,
+                req(),
+                body(chunks),
+                &mut |resp| head.replace(resp).is_none(),
+                &mut |chunk| {
+                    out.push(chunk);
+                    true
+                },
+            )?;
+            Ok((head, out))
+        };
+
+        let (head, out) = pipe("upper", &[b"ab", b"c"])?;
+        let head = head.unwrap();
+        assert_eq!((head.status, head.headers["x-a"].as_str()), (201, "1"));
+        assert_eq!(head.body, None);
+        assert_eq!(out, ["AB", "C"]);
+
+        let (head, out) = pipe("count", &[b"a", b"b", b"c"])?;
+        assert_eq!(head.unwrap().text(), Some("3"));
+        assert!(out.is_empty());
+        assert!(pipe("nope", &[]).is_err());
+
+        // 不是 UTF-8 之后的块以 Uint8Array 交给 JS，JS 可以返回 ArrayBuffer 和 Uint8Array
+        let (_, out) = pipe("types", &[b"a\xc3", b"\xa9", b"\xff\x00", b"b"])?;
+        assert_eq!(out, [&b"a"[..], b"\xc3\xa9", b"\xff\x00", b"b", b"\xfe"]);
+        let (head, _) = pipe("text", &[b"ok"])?;
+        assert_eq!(head.unwrap().text(), Some("ok"));
+        let err = pipe("text", &[b"\xff"]).unwrap_err();
+        assert!(format!("{err:#}").contains("request body is not valid UTF-8"));
+        let err = pipe("number", &[]).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("body chunks must be strings, Uint8Array or ArrayBuffer")
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn js_worker_should_forward_console_output() -> anyhow::Result<()> {
+        use crate::invoke::InvokeEvent;
+
+        let code = r#"(function(){
+            console.info("at load");
+            async function hello() {
+                console.log("hi", 1, { a: [2] });
+                console.error(new TypeError("bad"));
+                return { status: 200, headers: {} };
+            }
+            return { hello };
+        })();"#;
+        let worker = QuickJs::try_new(code)?;
+        let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+        crate::engine::set_log(Some(send));
+        worker.run("hello", Req::builder().method("GET").url("/").build())?;
+        crate::engine::set_log(None);
+
+        let mut messages = vec![];
+        while let Ok(InvokeEvent::Log { message }) = recv.try_recv() {
+            messages.push(message);
+        }
+        assert_eq!(messages[0], r#"hi 1 {"a":[2]}"#);
+        assert!(messages[1].starts_with("TypeError: bad\n"));
+        assert_eq!(worker.eval("typeof console.debug")?, r#""function""#);
+        Ok(())
+    }
+}
@@ -13,8 +13,20 @@ pub enum AppError {
     RoutePathNotFound(String),
     #[error("Method not allowed: {0}")]
     RouteMethodNotAllowed(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    #[error("Bad request: {0:#}")]
+    BadRequest(anyhow::Error),
+    #[error("Out of memory: {0}")]
+    OutOfMemory(String),
     #[error("Anyhow error: {0}")]
-    Anyhow(#[from] anyhow::Error),
+    Anyhow(anyhow::Error),
     #[error("Serde json error: {0}")]
     Serde(#[from] serde_json::Error),
 }
@@ -25,9 +37,25 @@ impl IntoResponse for AppError {
             AppError::HostNotFound(_) => StatusCode::NOT_FOUND,
             AppError::RoutePathNotFound(_) => StatusCode::NOT_FOUND,
             AppError::RouteMethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::OutOfMemory(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Serde(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
         (code, self.to_string().clone()).into_response()
     }
 }
+
+impl From<anyhow::Error> for AppError {
+    /// worker 返回的 `AppError` 经过 anyhow 传递后还原，保留其状态码
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast() {
+            Ok(e) => e,
+            Err(e) => AppError::Anyhow(e),
+        }
+    }
+}
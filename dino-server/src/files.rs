@@ -0,0 +1,495 @@
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    ops::Bound,
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, Response, StatusCode, header},
+};
+use axum_extra::headers::{
+    ContentLength, ContentRange, ETag, HeaderMapExt, IfRange, LastModified, Range,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    encoding::{self, Encoding},
+    engine::Resp,
+};
+
+/// 按扩展名推断的 Content-Type，handler 可以在 `serveFile` 的 headers 中指定
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("ico", "image/x-icon"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("pdf", "application/pdf"),
+    ("wasm", "application/wasm"),
+];
+
+/// 请求的 Range 对应的响应
+#[derive(Debug, PartialEq, Eq)]
+enum Ranged {
+    Full,
+    /// 闭区间 [start, end]
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// handler 的返回值转换为响应，返回 `serveFile` 时读取 tenant 文件目录 `root` 下的文件
+pub(crate) async fn into_response(
+    mut resp: Resp,
+    headers: &HeaderMap,
+    root: &Path,
+) -> Result<Response<Body>> {
+    let Some(path) = resp.file.take() else {
+        return compress(resp, headers);
+    };
+    match resolve(root, &path).await {
+        Ok(path) => serve_file(resp, &path, root, headers).await,
+        Err(status) => Ok(Response::builder().status(status).body(Body::empty())?),
+    }
+}
+
+/// 把 handler 给出的相对路径解析到 `root` 下，符号链接解析后也必须在 `root` 下。
+/// 绝对路径和 `root` 之外的路径返回 403，不存在的文件返回 404
+async fn resolve(root: &Path, path: &str) -> Result<PathBuf, StatusCode> {
+    let path = Path::new(path);
+    if path
+        .components()
+        .any(|c| matches!(c, Component::RootDir | Component::Prefix(_)))
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    contain(root, &root.join(path)).await
+}
+
+/// 解析 `path` 中的 `..` 和符号链接，结果不在 `root` 下时返回 403
+async fn contain(root: &Path, path: &Path) -> Result<PathBuf, StatusCode> {
+    let root = tokio::fs::canonicalize(root)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let resolved = tokio::fs::canonicalize(path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    match resolved.starts_with(&root) {
+        true => Ok(resolved),
+        false => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// 按 `Accept-Encoding` 压缩文本类的 body，handler 设置了 `Content-Encoding`
+/// 或 `compress: false` 时原样返回
+fn compress(mut resp: Resp, headers: &HeaderMap) -> Result<Response<Body>> {
+    let eligible = resp.compress != Some(false)
+        && resp
+            .body
+            .as_ref()
+            .is_some_and(|b| b.len() >= encoding::MIN_COMPRESS_SIZE)
+        && find_header(&resp.headers, "content-encoding").is_none()
+        && encoding::compressible(find_header(&resp.headers, "content-type"));
+    if !eligible {
+        return Response::try_from(resp);
+    }
+    let encoded = match Encoding::negotiate(headers) {
+        Some(encoding) => {
+            let body = resp.body.take().unwrap_or_default();
+            resp.headers
+                .insert("content-encoding".into(), encoding.name().into());
+            Some(encoding.encode(&body)?)
+        }
+        None => None,
+    };
+    let mut response = Response::try_from(resp)?;
+    if let Some(encoded) = encoded {
+        *response.body_mut() = Body::from(encoded);
+    }
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    Ok(response)
+}
+
+fn find_header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// 文件旁边的预压缩版本 `<file>.br` 或 `<file>.gz` 中客户端接受的一个，
+/// 同时返回是否存在预压缩版本，存在时响应需要 `Vary: Accept-Encoding`。
+/// 解析后不在 `root` 下的版本忽略
+async fn precompressed(
+    path: &Path,
+    root: &Path,
+    headers: &HeaderMap,
+) -> (Option<(PathBuf, Encoding)>, bool) {
+    let mut found = false;
+    for encoding in Encoding::ALL {
+        let mut sibling = path.as_os_str().to_owned();
+        sibling.push(".");
+        sibling.push(encoding.extension());
+        let Ok(sibling) = contain(root, Path::new(&sibling)).await else {
+            continue;
+        };
+        if !tokio::fs::metadata(&sibling)
+            .await
+            .is_ok_and(|meta| meta.is_file())
+        {
+            continue;
+        }
+        found = true;
+        if encoding.accepted(headers) {
+            return (Some((sibling, encoding)), true);
+        }
+    }
+    (None, found)
+}
+
+/// 流式发送文件，200 响应支持单个区间的 `Range` 和 `If-Range`，
+/// 请求多个区间时返回整个文件。客户端接受时发送预压缩的版本，Range 作用于压缩后的内容
+async fn serve_file(
+    resp: Resp,
+    path: &Path,
+    root: &Path,
+    headers: &HeaderMap,
+) -> Result<Response<Body>> {
+    let (variant, vary) = match resp.compress {
+        Some(false) => (None, false),
+        _ => precompressed(path, root, headers).await,
+    };
+    let source = variant.as_ref().map_or(path, |(p, _)| p.as_path());
+    let mut file = File::open(source)
+        .await
+        .with_context(|| format!("failed to open {}", source.display()))?;
+    let meta = file.metadata().await?;
+    let len = meta.len();
+    let modified = meta.modified().ok();
+    let mtime = modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    let etag: ETag = format!("\"{len:x}-{mtime:x}\"").parse()?;
+    let last_modified = modified.map(LastModified::from);
+
+    let ranged = match resp.status {
+        200 => ranged(headers, len, &etag, last_modified.as_ref()),
+        _ => Ranged::Full,
+    };
+
+    let mut builder = Response::builder();
+    let resp_headers = builder.headers_mut().context("invalid response")?;
+    for (k, v) in resp.headers {
+        resp_headers.insert(k.parse::<header::HeaderName>()?, v.parse()?);
+    }
+    if !resp_headers.contains_key(header::CONTENT_TYPE) {
+        let content_type = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| {
+                CONTENT_TYPES
+                    .iter()
+                    .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+            })
+            .map_or("application/octet-stream", |(_, t)| t);
+        resp_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    }
+    resp_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some((_, encoding)) = variant {
+        resp_headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.name()),
+        );
+    }
+    if vary {
+        resp_headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    resp_headers.typed_insert(etag);
+    if let Some(last_modified) = last_modified {
+        resp_headers.typed_insert(last_modified);
+    }
+
+    let (status, start, size) = match ranged {
+        Ranged::Full => (resp.status, 0, len),
+        Ranged::Partial(start, end) => {
+            resp_headers.typed_insert(ContentRange::bytes(start..=end, len)?);
+            (StatusCode::PARTIAL_CONTENT.as_u16(), start, end - start + 1)
+        }
+        Ranged::Unsatisfiable => {
+            resp_headers.typed_insert(ContentRange::unsatisfied_bytes(len));
+            let status = StatusCode::RANGE_NOT_SATISFIABLE;
+            return Ok(builder.status(status).body(Body::empty())?);
+        }
+    };
+    resp_headers.typed_insert(ContentLength(size));
+    file.seek(SeekFrom::Start(start)).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(size)));
+    Ok(builder.status(status).body(body)?)
+}
+
+/// `If-Range` 与文件当前的版本不一致时忽略 `Range`
+fn ranged(headers: &HeaderMap, len: u64, etag: &ETag, modified: Option<&LastModified>) -> Ranged {
+    let Some(range) = headers.typed_get::<Range>() else {
+        return Ranged::Full;
+    };
+    if let Some(if_range) = headers.typed_get::<IfRange>()
+        && if_range.is_modified(Some(etag), modified)
+    {
+        return Ranged::Full;
+    }
+    let ranges: Vec<_> = range.satisfiable_ranges(len).collect();
+    let [(start, end)] = ranges[..] else {
+        return Ranged::Full;
+    };
+    let start = match start {
+        Bound::Included(n) => n,
+        Bound::Excluded(n) => n + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match end {
+        Bound::Included(n) => n.min(len.saturating_sub(1)),
+        Bound::Excluded(n) => n.saturating_sub(1).min(len.saturating_sub(1)),
+        Bound::Unbounded => len.saturating_sub(1),
+    };
+    match start < len && start <= end {
+        true => Ranged::Partial(start, end),
+        false => Ranged::Unsatisfiable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::body::to_bytes;
+
+    use super::*;
+    use crate::engine::{Engine, JsWorker, Req};
+
+    /// 在 `root` 下调用 `serveFile(path)`
+    async fn serve(root: &Path, path: &str, headers: &HeaderMap) -> Result<Response<Body>> {
+        let code = r#"
+        (function(){
+            async function media(req) {
+                return serveFile(req.query.path, { headers: { "cache-control": "no-cache" } });
+            }
+            return { media };
+        })();
+        "#;
+        let worker = JsWorker::try_new(code)?;
+        let req = Req::builder()
+            .url("/media")
+            .method("GET")
+            .query(HashMap::from([("path".into(), path.to_string())]))
+            .build();
+        let resp = worker.run("media", req)?;
+        into_response(resp, headers, root).await
+    }
+
+    async fn get(root: &Path, range: &[(&str, &str)]) -> Result<(u16, Option<String>, String)> {
+        let mut headers = HeaderMap::new();
+        for (k, v) in range {
+            headers.insert(k.parse::<header::HeaderName>()?, v.parse()?);
+        }
+        let resp = serve(root, "media.mp4", &headers).await?;
+        assert_eq!(resp.headers()["cache-control"], "no-cache");
+        assert_eq!(resp.headers()["content-type"], "video/mp4");
+        let content_range = resp
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .map(|v| v.to_str().unwrap().to_string());
+        let status = resp.status().as_u16();
+        let body = to_bytes(resp.into_body(), usize::MAX).await?;
+        Ok((status, content_range, String::from_utf8(body.to_vec())?))
+    }
+
+    #[tokio::test]
+    async fn serve_file_should_support_range_requests() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("dino-files-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root)?;
+        let path = root.join("media.mp4");
+        std::fs::write(&path, "0123456789")?;
+
+        assert_eq!(get(&root, &[]).await?, (200, None, "0123456789".into()));
+        let partial =
+            |range: &str, body: &str| (206, Some(format!("bytes {range}/10")), body.into());
+        assert_eq!(
+            get(&root, &[("range", "bytes=2-4")]).await?,
+            partial("2-4", "234")
+        );
+        assert_eq!(
+            get(&root, &[("range", "bytes=7-")]).await?,
+            partial("7-9", "789")
+        );
+        assert_eq!(
+            get(&root, &[("range", "bytes=-3")]).await?,
+            partial("7-9", "789")
+        );
+        assert_eq!(
+            get(&root, &[("range", "bytes=8-20")]).await?,
+            partial("8-9", "89")
+        );
+        assert_eq!(
+            get(&root, &[("range", "bytes=10-")]).await?,
+            (416, Some("bytes */10".into()), "".into())
+        );
+        // 多个区间时返回整个文件
+        assert_eq!(get(&root, &[("range", "bytes=0-1,4-5")]).await?.0, 200);
+
+        // If-Range 不匹配时返回整个文件
+        let stale = [("range", "bytes=2-4"), ("if-range", "\"stale\"")];
+        assert_eq!(get(&root, &stale).await?.0, 200);
+        let meta = std::fs::metadata(&path)?;
+        let mtime = meta.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+        let etag = format!("\"{:x}-{mtime:x}\"", meta.len());
+        let fresh = [("range", "bytes=2-4"), ("if-range", etag.as_str())];
+        assert_eq!(get(&root, &fresh).await?, partial("2-4", "234"));
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn serve_file_should_stay_in_tenant_root() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dino-files-{}", uuid::Uuid::new_v4()));
+        let root = dir.join("a.com");
+        std::fs::create_dir_all(root.join("media"))?;
+        std::fs::write(root.join("ok.txt"), "ok")?;
+        std::fs::write(dir.join("secret.txt"), "secret")?;
+        let status = async |path: &str| -> Result<u16> {
+            Ok(serve(&root, path, &HeaderMap::new())
+                .await?
+                .status()
+                .as_u16())
+        };
+
+        assert_eq!(status("ok.txt").await?, 200);
+        assert_eq!(status("media/../ok.txt").await?, 200);
+        assert_eq!(status("missing.txt").await?, 404);
+        assert_eq!(status("../secret.txt").await?, 403);
+        assert_ne!(status("../../etc/passwd").await?, 200);
+        assert_eq!(status("../../../../../../../../etc/passwd").await?, 403);
+        assert_eq!(status("/etc/passwd").await?, 403);
+        let absolute = dir.join("secret.txt").display().to_string();
+        assert_eq!(status(&absolute).await?, 403);
+        // 指向 root 之外的符号链接
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("link.txt"))?;
+            assert_eq!(status("link.txt").await?, 403);
+        }
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn serve_file_should_prefer_precompressed_siblings() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("dino-files-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root)?;
+        let path = root.join("app.js");
+        std::fs::write(&path, "plain")?;
+        let sibling = |ext: &str| PathBuf::from(format!("{}.{ext}", path.display()));
+        std::fs::write(sibling("gz"), "gzipped")?;
+        std::fs::write(sibling("br"), "brotli")?;
+
+        let serve = async |accept: &str, compress: Option<bool>| -> Result<_> {
+            let resp = Resp {
+                status: 200,
+                headers: HashMap::new(),
+                body: None,
+                file: Some("app.js".into()),
+                compress,
+            };
+            let headers = HeaderMap::from_iter([(header::ACCEPT_ENCODING, accept.parse()?)]);
+            let resp = into_response(resp, &headers, &root).await?;
+            assert_eq!(
+                resp.headers()["content-type"],
+                "text/javascript; charset=utf-8"
+            );
+            let encoding = resp
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|v| v.to_str().unwrap().to_string());
+            let vary = resp.headers().contains_key(header::VARY);
+            let body = to_bytes(resp.into_body(), usize::MAX).await?;
+            Ok((encoding, vary, String::from_utf8(body.to_vec())?))
+        };
+        let some = |v: &str| Some(v.to_string());
+        assert_eq!(
+            serve("gzip, br", None).await?,
+            (some("br"), true, "brotli".into())
+        );
+        assert_eq!(
+            serve("gzip", None).await?,
+            (some("gzip"), true, "gzipped".into())
+        );
+        assert_eq!(serve("identity", None).await?, (None, true, "plain".into()));
+        assert_eq!(
+            serve("br", Some(false)).await?,
+            (None, false, "plain".into())
+        );
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn body_should_be_compressed_when_accepted() -> Result<()> {
+        let text = "dino ".repeat(500);
+        let resp = |content_type: &str, compress: Option<bool>| Resp {
+            status: 200,
+            headers: HashMap::from([("Content-Type".into(), content_type.into())]),
+            body: Some(text.clone().into()),
+            file: None,
+            compress,
+        };
+        let gzip = HeaderMap::from_iter([(header::ACCEPT_ENCODING, "gzip".parse()?)]);
+
+        let compressed = into_response(resp("text/plain", None), &gzip, Path::new(".")).await?;
+        assert_eq!(compressed.headers()["content-encoding"], "gzip");
+        assert_eq!(compressed.headers()["vary"], "accept-encoding");
+        let body = to_bytes(compressed.into_body(), usize::MAX).await?;
+        let decoded = encoding::decode_body(
+            &HeaderMap::from_iter([(header::CONTENT_ENCODING, "gzip".parse()?)]),
+            body,
+            usize::MAX,
+        )
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+        assert_eq!(decoded, text.as_bytes());
+
+        for (content_type, compress) in [("text/plain", Some(false)), ("image/png", None)] {
+            let plain = into_response(resp(content_type, compress), &gzip, Path::new(".")).await?;
+            assert!(!plain.headers().contains_key(header::CONTENT_ENCODING));
+            let body = to_bytes(plain.into_body(), usize::MAX).await?;
+            assert_eq!(body, text.as_bytes());
+        }
+        // 客户端不接受压缩时只加 Vary
+        let plain =
+            into_response(resp("text/plain", None), &HeaderMap::new(), Path::new(".")).await?;
+        assert!(!plain.headers().contains_key(header::CONTENT_ENCODING));
+        assert_eq!(plain.headers()["vary"], "accept-encoding");
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+//! cargo-fuzz 的结构化入口，fuzz target 在仓库根目录的 `fuzz/` 中：
+//!
+//! ```text
+//! cargo +nightly fuzz run assemble_req
+//! ```
+//!
+//! 这些函数走和 `start_server` 相同的解析路径，输入来自客户端或 handler，
+//! 不合法时返回错误，任何 panic 都是 bug
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    extract::Query,
+    http::{HeaderMap, Method, Uri},
+    response::Response,
+};
+use rquickjs::{Context, FromJs, IntoJs, Runtime};
+
+use crate::{
+    ProjectConfig, SwappableAppRouter, assemble_req,
+    engine::{Req, Resp},
+    ensure_http,
+};
+
+/// 覆盖静态路径、参数、通配、各种方法和非 HTTP 路由的路由表
+const ROUTES: &str = r#"
+name: fuzz
+routes:
+  /:
+    - method: GET
+      handler: index
+  /api/{name}/{id}:
+    - method: GET
+      handler: show
+    - method: POST
+      handler: create
+    - method: DELETE
+      handler: remove
+  /api/{name}/static:
+    - method: PUT
+      handler: update
+  /files/{*path}:
+    - method: GET
+      handler: files
+    - method: HEAD
+      handler: files
+  /events:
+    - method: GET
+      handler: events
+      type: sse
+  /ws:
+    - method: GET
+      handler: ws
+      type: websocket
+"#;
+
+thread_local! {
+    static CONTEXT: Context = Context::full(&Runtime::new().unwrap()).unwrap();
+}
+
+/// fuzz 用的路由表，handler 不会被执行
+pub fn router() -> Result<SwappableAppRouter> {
+    let config: ProjectConfig = serde_yaml::from_str(ROUTES)?;
+    SwappableAppRouter::try_new("", config.routes)
+}
+
+/// 匹配路由，返回 handler 名称
+pub fn match_route(router: &SwappableAppRouter, method: &[u8], path: &str) -> Result<String> {
+    let method = Method::from_bytes(method)?;
+    let router = router.load();
+    let matched = router.match_it(method, path)?;
+    Ok(router.handler(matched.value.handler).to_string())
+}
+
+/// 从原始的方法、URI 和 body 组装传给 handler 的 Req
+pub fn assemble(router: &SwappableAppRouter, method: &[u8], uri: &str, body: &[u8]) -> Result<Req> {
+    let method = Method::from_bytes(method)?;
+    let uri: Uri = uri.parse()?;
+    let Query(query) = Query::<HashMap<String, String>>::try_from_uri(&uri)?;
+    let router = router.load();
+    let matched = router.match_it(method.clone(), uri.path())?;
+    ensure_http(&matched, &uri)?;
+    let headers = HeaderMap::new();
+    assemble_req(
+        query,
+        &matched,
+        method,
+        &uri,
+        &headers,
+        Bytes::copy_from_slice(body),
+    )
+}
+
+/// 把 Req 转换为 JS 对象，返回 JS 中 `JSON.stringify` 的结果
+pub fn marshal_req(req: Req) -> Result<String> {
+    CONTEXT.with(|ctx| {
+        ctx.with(|ctx| {
+            let value = req.into_js(&ctx)?;
+            let json = ctx.json_stringify(value)?;
+            Ok(json.map(|s| s.to_string()).transpose()?.unwrap_or_default())
+        })
+    })
+}
+
+/// 把 handler 返回的 JSON 转换为 HTTP 响应
+pub fn marshal_resp(json: &str) -> Result<Response<Body>> {
+    let resp = CONTEXT.with(|ctx| {
+        ctx.with(|ctx| -> Result<Resp> {
+            let value = ctx.json_parse(json)?;
+            Ok(Resp::from_js(&ctx, value)?)
+        })
+    })?;
+    Response::try_from(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzz_api_should_reject_invalid_input() -> Result<()> {
+        let router = router()?;
+        assert_eq!(match_route(&router, b"GET", "/files/a/b")?, "files");
+        // 扩展方法曾经在路由匹配时 panic
+        assert!(match_route(&router, b"PROPFIND", "/").is_err());
+        assert!(assemble(&router, b"GET", "/ws", b"").is_err());
+
+        let req = assemble(&router, b"POST", "/api/user/7?x=1", b"{}")?;
+        assert_eq!(req.params["id"], "7");
+        let json = marshal_req(req)?;
+        let req: Req = serde_json::from_str(&json)?;
+        assert_eq!(
+            (
+                req.query["x"].as_str(),
+                req.body.as_ref().and_then(|b| b.as_str())
+            ),
+            ("1", Some("{}"))
+        );
+        // 非 UTF-8 的 body 以 Uint8Array 传给 JS
+        let req = assemble(&router, b"POST", "/api/a/1", &[0xff])?;
+        assert_eq!(req.body.as_deref(), Some(&[0xff][..]));
+        assert!(marshal_req(req).is_ok());
+
+        let resp = marshal_resp(r#"{"status":201,"headers":{"x-a":"b"},"body":"ok"}"#)?;
+        assert_eq!(resp.status(), 201);
+        // handler 返回非对象或不合法的 status、header 曾经 panic
+        assert!(marshal_resp("1").is_err());
+        assert!(marshal_resp(r#"{"status":1000,"headers":{}}"#).is_err());
+        assert!(marshal_resp(r#"{"status":200,"headers":{"a b":"c"}}"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    fs,
+    path::{Component, Path},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use git2::{Direction, ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    AppState,
+    admin::constant_time_eq,
+    builder::{self, MAX_SOURCE_SIZE, SourceBuild, TempDir},
+};
+
+/// 部署源码所在的 git 仓库
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitSource {
+    pub url: String,
+    /// 分支或 tag，缺省为远程的默认分支
+    #[serde(default, rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// 校验 push webhook 签名（`X-Hub-Signature-256`）的密钥，为 None 时不接受 webhook
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// `url#ref`
+impl FromStr for GitSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (url, reference) = match s.rsplit_once('#') {
+            Some((url, reference)) => (url, Some(reference.to_string())),
+            None => (s, None),
+        };
+        ensure!(!url.is_empty(), "repository url is empty");
+        ensure!(
+            reference.as_deref() != Some(""),
+            "ref is empty, use {url} for the default branch"
+        );
+        Ok(Self {
+            url: url.to_string(),
+            reference,
+            secret: None,
+        })
+    }
+}
+
+impl GitSource {
+    /// 校验 `sha256=<hex>` 形式的 HMAC 签名
+    pub fn verify(&self, body: &[u8], signature: &str) -> bool {
+        let Some(secret) = &self.secret else {
+            return false;
+        };
+        let Some(signature) = signature.strip_prefix("sha256=") else {
+            return false;
+        };
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key");
+        mac.update(body);
+        let expected: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        constant_time_eq(
+            expected.as_bytes(),
+            signature.to_ascii_lowercase().as_bytes(),
+        )
+    }
+
+    /// push 事件的 ref（如 `refs/heads/main`）是否为部署的分支或 tag，没有指定 ref 时
+    /// 部署的是默认分支，无法判断，都重新部署
+    pub fn tracks(&self, pushed: &str) -> bool {
+        let Some(reference) = &self.reference else {
+            return true;
+        };
+        pushed == reference
+            || pushed.strip_prefix("refs/heads/") == Some(reference)
+            || pushed.strip_prefix("refs/tags/") == Some(reference)
+    }
+}
+
+impl AppState {
+    /// 拉取 `source` 并打包，返回 commit 和打包结果。
+    /// 每个 tenant 在 `<git_dir>/<host>.git` 有一个 bare 仓库，只保存拉取的对象，
+    /// 在确认 tenant 存在之后才创建
+    pub(crate) fn build_git(
+        &self,
+        host: &str,
+        source: &GitSource,
+    ) -> Result<(String, SourceBuild)> {
+        check_url(&source.url)?;
+        ensure!(self.routers.contains_key(host), "tenant {host} not found");
+        let repo = open_or_init(&self.git_dir.join(format!("{host}.git")))?;
+        build(&repo, source)
+    }
+
+    /// 拉取、打包并替换 tenant 的代码，记录仓库以便 webhook 重新部署，返回部署的 commit
+    pub fn deploy_git(&self, host: &str, source: GitSource, actor: &str) -> Result<String> {
+        let (commit, build) = self.build_git(host, &source)?;
+        self.swap_with_config(host, build.code, &build.config, actor)?;
+        self.git_sources.insert(host.to_string(), source);
+        Ok(commit)
+    }
+
+    /// tenant 最近一次从 git 部署时的仓库
+    pub(crate) fn git_source(&self, host: &str) -> Option<GitSource> {
+        self.git_sources.get(host).map(|s| s.clone())
+    }
+}
+
+/// 只允许远程仓库：`https://`、`ssh://` 或 `git@host:path`，
+/// 本地路径和 `file://` 会读取服务器上的其他仓库
+fn check_url(url: &str) -> Result<()> {
+    let scp = url
+        .strip_prefix("git@")
+        .and_then(|rest| rest.split_once(':'))
+        .is_some_and(|(host, _)| !host.is_empty() && !host.contains('/'));
+    ensure!(
+        url.starts_with("https://") || url.starts_with("ssh://") || scp,
+        "unsupported repository url {url}, use https://, ssh:// or git@host:path"
+    );
+    Ok(())
+}
+
+/// 拉取 `source` 的 commit 并在临时目录中打包
+fn build(repo: &Repository, source: &GitSource) -> Result<(String, SourceBuild)> {
+    let commit = fetch(repo, source)?;
+    let dir = TempDir::new()?;
+    export(repo, commit, &dir.0)?;
+    let build = builder::build_dir(&dir.0)?;
+    Ok((commit.to_string(), build))
+}
+
+fn open_or_init(path: &Path) -> Result<Repository> {
+    if path.exists() {
+        return Ok(Repository::open_bare(path)?);
+    }
+    fs::create_dir_all(path)?;
+    Ok(Repository::init_bare(path)?)
+}
+
+/// 先在远程的 ref 中查找 `HEAD`、`<ref>`、`refs/heads/<ref>` 或 `refs/tags/<ref>`，
+/// 远程没有这个 ref 时 fetch 本身不会报错
+fn fetch(repo: &Repository, source: &GitSource) -> Result<Oid> {
+    let mut remote = repo.remote_anonymous(&source.url)?;
+    let reference = source.reference.as_deref().unwrap_or("HEAD");
+    let candidates = [
+        reference.to_string(),
+        format!("refs/heads/{reference}"),
+        format!("refs/tags/{reference}"),
+    ];
+    remote
+        .connect(Direction::Fetch)
+        .with_context(|| format!("failed to connect to {}", source.url))?;
+    let (name, oid) = remote
+        .list()?
+        .iter()
+        .filter_map(|head| {
+            let rank = candidates.iter().position(|c| c == head.name())?;
+            Some((rank, head.name().to_string(), head.oid()))
+        })
+        .min_by_key(|(rank, ..)| *rank)
+        .map(|(_, name, oid)| (name, oid))
+        .with_context(|| format!("failed to fetch {reference} from {}", source.url))?;
+    remote.disconnect()?;
+    remote
+        .fetch(&[&name], None, None)
+        .with_context(|| format!("failed to fetch {reference} from {}", source.url))?;
+    Ok(repo.find_object(oid, None)?.peel_to_commit()?.id())
+}
+
+/// 把 commit 的文件写入 `dir`，忽略符号链接和子模块
+fn export(repo: &Repository, commit: Oid, dir: &Path) -> Result<()> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    let mut size = 0;
+    let mut ret = Ok(());
+    tree.walk(TreeWalkMode::PreOrder, |parent, entry| {
+        let step = (|| {
+            let name = entry.name().context("file name is not UTF-8")?;
+            let path = Path::new(parent).join(name);
+            ensure!(
+                path.components()
+                    .all(|c| matches!(c, Component::Normal(n) if n != ".git")),
+                "invalid path in repository: {}",
+                path.display()
+            );
+            match entry.kind() {
+                Some(ObjectType::Tree) => fs::create_dir_all(dir.join(&path))?,
+                Some(ObjectType::Blob) if entry.filemode() != 0o120000 => {
+                    let blob = repo.find_blob(entry.id())?;
+                    size += blob.size() as u64;
+                    ensure!(
+                        size <= MAX_SOURCE_SIZE,
+                        "repository is larger than {} MiB",
+                        MAX_SOURCE_SIZE >> 20
+                    );
+                    fs::write(dir.join(&path), blob.content())?;
+                }
+                _ => {}
+            }
+            Ok(())
+        })();
+        match step {
+            Ok(()) => TreeWalkResult::Ok,
+            Err(e) => {
+                ret = Err(e);
+                TreeWalkResult::Abort
+            }
+        }
+    })
+    .or_else(|e| match ret.is_err() {
+        true => Ok(()),
+        false => Err(e),
+    })?;
+    ret
+}
+
+/// 签名校验失败或 push 的不是部署的 ref 时不部署
+pub(crate) fn check_push(source: &GitSource, body: &[u8], signature: Option<&str>) -> Result<bool> {
+    let Some(signature) = signature else {
+        bail!("missing X-Hub-Signature-256 header");
+    };
+    ensure!(source.verify(body, signature), "invalid webhook signature");
+    let push: serde_json::Value = serde_json::from_slice(body).unwrap_or_default();
+    Ok(push["ref"].as_str().is_none_or(|r| source.tracks(r)))
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Signature;
+
+    use super::*;
+
+    fn commit(repo: &Repository, files: &[(&str, &str)], message: &str) -> Result<Oid> {
+        let mut index = repo.index()?;
+        let root = repo.workdir().unwrap();
+        for (path, content) in files {
+            fs::write(root.join(path), content)?;
+            index.add_path(Path::new(path))?;
+        }
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let sig = Signature::now("dino", "dino@example.com")?;
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        Ok(repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)?)
+    }
+
+    #[test]
+    fn build_should_fetch_refs() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dino-git-{}", uuid::Uuid::new_v4()));
+        let origin = Repository::init(dir.join("origin"))?;
+        let config = "name: demo\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n";
+        let hello = |body: &str| {
+            format!(
+                "export async function hello() {{ return {{ status: 200, headers: {{}}, body: '{body}' }}; }}"
+            )
+        };
+        let v1 = commit(
+            &origin,
+            &[("config.yml", config), ("main.ts", &hello("v1"))],
+            "v1",
+        )?;
+        origin.tag_lightweight("v1", &origin.find_object(v1, None)?, false)?;
+        commit(&origin, &[("main.ts", &hello("v2"))], "v2")?;
+
+        // 测试中从本地仓库拉取，绕过 `build_git` 对 url 的检查
+        let repo = open_or_init(&dir.join("repos/a.com.git"))?;
+        let url = dir.join("origin").display().to_string();
+
+        let (_, build) = super::build(&repo, &url.parse()?)?;
+        assert!(build.code.contains("\"v2\""));
+        let (commit, build) = super::build(&repo, &format!("{url}#v1").parse()?)?;
+        assert_eq!(commit, v1.to_string());
+        assert!(build.code.contains("\"v1\""));
+        let err = super::build(&repo, &format!("{url}#nope").parse()?).unwrap_err();
+        assert!(err.to_string().starts_with("failed to fetch nope"));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn build_git_should_reject_local_urls_and_unknown_tenants() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dino-git-{}", uuid::Uuid::new_v4()));
+        let router = crate::SwappableAppRouter::try_new("", Default::default())?;
+        let mut state =
+            AppState::with_routers([("a.com".to_string(), router)].into_iter().collect());
+        state.git_dir = dir.clone();
+
+        for url in [
+            "/srv/other.git",
+            "../other.git",
+            "file:///srv/other.git",
+            "git@/srv/x",
+        ] {
+            let err = state.build_git("a.com", &url.parse()?).unwrap_err();
+            assert!(err.to_string().starts_with("unsupported repository url"));
+        }
+        let source = "https://example.com/demo.git".parse()?;
+        let err = state.build_git("b.com", &source).unwrap_err();
+        assert_eq!(err.to_string(), "tenant b.com not found");
+        assert!(!dir.exists());
+
+        for url in [
+            "https://example.com/demo.git",
+            "ssh://git@example.com/demo.git",
+            "git@example.com:demo.git",
+        ] {
+            check_url(url)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn check_push_should_verify_signature_and_ref() -> Result<()> {
+        let mut source: GitSource = "https://example.com/demo.git#main".parse()?;
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        // 没有密钥时不接受 webhook
+        assert!(check_push(&source, body, Some("sha256=00")).is_err());
+
+        source.secret = Some("s3cret".into());
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret")?;
+        mac.update(body);
+        let signature: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        let signature = format!("sha256={signature}");
+        assert!(check_push(&source, body, Some(&signature))?);
+        assert!(check_push(&source, body, None).is_err());
+        assert!(check_push(&source, b"{}", Some(&signature)).is_err());
+
+        source.reference = Some("release".into());
+        assert!(!check_push(&source, body, Some(&signature))?);
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fs,
+    net::ToSocketAddrs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    capability::{self, Capability},
+    replay::Replay,
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, Datelike, NaiveDateTime, Offset, TimeZone, Timelike};
+use chrono_tz::Tz;
+use rquickjs::{
+    Array, ArrayBuffer, Ctx, Exception, Function, Object, TypedArray, Value,
+    function::{Constructor, Opt, This},
+};
+use sha2::{Digest, digest::DynDigest};
+
+/// 把 host op 注册到模块对象上
+type Register = for<'js> fn(&Ctx<'js>, &Object<'js>) -> rquickjs::Result<()>;
+
+/// 由 Rust 实现的核心模块，打包时 `import fs from "fs"` 会被转发到这里
+pub struct HostModule {
+    pub name: &'static str,
+    pub exports: &'static [&'static str],
+    register: Register,
+}
+
+/// worker 中可用的核心模块，未列出的（net、http 等）在打包时直接报错
+pub static HOST_MODULES: &[HostModule] = &[
+    HostModule {
+        name: "fs",
+        exports: &["readFileSync", "writeFileSync", "existsSync"],
+        register: register_fs,
+    },
+    HostModule {
+        name: "dns",
+        exports: &["lookup"],
+        register: register_dns,
+    },
+    HostModule {
+        name: "time",
+        exports: &["now", "format", "parse", "offset", "parts"],
+        register: register_time,
+    },
+    HostModule {
+        name: "crypto",
+        exports: &["createHash", "randomUUID", "randomInt"],
+        register: register_crypto,
+    },
+];
+
+/// worker 提供的全局 API
+pub static HOST_GLOBALS: &[&str] = &[
+    "print",
+    "console",
+    "structuredClone",
+    "queueMicrotask",
+    "dispatch",
+    "serveFile",
+    "bindings",
+    "objects",
+    "fetch",
+    "caches",
+];
+
+/// 返回核心模块及其导出，用于打包时生成 shim
+pub fn host_modules() -> HashMap<String, Vec<String>> {
+    HOST_MODULES
+        .iter()
+        .map(|m| {
+            let exports = m.exports.iter().map(|e| e.to_string()).collect();
+            (m.name.to_string(), exports)
+        })
+        .collect()
+}
+
+thread_local! {
+    // 每个 worker 独占一个线程，所以请求的 replay 状态放在线程局部变量里
+    static RNG: Cell<u64> = Cell::new(Replay::new().seed);
+    static FROZEN_NOW: Cell<Option<f64>> = const { Cell::new(None) };
+    // `dino:time` 的默认时区，worker 线程启动时设置为 tenant 的时区
+    static TIMEZONE: Cell<Tz> = const { Cell::new(Tz::UTC) };
+}
+
+/// 设置当前线程 `dino:time` 的默认时区，None 时为 UTC
+pub fn set_timezone(tz: Option<Tz>) {
+    TIMEZONE.set(tz.unwrap_or(Tz::UTC));
+}
+
+/// 设置当前请求的随机数种子和时间，None 时恢复真实时钟
+pub(crate) fn set_replay(replay: Option<Replay>) {
+    match replay {
+        Some(replay) => {
+            RNG.set(replay.seed);
+            FROZEN_NOW.set(Some(replay.now));
+        }
+        None => {
+            RNG.set(Replay::new().seed);
+            FROZEN_NOW.set(None);
+        }
+    }
+}
+
+/// splitmix64，返回 [0, 1) 之间的浮点数
+fn random() -> f64 {
+    let mut z = RNG.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+    RNG.set(z);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 当前时间戳（毫秒），replay 时返回冻结的时间
+fn now() -> f64 {
+    FROZEN_NOW.get().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as f64
+    })
+}
+
+/// 在 `globalThis.__dino_host` 上安装所有核心模块
+pub(crate) fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    let host = Object::new(ctx.clone())?;
+    for module in HOST_MODULES {
+        let obj = Object::new(ctx.clone())?;
+        match Capability::of_module(module.name) {
+            Some(capability) if !capability::allowed(capability) => {
+                capability::deny(ctx, &obj, capability, module.exports)?;
+            }
+            _ => (module.register)(ctx, &obj)?,
+        }
+        host.set(module.name, obj)?;
+    }
+    let global = ctx.globals();
+    global.set("__dino_host", host)?;
+    global.set(
+        "structuredClone",
+        Function::new(ctx.clone(), structured_clone)?,
+    )?;
+    global.set(
+        "queueMicrotask",
+        Function::new(ctx.clone(), queue_microtask)?,
+    )?;
+
+    // 随机数和时间由 host 提供，replay 时可以复现
+    let math: Object = global.get("Math")?;
+    math.set("random", Function::new(ctx.clone(), random)?)?;
+    let date: Object = global.get("Date")?;
+    date.set("now", Function::new(ctx.clone(), now)?)?;
+    Ok(())
+}
+
+fn throw(ctx: &Ctx, e: impl ToString) -> rquickjs::Error {
+    Exception::throw_message(ctx, &e.to_string())
+}
+
+fn register_fs<'js>(ctx: &Ctx<'js>, obj: &Object<'js>) -> rquickjs::Result<()> {
+    fn read_file_sync(ctx: Ctx, path: String) -> rquickjs::Result<String> {
+        fs::read_to_string(path).map_err(|e| throw(&ctx, e))
+    }
+    fn write_file_sync(ctx: Ctx, path: String, data: String) -> rquickjs::Result<()> {
+        fs::write(path, data).map_err(|e| throw(&ctx, e))
+    }
+    fn exists_sync(path: String) -> bool {
+        fs::exists(path).unwrap_or(false)
+    }
+
+    obj.set("readFileSync", Function::new(ctx.clone(), read_file_sync)?)?;
+    obj.set(
+        "writeFileSync",
+        Function::new(ctx.clone(), write_file_sync)?,
+    )?;
+    obj.set("existsSync", Function::new(ctx.clone(), exists_sync)?)?;
+    Ok(())
+}
+
+fn register_dns<'js>(ctx: &Ctx<'js>, obj: &Object<'js>) -> rquickjs::Result<()> {
+    fn lookup(ctx: Ctx, host: String) -> rquickjs::Result<Vec<String>> {
+        let addrs = (host.as_str(), 0)
+            .to_socket_addrs()
+            .map_err(|e| throw(&ctx, e))?;
+        Ok(addrs.map(|addr| addr.ip().to_string()).collect())
+    }
+
+    obj.set("lookup", Function::new(ctx.clone(), lookup)?)?;
+    Ok(())
+}
+
+/// `dino:time`：QuickJS 的 Intl 支持很有限，时间格式化和时区换算由 Rust 实现。
+/// 时间戳与 `Date.now()` 一致，单位为毫秒，时区使用 IANA 名称，默认为 tenant 的时区，
+/// 没有设置时为 UTC。`Date` 的本地时间方法仍然使用服务器的时区
+fn register_time<'js>(ctx: &Ctx<'js>, obj: &Object<'js>) -> rquickjs::Result<()> {
+    fn tz(ctx: &Ctx, name: Opt<String>) -> rquickjs::Result<Tz> {
+        match name.0 {
+            Some(name) => name
+                .parse()
+                .map_err(|_| Exception::throw_range(ctx, &format!("invalid time zone: {name}"))),
+            None => Ok(TIMEZONE.get()),
+        }
+    }
+    fn datetime(ctx: &Ctx, ms: f64, zone: Opt<String>) -> rquickjs::Result<DateTime<Tz>> {
+        let tz = tz(ctx, zone)?;
+        match DateTime::from_timestamp_millis(ms as i64) {
+            Some(dt) if ms.is_finite() => Ok(dt.with_timezone(&tz)),
+            _ => Err(Exception::throw_range(ctx, "invalid timestamp")),
+        }
+    }
+
+    // pattern 使用 strftime 语法，如 `%Y-%m-%d %H:%M`
+    fn format(ctx: Ctx, ms: f64, pattern: String, zone: Opt<String>) -> rquickjs::Result<String> {
+        let dt = datetime(&ctx, ms, zone)?;
+        let mut ret = String::new();
+        std::fmt::write(&mut ret, format_args!("{}", dt.format(&pattern)))
+            .map_err(|_| throw(&ctx, format!("invalid format: {pattern}")))?;
+        Ok(ret)
+    }
+    // 没有 pattern 时按 RFC 3339 解析，否则按 pattern 解析为 `zone` 中的本地时间
+    fn parse(
+        ctx: Ctx,
+        text: String,
+        pattern: Opt<String>,
+        zone: Opt<String>,
+    ) -> rquickjs::Result<f64> {
+        let ret = match pattern.0 {
+            None => DateTime::parse_from_rfc3339(&text)
+                .map(|dt| dt.timestamp_millis())
+                .map_err(|e| throw(&ctx, e)),
+            Some(pattern) => {
+                let naive =
+                    NaiveDateTime::parse_from_str(&text, &pattern).map_err(|e| throw(&ctx, e))?;
+                tz(&ctx, zone)?
+                    .from_local_datetime(&naive)
+                    .earliest()
+                    .map(|dt| dt.timestamp_millis())
+                    .ok_or_else(|| throw(&ctx, format!("{text} does not exist in time zone")))
+            }
+        };
+        Ok(ret? as f64)
+    }
+    // 相对 UTC 的偏移，单位为分钟
+    fn offset(ctx: Ctx, ms: f64, zone: Opt<String>) -> rquickjs::Result<i32> {
+        let dt = datetime(&ctx, ms, zone)?;
+        Ok(dt.offset().fix().local_minus_utc() / 60)
+    }
+    fn parts<'js>(ctx: Ctx<'js>, ms: f64, zone: Opt<String>) -> rquickjs::Result<Object<'js>> {
+        let dt = datetime(&ctx, ms, zone)?;
+        let obj = Object::new(ctx)?;
+        obj.set("year", dt.year())?;
+        obj.set("month", dt.month())?;
+        obj.set("day", dt.day())?;
+        obj.set("hour", dt.hour())?;
+        obj.set("minute", dt.minute())?;
+        obj.set("second", dt.second())?;
+        obj.set("millisecond", dt.timestamp_subsec_millis())?;
+        obj.set("weekday", dt.weekday().number_from_monday())?;
+        obj.set("offset", dt.offset().fix().local_minus_utc() / 60)?;
+        Ok(obj)
+    }
+
+    obj.set("now", Function::new(ctx.clone(), now)?)?;
+    obj.set("format", Function::new(ctx.clone(), format)?)?;
+    obj.set("parse", Function::new(ctx.clone(), parse)?)?;
+    obj.set("offset", Function::new(ctx.clone(), offset)?)?;
+    obj.set("parts", Function::new(ctx.clone(), parts)?)?;
+    Ok(())
+}
+
+/// `node:crypto` 的子集：哈希由 Rust 计算，随机数来自 host，replay 时可以复现
+fn register_crypto<'js>(ctx: &Ctx<'js>, obj: &Object<'js>) -> rquickjs::Result<()> {
+    // 累积 update 的数据，digest 时一次交给 Rust
+    const CREATE_HASH: &str = r#"(digest) => function createHash(algorithm) {
+  digest(algorithm, []);
+  const chunks = [];
+  return {
+    update(data) { chunks.push(data); return this; },
+    digest(encoding) { return digest(algorithm, chunks, encoding); },
+  };
+}"#;
+
+    fn hasher(ctx: &Ctx, algorithm: &str) -> rquickjs::Result<Box<dyn DynDigest>> {
+        Ok(match algorithm.to_ascii_lowercase().as_str() {
+            "sha1" => Box::new(sha1::Sha1::new()),
+            "sha256" => Box::new(sha2::Sha256::new()),
+            "sha384" => Box::new(sha2::Sha384::new()),
+            "sha512" => Box::new(sha2::Sha512::new()),
+            _ => {
+                return Err(throw(
+                    ctx,
+                    format!("Digest method not supported: {algorithm}"),
+                ));
+            }
+        })
+    }
+    // 数据可以是字符串（UTF-8）或 Uint8Array，不指定 encoding 时返回 Uint8Array
+    fn digest<'js>(
+        ctx: Ctx<'js>,
+        algorithm: String,
+        chunks: Vec<Value<'js>>,
+        encoding: Opt<String>,
+    ) -> rquickjs::Result<Value<'js>> {
+        let mut hasher = hasher(&ctx, &algorithm)?;
+        for chunk in chunks {
+            if let Some(s) = chunk.as_string() {
+                hasher.update(s.to_string()?.as_bytes());
+            } else if let Some(bytes) = TypedArray::<u8>::from_value(chunk)
+                .ok()
+                .and_then(|a| a.as_bytes().map(<[u8]>::to_vec))
+            {
+                hasher.update(&bytes);
+            } else {
+                return Err(throw(&ctx, "data must be a string or Uint8Array"));
+            }
+        }
+        let hash = hasher.finalize();
+        match encoding.0.as_deref() {
+            None => Ok(TypedArray::<u8>::new(ctx, hash.to_vec())?.into_value()),
+            Some("hex") => {
+                let hex: String = hash.iter().map(|b| format!("{b:02x}")).collect();
+                rquickjs::String::from_str(ctx, &hex).map(|s| s.into_value())
+            }
+            Some("base64") => {
+                rquickjs::String::from_str(ctx, &BASE64.encode(hash)).map(|s| s.into_value())
+            }
+            Some(encoding) => Err(throw(&ctx, format!("unsupported encoding: {encoding}"))),
+        }
+    }
+    fn random_uuid() -> String {
+        let mut bytes: [u8; 16] = std::array::from_fn(|_| (random() * 256.0) as u8);
+        // 版本 4，RFC 4122 变体
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        let parts = [
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..],
+        ];
+        parts.join("-")
+    }
+    // 与 Node 一致：只传一个参数时范围为 [0, max)
+    fn random_int(min: f64, max: Opt<f64>) -> f64 {
+        let (min, max) = match max.0 {
+            Some(max) => (min, max),
+            None => (0.0, min),
+        };
+        (random() * (max - min)).floor() + min
+    }
+
+    let create_hash: Function = ctx.eval(CREATE_HASH)?;
+    let create_hash: Function = create_hash.call((Function::new(ctx.clone(), digest)?,))?;
+    obj.set("createHash", create_hash)?;
+    obj.set("randomUUID", Function::new(ctx.clone(), random_uuid)?)?;
+    obj.set("randomInt", Function::new(ctx.clone(), random_int)?)?;
+    Ok(())
+}
+
+/// 深拷贝 JS 值，支持循环引用以及 Map/Set/Date/ArrayBuffer/TypedArray
+fn structured_clone<'js>(ctx: Ctx<'js>, value: Value<'js>) -> rquickjs::Result<Value<'js>> {
+    Cloner {
+        ctx,
+        seen: HashMap::new(),
+    }
+    .clone(value)
+}
+
+struct Cloner<'js> {
+    ctx: Ctx<'js>,
+    // 已拷贝的对象，key 是原对象
+    seen: HashMap<Value<'js>, Value<'js>>,
+}
+
+impl<'js> Cloner<'js> {
+    fn clone(&mut self, value: Value<'js>) -> rquickjs::Result<Value<'js>> {
+        if value.is_function() || value.is_symbol() {
+            return Err(Exception::throw_type(
+                &self.ctx,
+                &format!("{} could not be cloned", value.type_name()),
+            ));
+        }
+        let Some(obj) = value.as_object().cloned() else {
+            return Ok(value);
+        };
+        if let Some(cloned) = self.seen.get(&value) {
+            return Ok(cloned.clone());
+        }
+
+        let ctor = |name: &str| self.ctx.globals().get::<_, Constructor>(name);
+        if let Some(buf) = ArrayBuffer::from_object(obj.clone()) {
+            let bytes = buf.as_bytes().unwrap_or_default();
+            let cloned = ArrayBuffer::new_copy(self.ctx.clone(), bytes)?.into_value();
+            self.seen.insert(value, cloned.clone());
+            return Ok(cloned);
+        }
+        if obj.is_instance_of(ctor("Date")?) {
+            let get_time: Function = obj.get("getTime")?;
+            let time: f64 = get_time.call((This(obj.clone()),))?;
+            return ctor("Date")?.construct((time,));
+        }
+        let is_view: Function = ctor("ArrayBuffer")?.get("isView")?;
+        if is_view.call((value.clone(),))? {
+            // TypedArray/DataView：拷贝底层 buffer 后用同样的构造函数重建
+            let buffer = self.clone(obj.get("buffer")?)?;
+            let view_ctor: Constructor = obj.get("constructor")?;
+            let offset: Value = obj.get("byteOffset")?;
+            let len: Value = match obj.contains_key("length")? {
+                true => obj.get("length")?,
+                false => obj.get("byteLength")?,
+            };
+            let cloned: Value = view_ctor.construct((buffer, offset, len))?;
+            self.seen.insert(value, cloned.clone());
+            return Ok(cloned);
+        }
+
+        let array_from: Function = ctor("Array")?.get("from")?;
+        for name in ["Map", "Set"] {
+            if !obj.is_instance_of(ctor(name)?) {
+                continue;
+            }
+            let cloned: Object = ctor(name)?.construct(())?;
+            self.seen.insert(value.clone(), cloned.clone().into_value());
+            let entries: Array = array_from.call((value.clone(),))?;
+            for entry in entries.iter::<Value>() {
+                let entry = entry?;
+                match name {
+                    "Map" => {
+                        let pair: Array = entry.get()?;
+                        let k = self.clone(pair.get(0)?)?;
+                        let v = self.clone(pair.get(1)?)?;
+                        let set: Function = cloned.get("set")?;
+                        set.call::<_, Value>((This(cloned.clone()), k, v))?;
+                    }
+                    _ => {
+                        let v = self.clone(entry)?;
+                        let add: Function = cloned.get("add")?;
+                        add.call::<_, Value>((This(cloned.clone()), v))?;
+                    }
+                }
+            }
+            return Ok(cloned.into_value());
+        }
+
+        if let Some(array) = obj.clone().into_array() {
+            let cloned = Array::new(self.ctx.clone())?;
+            self.seen.insert(value, cloned.clone().into_value());
+            for (i, item) in array.iter::<Value>().enumerate() {
+                cloned.set(i, self.clone(item?)?)?;
+            }
+            return Ok(cloned.into_value());
+        }
+
+        let cloned = Object::new(self.ctx.clone())?;
+        self.seen.insert(value, cloned.clone().into_value());
+        for prop in obj.props::<String, Value>() {
+            let (k, v) = prop?;
+            cloned.set(k, self.clone(v)?)?;
+        }
+        Ok(cloned.into_value())
+    }
+}
+
+/// 把回调放进微任务队列，在当前任务结束后执行
+fn queue_microtask<'js>(ctx: Ctx<'js>, callback: Function<'js>) -> rquickjs::Result<()> {
+    let (promise, resolve, _) = ctx.promise()?;
+    let then: Function = promise.get("then")?;
+    then.call::<_, Value>((This(promise), callback))?;
+    resolve.call::<_, ()>(())
+}
@@ -0,0 +1,310 @@
+//! `dino run --inspect` 的调试通道，实现 Chrome DevTools 协议（CDP）的一部分：
+//! DevTools 或 IDE 连接到 tenant 后，可以在运行中的 worker 里求值，并实时看到 handler 的 `print` 输出。
+//!
+//! 内嵌的 QuickJS 没有调试器接口，断点和单步执行的请求会返回错误
+
+use std::{net::SocketAddr, sync::OnceLock};
+
+use anyhow::Result;
+use axum::{
+    Json, Router,
+    extract::{
+        Path, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+    routing::get,
+};
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::broadcast};
+use tracing::{info, warn};
+
+use crate::{AppState, binding};
+
+/// 与 Node.js 相同的默认调试端口，IDE 无需额外配置即可发现
+pub const DEFAULT_INSPECT_ADDR: &str = "127.0.0.1:9229";
+/// CDP 中唯一的执行上下文
+const CONTEXT_ID: u64 = 1;
+/// 调试会话未及时读取时最多缓存的输出
+const CONSOLE_CAPACITY: usize = 256;
+
+/// 需要引擎支持的调试命令
+const UNSUPPORTED: &[&str] = &[
+    "Debugger.setBreakpoint",
+    "Debugger.setBreakpointByUrl",
+    "Debugger.setBreakpointsActive",
+    "Debugger.pause",
+    "Debugger.stepInto",
+    "Debugger.stepOver",
+    "Debugger.stepOut",
+    "Debugger.evaluateOnCallFrame",
+];
+
+/// handler 的输出，`(host, msg)`，只在有调试会话时创建
+static CONSOLE: OnceLock<broadcast::Sender<(String, String)>> = OnceLock::new();
+
+/// 转发 worker 的输出给调试会话，由 `print` 调用
+pub(crate) fn emit(msg: &str) {
+    if let Some(console) = CONSOLE.get()
+        && console.receiver_count() > 0
+        && let Some(host) = binding::caller()
+    {
+        let _ = console.send((host, msg.to_string()));
+    }
+}
+
+fn subscribe() -> broadcast::Receiver<(String, String)> {
+    CONSOLE
+        .get_or_init(|| broadcast::channel(CONSOLE_CAPACITY).0)
+        .subscribe()
+}
+
+/// 在 `addr` 上提供 DevTools 的发现接口和每个 tenant 的 WebSocket 调试会话
+pub(crate) async fn spawn(state: AppState, addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    info!("Debugger listening on ws://{addr}/<host>");
+    let app = Router::new()
+        .route("/json/version", get(version))
+        .route("/json", get(list))
+        .route("/json/list", get(list))
+        .route("/{host}", get(connect))
+        .with_state((state, addr));
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            warn!("Debugger stopped: {e}");
+        }
+    });
+    Ok(())
+}
+
+async fn version() -> Json<Value> {
+    Json(json!({
+        "Browser": format!("dino/{}", env!("CARGO_PKG_VERSION")),
+        "Protocol-Version": "1.3",
+    }))
+}
+
+/// 每个 tenant 是一个调试目标
+async fn list(State((state, addr)): State<(AppState, SocketAddr)>) -> Json<Value> {
+    let targets: Vec<_> = state
+        .tenants()
+        .into_iter()
+        .map(|t| {
+            json!({
+                "id": t.host,
+                "type": "node",
+                "title": t.host,
+                "description": "dino tenant",
+                "url": format!("dino://{}", t.host),
+                "webSocketDebuggerUrl": format!("ws://{addr}/{}", t.host),
+                "devtoolsFrontendUrl": format!(
+                    "devtools://devtools/bundled/js_app.html?experiments=true&v8only=true&ws={addr}/{}",
+                    t.host
+                ),
+            })
+        })
+        .collect();
+    Json(json!(targets))
+}
+
+async fn connect(
+    State((state, _)): State<(AppState, SocketAddr)>,
+    Path(host): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let host = host.to_ascii_lowercase();
+    ws.on_upgrade(move |socket| session(state, host, socket))
+}
+
+async fn session(state: AppState, host: String, mut socket: WebSocket) {
+    info!("Debugger attached to {host}");
+    let mut console = subscribe();
+    let mut enabled = false;
+    loop {
+        let replies = tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let (state, host) = (state.clone(), host.clone());
+                    // 求值要等 worker 空闲，不能占用 tokio 的线程
+                    let ret = tokio::task::spawn_blocking(move || state.handle_cdp(&host, &text));
+                    let Ok((replies, runtime)) = ret.await else { break };
+                    enabled |= runtime;
+                    replies
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            line = console.recv() => match line {
+                Ok((from, msg)) if enabled && from == host => vec![console_event(&msg)],
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        for reply in replies {
+            if socket
+                .send(Message::Text(reply.to_string().into()))
+                .await
+                .is_err()
+            {
+                info!("Debugger detached from {host}");
+                return;
+            }
+        }
+    }
+    info!("Debugger detached from {host}");
+}
+
+fn console_event(msg: &str) -> Value {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64() * 1000.0);
+    json!({
+        "method": "Runtime.consoleAPICalled",
+        "params": {
+            "type": "log",
+            "args": [{ "type": "string", "value": msg }],
+            "executionContextId": CONTEXT_ID,
+            "timestamp": timestamp,
+        },
+    })
+}
+
+impl AppState {
+    /// 处理一条 CDP 消息，返回要发送的回复和事件，以及是否开启了 `Runtime` 域
+    pub(crate) fn handle_cdp(&self, host: &str, msg: &str) -> (Vec<Value>, bool) {
+        let Ok(msg) = serde_json::from_str::<Value>(msg) else {
+            return (vec![], false);
+        };
+        let id = msg["id"].clone();
+        let method = msg["method"].as_str().unwrap_or_default();
+        let reply = |result: Value| json!({ "id": id, "result": result });
+        let error = |code: i64, message: String| json!({ "id": id, "error": { "code": code, "message": message } });
+        match method {
+            "Runtime.enable" => {
+                let created = json!({
+                    "method": "Runtime.executionContextCreated",
+                    "params": {
+                        "context": { "id": CONTEXT_ID, "origin": "", "name": host },
+                    },
+                });
+                (vec![reply(json!({})), created], true)
+            }
+            // DevTools 在输入时用它检查语法，不做检查
+            "Runtime.compileScript" => (vec![reply(json!({}))], false),
+            "Runtime.evaluate" => {
+                let expression = msg["params"]["expression"].as_str().unwrap_or_default();
+                let result = match self.eval(host, expression) {
+                    Ok(text) => json!({ "result": remote_object(&text) }),
+                    Err(e) => {
+                        let exception = json!({
+                            "type": "object",
+                            "subtype": "error",
+                            "description": format!("{e:#}"),
+                        });
+                        json!({
+                            "result": exception,
+                            "exceptionDetails": {
+                                "exceptionId": 1,
+                                "text": "Uncaught",
+                                "lineNumber": 0,
+                                "columnNumber": 0,
+                                "exception": exception,
+                            },
+                        })
+                    }
+                };
+                (vec![reply(result)], false)
+            }
+            "Debugger.enable" => (vec![reply(json!({ "debuggerId": host }))], false),
+            m if UNSUPPORTED.contains(&m) => {
+                let message = format!("{m} is not supported, QuickJS has no debugger interface");
+                (vec![error(-32000, message)], false)
+            }
+            m if m.ends_with(".enable")
+                || m.ends_with(".disable")
+                || m == "Runtime.runIfWaitingForDebugger"
+                || m.starts_with("Debugger.set") =>
+            {
+                (vec![reply(json!({}))], false)
+            }
+            m => (vec![error(-32601, format!("'{m}' wasn't found"))], false),
+        }
+    }
+}
+
+/// 把 `Engine::eval` 的结果转换为 CDP 的 RemoteObject
+fn remote_object(text: &str) -> Value {
+    match text {
+        "undefined" => return json!({ "type": "undefined" }),
+        "[Function]" => return json!({ "type": "function", "description": "function" }),
+        _ => {}
+    }
+    match serde_json::from_str::<Value>(text) {
+        Ok(Value::Null) => json!({ "type": "object", "subtype": "null", "value": null }),
+        Ok(v @ Value::Bool(_)) => json!({ "type": "boolean", "value": v }),
+        Ok(v @ Value::Number(_)) => json!({ "type": "number", "value": v, "description": text }),
+        Ok(v @ Value::String(_)) => json!({ "type": "string", "value": v }),
+        Ok(v) => json!({ "type": "object", "value": v, "description": text }),
+        Err(_) => json!({ "type": "string", "value": text }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{ProjectConfig, SwappableAppRouter};
+
+    #[test]
+    fn cdp_should_evaluate_in_worker() -> Result<()> {
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: test\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?;
+        let code = "(function(){ let hits = 0; async function hello() { hits++; return { status: 200, headers: {}, body: String(hits) }; } return { hello }; })();";
+        let routers = DashMap::new();
+        routers.insert(
+            "debug.test".to_string(),
+            SwappableAppRouter::try_new(code, config.routes)?,
+        );
+        let state = AppState::with_routers(routers);
+        let call = |msg: Value| state.handle_cdp("debug.test", &msg.to_string());
+
+        let (replies, enabled) = call(json!({ "id": 1, "method": "Runtime.enable" }));
+        assert!(enabled);
+        assert_eq!(replies[1]["params"]["context"]["name"], "debug.test");
+
+        // 求值在 worker 的全局环境中进行，状态在多次求值间保留
+        let evaluate = |id: u64, expression: &str| {
+            let msg = json!({ "id": id, "method": "Runtime.evaluate", "params": { "expression": expression } });
+            call(msg).0.remove(0)
+        };
+        evaluate(2, "globalThis.x = 40");
+        let reply = evaluate(3, "x + 2");
+        assert_eq!(reply["id"], 3);
+        assert_eq!(
+            reply["result"]["result"],
+            json!({ "type": "number", "value": 42, "description": "42" })
+        );
+        assert_eq!(
+            evaluate(4, "Object.keys(handlers)")["result"]["result"]["value"],
+            json!(["hello"])
+        );
+        let reply = evaluate(5, "throw new Error('boom')");
+        assert!(
+            reply["result"]["exceptionDetails"]["exception"]["description"]
+                .as_str()
+                .unwrap()
+                .contains("boom")
+        );
+
+        let (replies, _) = call(
+            json!({ "id": 6, "method": "Debugger.setBreakpointByUrl", "params": { "lineNumber": 1 } }),
+        );
+        assert_eq!(replies[0]["error"]["code"], -32000);
+        let (replies, _) = call(json!({ "id": 7, "method": "Debugger.enable" }));
+        assert_eq!(replies[0]["result"]["debuggerId"], "debug.test");
+        Ok(())
+    }
+}
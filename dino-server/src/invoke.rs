@@ -0,0 +1,84 @@
+use std::{collections::HashMap, time::Instant};
+
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    extract::Query,
+    http::{Method, Uri},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+
+use crate::{AppState, engine::Payload};
+
+/// 调用 handler 过程中产生的事件，以 NDJSON 的形式流式返回
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InvokeEvent {
+    Start {
+        handler: String,
+    },
+    /// handler 中 `print` 和 `console` 的输出
+    Log {
+        message: String,
+    },
+    Result {
+        status: u16,
+        headers: HashMap<String, String>,
+        body: Option<Payload>,
+        duration_ms: f64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+pub(crate) type LogSender = UnboundedSender<InvokeEvent>;
+
+impl AppState {
+    /// 调用 tenant 的路由，handler 的输出和结果通过 `events` 实时发送
+    pub fn invoke(
+        &self,
+        host: String,
+        method: Method,
+        uri: &Uri,
+        body: Bytes,
+        events: UnboundedSender<InvokeEvent>,
+    ) {
+        let start = Instant::now();
+        let ret = (|| {
+            let Query(query) = Query::<HashMap<String, String>>::try_from_uri(uri)?;
+            self.dispatch_with_log(host, method, uri, query, body, events.clone())
+        })();
+        let event = match ret {
+            Ok(resp) => InvokeEvent::Result {
+                status: resp.status,
+                headers: resp.headers,
+                body: resp.body,
+                duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+            },
+            Err(e) => InvokeEvent::Error {
+                message: format!("{e:#}"),
+            },
+        };
+        let _ = events.send(event);
+    }
+}
+
+/// 在阻塞线程中调用 handler，返回 NDJSON 流
+pub(crate) fn stream(state: AppState, host: String, method: Method, uri: Uri, body: Bytes) -> Body {
+    let (send, recv) = unbounded_channel();
+    tokio::task::spawn_blocking(move || state.invoke(host, method, &uri, body, send));
+    let stream = UnboundedReceiverStream::new(recv).map(|event| {
+        let mut line = serde_json::to_vec(&event)?;
+        line.push(b'\n');
+        Ok::<_, serde_json::Error>(line)
+    });
+    Body::from_stream(stream)
+}
+
+pub(crate) fn emit(log: &LogSender, message: String) -> Result<()> {
+    log.send(InvokeEvent::Log { message })?;
+    Ok(())
+}
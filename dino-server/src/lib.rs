@@ -1,111 +1,667 @@
+//! dino 的服务端。默认的 `server` feature 提供基于 tokio/axum 的 HTTP 服务器、
+//! worker 池和管理接口；关闭后只剩下配置、路由和 JS 引擎组成的核心，
+//! 不依赖 tokio，可以编译到 wasm32-wasi 嵌入其他宿主
+
+#[cfg(feature = "server")]
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex, OnceLock},
-    thread,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock, atomic::Ordering},
+    time::{Duration, Instant},
 };
 
-use anyhow::{Context, Result};
+#[cfg(feature = "server")]
+use anyhow::{Context, Result, bail};
+#[cfg(feature = "server")]
+use audit::short_hash;
+#[cfg(feature = "server")]
 use axum::{
     Router,
-    body::Bytes,
-    extract::{Query, State},
-    http::{Method, Response, Uri},
+    body::{Body, Bytes},
+    extract::{
+        FromRequest, Query, State,
+        ws::{WebSocketUpgrade, rejection::WebSocketUpgradeRejection},
+    },
+    http::{
+        HeaderMap, Method, Response, StatusCode, Uri,
+        header::{ACCEPT, CONTENT_LENGTH, COOKIE, LOCATION},
+    },
     response::IntoResponse,
-    routing::any,
+    routing::{any, get},
 };
+#[cfg(feature = "server")]
 use axum_extra::extract::Host;
-use crossbeam::channel::Sender;
+#[cfg(feature = "server")]
+use bodies::{BodyProblem, BodyStats};
+#[cfg(feature = "server")]
+use cache::ResponseCache;
+#[cfg(feature = "server")]
 use dashmap::DashMap;
-use engine::{JsWorker, Req, Resp};
+#[cfg(feature = "server")]
+use egress::Egress;
+#[cfg(feature = "server")]
+use engine::{Inspection, Payload, Req, Resp};
+#[cfg(feature = "server")]
 use error::AppError;
+#[cfg(feature = "server")]
+use invoke::LogSender;
+#[cfg(feature = "server")]
 use matchit::Match;
-use router::AppRouter;
+#[cfg(feature = "server")]
+use rooms::Rooms;
+#[cfg(feature = "server")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
 use tokio::net::TcpListener;
-use tracing::{error, info};
+#[cfg(feature = "server")]
+use tracing::{Span, error, info, instrument};
+#[cfg(feature = "server")]
+use versions::Versions;
 
+mod audit;
+mod capability;
 mod config;
+mod contract;
 pub mod engine;
-mod error;
+mod host;
+mod logging;
+mod replay;
 mod router;
+mod timing;
+
+#[cfg(feature = "server")]
+mod admin;
+#[cfg(feature = "server")]
+mod aliases;
+#[cfg(feature = "server")]
+mod binding;
+#[cfg(feature = "server")]
+mod bodies;
+#[cfg(feature = "build")]
+mod builder;
+#[cfg(feature = "server")]
+mod cache;
+#[cfg(feature = "server")]
+mod cgroup;
+#[cfg(feature = "server")]
+mod dev;
+#[cfg(feature = "server")]
+mod egress;
+#[cfg(feature = "server")]
+mod encoding;
+#[cfg(feature = "server")]
+mod error;
+#[cfg(feature = "server")]
+mod files;
+#[cfg(feature = "server")]
+pub mod fuzzing;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "server")]
+mod inspector;
+#[cfg(feature = "server")]
+mod invoke;
+#[cfg(feature = "server")]
+mod object;
+#[cfg(feature = "server")]
+mod pipe;
+#[cfg(feature = "server")]
+mod preflight;
+#[cfg(feature = "server")]
+mod previews;
+#[cfg(feature = "server")]
+mod reload;
+#[cfg(feature = "server")]
+mod rooms;
+#[cfg(feature = "server")]
+mod shutdown;
+#[cfg(feature = "server")]
+mod sse;
+#[cfg(feature = "server")]
+pub mod testing;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "server")]
+mod upload;
+#[cfg(feature = "server")]
+mod usage;
+#[cfg(feature = "server")]
+mod versions;
+#[cfg(feature = "server")]
+mod watchdog;
+#[cfg(feature = "server")]
+mod worker;
+
+pub use audit::{AuditAction, AuditEvent, AuditLog, AuditQuery};
+pub use capability::{Capabilities, Capability};
+pub use config::{
+    CONFIG_VERSION, ConfigIssue, CronJob, DeployConfig, Priority, ProjectConfig, ProjectRoutes,
+    RouteKind, RoutingOptions, TrailingSlash, UploadMode,
+};
+pub use host::{HOST_GLOBALS, HOST_MODULES, HostModule, host_modules, set_timezone};
+pub use logging::{LOG_ENV, LogConfig, LogFormat, LogSink};
+pub use replay::{Recorder, Replay, ReplayRecord};
+pub use router::{AppRouter, Endpoint, HandlerId, Handlers, RouteEntry, SwappableAppRouter};
+pub use timing::Timing;
 
-pub use config::ProjectConfig;
-pub use router::SwappableAppRouter;
+#[cfg(feature = "server")]
+pub use admin::{ADMIN_PREFIX, AdminConfig, ApiToken, Role};
+#[cfg(feature = "server")]
+pub use aliases::UnknownHost;
+#[cfg(feature = "server")]
+pub use binding::Bindings;
+#[cfg(feature = "build")]
+pub use builder::{SourceBuild, build_source};
+#[cfg(feature = "server")]
+pub use cache::DEFAULT_CACHE_BYTES;
+#[cfg(feature = "server")]
+pub use config::{ServerConfig, TenantSource};
+#[cfg(feature = "server")]
+pub use dev::{BuildFailure, DEV_STATUS_PATH, WatcherHealth};
+#[cfg(feature = "server")]
+pub use egress::EgressOptions;
+#[cfg(feature = "git")]
+pub use git::GitSource;
+#[cfg(feature = "server")]
+pub use inspector::DEFAULT_INSPECT_ADDR;
+#[cfg(feature = "server")]
+pub use invoke::InvokeEvent;
+#[cfg(feature = "server")]
+pub use object::ObjectStore;
+#[cfg(feature = "server")]
+pub use preflight::{Preflight, PreflightIssue};
+#[cfg(feature = "server")]
+pub use previews::{DEFAULT_PREVIEW_TTL, PREVIEW_SEPARATOR, Preview, parse_ttl};
+#[cfg(feature = "server")]
+pub use reload::ReloadOptions;
+#[cfg(feature = "server")]
+pub use shutdown::DEFAULT_SHUTDOWN_TIMEOUT;
+#[cfg(feature = "tls")]
+pub use tls::TlsOptions;
+#[cfg(feature = "server")]
+pub use usage::{DEFAULT_USAGE_INTERVAL, UsageLog, UsageQuery, UsageReport};
+#[cfg(feature = "server")]
+pub use versions::BundleVersion;
+#[cfg(feature = "server")]
+pub use worker::WorkerSettings;
+#[cfg(feature = "server")]
+use worker::{Queues, ScaleStats, WorkerHandle, WorkerPool};
 
+#[cfg(feature = "server")]
 #[derive(Clone, Debug)]
 pub struct AppState {
-    routers: DashMap<String, SwappableAppRouter>,
-    workers: Arc<Mutex<HashMap<String, Sender<WorkerMessage>>>>,
+    routers: Arc<DashMap<String, SwappableAppRouter>>,
+    // 每个 tenant 保留的版本，用于回滚
+    versions: Arc<DashMap<String, Versions>>,
+    // 预览环境，key 为预览环境的 host
+    previews: Arc<DashMap<String, Preview>>,
+    workers: Arc<Mutex<HashMap<String, WorkerHandle>>>,
+    // 每个 tenant 累计的扩缩容次数，worker 重启后保留
+    scale_stats: Arc<DashMap<String, Arc<ScaleStats>>>,
+    // 每个 tenant 收到的有问题的请求 body
+    body_stats: Arc<DashMap<String, Arc<BodyStats>>>,
+    // 每个 tenant 的对象线程，第一次调用对象时启动
+    objects: Arc<Mutex<HashMap<String, crossbeam::channel::Sender<object::ObjectMessage>>>>,
+    object_store: ObjectStore,
+    // 共享的出站 HTTP 客户端
+    egress: Egress,
+    // `caches` 保存的响应
+    cache: ResponseCache,
+    // 每个 tenant 的服务绑定
+    bindings: Arc<DashMap<String, Bindings>>,
+    // 每个 tenant 的路径匹配方式
+    routing: Arc<DashMap<String, RoutingOptions>>,
+    // 别名 host -> tenant 的 host
+    aliases: Arc<DashMap<String, String>>,
+    // 不属于任何 tenant 的 host 的响应
+    unknown_host: Option<UnknownHost>,
+    // WebSocket 连接和房间，worker 重启后保留
+    rooms: Rooms,
+    // 流式上传的文件保存在其中以 tenant 命名的子目录
+    upload_dir: PathBuf,
+    // `serveFile` 只能读取其中以 tenant 命名的子目录下的文件
+    files_dir: PathBuf,
+    // 从 git 部署的 tenant 的 bare 仓库目录，以及各 tenant 最近一次部署的仓库
+    #[cfg(feature = "git")]
+    git_dir: PathBuf,
+    #[cfg(feature = "git")]
+    git_sources: Arc<DashMap<String, GitSource>>,
+    // dev server 中各 tenant 最近一次失败的构建
+    build_errors: Arc<DashMap<String, BuildFailure>>,
+    // dev server 中各 tenant 文件监听任务的状态
+    watchers: Arc<DashMap<String, WatcherHealth>>,
+    // 每个 tenant 的 worker 线程设置
+    worker_settings: Arc<DashMap<String, WorkerSettings>>,
+    recorder: Option<Recorder>,
+    audit: Option<AuditLog>,
+    usage: Option<UsageLog>,
+    admin: Option<Arc<AdminConfig>>,
+    // 服务器配置文件和最近一次加载的内容，没有配置 reload 时为 None
+    server_config: Option<Arc<reload::ServerConfigState>>,
+    server_timing: bool,
+    check_contracts: bool,
 }
 
-#[derive(Clone)]
+/// `start_server_with` 的可选项
+#[cfg(feature = "server")]
+#[derive(Debug, Default, Clone)]
+pub struct ServerOptions {
+    /// 记录每个请求及其随机数种子和时间，供 `dino replay` 复现
+    pub recorder: Option<Recorder>,
+    /// 记录代码替换、配置变化和 worker 重启
+    pub audit: Option<AuditLog>,
+    /// 定期把各 tenant 的请求数、CPU 时间、出站流量和存储用量写入用量日志，供管理接口导出
+    pub usage: Option<UsageLog>,
+    /// 管理接口的 token 配置，为 None 时不提供管理接口
+    pub admin: Option<AdminConfig>,
+    /// 在该地址上单独提供管理接口，为 None 时管理接口和 tenant 共用服务端口
+    pub admin_addr: Option<std::net::SocketAddr>,
+    /// 收到 SIGHUP 或控制 socket 命令时按服务器配置重新加载 tenant
+    pub reload: Option<ReloadOptions>,
+    /// 在响应中加入 `Server-Timing` 头，用于 dev 模式下分析耗时
+    pub server_timing: bool,
+    /// dev server 模式，在 `DEV_STATUS_PATH` 提供状态接口，显示最近一次失败的构建和文件监听的状态
+    pub dev: bool,
+    /// 按路由声明的 `response` schema 校验 handler 的返回值，不符合时返回 500
+    pub check_contracts: bool,
+    /// 按 host 设置 worker 线程的 CPU 绑定和优先级
+    pub workers: HashMap<String, WorkerSettings>,
+    /// 对象存储，为 None 时对象的状态只保存在内存中
+    pub object_store: Option<ObjectStore>,
+    /// `fetch` 的并发、超时和熔断设置，为 None 时使用默认值
+    pub egress: Option<EgressOptions>,
+    /// 每个 tenant 的 `caches` 可以使用的字节数，为 None 时使用 `DEFAULT_CACHE_BYTES`
+    pub cache_bytes: Option<usize>,
+    /// 流式上传的文件保存目录，为 None 时使用系统临时目录下的 `dino-uploads`
+    pub upload_dir: Option<PathBuf>,
+    /// `serveFile` 读取文件的目录，每个 tenant 只能读取以其 host 命名的子目录，
+    /// 为 None 时使用系统临时目录下的 `dino-files`
+    pub files_dir: Option<PathBuf>,
+    /// 在该地址上提供 Chrome DevTools 协议的调试通道，见 `inspector`
+    pub inspect: Option<std::net::SocketAddr>,
+    /// 不属于任何 tenant（也不是别名）的 host 的响应，为 None 时按找不到路由处理
+    pub unknown_host: Option<UnknownHost>,
+    /// 收到退出信号后等待处理中的请求和 worker 队列的时间，为 None 时使用 `DEFAULT_SHUTDOWN_TIMEOUT`
+    pub shutdown_timeout: Option<Duration>,
+    /// 除 SIGTERM 和 Ctrl-C 外，通知后也会优雅退出，`start_server` 处理完请求后返回
+    pub shutdown: Option<Arc<tokio::sync::Notify>>,
+    /// 开启 TLS 模式，为 tenant 的 host 自动申请证书并提供 HTTPS
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsOptions>,
+    /// 从 git 部署时拉取的仓库保存目录，为 None 时使用系统临时目录下的 `dino-git`
+    #[cfg(feature = "git")]
+    pub git_dir: Option<PathBuf>,
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Clone)]
 pub struct TenantRouter {
     host: String,
     router: SwappableAppRouter,
+    bindings: Bindings,
+    timezone: Option<chrono_tz::Tz>,
+    memory_limit_mb: Option<usize>,
+    routing: RoutingOptions,
+    aliases: Vec<String>,
+}
+
+/// tenant 当前的部署状态
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantStatus {
+    pub host: String,
+    /// 代码的 hash
+    pub code: String,
+    /// 路由配置的 hash
+    pub routes: String,
+    /// 当前的版本号
+    #[serde(default)]
+    pub version: u64,
+    /// 可以回滚的历史版本数量
+    pub history: usize,
+    pub worker_running: bool,
+    /// 当前的 worker 线程数
+    #[serde(default)]
+    pub workers: usize,
+    /// 累计扩容次数
+    #[serde(default)]
+    pub scale_ups: u64,
+    /// 累计因空闲而回收的次数
+    #[serde(default)]
+    pub scale_downs: u64,
+    /// 累计被 watchdog 中断的卡住的 worker 数
+    #[serde(default)]
+    pub stalls: u64,
+    /// `fetch` 累计发送和接收的 body 字节数
+    #[serde(default)]
+    pub egress_sent: u64,
+    #[serde(default)]
+    pub egress_received: u64,
+    /// 累计因超过大小上限被拒绝的请求 body 数
+    #[serde(default)]
+    pub body_too_large: u64,
+    /// 累计声明为文本或 JSON 但不是 UTF-8 的请求 body 数
+    #[serde(default)]
+    pub body_invalid_utf8: u64,
+    /// 累计声明为 JSON 但无法解析的请求 body 数
+    #[serde(default)]
+    pub body_invalid_json: u64,
+    /// 以上有问题的 body 的累计字节数
+    #[serde(default)]
+    pub body_problem_bytes: u64,
 }
 
+/// 等待 worker 返回运行时状态的时间
+#[cfg(feature = "server")]
+const INSPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[cfg(feature = "server")]
 static CURRENT_STATE: OnceLock<AppState> = OnceLock::new();
 
 // 添加一个特殊的消息类型用于终止 worker
+#[cfg(feature = "server")]
 #[derive(Debug)]
 enum WorkerMessage {
     Request(Box<Request>),
+    Stream(Box<StreamRequest>),
+    Pipe(Box<PipeRequest>),
+    /// 查看 worker 的运行时状态
+    Inspect(oneshot::Sender<Result<Inspection>>),
+    /// 在 worker 的全局环境中求值，用于调试
+    Eval(String, oneshot::Sender<Result<String>>),
     Shutdown,
 }
 
+/// SSE 路由的请求，worker 迭代 handler 返回的事件并逐个发送
+#[cfg(feature = "server")]
+#[derive(Debug)]
+struct StreamRequest {
+    req: Req,
+    handler: Arc<str>,
+    span: Span,
+    // 事件的 JSON，接收方关闭表示客户端已断开
+    send: tokio::sync::mpsc::UnboundedSender<String>,
+}
+
+/// 流式路由的请求，body 的块从 `body` 读取，响应头和 body 的块发送到 `head` 和 `chunks`
+#[cfg(feature = "server")]
+#[derive(Debug)]
+struct PipeRequest {
+    req: Req,
+    handler: Arc<str>,
+    span: Span,
+    body: tokio::sync::mpsc::Receiver<Result<Bytes, String>>,
+    head: oneshot::Sender<Resp>,
+    // 接收方关闭表示客户端已断开
+    chunks: tokio::sync::mpsc::Sender<Bytes>,
+}
+
+/// worker 对请求的回复，handler 出错时一般直接丢弃，只有需要区分状态码的错误才回复 `Err`
+#[cfg(feature = "server")]
+type Reply = Result<(Resp, Timing), AppError>;
+
+#[cfg(feature = "server")]
 #[derive(Debug)]
 struct Request {
     req: Req,
-    handler: String,
-    send: oneshot::Sender<Resp>,
+    // 路由表中驻留的名称，复制时只增加引用计数
+    handler: Arc<str>,
+    replay: Option<Replay>,
+    queued_at: Instant,
+    // 发送请求时的 span，worker 中的日志挂在该请求下
+    span: Span,
+    // 调用方需要实时接收 handler 输出时设置
+    log: Option<LogSender>,
+    send: oneshot::Sender<Reply>,
 }
 
+#[cfg(feature = "server")]
 impl WorkerMessage {
-    pub fn new_request(req: Req, handler: String) -> (Self, oneshot::Receiver<Resp>) {
+    pub fn new_request(
+        req: Req,
+        handler: Arc<str>,
+        replay: Option<Replay>,
+        log: Option<LogSender>,
+    ) -> (Self, oneshot::Receiver<Reply>) {
         let (send, recv) = oneshot::channel();
-        (
-            Self::Request(Box::new(Request { req, handler, send })),
-            recv,
-        )
+        let req = Request {
+            req,
+            handler,
+            replay,
+            queued_at: Instant::now(),
+            span: Span::current(),
+            log,
+            send,
+        };
+        (Self::Request(Box::new(req)), recv)
+    }
+
+    pub fn new_stream(
+        req: Req,
+        handler: Arc<str>,
+    ) -> (Self, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+        let req = StreamRequest {
+            req,
+            handler,
+            span: Span::current(),
+            send,
+        };
+        (Self::Stream(Box::new(req)), recv)
     }
 }
 
+#[cfg(feature = "server")]
 pub async fn start_server(port: u16, routers: Vec<TenantRouter>) -> Result<()> {
+    start_server_with(port, routers, ServerOptions::default()).await
+}
+
+#[cfg(feature = "server")]
+pub async fn start_server_with(
+    port: u16,
+    routers: Vec<TenantRouter>,
+    options: ServerOptions,
+) -> Result<()> {
     let addr = format!("0.0.0.0:{port}");
     let listener = TcpListener::bind(addr).await?;
+    start_server_on(listener, routers, options).await
+}
+
+/// 在已经绑定的 `listener` 上启动服务器，调用方可以先绑定端口 0 再从中取得实际端口
+#[cfg(feature = "server")]
+pub async fn start_server_on(
+    listener: TcpListener,
+    routers: Vec<TenantRouter>,
+    options: ServerOptions,
+) -> Result<()> {
     let map = DashMap::new();
+    let bindings = DashMap::new();
+    let routing = DashMap::new();
+    let mut aliases = vec![];
+    let mut workers = options.workers;
 
     for router in routers {
+        if let Some(tz) = router.timezone {
+            workers.entry(router.host.clone()).or_default().timezone = Some(tz);
+        }
+        if let Some(limit) = router.memory_limit_mb {
+            workers
+                .entry(router.host.clone())
+                .or_default()
+                .memory_limit_mb = limit;
+        }
+        bindings.insert(router.host.clone(), router.bindings);
+        routing.insert(router.host.clone(), router.routing);
+        aliases.push((router.host.clone(), router.aliases));
         map.insert(router.host, router.router);
     }
 
     info!("Listening on: {}", listener.local_addr()?);
-    let state = AppState::new(map);
-    let app = Router::new()
+    let mut state = AppState::with_worker_settings(map, workers);
+    state.recorder = options.recorder;
+    state.audit = options.audit;
+    state.usage = options.usage;
+    state.admin = options.admin.map(Arc::new);
+    state.server_timing = options.server_timing;
+    state.check_contracts = options.check_contracts;
+    state.bindings = Arc::new(bindings);
+    state.routing = Arc::new(routing);
+    state.unknown_host = options.unknown_host;
+    for (host, aliases) in aliases {
+        state.set_aliases(&host, &aliases)?;
+    }
+    state.object_store = options.object_store.unwrap_or_default();
+    if let Some(egress) = options.egress {
+        state.egress = Egress::new(egress);
+    }
+    if let Some(bytes) = options.cache_bytes {
+        state.cache = ResponseCache::new(bytes);
+    }
+    if let Some(dir) = options.upload_dir {
+        state.upload_dir = dir;
+    }
+    if let Some(dir) = options.files_dir {
+        state.files_dir = dir;
+    }
+    #[cfg(feature = "git")]
+    if let Some(dir) = options.git_dir {
+        state.git_dir = dir;
+    }
+    if let Some(reload) = &options.reload {
+        let config = reload::ServerConfigState::new(reload.server_config.clone());
+        state.server_config = Some(Arc::new(config));
+    }
+    CURRENT_STATE.set(state.clone()).unwrap();
+    if let Some(reload) = options.reload {
+        reload::spawn(state.clone(), reload)?;
+    }
+    if let Some(addr) = options.inspect {
+        inspector::spawn(state.clone(), addr).await?;
+    }
+    previews::spawn_cleanup(state.clone());
+    watchdog::spawn(state.clone());
+    usage::spawn(state.clone());
+    let mut app = Router::new();
+    let admin = match options.admin_addr {
+        Some(addr) => Some(admin::bind(state.clone(), addr).await?),
+        None => {
+            app = app.nest(ADMIN_PREFIX, admin::router());
+            None
+        }
+    };
+    if options.dev {
+        app = app.route(DEV_STATUS_PATH, get(dev::status));
+    }
+    let app = app
         .route("/{*path}", any(handler))
-        .with_state(state);
-    axum::serve(listener, app.into_make_service()).await?;
-
-    Ok(())
+        .with_state(state.clone());
+    #[cfg(feature = "tls")]
+    if let Some(tls) = options.tls {
+        tls::spawn(state.clone(), app.clone(), tls).await?;
+    }
+    let timeout = options
+        .shutdown_timeout
+        .unwrap_or(shutdown::DEFAULT_SHUTDOWN_TIMEOUT);
+    shutdown::serve(listener, app, admin, state, options.shutdown, timeout).await
 }
 
+#[cfg(feature = "server")]
+#[instrument(
+    name = "request",
+    skip_all,
+    fields(%method, host = %host, path = %uri.path(), request_id = %request_id(&headers))
+)]
 async fn handler(
     State(state): State<AppState>,
-    Query(query): Query<HashMap<String, String>>,
     method: Method,
     Host(mut host): Host,
     uri: Uri,
-    body: Bytes,
-) -> Result<impl IntoResponse, AppError> {
-    let _ = host.split_off(host.find(':').unwrap_or(host.len()));
-    let router = get_router(host.clone(), &state)?;
-    let matched = router.match_it(method.clone(), uri.path())?;
-    let req = assemble_req(query, &matched, method, &uri, body)?;
-    let handler = matched.value;
-    let resp = state.send(host, handler.to_string(), req)?;
+    headers: HeaderMap,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+    request: axum::extract::Request,
+) -> Result<Response<Body>, AppError> {
+    let port = host.split_off(host.find(':').unwrap_or(host.len()));
+    // host 不区分大小写，tenant 和别名都以小写保存
+    host.make_ascii_lowercase();
+    if let Some(resp) = state.check_host(&host, &port, &uri) {
+        return Ok(resp);
+    }
+    let uri = state.accept_version(&host, &method, uri, &headers);
+    let (uri, redirect) = state.canonical_uri(&host, &method, uri);
+    if redirect {
+        let mut resp = Response::new(Body::empty());
+        *resp.status_mut() = StatusCode::PERMANENT_REDIRECT;
+        if let Ok(location) = uri.to_string().parse() {
+            resp.headers_mut().insert(LOCATION, location);
+        }
+        return Ok(resp);
+    }
+    let Query(query) = Query::<HashMap<String, String>>::try_from_uri(&uri)
+        .map_err(|e| AppError::BadRequest(e.into()))?;
+    if let Ok(ws) = ws
+        && let Some(resp) = state.upgrade_websocket(&host, &uri, &query, &headers, ws)?
+    {
+        return Ok(resp);
+    }
+    if sse::accepts_event_stream(&headers)
+        && let Some(resp) = state.stream_events(&host, &uri, &query, &headers)?
+    {
+        return Ok(resp);
+    }
+    if state.pipe_route(&host, &method, &uri) {
+        let body = request.into_body();
+        return state.pipe(&host, method, &uri, query, &headers, body).await;
+    }
+    if state.streaming_upload(&host, &method, &uri) {
+        let body = request.into_body();
+        let root = state.files_dir.join(&host);
+        let resp = state
+            .upload(host, method, &uri, query, &headers, body)
+            .await?;
+        return Ok(files::into_response(resp, &headers, &root).await?);
+    }
+    let body = match Bytes::from_request(request, &state).await {
+        Ok(body) => body,
+        Err(rejection) => {
+            if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                let len = headers
+                    .get(CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok()?.parse().ok())
+                    .unwrap_or_default();
+                state.record_body(&host, BodyProblem::TooLarge, len, b"");
+            }
+            return Ok(rejection.into_response());
+        }
+    };
+    // handler 无法自己解压，压缩的 body 在这里解压后再交给 JS
+    let body = match encoding::decode_body(&headers, body.clone(), encoding::MAX_DECODED_BODY) {
+        Ok(body) => body,
+        Err(e) => {
+            if matches!(e, AppError::PayloadTooLarge(_)) {
+                let len = body.len() as u64;
+                state.record_body(&host, BodyProblem::TooLarge, len, b"");
+            }
+            return Err(e);
+        }
+    };
+    if let Some(problem) = bodies::check_body(&headers, &body) {
+        state.record_body(&host, problem, body.len() as u64, &body);
+    }
+    let root = state.files_dir.join(&host);
+    let (resp, mut timing) = state.dispatch_timed(host, method, &uri, query, &headers, body)?;
 
-    Ok(Response::from(resp))
+    let start = Instant::now();
+    let mut resp = files::into_response(resp, &headers, &root).await?;
+    timing.serialize += start.elapsed();
+    if state.server_timing
+        && let Ok(value) = timing.header_value().parse()
+    {
+        resp.headers_mut().insert("server-timing", value);
+    }
+    Ok(resp)
 }
 
+#[cfg(feature = "server")]
 fn get_router(host: String, state: &AppState) -> Result<AppRouter> {
     let router = state
         .routers
@@ -115,11 +671,65 @@ fn get_router(host: String, state: &AppState) -> Result<AppRouter> {
     Ok(router)
 }
 
+/// SSE、WebSocket 和流式路由只接受对应的请求
+#[cfg(feature = "server")]
+fn ensure_http(matched: &Match<Endpoint>, uri: &Uri) -> Result<()> {
+    match matched.value.kind {
+        RouteKind::Http => Ok(()),
+        RouteKind::Sse => bail!(
+            "route {} only accepts event streams (Accept: text/event-stream)",
+            uri.path()
+        ),
+        RouteKind::WebSocket => bail!("route {} only accepts WebSocket connections", uri.path()),
+        RouteKind::Stream => bail!("route {} streams its body over HTTP only", uri.path()),
+    }
+}
+
+/// 替换 `uri` 的路径，保留 query
+#[cfg(feature = "server")]
+fn with_path(uri: &Uri, path: String) -> String {
+    match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    }
+}
+
+/// 请求 id，使用客户端或网关传入的 `x-request-id`，没有时生成一个
+#[cfg(feature = "server")]
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map_or_else(|| uuid::Uuid::new_v4().simple().to_string(), str::to_string)
+}
+
+/// 转换请求头，名字为小写；同名的多个值按 HTTP 的规则以 `, ` 合并，Cookie 以 `; ` 合并
+#[cfg(feature = "server")]
+fn header_map(headers: &HeaderMap) -> HashMap<String, String> {
+    let mut ret: HashMap<String, String> = HashMap::new();
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        match ret.get_mut(name.as_str()) {
+            Some(prev) => {
+                prev.push_str(if name == COOKIE { "; " } else { ", " });
+                prev.push_str(&value);
+            }
+            None => {
+                ret.insert(name.to_string(), value.into_owned());
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(feature = "server")]
 fn assemble_req(
     query: HashMap<String, String>,
-    matched: &Match<&str>,
+    matched: &Match<Endpoint>,
     method: Method,
     uri: &Uri,
+    headers: &HeaderMap,
     body: Bytes,
 ) -> Result<Req> {
     let params: HashMap<String, String> = matched
@@ -127,15 +737,12 @@ fn assemble_req(
         .iter()
         .map(|(k, v)| (k.to_string(), v.to_string()))
         .collect();
-    let body = if body.is_empty() {
-        None
-    } else {
-        Some(String::from_utf8(body.to_vec()).context("Failed to convert body to string")?)
-    };
+    // 非 UTF-8 的 body（如上传的图片）以 `Uint8Array` 传给 handler
+    let body = (!body.is_empty()).then(|| Payload::from(body.to_vec()));
     let req = Req::builder()
         .method(method.to_string())
         .url(uri.to_string())
-        .headers(HashMap::new())
+        .headers(header_map(headers))
         .query(query)
         .params(params)
         .body(body)
@@ -143,20 +750,79 @@ fn assemble_req(
     Ok(req)
 }
 
+#[cfg(feature = "server")]
 impl AppState {
     pub fn new(routers: DashMap<String, SwappableAppRouter>) -> Self {
+        let state = Self::with_routers(routers);
+        CURRENT_STATE.set(state.clone()).unwrap();
+        state
+    }
+
+    /// 创建 state 并启动 worker，但不注册为全局 state
+    pub(crate) fn with_routers(routers: DashMap<String, SwappableAppRouter>) -> Self {
+        Self::with_worker_settings(routers, HashMap::new())
+    }
+
+    pub(crate) fn with_worker_settings(
+        routers: DashMap<String, SwappableAppRouter>,
+        settings: HashMap<String, WorkerSettings>,
+    ) -> Self {
         let workers = Arc::new(Mutex::new(HashMap::new()));
+        let scale_stats = DashMap::new();
         for item in &routers {
-            let (send, recv) = crossbeam::channel::unbounded::<WorkerMessage>();
             let code = item.value().load().code;
-            thread::Builder::new()
-                .name(format!("worker-{}", item.key()))
-                .spawn(move || jsworker_execute(code, recv))
-                .unwrap();
-            workers.lock().unwrap().insert(item.key().to_string(), send);
+            let worker_settings = settings.get(item.key()).cloned().unwrap_or_default();
+            let stats = Arc::new(ScaleStats::default());
+            let handle =
+                WorkerPool::spawn(item.key(), code, worker_settings, stats.clone()).unwrap();
+            workers
+                .lock()
+                .unwrap()
+                .insert(item.key().to_string(), handle);
+            scale_stats.insert(item.key().to_string(), stats);
+        }
+        let state = Self {
+            routers: Arc::new(routers),
+            versions: Arc::new(DashMap::new()),
+            previews: Arc::new(DashMap::new()),
+            workers,
+            scale_stats: Arc::new(scale_stats),
+            body_stats: Arc::new(DashMap::new()),
+            bindings: Arc::new(DashMap::new()),
+            routing: Arc::new(DashMap::new()),
+            aliases: Arc::new(DashMap::new()),
+            unknown_host: None,
+            objects: Arc::new(Mutex::new(HashMap::new())),
+            object_store: ObjectStore::default(),
+            egress: Egress::default(),
+            cache: ResponseCache::default(),
+            rooms: Rooms::default(),
+            upload_dir: std::env::temp_dir().join("dino-uploads"),
+            files_dir: std::env::temp_dir().join("dino-files"),
+            #[cfg(feature = "git")]
+            git_dir: std::env::temp_dir().join("dino-git"),
+            #[cfg(feature = "git")]
+            git_sources: Arc::new(DashMap::new()),
+            build_errors: Arc::new(DashMap::new()),
+            watchers: Arc::new(DashMap::new()),
+            worker_settings: Arc::new(settings.into_iter().collect()),
+            recorder: None,
+            audit: None,
+            usage: None,
+            admin: None,
+            server_config: None,
+            server_timing: false,
+            check_contracts: false,
+        };
+        // 启动时加载的代码为各 tenant 的版本 1
+        for host in state
+            .routers
+            .iter()
+            .map(|r| r.key().clone())
+            .collect::<Vec<_>>()
+        {
+            state.record_version(&host, "dino-server").unwrap();
         }
-        let state = Self { routers, workers };
-        CURRENT_STATE.set(state.clone()).unwrap();
         state
     }
 
@@ -164,7 +830,182 @@ impl AppState {
         CURRENT_STATE.get()
     }
 
+    /// 替换 tenant 的代码和路由并重启 worker，变更会记录到审计日志
+    pub fn swap(
+        &self,
+        host: &str,
+        code: impl Into<String>,
+        routes: ProjectRoutes,
+        actor: &str,
+    ) -> Result<()> {
+        self.swap_inner(host, code, routes, None, actor)
+    }
+
+    /// 以项目配置替换 tenant 的代码和路由，替换成功后才应用配置中的其他设置，
+    /// 代码或路由有误时 tenant 的设置保持不变
+    pub fn swap_with_config(
+        &self,
+        host: &str,
+        code: impl Into<String>,
+        config: &ProjectConfig,
+        actor: &str,
+    ) -> Result<()> {
+        self.swap_inner(host, code, config.routes.clone(), Some(config), actor)
+    }
+
+    fn swap_inner(
+        &self,
+        host: &str,
+        code: impl Into<String>,
+        routes: ProjectRoutes,
+        config: Option<&ProjectConfig>,
+        actor: &str,
+    ) -> Result<()> {
+        let host = &host.to_ascii_lowercase();
+        let router = self.routers.get(host).context("Router not found")?.clone();
+        let old = router.load();
+        router.swap(code, routes)?;
+        if let Some(config) = config {
+            self.apply_config(host, config);
+        }
+        let new = router.load();
+        self.record_version(host, actor)?;
+
+        let (old_code, new_code) = (short_hash(&old.code), short_hash(&new.code));
+        if old_code != new_code {
+            let event = AuditEvent::new(host, actor, AuditAction::CodeSwap)
+                .with_change(Some(old_code), Some(new_code));
+            self.audit(event);
+        }
+        if old.routes_hash != new.routes_hash {
+            let event = AuditEvent::new(host, actor, AuditAction::ConfigChange)
+                .with_change(Some(old.routes_hash), Some(new.routes_hash));
+            self.audit(event);
+        }
+        self.restart_worker(host, actor)
+    }
+
+    /// 添加新的 tenant 并启动 worker
+    pub fn add_tenant(
+        &self,
+        host: &str,
+        code: impl Into<String>,
+        routes: ProjectRoutes,
+        actor: &str,
+    ) -> Result<()> {
+        self.add_tenant_inner(host, code, routes, None, actor)
+    }
+
+    /// 以项目配置添加新的 tenant，tenant 注册成功后才应用配置中的其他设置
+    pub fn add_tenant_with_config(
+        &self,
+        host: &str,
+        code: impl Into<String>,
+        config: &ProjectConfig,
+        actor: &str,
+    ) -> Result<()> {
+        self.add_tenant_inner(host, code, config.routes.clone(), Some(config), actor)
+    }
+
+    fn add_tenant_inner(
+        &self,
+        host: &str,
+        code: impl Into<String>,
+        routes: ProjectRoutes,
+        config: Option<&ProjectConfig>,
+        actor: &str,
+    ) -> Result<()> {
+        let host = &host.to_ascii_lowercase();
+        if self.routers.contains_key(host) {
+            anyhow::bail!("Tenant already exists: {host}");
+        }
+        let code = code.into();
+        let event = AuditEvent::new(host, actor, AuditAction::Admin)
+            .with_change(None, Some(short_hash(&code)))
+            .with_detail("add tenant");
+        let router = SwappableAppRouter::try_new(code, routes)?;
+        self.routers.insert(host.to_string(), router);
+        if let Some(config) = config {
+            self.apply_config(host, config);
+        }
+        self.record_version(host, actor)?;
+        self.audit(event);
+        self.restart_worker(host, actor)
+    }
+
+    /// 删除 tenant 并停止其 worker
+    pub fn remove_tenant(&self, host: &str, actor: &str) -> Result<()> {
+        let host = &host.to_ascii_lowercase();
+        let (_, router) = self
+            .routers
+            .remove(host)
+            .with_context(|| format!("Tenant not found: {host}"))?;
+        self.versions.remove(host);
+        self.remove_previews_of(host, actor);
+        self.bindings.remove(host);
+        self.routing.remove(host);
+        self.aliases.retain(|_, canonical| canonical != host);
+        self.stop_objects(host);
+        self.cache.clear(host);
+        if let Some(handle) = self.workers.lock().unwrap().remove(host) {
+            handle.shutdown();
+        }
+        self.scale_stats.remove(host);
+        self.body_stats.remove(host);
+        // 之后以同样的 host 添加的 tenant 不继承这些设置
+        self.worker_settings.remove(host);
+        self.build_errors.remove(host);
+        self.watchers.remove(host);
+        #[cfg(feature = "git")]
+        self.git_sources.remove(host);
+        let event = AuditEvent::new(host, actor, AuditAction::Admin)
+            .with_change(Some(short_hash(&router.load().code)), None)
+            .with_detail("remove tenant");
+        self.audit(event);
+        Ok(())
+    }
+
+    /// 返回所有 tenant 的状态，按 host 排序
+    pub fn tenants(&self) -> Vec<TenantStatus> {
+        let workers = self.workers.lock().unwrap();
+        let mut tenants: Vec<_> = self
+            .routers
+            .iter()
+            .map(|item| {
+                let router = item.value().load();
+                let stats = self.scale_stats(item.key());
+                let versions = self.versions.get(item.key());
+                let egress = self.egress.stats(item.key());
+                let bodies = self.body_stats(item.key());
+                TenantStatus {
+                    host: item.key().clone(),
+                    code: short_hash(&router.code),
+                    routes: router.routes_hash,
+                    version: versions.as_ref().map_or(0, |v| v.current()),
+                    history: versions.as_ref().map_or(0, |v| v.history()),
+                    worker_running: workers.contains_key(item.key()),
+                    workers: workers.get(item.key()).map_or(0, |h| h.pool.size()),
+                    scale_ups: stats.scale_ups.load(Ordering::Relaxed),
+                    scale_downs: stats.scale_downs.load(Ordering::Relaxed),
+                    stalls: stats.stalls.load(Ordering::Relaxed),
+                    egress_sent: egress.sent.load(Ordering::Relaxed),
+                    egress_received: egress.received.load(Ordering::Relaxed),
+                    body_too_large: bodies.too_large.load(Ordering::Relaxed),
+                    body_invalid_utf8: bodies.invalid_utf8.load(Ordering::Relaxed),
+                    body_invalid_json: bodies.invalid_json.load(Ordering::Relaxed),
+                    body_problem_bytes: bodies.bytes.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        tenants.sort_by(|a, b| a.host.cmp(&b.host));
+        tenants
+    }
+
     pub fn update_worker(&self, host: &str) -> Result<()> {
+        self.restart_worker(host, "dino-server")
+    }
+
+    pub(crate) fn restart_worker(&self, host: &str, actor: &str) -> Result<()> {
         let mut workers = self.workers.lock().unwrap();
 
         // 获取最新的code
@@ -174,59 +1015,435 @@ impl AppState {
             .context("Router not found")?
             .load()
             .code;
+        let event = AuditEvent::new(host, actor, AuditAction::WorkerRestart)
+            .with_detail(format!("code {}", short_hash(&code)));
 
-        let (new_send, new_recv) = crossbeam::channel::unbounded();
+        self.stop_objects(host);
         // 启动新 worker 线程
-        thread::Builder::new()
-            .name(format!("worker-{}", host))
-            .spawn(move || jsworker_execute(code, new_recv))?;
+        let stats = self.scale_stats(host);
+        let handle = WorkerPool::spawn(host, code, self.worker_settings(host), stats)?;
 
         // 更新 worker 映射
-        let old_sender = workers.insert(host.to_string(), new_send);
+        let old = workers.insert(host.to_string(), handle);
 
         // 关闭旧 worker（如果有）
-        if let Some(old_sender) = old_sender {
-            let _ = old_sender.send(WorkerMessage::Shutdown);
+        if let Some(old) = old {
+            old.shutdown();
         }
 
         info!("Worker updated successfully for host: {}", host);
+        drop(workers);
+        self.audit(event);
         Ok(())
     }
 
-    pub fn send(&self, host: String, handler: String, req: Req) -> Result<Resp> {
-        let workers = self.workers.lock().unwrap();
+    /// 设置 tenant 的 worker 线程参数，下次重启 worker 时生效
+    pub fn set_worker_settings(&self, host: &str, settings: WorkerSettings) {
+        self.worker_settings.insert(host.to_string(), settings);
+    }
 
-        let send = workers.get(&host).context("Worker not found")?;
-        let (msg, recv) = WorkerMessage::new_request(req, handler);
-        if let Err(e) = send.send(msg) {
-            error!("Send to jsworker error: {}", e);
+    /// 应用项目配置中除代码和路由之外的设置：服务绑定、路径匹配方式和时区，
+    /// 时区在下次重启 worker 时生效
+    pub fn apply_config(&self, host: &str, config: &ProjectConfig) {
+        self.set_bindings(host, Bindings::from_config(config));
+        self.routing.insert(host.to_string(), config.routing);
+        if let Some(tz) = config.timezone {
+            let mut settings = self.worker_settings.entry(host.to_string()).or_default();
+            settings.timezone = Some(tz);
+        }
+        if let Some(limit) = config.memory_limit_mb {
+            let mut settings = self.worker_settings.entry(host.to_string()).or_default();
+            settings.memory_limit_mb = limit;
+        }
+    }
+
+    fn scale_stats(&self, host: &str) -> Arc<ScaleStats> {
+        self.scale_stats
+            .entry(host.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn worker_settings(&self, host: &str) -> WorkerSettings {
+        self.worker_settings
+            .get(host)
+            .map(|s| s.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn audit(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit
+            && let Err(e) = audit.append(&event)
+        {
+            error!("Write audit log error: {}", e);
+        }
+    }
+
+    /// `Accept` 头选择了 API 版本时，把请求改写为该版本的路径
+    fn accept_version(&self, host: &str, method: &Method, uri: Uri, headers: &HeaderMap) -> Uri {
+        let Some(accept) = headers.get(ACCEPT).and_then(|v| v.to_str().ok()) else {
+            return uri;
+        };
+        let Some(router) = self.routers.get(host) else {
+            return uri;
+        };
+        let Some(path) = router
+            .routes
+            .load()
+            .accept_version(method, uri.path(), accept)
+        else {
+            return uri;
+        };
+        with_path(&uri, path).parse().unwrap_or(uri)
+    }
+
+    /// 按 tenant 的 `routing` 设置把只差结尾的 `/` 或大小写的请求改写为声明的路径，
+    /// 结尾的 `/` 设置为 `redirect` 时同时返回 true，由调用方重定向到改写后的路径
+    fn canonical_uri(&self, host: &str, method: &Method, uri: Uri) -> (Uri, bool) {
+        let Some(options) = self.routing.get(host).map(|o| *o) else {
+            return (uri, false);
+        };
+        let Some(router) = self.routers.get(host) else {
+            return (uri, false);
+        };
+        let Some(path) = router
+            .routes
+            .load()
+            .canonical_path(method, uri.path(), &options)
+        else {
+            return (uri, false);
+        };
+        match with_path(&uri, path).parse() {
+            Ok(canonical) => (canonical, options.trailing_slash == TrailingSlash::Redirect),
+            Err(_) => (uri, false),
         }
-        let resp = recv.recv()?;
+    }
+
+    pub fn dispatch(
+        &self,
+        host: String,
+        method: Method,
+        uri: &Uri,
+        query: HashMap<String, String>,
+        headers: &HeaderMap,
+        body: Bytes,
+    ) -> Result<Resp> {
+        self.dispatch_timed(host, method, uri, query, headers, body)
+            .map(|(resp, _)| resp)
+    }
+
+    /// 和 `dispatch` 一样，同时返回各阶段的耗时
+    pub fn dispatch_timed(
+        &self,
+        host: String,
+        method: Method,
+        uri: &Uri,
+        query: HashMap<String, String>,
+        headers: &HeaderMap,
+        body: Bytes,
+    ) -> Result<(Resp, Timing)> {
+        let start = Instant::now();
+        let router = get_router(host.clone(), self)?;
+        let matched = router.match_it(method.clone(), uri.path())?;
+        ensure_http(&matched, uri)?;
+        let req = assemble_req(query, &matched, method, uri, headers, body)?;
+        let handler = router.handler(matched.value.handler).clone();
+        let route = start.elapsed();
+        let priority = matched.value.priority;
+        let (resp, timing) = self.send_timed(host, handler.clone(), priority, req, None)?;
+        self.check_contract(&router, &handler, &resp)?;
+        Ok((resp, Timing { route, ..timing }))
+    }
+
+    /// 和 `dispatch` 一样，handler 的输出实时发送到 `log`
+    pub(crate) fn dispatch_with_log(
+        &self,
+        host: String,
+        method: Method,
+        uri: &Uri,
+        query: HashMap<String, String>,
+        body: Bytes,
+        log: LogSender,
+    ) -> Result<Resp> {
+        let router = get_router(host.clone(), self)?;
+        let matched = router.match_it(method.clone(), uri.path())?;
+        ensure_http(&matched, uri)?;
+        let req = assemble_req(query, &matched, method, uri, &HeaderMap::new(), body)?;
+        let handler = router.handler(matched.value.handler).clone();
+        let _ = log.send(InvokeEvent::Start {
+            handler: handler.to_string(),
+        });
+        let priority = matched.value.priority;
+        let (resp, _) = self.send_timed(host, handler.clone(), priority, req, Some(log))?;
+        self.check_contract(&router, &handler, &resp)?;
         Ok(resp)
     }
-}
 
-fn jsworker_execute(code: String, recv: crossbeam::channel::Receiver<WorkerMessage>) -> Result<()> {
-    let worker = JsWorker::try_new(&code).context("Failed to create worker")?;
-    while let Ok(msg) = recv.recv() {
-        match msg {
-            WorkerMessage::Request(req) => {
-                let resp = worker.run(&req.handler, req.req)?;
-                if let Err(e) = req.send.send(resp) {
-                    error!("Send resp to oneshot error: {}", e);
-                }
-            }
-            WorkerMessage::Shutdown => {
-                info!("Worker shutdown");
-                return Ok(());
+    /// 开启契约检查时，handler 的返回值不符合路由声明的契约则报错
+    fn check_contract(&self, router: &AppRouter, handler: &str, resp: &Resp) -> Result<()> {
+        if !self.check_contracts {
+            return Ok(());
+        }
+        let violations = router.contracts.check(handler, resp);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let violations = violations.join("; ");
+        error!("Handler {handler} violates its response contract: {violations}");
+        bail!("handler {handler} violates its response contract: {violations}")
+    }
+
+    pub fn send(&self, host: String, handler: impl Into<Arc<str>>, req: Req) -> Result<Resp> {
+        self.send_timed(host, handler.into(), Priority::Normal, req, None)
+            .map(|(resp, _)| resp)
+    }
+
+    /// 在阻塞线程中等待 worker 的响应，不占用 tokio 的线程
+    async fn send_blocking(
+        &self,
+        host: String,
+        handler: Arc<str>,
+        priority: Priority,
+        req: Req,
+    ) -> Result<Resp> {
+        let state = self.clone();
+        tokio::task::spawn_blocking(move || {
+            state
+                .send_timed(host, handler, priority, req, None)
+                .map(|(resp, _)| resp)
+        })
+        .await?
+    }
+
+    /// 由 tenant 的一个 worker 返回运行时状态，worker 正在处理请求时要等它空闲，
+    /// 超过 `INSPECT_TIMEOUT` 没有回应说明 worker 可能卡住了
+    pub fn inspect(&self, host: &str) -> Result<Inspection> {
+        self.ask_worker(host, WorkerMessage::Inspect)
+    }
+
+    /// 在 tenant 的一个 worker 中求值，返回结果的字符串形式
+    pub fn eval(&self, host: &str, code: &str) -> Result<String> {
+        self.ask_worker(host, |send| WorkerMessage::Eval(code.to_string(), send))
+    }
+
+    fn ask_worker<T>(
+        &self,
+        host: &str,
+        msg: impl FnOnce(oneshot::Sender<Result<T>>) -> WorkerMessage,
+    ) -> Result<T> {
+        let queues = self.queues(host)?;
+        let (send, recv) = oneshot::channel();
+        queues
+            .send(msg(send), Priority::Interactive)
+            .map_err(|_| anyhow::anyhow!("Worker of {host} has stopped"))?;
+        recv.recv_timeout(INSPECT_TIMEOUT)
+            .map_err(|_| anyhow::anyhow!("Worker of {host} did not respond, it may be stuck"))?
+    }
+
+    /// 等待响应时不持有锁，否则所有请求都会被串行化
+    fn queues(&self, host: &str) -> Result<Queues> {
+        let workers = self.workers.lock().unwrap();
+        let handle = workers.get(host).context("Worker not found")?;
+        Ok(handle.queues.clone())
+    }
+
+    fn send_timed(
+        &self,
+        host: String,
+        handler: Arc<str>,
+        priority: Priority,
+        req: Req,
+        log: Option<LogSender>,
+    ) -> Result<(Resp, Timing)> {
+        let queues = self.queues(&host)?;
+        // 记录模式下固定随机数种子和时间，便于复现
+        let record = self.recorder.as_ref().map(|_| (Replay::new(), req.clone()));
+        let replay = record.as_ref().map(|(replay, _)| *replay);
+        let (msg, recv) = WorkerMessage::new_request(req, handler.clone(), replay, log);
+        if let Err(e) = queues.send(msg, priority) {
+            error!("Send to jsworker error: {}", e);
+        }
+        let (resp, timing) = recv.recv()??;
+
+        if let (Some(recorder), Some((replay, req))) = (&self.recorder, record) {
+            let record = ReplayRecord {
+                host,
+                handler: handler.to_string(),
+                req,
+                replay,
+                status: resp.status,
+                body: resp.body.clone(),
+            };
+            if let Err(e) = recorder.record(&record) {
+                error!("Record request error: {}", e);
             }
         }
+        Ok((resp, timing))
     }
-    Ok(())
 }
 
+#[cfg(feature = "server")]
 impl TenantRouter {
     pub fn new(host: String, router: SwappableAppRouter) -> Self {
-        Self { host, router }
+        Self {
+            host: host.to_ascii_lowercase(),
+            router,
+            bindings: Bindings::default(),
+            timezone: None,
+            memory_limit_mb: None,
+            routing: RoutingOptions::default(),
+            aliases: vec![],
+        }
+    }
+
+    pub fn with_bindings(mut self, bindings: Bindings) -> Self {
+        self.bindings = bindings;
+        self
+    }
+
+    /// 项目配置的 `timezone`，覆盖 worker 设置中的时区
+    pub fn with_timezone(mut self, timezone: Option<chrono_tz::Tz>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// 项目配置的 `memory_limit_mb`，覆盖 worker 设置中的内存上限
+    pub fn with_memory_limit(mut self, memory_limit_mb: Option<usize>) -> Self {
+        self.memory_limit_mb = memory_limit_mb;
+        self
+    }
+
+    /// 项目配置的 `routing`
+    pub fn with_routing(mut self, routing: RoutingOptions) -> Self {
+        self.routing = routing;
+        self
+    }
+
+    /// 重定向到该 tenant 的别名 host
+    pub fn with_aliases(mut self, aliases: Vec<String>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatch_should_pass_request_headers() -> Result<()> {
+        let code = r#"(function(){ async function hello(req) { return { status: 200, headers: {}, body: JSON.stringify(req.headers) }; } return { hello }; })();"#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: test\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?;
+        let router = SwappableAppRouter::try_new(code, config.routes)?;
+        let state = AppState::with_routers(DashMap::from_iter([("a.com".to_string(), router)]));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer t".parse()?);
+        headers.append("accept", "text/html".parse()?);
+        headers.append("accept", "application/json".parse()?);
+        headers.append("cookie", "a=1".parse()?);
+        headers.append("cookie", "b=2".parse()?);
+        let uri: Uri = "/hello".parse()?;
+        let resp = state.dispatch(
+            "a.com".into(),
+            Method::GET,
+            &uri,
+            HashMap::new(),
+            &headers,
+            Bytes::new(),
+        )?;
+        let seen: HashMap<String, String> = serde_json::from_str(resp.text().unwrap_or_default())?;
+        assert_eq!(seen["authorization"], "Bearer t");
+        assert_eq!(seen["accept"], "text/html, application/json");
+        assert_eq!(seen["cookie"], "a=1; b=2");
+        Ok(())
+    }
+
+    #[test]
+    fn failed_swap_should_keep_project_config() -> Result<()> {
+        let code = r#"(function(){ async function hello() { return { status: 200, headers: {}, body: "ok" }; } return { hello }; })();"#;
+        let state = AppState::with_routers(DashMap::new());
+        state.add_tenant("a.com", code, Default::default(), "test")?;
+
+        let bad: ProjectConfig = serde_yaml::from_str(
+            "name: test\ntimezone: Asia/Shanghai\nroutes:\n  /a/{:\n    - method: GET\n      handler: hello\n",
+        )?;
+        assert!(state.swap_with_config("a.com", code, &bad, "test").is_err());
+        assert_eq!(state.worker_settings("a.com"), WorkerSettings::default());
+
+        let good: ProjectConfig = serde_yaml::from_str(
+            "name: test\ntimezone: Asia/Shanghai\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?;
+        state.swap_with_config("a.com", code, &good, "test")?;
+        assert_eq!(
+            state.worker_settings("a.com").timezone,
+            Some(chrono_tz::Asia::Shanghai)
+        );
+
+        // 已存在的 host 或有误的路由都不会改变设置
+        assert!(
+            state
+                .add_tenant_with_config("b.com", code, &bad, "test")
+                .is_err()
+        );
+        assert_eq!(state.worker_settings("b.com"), WorkerSettings::default());
+        let other: ProjectConfig = serde_yaml::from_str("name: test\ntimezone: UTC\nroutes: {}\n")?;
+        assert!(
+            state
+                .add_tenant_with_config("a.com", code, &other, "test")
+                .is_err()
+        );
+        assert_eq!(
+            state.worker_settings("a.com").timezone,
+            Some(chrono_tz::Asia::Shanghai)
+        );
+        state.add_tenant_with_config("b.com", code, &good, "test")?;
+        assert_eq!(
+            state.worker_settings("b.com").timezone,
+            Some(chrono_tz::Asia::Shanghai)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn tenants_should_be_added_and_removed_at_runtime() -> Result<()> {
+        let code = r#"(function(){ async function hello() { return { status: 200, headers: {}, body: "ok" }; } return { hello }; })();"#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: test\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?;
+        let state = AppState::with_routers(DashMap::new());
+        let call = || {
+            let req = Req::builder().method("GET").url("/hello").build();
+            state.send("a.com".into(), "hello", req)
+        };
+        assert!(call().is_err());
+
+        state.add_tenant("a.com", code, config.routes.clone(), "test")?;
+        state.set_worker_settings(
+            "a.com",
+            WorkerSettings {
+                nice: Some(0),
+                ..Default::default()
+            },
+        );
+        assert_eq!(call()?.text(), Some("ok"));
+        assert!(
+            state
+                .add_tenant("a.com", code, config.routes.clone(), "test")
+                .is_err()
+        );
+        assert!(state.tenants()[0].worker_running);
+
+        state.remove_tenant("a.com", "test")?;
+        assert!(call().is_err());
+        assert!(state.tenants().is_empty());
+        assert_eq!(state.worker_settings("a.com"), WorkerSettings::default());
+        assert!(state.remove_tenant("a.com", "test").is_err());
+
+        state.add_tenant("a.com", code, config.routes, "test")?;
+        assert_eq!(call()?.text(), Some("ok"));
+        Ok(())
     }
 }
@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+#[cfg(feature = "server")]
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    net::TcpStream,
+    sync::{Mutex, OnceLock},
+};
+
+#[cfg(feature = "server")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use tracing_subscriber::{
+    EnvFilter, Layer as _, Registry, fmt::writer::BoxMakeWriter, layer::SubscriberExt, reload,
+    util::SubscriberInitExt,
+};
+
+/// 覆盖配置中日志级别的环境变量，格式同 `level`
+pub const LOG_ENV: &str = "DINO_LOG";
+
+// `init` 安装的日志级别，reload 服务器配置时替换
+#[cfg(feature = "server")]
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// 日志配置，`dino run` 读取 config.yml 的 `logging`，服务器读取服务器配置的 `logging`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+    /// 可以按模块设置级别，例如 `info,dino_server=debug`
+    #[serde(default = "default_level")]
+    pub level: String,
+    /// `stdout`、`file: <path>` 或 `tcp: <addr>`
+    #[serde(default, with = "serde_yaml::with::singleton_map")]
+    pub sink: LogSink,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    /// 每行一个 JSON，便于日志采集
+    Json,
+}
+
+/// 日志输出的位置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSink {
+    #[default]
+    Stdout,
+    /// 追加写入文件
+    File(PathBuf),
+    /// 写入 TCP 连接，例如 vector 的 socket source，断开后会重连
+    Tcp(String),
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            level: default_level(),
+            sink: LogSink::default(),
+        }
+    }
+}
+
+fn default_level() -> String {
+    "info".to_string()
+}
+
+#[cfg(feature = "server")]
+impl LogConfig {
+    /// 安装全局的 tracing subscriber
+    pub fn init(&self) -> Result<()> {
+        let level = std::env::var(LOG_ENV).unwrap_or_else(|_| self.level.clone());
+        let filter =
+            EnvFilter::try_new(&level).with_context(|| format!("invalid log level: {level}"))?;
+        let writer = match &self.sink {
+            LogSink::Stdout => BoxMakeWriter::new(io::stdout),
+            LogSink::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("failed to open log file {}", path.display()))?;
+                BoxMakeWriter::new(Mutex::new(file))
+            }
+            LogSink::Tcp(addr) => BoxMakeWriter::new(TcpSink::new(addr.clone())),
+        };
+        // 输出到文件或 TCP 时不需要颜色
+        let ansi = self.sink == LogSink::Stdout;
+        let layer = tracing_subscriber::fmt::layer()
+            .with_ansi(ansi)
+            .with_writer(writer);
+        let layer = match self.format {
+            LogFormat::Pretty => layer.boxed(),
+            LogFormat::Json => layer.json().boxed(),
+        };
+        let (filter, handle) = reload::Layer::new(filter);
+        tracing_subscriber::registry()
+            .with(layer.with_filter(filter))
+            .try_init()?;
+        let _ = FILTER.set(handle);
+        Ok(())
+    }
+}
+
+/// 修改 `init` 安装的日志级别，设置了 `DINO_LOG` 或还没有安装时不做修改。
+/// 格式和输出位置不能修改
+#[cfg(feature = "server")]
+pub(crate) fn set_level(level: &str) -> Result<()> {
+    let filter =
+        EnvFilter::try_new(level).with_context(|| format!("invalid log level: {level}"))?;
+    if std::env::var(LOG_ENV).is_err()
+        && let Some(handle) = FILTER.get()
+    {
+        handle.reload(filter)?;
+    }
+    Ok(())
+}
+
+/// 写入 TCP 的日志，写失败时丢弃连接，下一条日志重新连接
+#[cfg(feature = "server")]
+struct TcpSink {
+    addr: String,
+    stream: Mutex<Option<TcpStream>>,
+}
+
+#[cfg(feature = "server")]
+impl TcpSink {
+    fn new(addr: String) -> Self {
+        Self {
+            addr,
+            stream: Mutex::new(None),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl Write for &TcpSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut stream = self.stream.lock().unwrap();
+        if stream.is_none() {
+            *stream = Some(TcpStream::connect(&self.addr)?);
+        }
+        let ret = stream.as_mut().map(|s| s.write_all(buf)).unwrap();
+        if ret.is_err() {
+            *stream = None;
+        }
+        ret.map(|_| buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "server")]
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for TcpSink {
+    type Writer = &'a TcpSink;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_config_should_deserialize() -> anyhow::Result<()> {
+        let config: LogConfig = serde_yaml::from_str(
+            "format: json\nlevel: warn,dino_server=debug\nsink:\n  tcp: 127.0.0.1:9000\n",
+        )?;
+        assert_eq!(config.format, LogFormat::Json);
+        assert_eq!(config.sink, LogSink::Tcp("127.0.0.1:9000".into()));
+
+        let config: LogConfig = serde_yaml::from_str("sink:\n  file: /tmp/dino.log\n")?;
+        assert_eq!(config.format, LogFormat::Pretty);
+        assert_eq!(config.level, "info");
+        Ok(())
+    }
+}
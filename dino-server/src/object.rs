@@ -0,0 +1,370 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use crossbeam::channel::Sender;
+use rquickjs::{Ctx, Exception, Function};
+use tracing::{error, info};
+
+use crate::{
+    AppState,
+    engine::{Engine, JsWorker},
+};
+
+/// 类似 Durable Object 的单实例有状态对象：bundle 导出的 class 对每个 id 只有一个实例，
+/// 同一个 tenant 的所有对象在一个专用线程中执行，同一个 id 的请求因此是串行的
+pub(crate) const OBJECTS: &str = r#"
+globalThis.objects = new Proxy({}, {
+  get(_, cls) {
+    return {
+      get(id) {
+        return {
+          fetch: async (req = {}) =>
+            JSON.parse(__dino_object_fetch(String(cls), String(id), JSON.stringify(req))),
+        };
+      },
+    };
+  },
+});
+
+const __dino_instances = new Map();
+globalThis.__dino_object_call = async function (cls, id, req) {
+  const key = `${cls}\u0000${id}`;
+  let obj = __dino_instances.get(key);
+  if (!obj) {
+    const Class = globalThis.handlers[cls];
+    if (typeof Class !== "function") {
+      throw new Error(`object class not found: ${cls}`);
+    }
+    const storage = {
+      get(k) {
+        const v = __dino_storage_get(cls, id, String(k));
+        return v === undefined ? undefined : JSON.parse(v);
+      },
+      put(k, v) {
+        __dino_storage_put(cls, id, String(k), JSON.stringify(v));
+      },
+      delete(k) {
+        __dino_storage_delete(cls, id, String(k));
+      },
+    };
+    obj = new Class({ id, storage });
+    __dino_instances.set(key, obj);
+  }
+  const resp = await obj.fetch({
+    headers: {}, query: {}, params: {}, url: "/", method: "GET", ...JSON.parse(req),
+  });
+  return JSON.stringify(resp);
+};
+"#;
+
+/// 对象的持久化存储，配置了目录时每个对象保存为一个 JSON 文件，否则只保存在内存中
+#[derive(Debug, Clone, Default)]
+pub struct ObjectStore {
+    dir: Option<PathBuf>,
+    cache: Arc<Mutex<HashMap<ObjectKey, BTreeMap<String, String>>>>,
+}
+
+/// (tenant, class, id)
+type ObjectKey = (String, String, String);
+
+/// 发给对象线程的请求
+pub(crate) struct ObjectMessage {
+    class: String,
+    id: String,
+    req: String,
+    send: oneshot::Sender<Result<String>>,
+}
+
+thread_local! {
+    // 对象线程所属的 tenant 及其存储，普通 worker 线程中为 None
+    static OBJECT_CONTEXT: RefCell<Option<(String, ObjectStore)>> = const { RefCell::new(None) };
+}
+
+impl ObjectStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            cache: Default::default(),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &ObjectKey, name: &str) -> Result<Option<String>> {
+        self.with_object(key, false, |data| data.get(name).cloned())
+    }
+
+    pub(crate) fn put(&self, key: &ObjectKey, name: &str, value: String) -> Result<()> {
+        self.with_object(key, true, |data| {
+            data.insert(name.to_string(), value);
+        })
+    }
+
+    /// 保存状态的目录，为 None 时只保存在内存中
+    pub(crate) fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// 是否保存到磁盘
+    #[cfg(feature = "tls")]
+    pub(crate) fn persistent(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    fn delete(&self, key: &ObjectKey, name: &str) -> Result<()> {
+        self.with_object(key, true, |data| {
+            data.remove(name);
+        })
+    }
+
+    /// tenant 的对象状态占用的字节数，保存在内存中时按 key 和 value 的长度计算
+    pub(crate) fn tenant_bytes(&self, tenant: &str) -> u64 {
+        match &self.dir {
+            Some(dir) => crate::usage::dir_size(&dir.join(tenant)),
+            None => self
+                .cache
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|((t, _, _), _)| t == tenant)
+                .flat_map(|(_, data)| data.iter())
+                .map(|(k, v)| (k.len() + v.len()) as u64)
+                .sum(),
+        }
+    }
+
+    /// `write` 为 true 时在修改后写回磁盘
+    fn with_object<T>(
+        &self,
+        key: &ObjectKey,
+        write: bool,
+        f: impl FnOnce(&mut BTreeMap<String, String>) -> T,
+    ) -> Result<T> {
+        let mut cache = self.cache.lock().unwrap();
+        if !cache.contains_key(key) {
+            let data = match self.path(key) {
+                Some(path) if path.exists() => serde_json::from_slice(&fs::read(&path)?)?,
+                _ => BTreeMap::new(),
+            };
+            cache.insert(key.clone(), data);
+        }
+        let data = cache.get_mut(key).unwrap();
+        let ret = f(data);
+        if write && let Some(path) = self.path(key) {
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(&path, serde_json::to_vec(data)?)?;
+        }
+        Ok(ret)
+    }
+
+    /// `<dir>/<tenant>/<class>/<hash(id)>.json`，id 可能包含任意字符，所以用 hash 作为文件名
+    fn path(&self, (tenant, class, id): &ObjectKey) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        let name = blake3::hash(id.as_bytes()).to_string();
+        Some(dir.join(tenant).join(class).join(format!("{name}.json")))
+    }
+}
+
+impl AppState {
+    /// 把请求交给 tenant 中 `class` 的 `id` 实例处理，对象线程在第一次调用时启动
+    pub fn call_object(&self, host: &str, class: &str, id: &str, req: String) -> Result<String> {
+        let send = {
+            let mut objects = self.objects.lock().unwrap();
+            match objects.get(host) {
+                Some(send) => send.clone(),
+                None => {
+                    let code = self
+                        .routers
+                        .get(host)
+                        .with_context(|| format!("Tenant not found: {host}"))?
+                        .load()
+                        .code;
+                    let timezone = self.worker_settings(host).timezone;
+                    let send = spawn(host, code, self.object_store.clone(), timezone)?;
+                    objects.insert(host.to_string(), send.clone());
+                    send
+                }
+            }
+        };
+        let (tx, rx) = oneshot::channel();
+        let msg = ObjectMessage {
+            class: class.to_string(),
+            id: id.to_string(),
+            req,
+            send: tx,
+        };
+        send.send(msg).context("Object worker has stopped")?;
+        rx.recv()?
+    }
+
+    /// 代码更新或 tenant 删除时停止对象线程，实例在下次调用时用新代码重新创建，存储不受影响
+    pub(crate) fn stop_objects(&self, host: &str) {
+        self.objects.lock().unwrap().remove(host);
+    }
+}
+
+fn spawn(
+    host: &str,
+    code: String,
+    store: ObjectStore,
+    timezone: Option<chrono_tz::Tz>,
+) -> Result<Sender<ObjectMessage>> {
+    let (send, recv) = crossbeam::channel::unbounded::<ObjectMessage>();
+    let tenant = host.to_string();
+    thread::Builder::new()
+        .name(format!("objects-{host}"))
+        .spawn(move || {
+            OBJECT_CONTEXT.with(|c| *c.borrow_mut() = Some((tenant.clone(), store)));
+            crate::set_timezone(timezone);
+            let worker = match JsWorker::try_new(&code) {
+                Ok(worker) => worker,
+                Err(e) => return error!("Failed to create object worker for {tenant}: {e:#}"),
+            };
+            // sender 被移除后 recv 返回错误，线程退出
+            while let Ok(msg) = recv.recv() {
+                let ret = worker.call_object(&msg.class, &msg.id, &msg.req);
+                let _ = msg.send.send(ret);
+            }
+            info!("Object worker for {tenant} stopped");
+        })?;
+    Ok(send)
+}
+
+pub(crate) fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    let global = ctx.globals();
+    global.set(
+        "__dino_object_fetch",
+        Function::new(ctx.clone(), object_fetch)?,
+    )?;
+    global.set(
+        "__dino_storage_get",
+        Function::new(ctx.clone(), storage_get)?,
+    )?;
+    global.set(
+        "__dino_storage_put",
+        Function::new(ctx.clone(), storage_put)?,
+    )?;
+    global.set(
+        "__dino_storage_delete",
+        Function::new(ctx.clone(), storage_delete)?,
+    )?;
+    ctx.eval::<(), _>(OBJECTS)
+}
+
+fn throw(ctx: &Ctx, e: anyhow::Error) -> rquickjs::Error {
+    Exception::throw_message(ctx, &format!("{e:#}"))
+}
+
+fn object_fetch(ctx: Ctx, class: String, id: String, req: String) -> rquickjs::Result<String> {
+    let ret = (|| {
+        // 对象线程是单线程的，对象再调用本 tenant 的对象会死锁
+        anyhow::ensure!(
+            OBJECT_CONTEXT.with(|c| c.borrow().is_none()),
+            "objects can't be called from another object"
+        );
+        let host = crate::binding::caller().context("objects are not available here")?;
+        let state = AppState::get_current().context("server is not running")?;
+        state.call_object(&host, &class, &id, req)
+    })();
+    ret.map_err(|e| throw(&ctx, e))
+}
+
+fn with_storage<T>(
+    ctx: &Ctx,
+    class: String,
+    id: String,
+    f: impl FnOnce(&ObjectStore, &ObjectKey) -> Result<T>,
+) -> rquickjs::Result<T> {
+    OBJECT_CONTEXT
+        .with(|c| {
+            let c = c.borrow();
+            let (tenant, store) = c
+                .as_ref()
+                .context("storage is only available inside objects")?;
+            f(store, &(tenant.clone(), class, id))
+        })
+        .map_err(|e| throw(ctx, e))
+}
+
+fn storage_get(
+    ctx: Ctx,
+    class: String,
+    id: String,
+    key: String,
+) -> rquickjs::Result<Option<String>> {
+    with_storage(&ctx, class, id, |store, obj| store.get(obj, &key))
+}
+
+fn storage_put(
+    ctx: Ctx,
+    class: String,
+    id: String,
+    key: String,
+    value: String,
+) -> rquickjs::Result<()> {
+    with_storage(&ctx, class, id, |store, obj| store.put(obj, &key, value))
+}
+
+fn storage_delete(ctx: Ctx, class: String, id: String, key: String) -> rquickjs::Result<()> {
+    with_storage(&ctx, class, id, |store, obj| store.delete(obj, &key))
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{ProjectRoutes, SwappableAppRouter};
+
+    #[test]
+    fn objects_should_keep_state_per_id() -> Result<()> {
+        let code = r#"
+        (function(){
+            class Counter {
+                constructor(state) { this.state = state; }
+                async fetch(req) {
+                    const n = (this.state.storage.get("n") ?? 0) + 1;
+                    this.state.storage.put("n", n);
+                    return { status: 200, headers: {}, body: `${this.state.id}:${n}` };
+                }
+            }
+            return { Counter };
+        })();
+        "#;
+        let dir = std::env::temp_dir().join(format!("dino-objects-{}", uuid::Uuid::new_v4()));
+        let routers = DashMap::new();
+        routers.insert(
+            "a.com".to_string(),
+            SwappableAppRouter::try_new(code, ProjectRoutes::new())?,
+        );
+        let mut state = AppState::with_routers(routers);
+        state.object_store = ObjectStore::new(&dir);
+
+        let call = |state: &AppState, id| -> Result<String> {
+            let resp = state.call_object("a.com", "Counter", id, "{}".into())?;
+            let resp: serde_json::Value = serde_json::from_str(&resp)?;
+            Ok(resp["body"].as_str().unwrap().to_string())
+        };
+        assert_eq!(call(&state, "x")?, "x:1");
+        assert_eq!(call(&state, "x")?, "x:2");
+        assert_eq!(call(&state, "y")?, "y:1");
+
+        // 重启后实例重新创建，状态从存储中恢复
+        state.stop_objects("a.com");
+        state.object_store = ObjectStore::new(&dir);
+        assert_eq!(call(&state, "x")?, "x:3");
+        assert!(
+            state
+                .call_object("a.com", "Nope", "x", "{}".into())
+                .is_err()
+        );
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
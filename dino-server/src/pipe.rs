@@ -0,0 +1,279 @@
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, Method, Response, Uri},
+};
+use tokio::sync::mpsc;
+use tokio_stream::{StreamExt, wrappers::ReceiverStream};
+use tracing::{error, info_span};
+
+use crate::{
+    AppState, PipeRequest, RouteKind, WorkerMessage, assemble_req,
+    engine::{BodyReader, Engine, JsWorker},
+    error::AppError,
+    get_router,
+    watchdog::Heartbeat,
+    worker::timeout_resp,
+};
+
+/// 请求和响应 body 在通道中缓存的块数，handler 或客户端读得慢时另一端等待
+const PIPE_BUFFER: usize = 8;
+
+impl AppState {
+    /// 请求是否匹配到流式路由
+    pub(crate) fn pipe_route(&self, host: &str, method: &Method, uri: &Uri) -> bool {
+        self.routers.get(host).is_some_and(|router| {
+            router
+                .routes
+                .load()
+                .match_it(method.clone(), uri.path())
+                .is_ok_and(|m| m.value.kind == RouteKind::Stream)
+        })
+    }
+
+    /// 把请求 body 逐块交给 handler，handler 返回响应头后立即开始发送响应，
+    /// 之后的 body 块随产生随发送。请求期间占用一个 worker
+    pub(crate) async fn pipe(
+        &self,
+        host: &str,
+        method: Method,
+        uri: &Uri,
+        query: HashMap<String, String>,
+        headers: &HeaderMap,
+        body: Body,
+    ) -> Result<Response<Body>, AppError> {
+        let router = get_router(host.to_string(), self)?;
+        let matched = router.match_it(method.clone(), uri.path())?;
+        let req = assemble_req(query, &matched, method, uri, headers, Bytes::new())?;
+        let handler = router.handler(matched.value.handler).clone();
+
+        let (body_send, body_recv) = mpsc::channel(PIPE_BUFFER);
+        tokio::spawn(async move {
+            let mut stream = body.into_data_stream();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| format!("failed to read request body: {e}"));
+                let failed = chunk.is_err();
+                // handler 不再读取时停止接收
+                if body_send.send(chunk).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let (head_send, head_recv) = oneshot::channel();
+        let (chunks_send, chunks_recv) = mpsc::channel(PIPE_BUFFER);
+        let req = PipeRequest {
+            req,
+            handler,
+            span: tracing::Span::current(),
+            body: body_recv,
+            head: head_send,
+            chunks: chunks_send,
+        };
+        self.queues(host)?
+            .send(WorkerMessage::Pipe(Box::new(req)), matched.value.priority)
+            .map_err(|e| anyhow::anyhow!("Send to jsworker error: {e}"))?;
+
+        let mut resp = head_recv
+            .await
+            .map_err(|_| anyhow::anyhow!("stream handler failed before responding"))?;
+        let first = resp.body.take().map(|b| Bytes::from(b.into_bytes()));
+        let body = tokio_stream::iter(first).chain(ReceiverStream::new(chunks_recv));
+        let mut builder = Response::builder().status(resp.status);
+        for (k, v) in resp.headers {
+            builder = builder.header(k, v);
+        }
+        let body = Body::from_stream(body.map(Ok::<_, Infallible>));
+        Ok(builder.body(body).map_err(anyhow::Error::from)?)
+    }
+}
+
+/// 在 worker 中执行流式 handler，直到响应结束、客户端断开或两次读写之间超时。
+/// 返回响应头之前超时回复 504，之后超时直接结束响应
+pub(crate) fn run(worker: &JsWorker, req: PipeRequest, beat: &Arc<Heartbeat>) {
+    let span = info_span!(parent: &req.span, "js", handler = %req.handler);
+    let _enter = span.enter();
+    let (mut head, chunks) = (Some(req.head), req.chunks);
+    let ret = worker.call_pipe(
+        &req.handler,
+        req.req,
+        body_reader(req.body, beat.clone()),
+        &mut |resp| head.take().is_some_and(|head| head.send(resp).is_ok()),
+        &mut |chunk| {
+            beat.progress();
+            chunks.blocking_send(chunk).is_ok()
+        },
+    );
+    if let Err(e) = ret {
+        if let Some(head) = head.take()
+            && beat.timed_out()
+        {
+            let _ = head.send(timeout_resp());
+        }
+        error!("Run pipe handler error: {e:#}");
+    }
+}
+
+/// 原样交给引擎的字节块，由引擎决定以字符串还是 `Uint8Array` 交给 JS
+fn body_reader(
+    mut recv: mpsc::Receiver<Result<Bytes, String>>,
+    beat: Arc<Heartbeat>,
+) -> BodyReader {
+    Box::new(move || {
+        let chunk = recv.blocking_recv().transpose().map_err(anyhow::Error::msg);
+        beat.progress();
+        chunk
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{ProjectConfig, SwappableAppRouter, WorkerSettings};
+
+    #[tokio::test]
+    async fn pipe_should_stream_request_and_response() -> anyhow::Result<()> {
+        let code = r#"
+        (function(){
+            async function echo(req) {
+                async function* chunks() {
+                    for await (const chunk of req.body) yield `[${chunk}]`;
+                }
+                return { status: 200, headers: { "content-type": "text/plain" }, body: chunks() };
+            }
+            async function raw(req) {
+                async function* chunks() {
+                    for await (const chunk of req.body) yield chunk;
+                }
+                return { status: 200, headers: {}, body: chunks() };
+            }
+            async function fail() { throw new Error("boom"); }
+            return { echo, raw, fail };
+        })();
+        "#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            r#"
+name: test
+routes:
+  /echo:
+    - method: POST
+      handler: echo
+      type: stream
+  /raw:
+    - method: POST
+      handler: raw
+      type: stream
+  /fail:
+    - method: PUT
+      handler: fail
+      type: stream
+  /plain:
+    - method: POST
+      handler: echo
+"#,
+        )?;
+        let routers = DashMap::new();
+        routers.insert(
+            "a.com".to_string(),
+            SwappableAppRouter::try_new(code, config.routes)?,
+        );
+        let state = AppState::with_routers(routers);
+        let uri: Uri = "/echo".parse()?;
+        assert!(state.pipe_route("a.com", &Method::POST, &uri));
+        assert!(!state.pipe_route("a.com", &Method::POST, &"/plain".parse()?));
+
+        // "é" 被拆在两个块之间
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = vec![
+            Ok(b"ab\xc3".to_vec()),
+            Ok(b"\xa9cd".to_vec()),
+            Ok(b"e".to_vec()),
+        ];
+        let body = Body::from_stream(tokio_stream::iter(chunks));
+        let resp = state
+            .pipe(
+                "a.com",
+                Method::POST,
+                &uri,
+                HashMap::new(),
+                &HeaderMap::new(),
+                body,
+            )
+            .await?;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["content-type"], "text/plain");
+        let body = to_bytes(resp.into_body(), usize::MAX).await?;
+        assert_eq!(std::str::from_utf8(&body)?, "[ab][écd][e]");
+
+        // 不是 UTF-8 的 body 原样往返
+        let bytes: Vec<u8> = (0..=255).rev().collect();
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> =
+            bytes.chunks(100).map(|c| Ok(c.to_vec())).collect();
+        let resp = state
+            .pipe(
+                "a.com",
+                Method::POST,
+                &"/raw".parse()?,
+                HashMap::new(),
+                &HeaderMap::new(),
+                Body::from_stream(tokio_stream::iter(chunks)),
+            )
+            .await?;
+        assert_eq!(to_bytes(resp.into_body(), usize::MAX).await?, bytes);
+
+        let ret = state
+            .pipe(
+                "a.com",
+                Method::PUT,
+                &"/fail".parse()?,
+                HashMap::new(),
+                &HeaderMap::new(),
+                Body::empty(),
+            )
+            .await;
+        assert!(ret.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pipe_should_time_out_stuck_handlers() -> anyhow::Result<()> {
+        let code = r#"
+        (function(){
+            async function spin(req) { while (true) {} }
+            return { spin };
+        })();
+        "#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: test\nroutes:\n  /spin:\n    - method: POST\n      handler: spin\n      type: stream\n",
+        )?;
+        let routers = DashMap::new();
+        routers.insert(
+            "a.com".to_string(),
+            SwappableAppRouter::try_new(code, config.routes)?,
+        );
+        let state = AppState::with_routers(routers);
+        let settings = WorkerSettings {
+            timeout_ms: 200,
+            ..Default::default()
+        };
+        state.set_worker_settings("a.com", settings);
+        state.update_worker("a.com")?;
+
+        // 返回响应头之前超时回复 504
+        let resp = state
+            .pipe(
+                "a.com",
+                Method::POST,
+                &"/spin".parse()?,
+                HashMap::new(),
+                &HeaderMap::new(),
+                Body::empty(),
+            )
+            .await?;
+        assert_eq!(resp.status(), 504);
+        Ok(())
+    }
+}
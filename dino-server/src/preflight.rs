@@ -0,0 +1,255 @@
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+};
+
+use anyhow::{Result, bail};
+use tracing::warn;
+
+use crate::{
+    Bindings, ServerConfig, ServerOptions, SwappableAppRouter, TenantRouter, TenantSource,
+    engine::{Engine, JsWorker},
+};
+
+/// 启动前检查发现的问题，`tenant` 为 None 时是服务器级别的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightIssue {
+    pub tenant: Option<String>,
+    pub message: String,
+}
+
+/// 启动前检查的结果：通过检查的 tenant 和所有问题
+#[derive(Debug)]
+pub struct Preflight {
+    pub tenants: Vec<TenantRouter>,
+    pub issues: Vec<PreflightIssue>,
+}
+
+impl fmt::Display for PreflightIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tenant {
+            Some(tenant) => write!(f, "{tenant}: {}", self.message),
+            None => write!(f, "server: {}", self.message),
+        }
+    }
+}
+
+impl Preflight {
+    /// 没有问题时返回所有 tenant，否则一次列出所有问题。`partial_start` 为 true 时
+    /// 跳过有问题的 tenant，只用健康的 tenant 启动，服务器级别的问题仍然失败
+    pub fn finish(self, partial_start: bool) -> Result<Vec<TenantRouter>> {
+        if self.issues.is_empty() {
+            return Ok(self.tenants);
+        }
+        let server_failed = self.issues.iter().any(|i| i.tenant.is_none());
+        if partial_start && !server_failed && !self.tenants.is_empty() {
+            for issue in &self.issues {
+                warn!("Skipping tenant {issue}");
+            }
+            return Ok(self.tenants);
+        }
+        let issues: Vec<_> = self.issues.iter().map(ToString::to_string).collect();
+        bail!("preflight checks failed:\n  {}", issues.join("\n  "))
+    }
+}
+
+impl ServerConfig {
+    /// 启动前检查所有 tenant 的代码、路由和 handler，以及端口、TLS 设置和存储目录，
+    /// 用于代替 `tenant_routers` 在启动时一次报告所有问题
+    pub fn preflight(&self, port: u16, options: &ServerOptions) -> Preflight {
+        let mut tenants = vec![];
+        let mut issues = vec![];
+        let mut hosts = HashSet::new();
+        let mut aliases = HashSet::new();
+        for tenant in &self.tenants {
+            let issue = |message: String| PreflightIssue {
+                tenant: Some(tenant.host.clone()),
+                message,
+            };
+            if !hosts.insert(&tenant.host) {
+                issues.push(issue("duplicate host".into()));
+                continue;
+            }
+            let count = issues.len();
+            for alias in &tenant.aliases {
+                let alias = alias.to_ascii_lowercase();
+                if self.tenants.iter().any(|t| t.host == alias) {
+                    issues.push(issue(format!("alias {alias} is a tenant")));
+                } else if !aliases.insert(alias.clone()) {
+                    issues.push(issue(format!("alias {alias} is used by another tenant")));
+                }
+            }
+            if issues.len() > count {
+                continue;
+            }
+            match check_tenant(tenant) {
+                Ok(router) => tenants.push(router),
+                Err(e) => issues.push(issue(format!("{e:#}"))),
+            }
+        }
+
+        let mut server = vec![];
+        let mut addrs = vec![("server", SocketAddr::from(([0, 0, 0, 0], port)))];
+        addrs.extend(options.admin_addr.map(|addr| ("admin API", addr)));
+        addrs.extend(options.inspect.map(|addr| ("inspector", addr)));
+        #[cfg(feature = "tls")]
+        if let Some(tls) = options.tls.as_ref().or(self.tls.as_ref()) {
+            addrs.push(("TLS", SocketAddr::from(([0, 0, 0, 0], tls.port))));
+            check_tls(tls, &mut server);
+        }
+        check_ports(&addrs, &mut server);
+        let dirs = [
+            ("upload dir", options.upload_dir.as_deref()),
+            ("files dir", options.files_dir.as_deref()),
+            (
+                "object store",
+                options.object_store.as_ref().and_then(|s| s.dir()),
+            ),
+            #[cfg(feature = "git")]
+            ("git dir", options.git_dir.as_deref()),
+        ];
+        for (name, dir) in dirs {
+            if let Some(dir) = dir
+                && let Err(e) = check_dir(dir)
+            {
+                server.push(format!("{name} {} is not writable: {e}", dir.display()));
+            }
+        }
+        issues.extend(server.into_iter().map(|message| PreflightIssue {
+            tenant: None,
+            message,
+        }));
+        Preflight { tenants, issues }
+    }
+}
+
+/// 读取代码和配置、构建路由，并在引擎中加载代码检查路由和定时任务的 handler 都已导出
+fn check_tenant(tenant: &TenantSource) -> Result<TenantRouter> {
+    let (code, config) = tenant.read()?;
+    let worker = JsWorker::try_new(&code)?;
+    let exported = worker.inspect()?.handlers;
+    let handlers = config.routes.values().flatten().map(|r| &r.handler);
+    let mut missing: Vec<&str> = vec![];
+    for handler in handlers.chain(config.crons.values().map(|job| &job.handler)) {
+        if !exported.contains(handler) && !missing.contains(&handler.as_str()) {
+            missing.push(handler);
+        }
+    }
+    if !missing.is_empty() {
+        bail!("handlers not exported: {}", missing.join(", "));
+    }
+    let bindings = Bindings::from_config(&config);
+    let (timezone, routing) = (config.timezone, config.routing);
+    let memory_limit = config.memory_limit_mb;
+    let router = SwappableAppRouter::try_new(code, config.routes)?;
+    Ok(TenantRouter::new(tenant.host.clone(), router)
+        .with_bindings(bindings)
+        .with_timezone(timezone)
+        .with_memory_limit(memory_limit)
+        .with_routing(routing)
+        .with_aliases(tenant.aliases.clone()))
+}
+
+/// 依次绑定所有需要监听的地址，检查结束前不释放，同一个地址配置两次也会被发现
+fn check_ports(addrs: &[(&str, SocketAddr)], issues: &mut Vec<String>) {
+    let mut listeners = vec![];
+    for &(name, addr) in addrs {
+        match TcpListener::bind(addr) {
+            Ok(listener) => listeners.push(listener),
+            // 端口 0 由系统分配，不会冲突
+            Err(e) if addr.port() != 0 => {
+                issues.push(format!("can't listen on {addr} for the {name}: {e}"))
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+fn check_tls(tls: &crate::TlsOptions, issues: &mut Vec<String>) {
+    for contact in &tls.contact {
+        if contact.starts_with("mailto:") || !contact.contains('@') {
+            issues.push(format!(
+                "TLS contact `{contact}` must be an email address without `mailto:`"
+            ));
+        }
+    }
+    if let Some(directory) = &tls.directory
+        && !directory
+            .parse::<http::Uri>()
+            .is_ok_and(|uri| uri.scheme_str() == Some("https") && uri.host().is_some())
+    {
+        issues.push(format!("TLS directory `{directory}` must be an https URL"));
+    }
+}
+
+/// 目录可以创建并写入文件
+fn check_dir(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(".dino-preflight");
+    fs::write(&probe, b"")?;
+    fs::remove_file(probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preflight_should_report_all_issues() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dino-preflight-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        let code = "(function(){ function hello(){ return { status: 200, body: 'ok' }; } return { hello }; })();";
+        fs::write(dir.join("a.mjs"), code)?;
+        fs::write(dir.join("bad.mjs"), "(function(){ return {")?;
+        fs::write(
+            dir.join("a.yml"),
+            "name: a\nroutes:\n  /:\n    - method: GET\n      handler: hello\n",
+        )?;
+        fs::write(
+            dir.join("b.yml"),
+            "name: b\nroutes:\n  /:\n    - method: GET\n      handler: missing\n",
+        )?;
+        fs::write(
+            dir.join("server.yml"),
+            r#"
+tenants:
+  - { host: a.com, code: a.mjs, config: a.yml }
+  - { host: b.com, code: a.mjs, config: b.yml }
+  - { host: c.com, code: bad.mjs, config: a.yml }
+  - { host: d.com, code: none.mjs, config: a.yml }
+  - { host: A.com, code: a.mjs, config: a.yml }
+"#,
+        )?;
+        let config = ServerConfig::load(dir.join("server.yml"))?;
+
+        let preflight = config.preflight(0, &ServerOptions::default());
+        let hosts: Vec<_> = preflight.tenants.iter().map(|t| t.host.as_str()).collect();
+        assert_eq!(hosts, ["a.com"]);
+        let failed: Vec<_> = preflight
+            .issues
+            .iter()
+            .map(|i| i.tenant.as_deref().unwrap())
+            .collect();
+        assert_eq!(failed, ["b.com", "c.com", "d.com", "a.com"]);
+        assert!(preflight.issues[0].message.contains("missing"));
+        let err = config
+            .preflight(0, &ServerOptions::default())
+            .finish(false)
+            .unwrap_err();
+        assert!(err.to_string().contains("d.com"));
+        assert_eq!(preflight.finish(true)?.len(), 1);
+
+        // 服务器级别的问题在 partial start 时也失败
+        let taken = TcpListener::bind("0.0.0.0:0")?;
+        let port = taken.local_addr()?.port();
+        let preflight = config.preflight(port, &ServerOptions::default());
+        assert!(preflight.issues.iter().any(|i| i.tenant.is_none()));
+        assert!(preflight.finish(true).is_err());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail, ensure};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{AppState, ProjectConfig};
+
+/// 预览环境的 host 为 `<name>--<tenant>`
+pub const PREVIEW_SEPARATOR: &str = "--";
+/// 未指定时预览环境保留的时间
+pub const DEFAULT_PREVIEW_TTL: Duration = Duration::from_secs(24 * 3600);
+const MAX_PREVIEW_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+/// 每个 tenant 同时存在的预览环境数量上限
+const MAX_PREVIEWS: usize = 10;
+/// 检查预览环境是否过期的间隔
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// tenant 的一个预览环境，过期后自动删除
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preview {
+    pub name: String,
+    /// 预览环境的 host
+    pub host: String,
+    /// 所属的 tenant
+    pub tenant: String,
+    pub expires_at: String,
+    #[serde(skip)]
+    expires: DateTime<Utc>,
+}
+
+/// 解析 `30m`、`12h`、`7d` 形式的保留时间，没有单位时为秒
+pub fn parse_ttl(s: &str) -> Result<Duration> {
+    let (n, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let n: u64 = n.parse().with_context(|| format!("invalid ttl: {s}"))?;
+    let secs = match unit {
+        "" | "s" => n,
+        "m" => n * 60,
+        "h" => n * 3600,
+        "d" => n * 24 * 3600,
+        _ => bail!("invalid ttl unit in {s}, expected s, m, h or d"),
+    };
+    Ok(Duration::from_secs(secs))
+}
+
+/// 预览名称作为 host 的一部分，只能是小写字母、数字和单个 `-`
+fn check_name(name: &str) -> Result<()> {
+    ensure!(
+        !name.is_empty()
+            && name.len() <= 63
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+            && !name.starts_with('-')
+            && !name.ends_with('-')
+            && !name.contains(PREVIEW_SEPARATOR),
+        "invalid preview name {name:?}, use lowercase letters, digits and single dashes"
+    );
+    Ok(())
+}
+
+impl AppState {
+    /// 把代码部署到 tenant 的预览环境 `<name>--<tenant>`，已存在时替换代码并重新计算过期时间
+    pub fn deploy_preview(
+        &self,
+        tenant: &str,
+        name: &str,
+        code: String,
+        config: ProjectConfig,
+        ttl: Option<Duration>,
+        actor: &str,
+    ) -> Result<Preview> {
+        check_name(name)?;
+        ensure!(
+            self.routers.contains_key(tenant) && !self.previews.contains_key(tenant),
+            "Tenant not found: {tenant}"
+        );
+        let ttl = ttl.unwrap_or(DEFAULT_PREVIEW_TTL);
+        ensure!(
+            ttl <= MAX_PREVIEW_TTL,
+            "ttl can't be longer than {} days",
+            MAX_PREVIEW_TTL.as_secs() / 24 / 3600
+        );
+        let host = format!("{name}{PREVIEW_SEPARATOR}{tenant}");
+        let exists = self.previews.contains_key(&host);
+        if !exists {
+            ensure!(
+                !self.routers.contains_key(&host),
+                "{host} is a tenant, not a preview"
+            );
+            ensure!(
+                self.previews(tenant).len() < MAX_PREVIEWS,
+                "{tenant} already has {MAX_PREVIEWS} previews"
+            );
+        }
+
+        match exists {
+            true => self.swap_with_config(&host, code, &config, actor)?,
+            false => self.add_tenant_with_config(&host, code, &config, actor)?,
+        }
+        let expires = Utc::now() + ttl;
+        let preview = Preview {
+            name: name.to_string(),
+            host: host.clone(),
+            tenant: tenant.to_string(),
+            expires_at: expires.to_rfc3339_opts(SecondsFormat::Secs, true),
+            expires,
+        };
+        self.previews.insert(host, preview.clone());
+        Ok(preview)
+    }
+
+    /// tenant 的预览环境，按名称排序
+    pub fn previews(&self, tenant: &str) -> Vec<Preview> {
+        let mut previews: Vec<_> = self
+            .previews
+            .iter()
+            .filter(|p| p.tenant == tenant)
+            .map(|p| p.value().clone())
+            .collect();
+        previews.sort_by(|a, b| a.name.cmp(&b.name));
+        previews
+    }
+
+    pub fn remove_preview(&self, tenant: &str, name: &str, actor: &str) -> Result<()> {
+        let host = format!("{name}{PREVIEW_SEPARATOR}{tenant}");
+        ensure!(
+            self.previews.contains_key(&host),
+            "Preview not found: {host}"
+        );
+        self.remove_tenant(&host, actor)
+    }
+
+    /// 删除过期的预览环境，返回删除的 host
+    pub(crate) fn expire_previews(&self) -> Vec<String> {
+        let now = Utc::now();
+        let expired: Vec<_> = self
+            .previews
+            .iter()
+            .filter(|p| p.expires <= now)
+            .map(|p| p.key().clone())
+            .collect();
+        for host in &expired {
+            match self.remove_tenant(host, "dino-server") {
+                Ok(()) => info!("Preview {host} expired"),
+                Err(e) => warn!("failed to remove expired preview {host}: {e:#}"),
+            }
+        }
+        expired
+    }
+
+    /// 删除 tenant 时一起删除它的预览环境
+    pub(crate) fn remove_previews_of(&self, tenant: &str, actor: &str) {
+        self.previews.remove(tenant);
+        for preview in self.previews(tenant) {
+            if let Err(e) = self.remove_tenant(&preview.host, actor) {
+                warn!("failed to remove preview {}: {e:#}", preview.host);
+            }
+        }
+    }
+}
+
+pub(crate) fn spawn_cleanup(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            state.expire_previews();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::SwappableAppRouter;
+
+    fn config() -> Result<ProjectConfig> {
+        Ok(serde_yaml::from_str(
+            "name: test\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?)
+    }
+
+    fn code(body: &str) -> String {
+        format!(
+            "(function(){{ async function hello() {{ return {{ status: 200, headers: {{}}, body: '{body}' }}; }} return {{ hello }}; }})();"
+        )
+    }
+
+    #[test]
+    fn previews_should_expire_and_follow_tenant() -> Result<()> {
+        let routers = DashMap::new();
+        routers.insert(
+            "app.localhost".to_string(),
+            SwappableAppRouter::try_new(code("main"), config()?.routes)?,
+        );
+        let state = AppState::with_routers(routers);
+
+        let preview = state.deploy_preview(
+            "app.localhost",
+            "feature-x",
+            code("x"),
+            config()?,
+            None,
+            "ci",
+        )?;
+        assert_eq!(preview.host, "feature-x--app.localhost");
+        let router = state.routers.get(&preview.host).unwrap().load();
+        assert_eq!(router.code, code("x"));
+
+        // 再次部署时替换代码
+        state.deploy_preview(
+            "app.localhost",
+            "feature-x",
+            code("x2"),
+            config()?,
+            Some(Duration::ZERO),
+            "ci",
+        )?;
+        assert_eq!(state.versions(&preview.host).unwrap().0, 2);
+        state.deploy_preview("app.localhost", "other", code("o"), config()?, None, "ci")?;
+
+        let err = state
+            .deploy_preview(
+                "app.localhost",
+                "Feature/X",
+                code("x"),
+                config()?,
+                None,
+                "ci",
+            )
+            .unwrap_err();
+        assert!(err.to_string().starts_with("invalid preview name"));
+        let err = state
+            .deploy_preview(&preview.host, "nested", code("x"), config()?, None, "ci")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Tenant not found: feature-x--app.localhost"
+        );
+
+        assert_eq!(state.expire_previews(), ["feature-x--app.localhost"]);
+        assert!(!state.routers.contains_key(&preview.host));
+        let names: Vec<_> = state
+            .previews("app.localhost")
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, ["other"]);
+
+        state.remove_tenant("app.localhost", "admin")?;
+        assert!(state.routers.is_empty());
+        assert!(state.previews.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_ttl_should_support_units() -> Result<()> {
+        assert_eq!(parse_ttl("90")?, Duration::from_secs(90));
+        assert_eq!(parse_ttl("30m")?, Duration::from_secs(1800));
+        assert_eq!(parse_ttl("2d")?, Duration::from_secs(2 * 24 * 3600));
+        assert!(parse_ttl("2w").is_err());
+        assert!(parse_ttl("h").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwapOption;
+use tracing::{info, warn};
+
+use crate::{AppState, ServerConfig, logging, router::routes_hash};
+
+/// 通过 SIGHUP 或控制 socket 触发 reload 时的操作者
+const RELOAD_ACTOR: &str = "dino-server (reload)";
+
+/// 从磁盘重新加载 tenant 的配置
+#[derive(Debug, Clone)]
+pub struct ReloadOptions {
+    /// 服务器配置文件，每次 reload 都会重新读取
+    pub server_config: PathBuf,
+    /// 控制 socket，写入一行 `reload` 即触发 reload
+    pub control_socket: Option<PathBuf>,
+}
+
+/// 服务器配置文件和最近一次加载的内容，像路由一样整体替换
+#[derive(Debug)]
+pub(crate) struct ServerConfigState {
+    path: PathBuf,
+    current: ArcSwapOption<ServerConfig>,
+    // SIGHUP、控制 socket 和管理接口同时触发时依次执行
+    lock: Mutex<()>,
+}
+
+impl ServerConfigState {
+    /// 读取启动时的配置，用于在 reload 时比较哪些设置发生了变化
+    pub(crate) fn new(path: PathBuf) -> Self {
+        let current = match ServerConfig::load(&path) {
+            Ok(config) => Some(Arc::new(config)),
+            Err(e) => {
+                warn!("Failed to load {}: {e:#}", path.display());
+                None
+            }
+        };
+        Self {
+            path,
+            current: ArcSwapOption::new(current),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl AppState {
+    /// 按服务器配置重新读取所有 tenant 的代码和路由，代码、路由和 worker 设置都没有变化的
+    /// tenant 不会重启，新增的 tenant 会被添加，从配置中删除的 tenant 会被删除，并更新日志级别。
+    /// 任何 tenant 读取失败时不做修改。返回发生变化的 tenant
+    pub fn reload_from(&self, config: &ServerConfig, actor: &str) -> Result<Vec<String>> {
+        let _guard = self.server_config.as_ref().map(|s| s.lock.lock().unwrap());
+        let sources = config
+            .tenants
+            .iter()
+            .map(|tenant| Ok((tenant, tenant.read()?)))
+            .collect::<Result<Vec<_>>>()?;
+        logging::set_level(&config.logging.level)?;
+        let previous = self
+            .server_config
+            .as_ref()
+            .and_then(|s| s.current.swap(Some(Arc::new(config.clone()))));
+        if let Some(previous) = &previous
+            && (previous.logging.format != config.logging.format
+                || previous.logging.sink != config.logging.sink)
+        {
+            warn!("Changes of logging format and sink take effect after restart");
+        }
+
+        let mut changed = vec![];
+        for (tenant, (code, config)) in sources {
+            let mut settings = tenant.worker.clone();
+            if config.timezone.is_some() {
+                settings.timezone = config.timezone;
+            }
+            if let Some(limit) = config.memory_limit_mb {
+                settings.memory_limit_mb = limit;
+            }
+            let settings_changed = self.worker_settings(&tenant.host) != settings;
+            self.set_worker_settings(&tenant.host, settings);
+            self.apply_config(&tenant.host, &config);
+            self.set_aliases(&tenant.host, &tenant.aliases)?;
+            let routes = config.routes;
+            let Some(router) = self.routers.get(&tenant.host).map(|r| r.load()) else {
+                self.add_tenant(&tenant.host, code, routes, actor)?;
+                changed.push(tenant.host.clone());
+                continue;
+            };
+            if router.code == code && router.routes_hash == routes_hash(&routes) {
+                if settings_changed {
+                    self.restart_worker(&tenant.host, actor)?;
+                    changed.push(tenant.host.clone());
+                }
+                continue;
+            }
+            self.swap(&tenant.host, code, routes, actor)?;
+            changed.push(tenant.host.clone());
+        }
+
+        // 只删除上一次配置中有的 tenant，通过管理接口添加的 tenant 不受影响
+        for tenant in previous.iter().flat_map(|p| &p.tenants) {
+            if !config.tenants.iter().any(|t| t.host == tenant.host)
+                && self.routers.contains_key(&tenant.host)
+            {
+                self.remove_tenant(&tenant.host, actor)?;
+                changed.push(tenant.host.clone());
+            }
+        }
+        Ok(changed)
+    }
+
+    /// 重新读取服务器配置文件并 reload
+    pub(crate) fn reload(&self, actor: &str) -> Result<Vec<String>> {
+        let state = self
+            .server_config
+            .as_ref()
+            .context("server was not started with a server config")?;
+        let config = ServerConfig::load(&state.path)?;
+        let changed = self.reload_from(&config, actor)?;
+        info!(
+            "Reloaded tenants from {}: {:?}",
+            state.path.display(),
+            changed
+        );
+        Ok(changed)
+    }
+}
+
+/// 监听 SIGHUP 和控制 socket
+pub(crate) fn spawn(state: AppState, options: ReloadOptions) -> Result<()> {
+    imp::spawn(state, options)
+}
+
+#[cfg(unix)]
+mod imp {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixListener,
+        signal::unix::{SignalKind, signal},
+    };
+
+    use super::*;
+
+    pub(super) fn spawn(state: AppState, options: ReloadOptions) -> Result<()> {
+        let mut hangup = signal(SignalKind::hangup())?;
+        let hup_state = state.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("SIGHUP received");
+                if let Err(e) = hup_state.reload(RELOAD_ACTOR) {
+                    warn!("reload failed: {e:#}");
+                }
+            }
+        });
+
+        let Some(socket) = options.control_socket else {
+            return Ok(());
+        };
+        // 上次异常退出可能留下 socket 文件
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)
+            .with_context(|| format!("failed to bind control socket {}", socket.display()))?;
+        info!("Control socket: {}", socket.display());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let reply = match line.trim() {
+                        "reload" => match state.reload(RELOAD_ACTOR) {
+                            Ok(changed) => format!("ok {}\n", changed.join(",")),
+                            Err(e) => format!("error {e:#}\n"),
+                        },
+                        cmd => format!("error unknown command: {cmd}\n"),
+                    };
+                    if writer.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::*;
+
+    pub(super) fn spawn(_state: AppState, _options: ReloadOptions) -> Result<()> {
+        warn!("reload via signal or control socket is only supported on unix");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+
+    #[test]
+    fn reload_from_should_swap_changed_tenants() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dino-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let code = "(function(){ function hello(){ return { status: 200, body: 'v1' }; } return { hello }; })();";
+        std::fs::write(dir.join("a.mjs"), code)?;
+        std::fs::write(
+            dir.join("a.yml"),
+            "name: a\nroutes:\n  /:\n    - method: GET\n      handler: hello\n",
+        )?;
+        std::fs::write(
+            dir.join("server.yml"),
+            "tenants:\n  - { host: a.com, code: a.mjs, config: a.yml }\n",
+        )?;
+        let config = ServerConfig::load(dir.join("server.yml"))?;
+
+        let state = AppState::with_routers(DashMap::new());
+        assert_eq!(state.reload_from(&config, "test")?, vec!["a.com"]);
+        // 没有变化时不会重启
+        assert!(state.reload_from(&config, "test")?.is_empty());
+
+        std::fs::write(dir.join("a.mjs"), code.replace("v1", "v2"))?;
+        assert_eq!(state.reload_from(&config, "test")?, vec!["a.com"]);
+        assert_eq!(state.tenants()[0].history, 1);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reload_should_apply_settings_and_remove_dropped_tenants() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dino-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let code = "(function(){ function hello(){ return { status: 200, body: 'ok' }; } return { hello }; })();";
+        std::fs::write(dir.join("a.mjs"), code)?;
+        std::fs::write(
+            dir.join("a.yml"),
+            "name: a\nroutes:\n  /:\n    - method: GET\n      handler: hello\n",
+        )?;
+        let server = dir.join("server.yml");
+        std::fs::write(
+            &server,
+            "tenants:\n  - { host: a.com, code: a.mjs, config: a.yml }\n  - { host: b.com, code: a.mjs, config: a.yml }\n",
+        )?;
+
+        let mut state = AppState::with_routers(DashMap::new());
+        state.server_config = Some(Arc::new(ServerConfigState::new(server.clone())));
+        assert_eq!(state.reload("test")?, vec!["a.com", "b.com"]);
+
+        // a.com 只修改了 worker 设置，b.com 被删除
+        std::fs::write(
+            &server,
+            "tenants:\n  - { host: a.com, code: a.mjs, config: a.yml, min_workers: 2, max_workers: 2 }\n",
+        )?;
+        assert_eq!(state.reload("test")?, vec!["a.com", "b.com"]);
+        let tenants = state.tenants();
+        assert_eq!(tenants.len(), 1);
+        assert_eq!(tenants[0].host, "a.com");
+        assert!(state.reload("test")?.is_empty());
+
+        // 读取失败时不做修改
+        std::fs::write(
+            &server,
+            "tenants:\n  - { host: c.com, code: missing.mjs, config: a.yml }\n",
+        )?;
+        assert!(state.reload("test").is_err());
+        assert_eq!(state.tenants().len(), 1);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    fs::{File, OpenOptions},
+    hash::{BuildHasher, RandomState},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Payload, Req};
+
+/// 请求执行时使用的随机数种子和冻结的 `Date.now()`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub now: f64,
+}
+
+/// 记录文件中的一行，足以让 `dino replay` 重新执行该请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayRecord {
+    pub host: String,
+    pub handler: String,
+    pub req: Req,
+    pub replay: Replay,
+    pub status: u16,
+    pub body: Option<Payload>,
+}
+
+/// 以 JSON Lines 格式追加记录请求
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl Replay {
+    /// 使用随机种子和当前时间
+    pub fn new() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as f64;
+        Self {
+            seed: RandomState::new().hash_one(now.to_bits()),
+            now,
+        }
+    }
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    pub fn try_new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open record file {}", path.display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn record(&self, record: &ReplayRecord) -> Result<()> {
+        let line = serde_json::to_string(record)?;
+        writeln!(self.file.lock().unwrap(), "{line}")?;
+        Ok(())
+    }
+}
+
+impl ReplayRecord {
+    /// 读取记录文件中的所有请求
+    pub fn load(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let file = File::open(path.as_ref())?;
+        BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use anyhow::{Context, Result, ensure};
+use axum::{
+    body::{Body, Bytes},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    http::{HeaderMap, Method, Response, Uri},
+};
+use dashmap::DashMap;
+use rquickjs::{Ctx, Exception, Function};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info};
+
+use crate::{
+    AppState, Priority, RouteKind, assemble_req,
+    engine::{Req, Resp},
+    get_router,
+};
+
+/// `rooms` 对象，连接和房间由服务器维护，worker 重启后依然有效
+pub(crate) const ROOMS: &str = r#"
+{
+  const text = (msg) => (typeof msg === "string" ? msg : JSON.stringify(msg));
+  globalThis.rooms = {
+    join: (ws, name) => __dino_rooms_join(String(ws), String(name)),
+    leave: (ws, name) => __dino_rooms_leave(String(ws), String(name)),
+    broadcast: (name, msg) => __dino_rooms_broadcast(String(name), text(msg)),
+    send: (ws, msg) => __dino_rooms_send(String(ws), text(msg)),
+    members: (name) => __dino_rooms_members(String(name)),
+  };
+}
+"#;
+
+/// tenant 的 WebSocket 连接和它们加入的房间
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Rooms {
+    next_id: Arc<AtomicU64>,
+    /// 连接 id -> 连接
+    conns: Arc<DashMap<String, Connection>>,
+    /// (tenant, 房间名) -> 连接 id
+    rooms: Arc<DashMap<(String, String), HashSet<String>>>,
+}
+
+#[derive(Debug)]
+struct Connection {
+    host: String,
+    send: UnboundedSender<String>,
+    rooms: HashSet<String>,
+}
+
+impl Rooms {
+    /// 注册一个连接，发给它的消息从返回的 receiver 中读取
+    pub(crate) fn connect(&self, host: &str) -> (String, UnboundedReceiver<String>) {
+        let id = format!("ws-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (send, recv) = mpsc::unbounded_channel();
+        let conn = Connection {
+            host: host.to_string(),
+            send,
+            rooms: HashSet::new(),
+        };
+        self.conns.insert(id.clone(), conn);
+        (id, recv)
+    }
+
+    /// 移除连接，同时退出它加入的所有房间
+    pub(crate) fn disconnect(&self, id: &str) {
+        let Some((_, conn)) = self.conns.remove(id) else {
+            return;
+        };
+        for room in conn.rooms {
+            self.remove_member(&conn.host, &room, id);
+        }
+    }
+
+    pub(crate) fn join(&self, host: &str, id: &str, room: &str) -> Result<()> {
+        self.connection(host, id)?.rooms.insert(room.to_string());
+        self.rooms
+            .entry((host.to_string(), room.to_string()))
+            .or_default()
+            .insert(id.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn leave(&self, host: &str, id: &str, room: &str) -> Result<()> {
+        self.connection(host, id)?.rooms.remove(room);
+        self.remove_member(host, room, id);
+        Ok(())
+    }
+
+    /// 发送给房间中的所有连接，返回发送的连接数
+    pub(crate) fn broadcast(&self, host: &str, room: &str, msg: &str) -> usize {
+        let members = self.members(host, room);
+        members
+            .iter()
+            .filter(|id| self.send(host, id, msg).is_ok())
+            .count()
+    }
+
+    pub(crate) fn send(&self, host: &str, id: &str, msg: &str) -> Result<()> {
+        self.connection(host, id)?
+            .send
+            .send(msg.to_string())
+            .ok()
+            .with_context(|| format!("connection {id} is closed"))
+    }
+
+    pub(crate) fn members(&self, host: &str, room: &str) -> Vec<String> {
+        let mut members: Vec<_> = self
+            .rooms
+            .get(&(host.to_string(), room.to_string()))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default();
+        members.sort();
+        members
+    }
+
+    /// tenant 只能操作自己的连接
+    fn connection(
+        &self,
+        host: &str,
+        id: &str,
+    ) -> Result<dashmap::mapref::one::RefMut<'_, String, Connection>> {
+        let conn = self
+            .conns
+            .get_mut(id)
+            .with_context(|| format!("connection not found: {id}"))?;
+        ensure!(conn.host == host, "connection not found: {id}");
+        Ok(conn)
+    }
+
+    fn remove_member(&self, host: &str, room: &str, id: &str) {
+        let key = (host.to_string(), room.to_string());
+        if let Some(mut ids) = self.rooms.get_mut(&key) {
+            ids.remove(id);
+        }
+        self.rooms.remove_if(&key, |_, ids| ids.is_empty());
+    }
+}
+
+impl AppState {
+    /// 请求匹配到 WebSocket 路由时升级连接，其他路由返回 None，按普通请求处理
+    pub(crate) fn upgrade_websocket(
+        &self,
+        host: &str,
+        uri: &Uri,
+        query: &HashMap<String, String>,
+        headers: &HeaderMap,
+        ws: WebSocketUpgrade,
+    ) -> Result<Option<Response<Body>>> {
+        let router = get_router(host.to_string(), self)?;
+        let matched = router.match_it(Method::GET, uri.path())?;
+        if matched.value.kind != RouteKind::WebSocket {
+            return Ok(None);
+        }
+        let handler = router.handler(matched.value.handler).clone();
+        let priority = matched.value.priority;
+        let req = assemble_req(
+            query.clone(),
+            &matched,
+            Method::GET,
+            uri,
+            headers,
+            Bytes::new(),
+        )?;
+        let (state, host) = (self.clone(), host.to_string());
+        Ok(Some(ws.on_upgrade(move |socket| {
+            state.serve_websocket(host, handler, priority, req, socket)
+        })))
+    }
+
+    /// 处理一个 WebSocket 连接：连接建立、收到文本消息和关闭时各调用一次 handler，
+    /// `req.event` 为 `open`、`message` 或 `close`，`req.ws` 为连接 id。
+    /// handler 返回的 body 发送给当前连接
+    pub(crate) async fn serve_websocket(
+        self,
+        host: String,
+        handler: Arc<str>,
+        priority: Priority,
+        req: Req,
+        mut socket: WebSocket,
+    ) {
+        let (id, mut outgoing) = self.rooms.connect(&host);
+        info!("WebSocket {id} connected to {host}");
+        let event = |event: &str, body: Option<String>| {
+            let mut req = req.clone();
+            req.ws = Some(id.clone());
+            req.event = Some(event.to_string());
+            req.body = body.map(Into::into);
+            req
+        };
+
+        self.websocket_event(&host, &handler, priority, &id, event("open", None))
+            .await;
+        loop {
+            tokio::select! {
+                msg = socket.recv() => match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        let req = event("message", Some(text.to_string()));
+                        self.websocket_event(&host, &handler, priority, &id, req).await;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    // ping/pong 由 axum 处理，二进制消息不支持
+                    Some(Ok(_)) => {}
+                },
+                Some(msg) = outgoing.recv() => {
+                    if socket.send(Message::Text(msg.into())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.rooms.disconnect(&id);
+        self.websocket_event(&host, &handler, priority, &id, event("close", None))
+            .await;
+        info!("WebSocket {id} disconnected from {host}");
+    }
+
+    async fn websocket_event(
+        &self,
+        host: &str,
+        handler: &Arc<str>,
+        priority: Priority,
+        id: &str,
+        req: Req,
+    ) {
+        let ret = self
+            .send_blocking(host.to_string(), handler.clone(), priority, req)
+            .await;
+        match ret {
+            Ok(Resp {
+                body: Some(body), ..
+            }) => {
+                let _ = self.rooms.send(host, id, &String::from_utf8_lossy(&body));
+            }
+            Ok(_) => {}
+            Err(e) => error!("WebSocket handler error: {e:#}"),
+        }
+    }
+}
+
+pub(crate) fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    let global = ctx.globals();
+    global.set("__dino_rooms_join", Function::new(ctx.clone(), join)?)?;
+    global.set("__dino_rooms_leave", Function::new(ctx.clone(), leave)?)?;
+    global.set(
+        "__dino_rooms_broadcast",
+        Function::new(ctx.clone(), broadcast)?,
+    )?;
+    global.set("__dino_rooms_send", Function::new(ctx.clone(), send)?)?;
+    global.set("__dino_rooms_members", Function::new(ctx.clone(), members)?)?;
+    ctx.eval::<(), _>(ROOMS)
+}
+
+/// 以当前 worker 所属的 tenant 调用 `f`
+fn with_rooms<T>(ctx: &Ctx, f: impl FnOnce(&Rooms, &str) -> Result<T>) -> rquickjs::Result<T> {
+    let ret = (|| {
+        let host = crate::binding::caller().context("rooms are not available here")?;
+        let state = AppState::get_current().context("server is not running")?;
+        f(&state.rooms, &host)
+    })();
+    ret.map_err(|e| Exception::throw_message(ctx, &format!("{e:#}")))
+}
+
+fn join(ctx: Ctx, ws: String, room: String) -> rquickjs::Result<()> {
+    with_rooms(&ctx, |rooms, host| rooms.join(host, &ws, &room))
+}
+
+fn leave(ctx: Ctx, ws: String, room: String) -> rquickjs::Result<()> {
+    with_rooms(&ctx, |rooms, host| rooms.leave(host, &ws, &room))
+}
+
+fn broadcast(ctx: Ctx, room: String, msg: String) -> rquickjs::Result<usize> {
+    with_rooms(&ctx, |rooms, host| Ok(rooms.broadcast(host, &room, &msg)))
+}
+
+fn send(ctx: Ctx, ws: String, msg: String) -> rquickjs::Result<()> {
+    with_rooms(&ctx, |rooms, host| rooms.send(host, &ws, &msg))
+}
+
+fn members(ctx: Ctx, room: String) -> rquickjs::Result<Vec<String>> {
+    with_rooms(&ctx, |rooms, host| Ok(rooms.members(host, &room)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rooms_should_broadcast_to_members() -> Result<()> {
+        let rooms = Rooms::default();
+        let (a, mut a_recv) = rooms.connect("chat.com");
+        let (b, mut b_recv) = rooms.connect("chat.com");
+        let (other, _) = rooms.connect("other.com");
+
+        rooms.join("chat.com", &a, "lobby")?;
+        rooms.join("chat.com", &b, "lobby")?;
+        // 不能操作其他 tenant 的连接
+        assert!(rooms.join("chat.com", &other, "lobby").is_err());
+        assert_eq!(rooms.members("chat.com", "lobby"), [a.clone(), b.clone()]);
+        assert!(rooms.members("other.com", "lobby").is_empty());
+
+        assert_eq!(rooms.broadcast("chat.com", "lobby", "hi"), 2);
+        assert_eq!(a_recv.try_recv()?, "hi");
+        assert_eq!(b_recv.try_recv()?, "hi");
+
+        rooms.leave("chat.com", &a, "lobby")?;
+        rooms.disconnect(&b);
+        assert_eq!(rooms.broadcast("chat.com", "lobby", "bye"), 0);
+        assert!(rooms.members("chat.com", "lobby").is_empty());
+        assert!(rooms.send("chat.com", &b, "x").is_err());
+        Ok(())
+    }
+}
@@ -1,90 +1,280 @@
-use anyhow::Result;
-use axum::http::Method;
-use std::sync::Arc;
+use anyhow::{Result, bail};
+use http::Method;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
 use arc_swap::ArcSwap;
+use indexmap::IndexMap;
 use matchit::{Match, Router};
 
-use crate::config::ProjectRoutes;
+use crate::{
+    audit::short_hash,
+    config::{
+        Priority, ProjectRoutes, RouteKind, RoutingOptions, TrailingSlash, UploadMode,
+        serialize_method, versioned_path,
+    },
+    contract::Contracts,
+};
 
 #[derive(Clone, Debug)]
 pub struct SwappableAppRouter {
     pub routes: Arc<ArcSwap<AppRouter>>,
+    /// 最近一次替换的路由表的代数
+    generation: Arc<AtomicU64>,
 }
 
 #[derive(Clone, Debug)]
 pub struct AppRouter {
     pub routes: Router<MethodRoute>,
     pub code: String,
+    /// 路由配置的 hash，用于判断配置是否变化
+    pub routes_hash: String,
+    /// 路由清单，每行为 `GET /path handler`，有版本的路由后面加上 `v1,v2`
+    pub manifest: Arc<[String]>,
+    /// 路由声明的 API 版本，按第一次出现的顺序
+    pub versions: Arc<[String]>,
+    /// 路由声明的响应契约
+    pub contracts: Contracts,
+    /// 路由中出现的 handler 名称，按 `HandlerId` 索引
+    pub handlers: Handlers,
+    /// 按配置中声明顺序排列的路由表
+    pub table: Arc<[RouteEntry]>,
+    /// 路由表的代数，创建时为 1，每次替换或恢复加 1
+    pub generation: u64,
 }
 
+/// 路由表中的一条路由
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteEntry {
+    pub path: String,
+    #[serde(serialize_with = "serialize_method")]
+    pub method: Method,
+    pub handler: String,
+    #[serde(rename = "type")]
+    pub kind: RouteKind,
+    pub priority: Priority,
+    pub upload: UploadMode,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<String>,
+}
+
+/// 路由构建时为 handler 名称分配的 id，匹配路由时不再复制名称
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerId(u32);
+
+/// 路由匹配的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Endpoint {
+    pub handler: HandlerId,
+    pub priority: Priority,
+    pub kind: RouteKind,
+    pub upload: UploadMode,
+}
+
+/// 驻留的 handler 名称表，同名 handler 共享一个 `Arc<str>`
+#[derive(Debug, Clone, Default)]
+pub struct Handlers(Arc<[Arc<str>]>);
+
 #[derive(Debug, Default, Clone)]
 pub struct MethodRoute {
-    get: Option<String>,
-    post: Option<String>,
-    put: Option<String>,
-    delete: Option<String>,
-    patch: Option<String>,
-    head: Option<String>,
-    options: Option<String>,
-    connect: Option<String>,
-    trace: Option<String>,
+    get: Option<Endpoint>,
+    post: Option<Endpoint>,
+    put: Option<Endpoint>,
+    delete: Option<Endpoint>,
+    patch: Option<Endpoint>,
+    head: Option<Endpoint>,
+    options: Option<Endpoint>,
+    connect: Option<Endpoint>,
+    trace: Option<Endpoint>,
+}
+
+pub(crate) fn routes_hash(routes: &ProjectRoutes) -> String {
+    let mut content = String::new();
+    for (path, methods) in routes {
+        for route in methods {
+            content.push_str(&format!("{path} {} {}\n", route.method, route.handler));
+            if let Some(schema) = &route.response {
+                content.push_str(&format!("{schema}\n"));
+            }
+            if route.priority != Priority::Normal {
+                content.push_str(&format!("priority {:?}\n", route.priority));
+            }
+            if route.kind != RouteKind::Http {
+                content.push_str(&format!("type {:?}\n", route.kind));
+            }
+            if route.upload != UploadMode::Buffered {
+                content.push_str(&format!("upload {:?}\n", route.upload));
+            }
+            if !route.versions.is_empty() {
+                content.push_str(&format!("versions {}\n", route.versions.join(",")));
+            }
+        }
+    }
+    short_hash(&content)
 }
 
 impl SwappableAppRouter {
     pub fn try_new(code: impl Into<String>, routes: ProjectRoutes) -> Result<Self> {
-        let router = Self::get_router(routes)?;
+        let router = Self::build(code, routes, 1)?;
         Ok(Self {
-            routes: Arc::new(ArcSwap::from_pointee(AppRouter {
-                routes: router,
-                code: code.into(),
-            })),
+            routes: Arc::new(ArcSwap::from_pointee(router)),
+            generation: Arc::new(AtomicU64::new(1)),
         })
     }
 
     pub fn swap(&self, code: impl Into<String>, routes: ProjectRoutes) -> Result<()> {
-        let router = Self::get_router(routes)?;
-        self.routes.store(Arc::new(AppRouter {
-            routes: router,
-            code: code.into(),
-        }));
+        let router = Self::build(code, routes, self.generation() + 1)?;
+        self.generation.store(router.generation, Ordering::Relaxed);
+        self.routes.store(Arc::new(router));
         Ok(())
     }
 
-    pub fn load(&self) -> AppRouter {
-        AppRouter {
-            routes: self.routes.load_full().routes.clone(),
-            code: self.routes.load_full().code.clone(),
+    /// 恢复之前保存的版本，恢复后的路由表是新的一代
+    pub fn restore(&self, mut router: AppRouter) {
+        router.generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        self.routes.store(Arc::new(router));
+    }
+
+    /// 当前路由表的代数，缓存路由信息的调用方据此判断路由是否变化
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    fn build(code: impl Into<String>, routes: ProjectRoutes, generation: u64) -> Result<AppRouter> {
+        let routes_hash = routes_hash(&routes);
+        let contracts = Contracts::from_routes(&routes)?;
+        let (routes, handlers, table) = Self::get_router(routes)?;
+        let manifest = table
+            .iter()
+            .map(|r| match r.versions.is_empty() {
+                true => format!("{} {} {}", r.method, r.path, r.handler),
+                false => format!(
+                    "{} {} {} {}",
+                    r.method,
+                    r.path,
+                    r.handler,
+                    r.versions.join(",")
+                ),
+            })
+            .collect();
+        let mut versions: Vec<String> = vec![];
+        for version in table.iter().flat_map(|r| &r.versions) {
+            if !versions.contains(version) {
+                versions.push(version.clone());
+            }
         }
+        Ok(AppRouter {
+            routes,
+            code: code.into(),
+            routes_hash,
+            manifest,
+            versions: versions.into(),
+            contracts,
+            handlers,
+            table,
+            generation,
+        })
     }
 
-    fn get_router(routes: ProjectRoutes) -> Result<Router<MethodRoute>> {
-        let mut router = Router::new();
+    pub fn load(&self) -> AppRouter {
+        self.routes.load_full().as_ref().clone()
+    }
+
+    /// 有版本的路由在每个版本的前缀路径下注册一次，不带前缀的路径交给没有声明版本的
+    /// 路由，没有时交给最后声明的版本
+    fn get_router(
+        routes: ProjectRoutes,
+    ) -> Result<(Router<MethodRoute>, Handlers, Arc<[RouteEntry]>)> {
+        let mut paths: IndexMap<String, MethodRoute> = IndexMap::new();
+        let mut unversioned = HashSet::new();
+        let mut table = Vec::new();
+        let mut names: Vec<Arc<str>> = Vec::new();
+        let mut ids: HashMap<String, HandlerId> = HashMap::new();
         for (path, methods) in routes {
-            let mut method_route = MethodRoute::default();
+            paths.entry(path.clone()).or_default();
             for method in methods {
-                match method.method {
-                    Method::GET => method_route.get = Some(method.handler),
-                    Method::POST => method_route.post = Some(method.handler),
-                    Method::PUT => method_route.put = Some(method.handler),
-                    Method::DELETE => method_route.delete = Some(method.handler),
-                    Method::PATCH => method_route.patch = Some(method.handler),
-                    Method::HEAD => method_route.head = Some(method.handler),
-                    Method::OPTIONS => method_route.options = Some(method.handler),
-                    Method::CONNECT => method_route.connect = Some(method.handler),
-                    Method::TRACE => method_route.trace = Some(method.handler),
-                    _ => unreachable!(),
+                let handler = *ids.entry(method.handler).or_insert_with_key(|name| {
+                    names.push(name.as_str().into());
+                    HandlerId(names.len() as u32 - 1)
+                });
+                if method.kind.get_only() && method.method != Method::GET {
+                    bail!("{:?} route {path} must use GET", method.kind);
+                }
+                if method.kind != RouteKind::Http && method.upload != UploadMode::Buffered {
+                    bail!("{:?} route {path} can't stream uploads", method.kind);
+                }
+                let endpoint = Endpoint {
+                    handler,
+                    priority: method.priority,
+                    kind: method.kind,
+                    upload: method.upload,
+                };
+                let key = (path.clone(), method.method.clone());
+                if method.versions.is_empty() {
+                    unversioned.insert(key);
+                    paths[&path].set(&method.method, endpoint);
+                } else if !unversioned.contains(&key) {
+                    paths[&path].set(&method.method, endpoint);
                 }
+                for version in &method.versions {
+                    paths
+                        .entry(versioned_path(version, &path))
+                        .or_default()
+                        .set(&method.method, endpoint);
+                }
+                table.push(RouteEntry {
+                    path: path.clone(),
+                    method: method.method,
+                    handler: names[handler.0 as usize].to_string(),
+                    kind: method.kind,
+                    priority: method.priority,
+                    upload: method.upload,
+                    versions: method.versions,
+                });
             }
+        }
+        let mut router = Router::new();
+        for (path, method_route) in paths {
             router.insert(path, method_route)?;
         }
-        Ok(router)
+        Ok((router, Handlers(names.into()), table.into()))
+    }
+}
+
+impl MethodRoute {
+    fn set(&mut self, method: &Method, endpoint: Endpoint) {
+        let slot = match *method {
+            Method::GET => &mut self.get,
+            Method::POST => &mut self.post,
+            Method::PUT => &mut self.put,
+            Method::DELETE => &mut self.delete,
+            Method::PATCH => &mut self.patch,
+            Method::HEAD => &mut self.head,
+            Method::OPTIONS => &mut self.options,
+            Method::CONNECT => &mut self.connect,
+            Method::TRACE => &mut self.trace,
+            _ => unreachable!(),
+        };
+        *slot = Some(endpoint);
+    }
+}
+
+impl Handlers {
+    /// 返回 id 对应的名称，id 必须来自同一个路由表
+    pub fn get(&self, id: HandlerId) -> &Arc<str> {
+        &self.0[id.0 as usize]
     }
 }
 
 impl AppRouter {
-    #[allow(elided_named_lifetimes)]
-    pub fn match_it<'m, 'p>(&'m self, method: Method, path: &'p str) -> Result<Match<&'m str>>
+    #[allow(mismatched_lifetime_syntaxes)]
+    pub fn match_it<'m, 'p>(&'m self, method: Method, path: &'p str) -> Result<Match<Endpoint>>
     where
         'p: 'm,
     {
@@ -92,16 +282,17 @@ impl AppRouter {
             return Err(anyhow::anyhow!("No route found for path: {}", path));
         };
         let handler = match method {
-            Method::GET => ret.value.get.as_deref(),
-            Method::POST => ret.value.post.as_deref(),
-            Method::PUT => ret.value.put.as_deref(),
-            Method::DELETE => ret.value.delete.as_deref(),
-            Method::PATCH => ret.value.patch.as_deref(),
-            Method::HEAD => ret.value.head.as_deref(),
-            Method::OPTIONS => ret.value.options.as_deref(),
-            Method::CONNECT => ret.value.connect.as_deref(),
-            Method::TRACE => ret.value.trace.as_deref(),
-            _ => unreachable!(),
+            Method::GET => ret.value.get,
+            Method::POST => ret.value.post,
+            Method::PUT => ret.value.put,
+            Method::DELETE => ret.value.delete,
+            Method::PATCH => ret.value.patch,
+            Method::HEAD => ret.value.head,
+            Method::OPTIONS => ret.value.options,
+            Method::CONNECT => ret.value.connect,
+            Method::TRACE => ret.value.trace,
+            // 扩展方法（如 PROPFIND）无法在配置中声明
+            _ => None,
         }
         .ok_or_else(|| anyhow::anyhow!("No handler found for method: {}", method))?;
 
@@ -110,6 +301,104 @@ impl AppRouter {
             params: ret.params,
         })
     }
+
+    /// 按 `Accept` 头选择 API 版本，返回对应版本的路由路径。`Accept` 中的
+    /// `application/vnd.<name>.<version>+json` 或 `version=<version>` 参数指定版本，
+    /// 版本没有声明或者该版本没有这个路由时返回 None，按原路径处理
+    pub fn accept_version(&self, method: &Method, path: &str, accept: &str) -> Option<String> {
+        if self.versions.is_empty() {
+            return None;
+        }
+        let version = accept.split(',').find_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let vendor = parts
+                .next()?
+                .split_once("/vnd.")
+                .and_then(|(_, subtype)| subtype.split('+').next()?.rsplit('.').next());
+            let param = parts.find_map(|p| p.strip_prefix("version="));
+            [param, vendor]
+                .into_iter()
+                .flatten()
+                .map(|v| v.trim_matches('"'))
+                .find(|v| self.versions.iter().any(|declared| declared == v))
+        })?;
+        let path = versioned_path(version, path);
+        self.match_it(method.clone(), &path).is_ok().then_some(path)
+    }
+
+    /// `path` 没有匹配的路由时，按 `options` 查找只差结尾的 `/` 或大小写的路由，
+    /// 返回改写后可以匹配的路径
+    pub fn canonical_path(
+        &self,
+        method: &Method,
+        path: &str,
+        options: &RoutingOptions,
+    ) -> Option<String> {
+        if options.is_default() || self.match_it(method.clone(), path).is_ok() {
+            return None;
+        }
+        let mut candidates = vec![path.to_string()];
+        if options.trailing_slash != TrailingSlash::Strict {
+            match path.strip_suffix('/') {
+                Some(p) if !p.is_empty() => candidates.push(p.to_string()),
+                Some(_) => {}
+                None => candidates.push(format!("{path}/")),
+            }
+        }
+        candidates.into_iter().find_map(|candidate| {
+            if candidate != path && self.match_it(method.clone(), &candidate).is_ok() {
+                return Some(candidate);
+            }
+            options
+                .case_insensitive
+                .then(|| self.fold_case(method, &candidate))
+                .flatten()
+        })
+    }
+
+    /// 逐段与声明的路径比较，固定的段不区分大小写时换成声明的写法，参数段保留原文
+    fn fold_case(&self, method: &Method, path: &str) -> Option<String> {
+        let segments: Vec<&str> = path.split('/').collect();
+        let patterns = self.table.iter().flat_map(|r| {
+            let versioned = r.versions.iter().map(|v| versioned_path(v, &r.path));
+            std::iter::once(r.path.clone()).chain(versioned)
+        });
+        for pattern in patterns {
+            let mut folded = vec![];
+            for (i, part) in pattern.split('/').enumerate() {
+                // catch-all 参数匹配剩余的所有段
+                if part.starts_with("{*") {
+                    folded.extend_from_slice(segments.get(i..).unwrap_or_default());
+                    break;
+                }
+                match segments.get(i) {
+                    Some(s) if part.contains('{') => folded.push(*s),
+                    Some(s) if part.eq_ignore_ascii_case(s) => folded.push(part),
+                    _ => break,
+                }
+            }
+            let candidate = folded.join("/");
+            if folded.len() >= segments.len()
+                && candidate != path
+                && self.match_it(method.clone(), &candidate).is_ok()
+            {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    /// 匹配到的 handler 名称
+    pub fn handler(&self, id: HandlerId) -> &Arc<str> {
+        self.handlers.get(id)
+    }
+
+    /// 按声明顺序列出所有路由的 `(path, method, handler)`
+    pub fn routes_iter(&self) -> impl Iterator<Item = (&str, &Method, &str)> {
+        self.table
+            .iter()
+            .map(|r| (r.path.as_str(), &r.method, r.handler.as_str()))
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -124,13 +413,16 @@ mod tests {
         let router = SwappableAppRouter::try_new("", config.routes).unwrap();
         let app_router = router.load();
         let match_result = app_router.match_it(Method::GET, "/api/hello/123").unwrap();
-        assert_eq!(match_result.value, "hello");
+        assert_eq!(&**app_router.handler(match_result.value.handler), "hello");
         assert_eq!(match_result.params.get("id"), Some("123"));
 
         let match_result = app_router.match_it(Method::POST, "/api/goodbye/2").unwrap();
-        assert_eq!(match_result.value, "hello");
+        assert_eq!(&**app_router.handler(match_result.value.handler), "hello");
         assert_eq!(match_result.params.get("id"), Some("2"));
         assert_eq!(match_result.params.get("name"), Some("goodbye"));
+        // 同名 handler 共享同一个 id
+        let other = app_router.match_it(Method::GET, "/api/hello/1").unwrap();
+        assert_eq!(other.value.handler, match_result.value.handler);
     }
 
     #[test]
@@ -140,16 +432,140 @@ mod tests {
         let router = SwappableAppRouter::try_new("", config.routes).unwrap();
         let app_router = router.load();
         let m = app_router.match_it(Method::GET, "/api/hello/1").unwrap();
-        assert_eq!(m.value, "hello");
+        assert_eq!(&**app_router.handler(m.value.handler), "hello");
 
         let new_config = include_str!("../fixtures/config1.yml");
         let new_config: ProjectConfig = serde_yaml::from_str(new_config).unwrap();
         router.swap("", new_config.routes).unwrap();
         let app_router = router.load();
         let m = app_router.match_it(Method::GET, "/api/hello/1").unwrap();
-        assert_eq!(m.value, "hello2");
+        assert_eq!(&**app_router.handler(m.value.handler), "hello2");
 
         let m = app_router.match_it(Method::POST, "/api/goodbye/2").unwrap();
-        assert_eq!(m.value, "handler2");
+        assert_eq!(&**app_router.handler(m.value.handler), "handler2");
+    }
+
+    #[test]
+    fn app_router_should_list_routes_by_generation() -> Result<()> {
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: t\nroutes:\n  /a:\n    - method: GET\n      handler: a\n    - method: POST\n      handler: b\n  /s:\n    - method: GET\n      handler: s\n      type: sse\n",
+        )?;
+        let router = SwappableAppRouter::try_new("", config.routes)?;
+        let first = router.load();
+        assert_eq!(router.generation(), 1);
+        assert_eq!(
+            first.routes_iter().collect::<Vec<_>>(),
+            [
+                ("/a", &Method::GET, "a"),
+                ("/a", &Method::POST, "b"),
+                ("/s", &Method::GET, "s"),
+            ]
+        );
+        assert_eq!(first.table[2].kind, RouteKind::Sse);
+        assert_eq!(
+            serde_json::to_value(&first.table[1])?,
+            serde_json::json!({ "path": "/a", "method": "POST", "handler": "b", "type": "http", "priority": "normal", "upload": "buffered" })
+        );
+
+        let config: ProjectConfig =
+            serde_yaml::from_str("name: t\nroutes:\n  /b:\n    - method: PUT\n      handler: b\n")?;
+        router.swap("", config.routes)?;
+        let second = router.load();
+        assert_eq!((router.generation(), second.generation), (2, 2));
+        assert_eq!(second.routes_iter().count(), 1);
+
+        // 回滚到旧的路由表也是新的一代
+        router.restore(first);
+        assert_eq!((router.generation(), router.load().generation), (3, 3));
+        assert_eq!(router.load().routes_iter().count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn app_router_should_select_versions() -> Result<()> {
+        let config: ProjectConfig = r#"
+name: t
+routes:
+  /users/{id}:
+    - method: GET
+      handler: getUserV1
+      versions: [v1]
+    - method: GET
+      handler: getUser
+      versions: [v2, v3]
+    - method: DELETE
+      handler: deleteUser
+  /:
+    - method: GET
+      handler: index
+      versions: [v1]
+"#
+        .parse()?;
+        let router = SwappableAppRouter::try_new("", config.routes)?.load();
+        assert_eq!(&*router.versions, ["v1", "v2", "v3"]);
+        let handler = |method: Method, path: &str| -> Result<String> {
+            let m = router.match_it(method, path)?;
+            Ok(router.handler(m.value.handler).to_string())
+        };
+        assert_eq!(handler(Method::GET, "/v1/users/1")?, "getUserV1");
+        assert_eq!(handler(Method::GET, "/v3/users/1")?, "getUser");
+        // 不带版本的请求交给最后声明的版本
+        assert_eq!(handler(Method::GET, "/users/1")?, "getUser");
+        assert_eq!(handler(Method::GET, "/v1")?, "index");
+        assert_eq!(handler(Method::DELETE, "/users/1")?, "deleteUser");
+        assert!(handler(Method::DELETE, "/v1/users/1").is_err());
+        assert!(handler(Method::GET, "/v4/users/1").is_err());
+
+        let accept = |accept: &str| router.accept_version(&Method::GET, "/users/1", accept);
+        assert_eq!(
+            accept("application/vnd.acme.v1+json").as_deref(),
+            Some("/v1/users/1")
+        );
+        assert_eq!(
+            accept("text/html, application/json; version=v2").as_deref(),
+            Some("/v2/users/1")
+        );
+        assert_eq!(accept("application/vnd.acme.v4+json"), None);
+        assert_eq!(accept("application/json"), None);
+        assert_eq!(
+            router.manifest[0],
+            "GET /users/{id} getUserV1 v1".to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn app_router_should_find_canonical_paths() -> Result<()> {
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: t\nroutes:\n  /Users/{id}:\n    - method: GET\n      handler: user\n  /files/{*rest}:\n    - method: GET\n      handler: file\n  /docs/:\n    - method: GET\n      handler: docs\n",
+        )?;
+        let router = SwappableAppRouter::try_new("", config.routes)?.load();
+        let canonical = |path: &str, routing: &str| -> Result<Option<String>> {
+            let options: RoutingOptions = serde_yaml::from_str(routing)?;
+            Ok(router.canonical_path(&Method::GET, path, &options))
+        };
+
+        let ignore = "trailing_slash: ignore";
+        assert_eq!(
+            canonical("/Users/Ab/", ignore)?.as_deref(),
+            Some("/Users/Ab")
+        );
+        assert_eq!(canonical("/docs", ignore)?.as_deref(), Some("/docs/"));
+        assert_eq!(canonical("/Users/Ab", ignore)?, None);
+        assert_eq!(canonical("/users/Ab/", ignore)?, None);
+        assert_eq!(canonical("/Users/Ab/", "{}")?, None);
+
+        let fold = "case_insensitive: true";
+        assert_eq!(canonical("/USERS/Ab", fold)?.as_deref(), Some("/Users/Ab"));
+        assert_eq!(
+            canonical("/Files/A/b", fold)?.as_deref(),
+            Some("/files/A/b")
+        );
+        assert_eq!(canonical("/users/Ab/", fold)?, None);
+        let both = "{ trailing_slash: redirect, case_insensitive: true }";
+        assert_eq!(canonical("/users/Ab/", both)?.as_deref(), Some("/Users/Ab"));
+        assert_eq!(canonical("/DOCS", both)?.as_deref(), Some("/docs/"));
+        assert!(serde_yaml::from_str::<RoutingOptions>("trailing_slash: loose").is_err());
+        Ok(())
     }
 }
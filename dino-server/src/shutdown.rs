@@ -0,0 +1,235 @@
+use std::{
+    future::IntoFuture,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use axum::Router;
+use tokio::{
+    net::TcpListener,
+    sync::{Notify, watch},
+};
+use tracing::{info, warn};
+
+use crate::AppState;
+
+/// 退出时等待处理中的请求和 worker 队列的默认时间
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+/// 等待 worker 退出时检查的间隔
+const POLL: Duration = Duration::from_millis(10);
+
+/// 提供服务直到收到 SIGTERM、Ctrl-C 或 `shutdown` 的通知：服务端口和单独的管理端口
+/// （`admin`）都停止接受新连接，等待处理中的请求完成，再让所有 worker 处理完队列中的
+/// 请求后退出。两者合计最多等待 `timeout`，超时后不再等待 SSE、WebSocket 等长连接
+pub(crate) async fn serve(
+    listener: TcpListener,
+    app: Router,
+    admin: Option<(TcpListener, Router)>,
+    state: AppState,
+    shutdown: Option<Arc<Notify>>,
+    timeout: Duration,
+) -> Result<()> {
+    let (started, drain) = tokio::sync::oneshot::channel();
+    // 服务端口开始退出时通知管理端口，服务端口出错返回时 sender 被丢弃，管理端口同样退出
+    let (stop, stopped) = watch::channel(false);
+    let admin = admin.map(|(listener, app)| {
+        let mut stopped = stopped.clone();
+        let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+            let _ = stopped.wait_for(|stop| *stop).await;
+        });
+        tokio::spawn(server.into_future())
+    });
+    let server = axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(async move {
+            wait(shutdown).await;
+            info!("Shutting down, waiting up to {timeout:?} for in-flight requests");
+            let _ = stop.send(true);
+            let _ = started.send(Instant::now() + timeout);
+        })
+        .into_future();
+    let mut server = std::pin::pin!(server);
+    let deadline = tokio::select! {
+        ret = &mut server => {
+            ret?;
+            None
+        }
+        Ok(deadline) = drain => Some(deadline),
+    };
+    let deadline = match deadline {
+        Some(deadline) => {
+            match tokio::time::timeout_at(deadline.into(), &mut server).await {
+                Ok(ret) => ret?,
+                Err(_) => warn!("Connections still open after {timeout:?}, shutting down anyway"),
+            }
+            deadline
+        }
+        None => Instant::now() + timeout,
+    };
+    if let Some(admin) = admin {
+        match tokio::time::timeout_at(deadline.into(), admin).await {
+            Ok(Ok(Err(e))) => warn!("Admin API stopped: {e}"),
+            Ok(_) => {}
+            Err(_) => warn!("Admin connections still open after {timeout:?}, shutting down anyway"),
+        }
+    }
+    state.shutdown_workers(deadline).await;
+    info!("Server stopped");
+    Ok(())
+}
+
+/// 等待退出信号
+async fn wait(shutdown: Option<Arc<Notify>>) {
+    let notified = async {
+        match shutdown {
+            Some(notify) => notify.notified().await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = terminate() => info!("SIGTERM received"),
+        _ = tokio::signal::ctrl_c() => info!("Ctrl-C received"),
+        _ = notified => {}
+    }
+}
+
+#[cfg(unix)]
+async fn terminate() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            term.recv().await;
+        }
+        Err(e) => {
+            warn!("Failed to listen for SIGTERM: {e}");
+            std::future::pending().await
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate() {
+    std::future::pending().await
+}
+
+impl AppState {
+    /// 向所有 worker 发送 Shutdown，等待它们处理完队列中的请求后退出，最多等到 `deadline`
+    pub(crate) async fn shutdown_workers(&self, deadline: Instant) {
+        let handles: Vec<_> = self.workers.lock().unwrap().drain().collect();
+        for (_, handle) in &handles {
+            handle.shutdown();
+        }
+        let running = || {
+            handles
+                .iter()
+                .filter(|(_, h)| h.pool.size() > 0)
+                .map(|(host, _)| host.as_str())
+                .collect::<Vec<_>>()
+        };
+        while !running().is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(POLL).await;
+        }
+        let running = running();
+        if !running.is_empty() {
+            warn!("Workers still running at shutdown: {}", running.join(", "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProjectConfig, ServerOptions, SwappableAppRouter, TenantRouter};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn server_should_drain_requests_on_shutdown() -> Result<()> {
+        let code = r#"
+        (function(){
+            async function slow() {
+                const start = Date.now();
+                while (Date.now() - start < 500) {}
+                return { status: 200, headers: {}, body: "slow" };
+            }
+            async function fast() { return { status: 200, headers: {}, body: "fast" }; }
+            return { slow, fast };
+        })();
+        "#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            r#"
+name: test
+routes:
+  /slow:
+    - method: GET
+      handler: slow
+  /fast:
+    - method: GET
+      handler: fast
+"#,
+        )?;
+        let router = SwappableAppRouter::try_new(code, config.routes)?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let notify = Arc::new(Notify::new());
+        let options = ServerOptions {
+            shutdown: Some(notify.clone()),
+            ..Default::default()
+        };
+        let tenants = vec![TenantRouter::new("127.0.0.1".into(), router)];
+        let server = tokio::spawn(crate::start_server_on(listener, tenants, options));
+        let get = |path: &str| {
+            let url = format!("http://{addr}{path}");
+            tokio::task::spawn_blocking(move || -> Result<String> {
+                Ok(ureq::get(&url).call()?.into_string()?)
+            })
+        };
+
+        assert_eq!(get("/fast").await??, "fast");
+        // 退出时正在处理的请求仍然完成
+        let slow = get("/slow");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        notify.notify_one();
+        assert_eq!(slow.await??, "slow");
+        tokio::time::timeout(Duration::from_secs(5), server).await???;
+        assert!(std::net::TcpStream::connect(addr).is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn admin_listener_should_drain_on_shutdown() -> Result<()> {
+        let state = AppState::with_routers(Default::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let admin = TcpListener::bind("127.0.0.1:0").await?;
+        let admin_addr = admin.local_addr()?;
+        let admin_app = Router::new().route(
+            "/slow",
+            axum::routing::get(|| async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                "slow"
+            }),
+        );
+        let notify = Arc::new(Notify::new());
+        let server = tokio::spawn(serve(
+            listener,
+            Router::new(),
+            Some((admin, admin_app)),
+            state,
+            Some(notify.clone()),
+            DEFAULT_SHUTDOWN_TIMEOUT,
+        ));
+
+        let url = format!("http://{admin_addr}/slow");
+        let slow = tokio::task::spawn_blocking(move || -> Result<String> {
+            Ok(ureq::get(&url).call()?.into_string()?)
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        notify.notify_one();
+        // 管理端口上处理中的请求完成后 serve 才返回
+        assert_eq!(slow.await??, "slow");
+        tokio::time::timeout(Duration::from_secs(5), server).await???;
+        assert!(std::net::TcpStream::connect(addr).is_err());
+        assert!(std::net::TcpStream::connect(admin_addr).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, convert::Infallible, time::Duration};
+
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, Method, Response, Uri, header},
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use serde::Deserialize;
+use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+
+use crate::{AppState, RouteKind, WorkerMessage, assemble_req, get_router};
+
+/// 没有事件时发送注释保持连接，避免被代理断开
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// handler 产生的一个事件，由 engine 的 `__dino_stream_next` 规范化
+#[derive(Debug, Default, Deserialize)]
+struct SseEvent {
+    data: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    event: Option<String>,
+    /// 客户端断开后重连前等待的毫秒数
+    #[serde(default)]
+    retry: Option<u64>,
+}
+
+/// `EventSource` 请求时带 `Accept: text/event-stream`
+pub(crate) fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"))
+}
+
+impl AppState {
+    /// 请求匹配到 SSE 路由时返回事件流，其他路由返回 None，按普通请求处理。
+    /// 流打开期间占用一个 worker，handler 的迭代器结束或客户端断开后释放
+    pub(crate) fn stream_events(
+        &self,
+        host: &str,
+        uri: &Uri,
+        query: &HashMap<String, String>,
+        headers: &HeaderMap,
+    ) -> Result<Option<Response<Body>>> {
+        let router = get_router(host.to_string(), self)?;
+        let matched = router.match_it(Method::GET, uri.path())?;
+        if matched.value.kind != RouteKind::Sse {
+            return Ok(None);
+        }
+        let req = assemble_req(
+            query.clone(),
+            &matched,
+            Method::GET,
+            uri,
+            headers,
+            Bytes::new(),
+        )?;
+        let handler = router.handler(matched.value.handler).clone();
+        let (msg, recv) = WorkerMessage::new_stream(req, handler);
+        self.queues(host)?
+            .send(msg, matched.value.priority)
+            .map_err(|e| anyhow::anyhow!("Send to jsworker error: {e}"))?;
+
+        let events =
+            UnboundedReceiverStream::new(recv).map(|json| Ok::<_, Infallible>(event(&json)));
+        let sse = Sse::new(events).keep_alive(KeepAlive::new().interval(KEEP_ALIVE));
+        let mut resp = sse.into_response();
+        // 关闭 nginx 等反向代理的缓冲，事件才能及时到达客户端
+        resp.headers_mut()
+            .insert("x-accel-buffering", "no".parse().unwrap());
+        Ok(Some(resp))
+    }
+}
+
+/// id 和 event 不能包含换行，否则会破坏事件格式，这样的字段被忽略
+fn event(json: &str) -> Event {
+    let event: SseEvent = serde_json::from_str(json).unwrap_or_else(|_| SseEvent {
+        data: json.to_string(),
+        ..Default::default()
+    });
+    let valid = |s: &String| !s.contains(['\n', '\r', '\0']);
+    let mut ret = Event::default().data(event.data);
+    if let Some(id) = event.id.filter(valid) {
+        ret = ret.id(id);
+    }
+    if let Some(name) = event.event.filter(valid) {
+        ret = ret.event(name);
+    }
+    if let Some(retry) = event.retry {
+        ret = ret.retry(Duration::from_millis(retry));
+    }
+    ret
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::Query,
+    http::{HeaderMap, Method, Uri},
+};
+use dashmap::DashMap;
+
+use crate::{AppState, ProjectConfig, RouteEntry, SwappableAppRouter, engine::Resp};
+
+const TEST_HOST: &str = "localhost";
+
+/// 在进程内运行 tenant 代码的测试客户端，不需要监听 TCP 端口。
+///
+/// 请求会走和 `start_server` 相同的路由匹配、Req 组装与 worker 调度流程。
+pub struct TestClient {
+    state: AppState,
+}
+
+impl TestClient {
+    /// 使用打包后的代码和项目配置启动一个 worker
+    pub fn try_new(code: impl Into<String>, config: ProjectConfig) -> Result<Self> {
+        let router = SwappableAppRouter::try_new(code, config.routes)?;
+        let routers = DashMap::new();
+        routers.insert(TEST_HOST.to_string(), router);
+        Ok(Self {
+            state: AppState::with_routers(routers),
+        })
+    }
+
+    pub fn get(&self, path: &str) -> Result<Resp> {
+        self.request(Method::GET, path, None)
+    }
+
+    pub fn post(&self, path: &str, body: impl Into<String>) -> Result<Resp> {
+        self.request(Method::POST, path, Some(body.into()))
+    }
+
+    /// 调用一个路由，`path` 可以带 query string
+    pub fn request(&self, method: Method, path: &str, body: Option<String>) -> Result<Resp> {
+        let uri: Uri = path.parse()?;
+        let Query(query) = Query::<HashMap<String, String>>::try_from_uri(&uri)?;
+        let body = body.map(Bytes::from).unwrap_or_default();
+        self.state.dispatch(
+            TEST_HOST.to_string(),
+            method,
+            &uri,
+            query,
+            &HeaderMap::new(),
+            body,
+        )
+    }
+
+    /// 当前生效的路由表
+    pub fn routes(&self) -> Vec<RouteEntry> {
+        self.state
+            .routers
+            .get(TEST_HOST)
+            .map_or_else(Vec::new, |router| router.load().table.to_vec())
+    }
+}
+
+impl Drop for TestClient {
+    fn drop(&mut self) {
+        for handle in self.state.workers.lock().unwrap().values() {
+            handle.shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_should_call_routes() -> Result<()> {
+        let code = r#"
+        (function(){
+            async function hello(req){
+                return {
+                    status:200,
+                    headers:{},
+                    body: JSON.stringify({id: req.params.id, q: req.query.q, body: req.body}),
+                };
+            }
+            return{hello:hello};
+        })();
+        "#;
+        let config = ProjectConfig::load("./fixtures/config.yml")?;
+        let client = TestClient::try_new(code, config)?;
+
+        let resp = client.get("/api/hello/1?q=dino")?;
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.text(), Some(r#"{"id":"1","q":"dino"}"#));
+
+        let resp = client.post("/api/hello/2", "data")?;
+        assert_eq!(resp.text(), Some(r#"{"id":"2","body":"data"}"#));
+
+        assert!(
+            client
+                .request(Method::DELETE, "/api/hello/1", None)
+                .is_err()
+        );
+        assert_eq!(client.routes().len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use std::{fmt::Write, time::Duration};
+
+/// 一次请求各阶段的耗时，dev 模式下以 `Server-Timing` 响应头返回
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing {
+    /// 路由匹配和请求组装
+    pub route: Duration,
+    /// 在 worker 队列中等待的时间
+    pub queue: Duration,
+    /// 执行 handler 的时间
+    pub exec: Duration,
+    /// 请求转换为 JS 对象、响应转换为 HTTP 响应的时间
+    pub serialize: Duration,
+    /// worker 启动后的第一个请求
+    pub cold: bool,
+}
+
+impl Timing {
+    /// `Server-Timing` 响应头的值
+    pub fn header_value(&self) -> String {
+        let mut value = String::new();
+        let metrics = [
+            ("route", self.route, None),
+            ("queue", self.queue, None),
+            (
+                "js",
+                self.exec,
+                Some(if self.cold { "cold" } else { "warm" }),
+            ),
+            ("serialize", self.serialize, None),
+        ];
+        for (i, (name, dur, desc)) in metrics.into_iter().enumerate() {
+            if i > 0 {
+                value.push_str(", ");
+            }
+            let _ = write!(value, "{name}");
+            if let Some(desc) = desc {
+                let _ = write!(value, ";desc=\"{desc}\"");
+            }
+            let _ = write!(value, ";dur={:.3}", dur.as_secs_f64() * 1000.0);
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timing_should_format_server_timing_header() {
+        let timing = Timing {
+            route: Duration::from_micros(120),
+            queue: Duration::from_micros(5),
+            exec: Duration::from_millis(3),
+            serialize: Duration::ZERO,
+            cold: true,
+        };
+        assert_eq!(
+            timing.header_value(),
+            r#"route;dur=0.120, queue;dur=0.005, js;desc="cold";dur=3.000, serialize;dur=0.000"#
+        );
+    }
+}
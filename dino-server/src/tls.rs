@@ -0,0 +1,362 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::{Router, serve::Listener};
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use dashmap::DashMap;
+use rustls_acme::{
+    AccountCache, AcmeConfig, CertCache, ResolvesServerCertAcme, acme::ACME_TLS_ALPN_NAME,
+};
+use serde::Deserialize;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    task::AbortHandle,
+};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        ServerConfig,
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+    },
+    server::TlsStream,
+};
+use tokio_stream::StreamExt;
+use tracing::{error, info, warn};
+
+use crate::{AppState, ObjectStore};
+
+/// 检查 tenant 变化、为新 host 申请证书的间隔
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// TLS 握手的超时时间，超时的连接直接关闭
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 证书和 ACME 账号在对象存储中的 class，证书按 tenant 保存，账号保存在空 tenant 下
+const ACME_CLASS: &str = "__acme";
+
+/// TLS 模式，开启后在 `port` 上提供 HTTPS，为每个 tenant 的 host 自动申请和续期证书
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsOptions {
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// ACME 账号的联系邮箱
+    #[serde(default)]
+    pub contact: Vec<String>,
+    /// 使用 Let's Encrypt 的正式环境，否则使用测试环境，测试环境的证书不被浏览器信任
+    #[serde(default)]
+    pub production: bool,
+    /// 其他 ACME 服务的 directory URL，设置后忽略 `production`
+    #[serde(default)]
+    pub directory: Option<String>,
+}
+
+fn default_port() -> u16 {
+    443
+}
+
+/// 按 SNI 选择 tenant 的证书，ACME 的 TLS-ALPN-01 验证也由 tenant 的 resolver 应答
+#[derive(Default)]
+struct TenantCerts {
+    hosts: DashMap<String, (Arc<ResolvesServerCertAcme>, AbortHandle)>,
+}
+
+impl fmt::Debug for TenantCerts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TenantCerts")
+            .field("hosts", &self.hosts.len())
+            .finish()
+    }
+}
+
+impl ResolvesServerCert for TenantCerts {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let host = client_hello.server_name()?.to_ascii_lowercase();
+        let resolver = self.hosts.get(&host)?.0.clone();
+        resolver.resolve(client_hello)
+    }
+}
+
+/// 把 ACME 的证书和账号保存在对象存储中，配置了目录时重启后不用重新申请
+struct AcmeCache {
+    store: ObjectStore,
+    host: String,
+}
+
+impl AcmeCache {
+    fn load(&self, tenant: &str, directory_url: &str, name: &str) -> Result<Option<Vec<u8>>> {
+        let key = (tenant.into(), ACME_CLASS.into(), directory_url.into());
+        let Some(value) = self.store.get(&key, name)? else {
+            return Ok(None);
+        };
+        Ok(Some(BASE64_STANDARD.decode(value)?))
+    }
+
+    fn store(&self, tenant: &str, directory_url: &str, name: &str, value: &[u8]) -> Result<()> {
+        let key = (tenant.into(), ACME_CLASS.into(), directory_url.into());
+        self.store.put(&key, name, BASE64_STANDARD.encode(value))
+    }
+}
+
+#[async_trait]
+impl CertCache for AcmeCache {
+    type EC = anyhow::Error;
+
+    async fn load_cert(&self, _domains: &[String], directory_url: &str) -> Result<Option<Vec<u8>>> {
+        self.load(&self.host, directory_url, "cert")
+    }
+
+    async fn store_cert(
+        &self,
+        _domains: &[String],
+        directory_url: &str,
+        cert: &[u8],
+    ) -> Result<()> {
+        self.store(&self.host, directory_url, "cert", cert)
+    }
+}
+
+#[async_trait]
+impl AccountCache for AcmeCache {
+    type EA = anyhow::Error;
+
+    async fn load_account(
+        &self,
+        contact: &[String],
+        directory_url: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        self.load("", directory_url, &format!("account {}", contact.join(",")))
+    }
+
+    async fn store_account(
+        &self,
+        contact: &[String],
+        directory_url: &str,
+        account: &[u8],
+    ) -> Result<()> {
+        let name = format!("account {}", contact.join(","));
+        self.store("", directory_url, &name, account)
+    }
+}
+
+/// 握手完成的 TLS 连接，由 `spawn` 中的接收任务送来
+struct TlsListener {
+    conns: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    addr: SocketAddr,
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.conns.recv().await {
+            Some(conn) => conn,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.addr)
+    }
+}
+
+/// 能够申请证书的 host：域名，不是 IP、单段的主机名或预览环境
+fn acme_eligible(state: &AppState, host: &str) -> bool {
+    host.contains('.')
+        && host.parse::<IpAddr>().is_err()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && !state.previews.contains_key(host)
+}
+
+impl TenantCerts {
+    /// 为新 tenant 启动证书的申请和续期，停止已删除 tenant 的任务
+    fn sync(&self, state: &AppState, options: &TlsOptions) {
+        let hosts: HashSet<String> = state
+            .routers
+            .iter()
+            .map(|r| r.key().to_ascii_lowercase())
+            .filter(|host| acme_eligible(state, host))
+            .collect();
+        self.hosts.retain(|host, (_, task)| {
+            let keep = hosts.contains(host);
+            if !keep {
+                task.abort();
+            }
+            keep
+        });
+        for host in hosts {
+            if !self.hosts.contains_key(&host) {
+                let (resolver, task) = provision(state, options, &host);
+                self.hosts.insert(host, (resolver, task));
+            }
+        }
+    }
+}
+
+/// 证书先从对象存储中加载，没有或即将过期时向 ACME 服务申请
+fn provision(
+    state: &AppState,
+    options: &TlsOptions,
+    host: &str,
+) -> (Arc<ResolvesServerCertAcme>, AbortHandle) {
+    let cache = AcmeCache {
+        store: state.object_store.clone(),
+        host: host.to_string(),
+    };
+    let config = AcmeConfig::new([host])
+        .contact(
+            options
+                .contact
+                .iter()
+                .map(|email| format!("mailto:{email}")),
+        )
+        .directory_lets_encrypt(options.production);
+    let config = match &options.directory {
+        Some(url) => config.directory(url),
+        None => config,
+    };
+    let mut acme = config.cache(cache).state();
+    let resolver = acme.resolver();
+    let host = host.to_string();
+    let task = tokio::spawn(async move {
+        while let Some(event) = acme.next().await {
+            match event {
+                Ok(event) => info!("ACME {host}: {event:?}"),
+                Err(e) => error!("ACME {host}: {e}"),
+            }
+        }
+    });
+    (resolver, task.abort_handle())
+}
+
+/// 在 `options.port` 上提供 HTTPS，和 HTTP 端口共用同一个 `app`
+pub(crate) async fn spawn(state: AppState, app: Router, options: TlsOptions) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", options.port))
+        .await
+        .with_context(|| format!("Failed to bind TLS port {}", options.port))?;
+    let addr = listener.local_addr()?;
+    info!("Listening on: {addr} (TLS)");
+    if !state.object_store.persistent() {
+        warn!(
+            "Object store has no directory, ACME certificates will be requested again after restart"
+        );
+    }
+
+    let certs = Arc::new(TenantCerts::default());
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_cert_resolver(certs.clone());
+    config.alpn_protocols = vec![
+        b"h2".to_vec(),
+        b"http/1.1".to_vec(),
+        ACME_TLS_ALPN_NAME.to_vec(),
+    ];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SYNC_INTERVAL);
+        loop {
+            interval.tick().await;
+            certs.sync(&state, &options);
+        }
+    });
+
+    let (send, conns) = mpsc::channel(128);
+    tokio::spawn(async move {
+        loop {
+            let (tcp, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    error!("Accept TLS connection error: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let (acceptor, send) = (acceptor.clone(), send.clone());
+            // 握手在单独的任务中进行，慢的客户端不影响其他连接
+            tokio::spawn(async move {
+                let mut tls =
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                        Ok(Ok(tls)) => tls,
+                        Ok(Err(_)) | Err(_) => return,
+                    };
+                // TLS-ALPN-01 验证在握手时完成，连接不承载 HTTP
+                if tls.get_ref().1.alpn_protocol() == Some(ACME_TLS_ALPN_NAME) {
+                    let _ = tls.shutdown().await;
+                    return;
+                }
+                let _ = send.send((tls, peer)).await;
+            });
+        }
+    });
+
+    let listener = TlsListener { conns, addr };
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app.into_make_service()).await {
+            error!("TLS server error: {e}");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn acme_cache_should_persist_in_object_store() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dino-acme-{}", uuid::Uuid::new_v4()));
+        let directory = "https://acme.test/directory";
+        let cache = AcmeCache {
+            store: ObjectStore::new(&dir),
+            host: "a.example.com".into(),
+        };
+        assert_eq!(cache.load_cert(&[], directory).await?, None);
+        cache.store_cert(&[], directory, b"cert\xff").await?;
+        let contact = ["mailto:ops@example.com".to_string()];
+        cache.store_account(&contact, directory, b"\x00key").await?;
+
+        // 新的存储从磁盘读取
+        let cache = AcmeCache {
+            store: ObjectStore::new(&dir),
+            host: "a.example.com".into(),
+        };
+        assert_eq!(
+            cache.load_cert(&[], directory).await?.as_deref(),
+            Some(&b"cert\xff"[..])
+        );
+        assert_eq!(
+            cache.load_account(&contact, directory).await?.as_deref(),
+            Some(&b"\x00key"[..])
+        );
+        assert_eq!(cache.load_cert(&[], "https://other/directory").await?, None);
+        let other = AcmeCache {
+            store: ObjectStore::new(&dir),
+            host: "b.example.com".into(),
+        };
+        assert_eq!(other.load_cert(&[], directory).await?, None);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn acme_should_only_cover_domains() {
+        let state = AppState::with_routers(DashMap::new());
+        assert!(acme_eligible(&state, "api.example.com"));
+        assert!(!acme_eligible(&state, "localhost"));
+        assert!(!acme_eligible(&state, "127.0.0.1"));
+        assert!(!acme_eligible(&state, "a b.example.com"));
+    }
+}
@@ -0,0 +1,219 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, Method, Uri, header},
+};
+use tokio::{fs, io::AsyncWriteExt};
+use tokio_stream::StreamExt;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    AppState, Priority, UploadMode, assemble_req,
+    engine::{Req, Resp, Upload},
+    error::AppError,
+    get_router,
+};
+
+/// 每接收这么多字节以 `progress` 事件调用一次 handler
+const PROGRESS_INTERVAL: u64 = 1 << 20;
+
+impl AppState {
+    /// 请求是否匹配到流式上传的路由，路由不存在时交给普通请求处理报错
+    pub(crate) fn streaming_upload(&self, host: &str, method: &Method, uri: &Uri) -> bool {
+        self.routers.get(host).is_some_and(|router| {
+            router
+                .routes
+                .load()
+                .match_it(method.clone(), uri.path())
+                .is_ok_and(|m| m.value.upload == UploadMode::Streaming)
+        })
+    }
+
+    /// 把 body 写入 `<upload_dir>/<tenant>/<uuid>`，接收完成后调用 handler。
+    /// 上传中断、handler 出错或返回非 2xx 状态时删除文件，否则由 tenant 自行管理
+    pub(crate) async fn upload(
+        &self,
+        host: String,
+        method: Method,
+        uri: &Uri,
+        query: HashMap<String, String>,
+        headers: &HeaderMap,
+        body: Body,
+    ) -> Result<Resp, AppError> {
+        let router = get_router(host.clone(), self)?;
+        let matched = router.match_it(method.clone(), uri.path())?;
+        let mut req = assemble_req(query, &matched, method, uri, headers, Bytes::new())?;
+        let handler = router.handler(matched.value.handler).clone();
+        let priority = matched.value.priority;
+
+        let dir = self.upload_dir.join(&host);
+        fs::create_dir_all(&dir)
+            .await
+            .map_err(anyhow::Error::from)?;
+        let path = dir.join(Uuid::new_v4().to_string());
+        let total = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse().ok());
+        let mut upload = Upload {
+            path: path.to_string_lossy().into_owned(),
+            received: 0,
+            total,
+        };
+
+        let ret = match self
+            .receive(&host, &handler, priority, &req, &mut upload, body)
+            .await
+        {
+            Ok(()) => {
+                req.upload = Some(upload);
+                self.send_blocking(host, handler.clone(), priority, req)
+                    .await
+                    .map_err(AppError::from)
+            }
+            Err(e) => Err(e),
+        };
+        if !matches!(&ret, Ok(resp) if (200..300).contains(&resp.status)) {
+            let _ = fs::remove_file(&path).await;
+        }
+        let resp = ret?;
+        self.check_contract(&router, &handler, &resp)?;
+        Ok(resp)
+    }
+
+    /// 逐块写入文件，进度事件的返回值被忽略
+    async fn receive(
+        &self,
+        host: &str,
+        handler: &Arc<str>,
+        priority: Priority,
+        req: &Req,
+        upload: &mut Upload,
+        body: Body,
+    ) -> Result<(), AppError> {
+        let mut file = fs::File::create(&upload.path)
+            .await
+            .map_err(anyhow::Error::from)?;
+        let mut stream = body.into_data_stream();
+        let mut reported = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| AppError::BadRequest(e.into()))?;
+            file.write_all(&chunk).await.map_err(anyhow::Error::from)?;
+            upload.received += chunk.len() as u64;
+            if upload.received - reported < PROGRESS_INTERVAL {
+                continue;
+            }
+            reported = upload.received;
+            let mut req = req.clone();
+            req.event = Some("progress".to_string());
+            req.upload = Some(upload.clone());
+            if let Err(e) = self
+                .send_blocking(host.to_string(), handler.clone(), priority, req)
+                .await
+            {
+                error!("Upload progress handler error: {e:#}");
+            }
+        }
+        file.flush().await.map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{ProjectConfig, SwappableAppRouter};
+
+    #[tokio::test]
+    async fn upload_should_stream_body_to_file() -> anyhow::Result<()> {
+        let code = r#"
+        (function(){
+            let progress = [];
+            async function upload(req) {
+                if (req.event === "progress") {
+                    progress.push(req.upload.received);
+                    return { status: 200, headers: {} };
+                }
+                const size = __dino_host.fs.readFileSync(req.upload.path).length;
+                const body = JSON.stringify({ size, progress, total: req.upload.total ?? null });
+                progress = [];
+                return { status: req.params.name === "bad" ? 400 : 201, headers: {}, body };
+            }
+            return { upload };
+        })();
+        "#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            r#"
+name: test
+routes:
+  /upload/{name}:
+    - method: PUT
+      handler: upload
+      upload: streaming
+"#,
+        )?;
+        let routers = DashMap::new();
+        routers.insert(
+            "a.com".to_string(),
+            SwappableAppRouter::try_new(code, config.routes)?,
+        );
+        let mut state = AppState::with_routers(routers);
+        state.upload_dir = std::env::temp_dir().join(format!("dino-uploads-{}", Uuid::new_v4()));
+
+        let uri: Uri = "/upload/ok".parse()?;
+        assert!(state.streaming_upload("a.com", &Method::PUT, &uri));
+        assert!(!state.streaming_upload("a.com", &Method::GET, &uri));
+
+        // 3 个 1MB 的块，每个块之后报告一次进度
+        let chunks = (0..3).map(|_| Ok::<_, std::io::Error>(vec![b'x'; 1 << 20]));
+        let body = Body::from_stream(tokio_stream::iter(chunks));
+        let resp = state
+            .upload(
+                "a.com".into(),
+                Method::PUT,
+                &uri,
+                HashMap::new(),
+                &HeaderMap::new(),
+                body,
+            )
+            .await?;
+        assert_eq!(resp.status, 201);
+        let mb = 1 << 20;
+        assert_eq!(
+            resp.text(),
+            Some(
+                format!(
+                    r#"{{"size":{},"progress":[{mb},{},{}],"total":null}}"#,
+                    3 * mb,
+                    2 * mb,
+                    3 * mb
+                )
+                .as_str()
+            )
+        );
+        let dir = state.upload_dir.join("a.com");
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+        // handler 返回错误状态时删除文件
+        let uri: Uri = "/upload/bad".parse()?;
+        let resp = state
+            .upload(
+                "a.com".into(),
+                Method::PUT,
+                &uri,
+                HashMap::new(),
+                &HeaderMap::new(),
+                Body::from("hi"),
+            )
+            .await?;
+        assert_eq!(resp.status, 400);
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+        std::fs::remove_dir_all(&state.upload_dir)?;
+        Ok(())
+    }
+}
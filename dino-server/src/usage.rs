@@ -0,0 +1,313 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, atomic::Ordering},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::AppState;
+
+/// 默认每小时生成一次用量报告
+pub const DEFAULT_USAGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 一个 tenant 在一个统计周期内的用量，用于计费和分摊成本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub tenant: String,
+    /// 统计周期的开始和结束，RFC 3339 格式
+    pub start: String,
+    pub end: String,
+    /// 处理的请求数，包括 SSE 和流式路由
+    pub requests: u64,
+    /// worker 线程执行 handler 占用的 CPU 时间（毫秒）
+    pub cpu_ms: u64,
+    /// `fetch` 发送和接收的 body 字节数
+    pub egress_bytes: u64,
+    /// 周期结束时对象状态和上传文件占用的字节数
+    pub storage_bytes: u64,
+}
+
+/// 查询条件，字段为 None 时不过滤
+#[derive(Debug, Default, Clone)]
+pub struct UsageQuery {
+    pub tenant: Option<String>,
+    /// 只返回在该时间之后结束的周期
+    pub from: Option<DateTime<Utc>>,
+    /// 只返回在该时间之前开始的周期
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// 按周期生成的用量报告，以 JSON Lines 格式追加保存
+#[derive(Debug, Clone)]
+pub struct UsageLog {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+    interval: Duration,
+    marks: Arc<Mutex<Marks>>,
+}
+
+/// 上一次报告的时间和当时各 tenant 的累计用量
+#[derive(Debug)]
+struct Marks {
+    at: DateTime<Utc>,
+    totals: HashMap<String, Totals>,
+}
+
+/// tenant 自启动以来的累计用量
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    requests: u64,
+    cpu_us: u64,
+    egress_bytes: u64,
+}
+
+impl UsageLog {
+    pub fn try_new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open usage log {}", path.display()))?;
+        Ok(Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+            interval: DEFAULT_USAGE_INTERVAL,
+            marks: Arc::new(Mutex::new(Marks {
+                at: Utc::now(),
+                totals: HashMap::new(),
+            })),
+        })
+    }
+
+    /// 设置生成报告的间隔
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn append(&self, reports: &[UsageReport]) -> Result<()> {
+        let mut file = self.file.lock().unwrap();
+        for report in reports {
+            writeln!(file, "{}", serde_json::to_string(report)?)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn query(&self, query: &UsageQuery) -> Result<Vec<UsageReport>> {
+        Self::query_file(&self.path, query)
+    }
+
+    /// 直接读取日志文件，不需要服务在运行
+    pub fn query_file(path: impl AsRef<Path>, query: &UsageQuery) -> Result<Vec<UsageReport>> {
+        let file = File::open(path.as_ref())?;
+        let mut reports = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let report: UsageReport = serde_json::from_str(&line)?;
+            let time = |s: &str| DateTime::parse_from_rfc3339(s).map(|t| t.to_utc());
+            let matched = query.tenant.as_ref().is_none_or(|t| *t == report.tenant)
+                && query
+                    .from
+                    .is_none_or(|from| time(&report.end).is_ok_and(|t| t > from))
+                && query
+                    .to
+                    .is_none_or(|to| time(&report.start).is_ok_and(|t| t < to));
+            if matched {
+                reports.push(report);
+            }
+        }
+        Ok(reports)
+    }
+}
+
+impl UsageReport {
+    pub const CSV_HEADER: &str = "tenant,start,end,requests,cpu_ms,egress_bytes,storage_bytes";
+
+    /// 导出为 CSV，第一行为表头
+    pub fn to_csv(reports: &[UsageReport]) -> String {
+        let mut csv = format!("{}\n", Self::CSV_HEADER);
+        for r in reports {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_field(&r.tenant),
+                r.start,
+                r.end,
+                r.requests,
+                r.cpu_ms,
+                r.egress_bytes,
+                r.storage_bytes
+            ));
+        }
+        csv
+    }
+}
+
+/// 含有逗号、引号或换行的字段加上引号
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl AppState {
+    fn usage_totals(&self, host: &str) -> Totals {
+        let stats = self.scale_stats(host);
+        let egress = self.egress.stats(host);
+        Totals {
+            requests: stats.requests.load(Ordering::Relaxed),
+            cpu_us: stats.cpu_us.load(Ordering::Relaxed),
+            egress_bytes: egress.sent.load(Ordering::Relaxed)
+                + egress.received.load(Ordering::Relaxed),
+        }
+    }
+
+    fn storage_bytes(&self, host: &str) -> u64 {
+        self.object_store.tenant_bytes(host) + dir_size(&self.upload_dir.join(host))
+    }
+
+    /// 结束当前统计周期，把各 tenant 在周期内的用量写入用量日志并返回，
+    /// 没有配置用量日志时返回空
+    pub fn report_usage(&self) -> Result<Vec<UsageReport>> {
+        let Some(log) = &self.usage else {
+            return Ok(vec![]);
+        };
+        let end = Utc::now();
+        let mut reports = {
+            let mut marks = log.marks.lock().unwrap();
+            let mut hosts: Vec<_> = self.routers.iter().map(|r| r.key().clone()).collect();
+            hosts.sort();
+            let mut totals = HashMap::new();
+            let mut reports = vec![];
+            for host in hosts {
+                let now = self.usage_totals(&host);
+                let prev = marks.totals.get(&host).copied().unwrap_or_default();
+                reports.push(UsageReport {
+                    tenant: host.clone(),
+                    start: marks.at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    end: end.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    // 删除后重新添加的 tenant 从 0 开始计数
+                    requests: now.requests.saturating_sub(prev.requests),
+                    cpu_ms: now.cpu_us.saturating_sub(prev.cpu_us) / 1000,
+                    egress_bytes: now.egress_bytes.saturating_sub(prev.egress_bytes),
+                    storage_bytes: 0,
+                });
+                totals.insert(host, now);
+            }
+            marks.at = end;
+            marks.totals = totals;
+            reports
+        };
+        // 统计目录大小可能较慢，不持有锁
+        for report in &mut reports {
+            report.storage_bytes = self.storage_bytes(&report.tenant);
+        }
+        log.append(&reports)?;
+        Ok(reports)
+    }
+}
+
+/// 目录下所有文件的大小之和，目录不存在时为 0
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map_or(0, |m| m.len()),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// 按用量日志的间隔定期生成报告
+pub(crate) fn spawn(state: AppState) {
+    let Some(interval) = state.usage.as_ref().map(|log| log.interval) else {
+        return;
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        // 第一次 tick 立即返回，跳过空的周期
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let ret = tokio::task::spawn_blocking({
+                let state = state.clone();
+                move || state.report_usage()
+            })
+            .await;
+            match ret {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => error!("Failed to write usage report: {e:#}"),
+                Err(e) => error!("Usage report task failed: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{
+        ProjectConfig, SwappableAppRouter,
+        engine::{Req, Resp},
+    };
+
+    #[test]
+    fn usage_should_report_deltas_per_period() -> Result<()> {
+        let code = r#"(function(){ async function hello() { return { status: 200, headers: {}, body: "ok" }; } return { hello }; })();"#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: test\nroutes:\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?;
+        let router = SwappableAppRouter::try_new(code, config.routes)?;
+        let mut state = AppState::with_routers(DashMap::from_iter([("a.com".to_string(), router)]));
+        let path = std::env::temp_dir().join(format!("dino-usage-{}.jsonl", uuid::Uuid::new_v4()));
+        state.usage = Some(UsageLog::try_new(&path)?);
+
+        let call = || -> Result<Resp> {
+            let req = Req::builder().method("GET").url("/hello").build();
+            state.send("a.com".into(), "hello", req)
+        };
+        call()?;
+        call()?;
+        let reports = state.report_usage()?;
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            (reports[0].tenant.as_str(), reports[0].requests),
+            ("a.com", 2)
+        );
+        call()?;
+        assert_eq!(state.report_usage()?[0].requests, 1);
+
+        let log = state.usage.as_ref().unwrap();
+        let all = log.query(&UsageQuery::default())?;
+        assert_eq!(all.len(), 2);
+        let query = UsageQuery {
+            tenant: Some("b.com".into()),
+            ..Default::default()
+        };
+        assert!(log.query(&query)?.is_empty());
+        let csv = UsageReport::to_csv(&all);
+        assert_eq!(csv.lines().next(), Some(UsageReport::CSV_HEADER));
+        assert!(csv.lines().nth(1).unwrap().starts_with("a.com,"));
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
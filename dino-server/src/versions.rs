@@ -0,0 +1,200 @@
+use anyhow::{Context, Result, ensure};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, AuditAction, AuditEvent, audit::short_hash, router::AppRouter};
+
+/// 每个 tenant 除当前版本外保留的版本数量
+const MAX_HISTORY: usize = 10;
+
+/// tenant 部署过的一个版本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleVersion {
+    /// 从 1 开始递增，服务器启动或添加 tenant 时的代码为版本 1
+    pub version: u64,
+    /// 代码的 hash
+    pub code: String,
+    /// 路由配置的 hash
+    pub routes: String,
+    /// 路由清单，每行为 `GET /path handler`
+    pub manifest: Vec<String>,
+    pub deployed_at: String,
+    pub actor: String,
+}
+
+/// tenant 保留的版本。回滚只切换当前版本，之后的版本仍然保留，可以再切换回去
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Versions {
+    /// 按版本号排序
+    bundles: Vec<(BundleVersion, AppRouter)>,
+    current: u64,
+}
+
+impl Versions {
+    /// 新部署的版本成为当前版本，超出数量时删除最旧的版本
+    fn push(&mut self, router: AppRouter, actor: &str) -> u64 {
+        let version = self.bundles.last().map_or(1, |(b, _)| b.version + 1);
+        let bundle = BundleVersion {
+            version,
+            code: short_hash(&router.code),
+            routes: router.routes_hash.clone(),
+            manifest: router.manifest.to_vec(),
+            deployed_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            actor: actor.to_string(),
+        };
+        self.bundles.push((bundle, router));
+        self.current = version;
+        if self.bundles.len() > MAX_HISTORY + 1 {
+            self.bundles.remove(0);
+        }
+        version
+    }
+
+    /// 可以切换到的版本数量
+    pub(crate) fn history(&self) -> usize {
+        self.bundles.len().saturating_sub(1)
+    }
+
+    pub(crate) fn current(&self) -> u64 {
+        self.current
+    }
+
+    /// `version` 为 None 时是当前版本之前的最新版本
+    fn find(&self, version: Option<u64>) -> Result<&(BundleVersion, AppRouter)> {
+        match version {
+            None => self
+                .bundles
+                .iter()
+                .rev()
+                .find(|(b, _)| b.version < self.current)
+                .context("No previous version to roll back to"),
+            Some(version) => {
+                ensure!(
+                    version != self.current,
+                    "Version {version} is already deployed"
+                );
+                self.bundles
+                    .iter()
+                    .find(|(b, _)| b.version == version)
+                    .with_context(|| format!("Version {version} not found"))
+            }
+        }
+    }
+}
+
+impl AppState {
+    /// 把 tenant 当前的代码和路由记录为新版本，返回版本号
+    pub(crate) fn record_version(&self, host: &str, actor: &str) -> Result<u64> {
+        let router = self.routers.get(host).context("Router not found")?.load();
+        Ok(self
+            .versions
+            .entry(host.to_string())
+            .or_default()
+            .push(router, actor))
+    }
+
+    /// tenant 的当前版本号和保留的版本，按版本号排序
+    pub fn versions(&self, host: &str) -> Option<(u64, Vec<BundleVersion>)> {
+        let versions = self.versions.get(host)?;
+        let bundles = versions.bundles.iter().map(|(b, _)| b.clone()).collect();
+        Some((versions.current, bundles))
+    }
+
+    /// 切换到保留的 `version`，为 None 时回滚到上一个版本，返回切换后的版本号
+    pub fn rollback(&self, host: &str, version: Option<u64>, actor: &str) -> Result<u64> {
+        let router = self.routers.get(host).context("Router not found")?.clone();
+        let (bundle, previous) = {
+            let mut versions = self.versions.get_mut(host).context("Router not found")?;
+            let (bundle, previous) = versions.find(version)?.clone();
+            versions.current = bundle.version;
+            (bundle, previous)
+        };
+        let current = router.load();
+        let event = AuditEvent::new(host, actor, AuditAction::Admin)
+            .with_change(
+                Some(short_hash(&current.code)),
+                Some(short_hash(&previous.code)),
+            )
+            .with_detail(format!("rollback to version {}", bundle.version));
+        router.restore(previous);
+        self.audit(event);
+        self.restart_worker(host, actor)?;
+        Ok(bundle.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{ProjectConfig, SwappableAppRouter};
+
+    fn routes(handler: &str) -> Result<crate::ProjectRoutes> {
+        let config: ProjectConfig = serde_yaml::from_str(&format!(
+            "name: test\nroutes:\n  /hello:\n    - method: GET\n      handler: {handler}\n"
+        ))?;
+        Ok(config.routes)
+    }
+
+    fn code(version: u64) -> String {
+        format!(
+            "(function(){{ async function hello() {{ return {{ status: 200, headers: {{}}, body: 'v{version}' }}; }} async function hi() {{ return hello(); }} return {{ hello, hi }}; }})();"
+        )
+    }
+
+    #[test]
+    fn rollback_should_switch_between_versions() -> Result<()> {
+        let routers = DashMap::new();
+        routers.insert(
+            "a.com".to_string(),
+            SwappableAppRouter::try_new(code(1), routes("hello")?)?,
+        );
+        let state = AppState::with_routers(routers);
+        state.swap("a.com", code(2), routes("hello")?, "alice")?;
+        state.swap("a.com", code(3), routes("hi")?, "bob")?;
+
+        let (current, versions) = state.versions("a.com").unwrap();
+        assert_eq!(current, 3);
+        let summary: Vec<_> = versions
+            .iter()
+            .map(|v| (v.version, v.actor.as_str(), v.manifest.join(",")))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1, "dino-server", "GET /hello hello".to_string()),
+                (2, "alice", "GET /hello hello".to_string()),
+                (3, "bob", "GET /hello hi".to_string()),
+            ]
+        );
+
+        let deployed = |version: u64| {
+            let router = state.routers.get("a.com").unwrap().load();
+            router.code == code(version)
+        };
+        assert_eq!(state.rollback("a.com", None, "carol")?, 2);
+        assert!(deployed(2));
+        assert_eq!(state.rollback("a.com", None, "carol")?, 1);
+        assert!(deployed(1));
+        let err = state.rollback("a.com", None, "carol").unwrap_err();
+        assert_eq!(err.to_string(), "No previous version to roll back to");
+
+        // 回滚后仍然可以切换到之后的版本，路由一起恢复
+        assert_eq!(state.rollback("a.com", Some(3), "carol")?, 3);
+        assert!(deployed(3));
+        let router = state.routers.get("a.com").unwrap().load();
+        assert_eq!(router.manifest.as_ref(), ["GET /hello hi".to_string()]);
+        let err = state.rollback("a.com", Some(3), "carol").unwrap_err();
+        assert_eq!(err.to_string(), "Version 3 is already deployed");
+        let err = state.rollback("a.com", Some(9), "carol").unwrap_err();
+        assert_eq!(err.to_string(), "Version 9 not found");
+
+        // 新部署的版本号接着最大的版本号
+        state.rollback("a.com", Some(1), "carol")?;
+        state.swap("a.com", code(4), routes("hello")?, "dave")?;
+        assert_eq!(state.versions("a.com").unwrap().0, 4);
+        assert_eq!(state.tenants()[0].history, 3);
+        Ok(())
+    }
+}
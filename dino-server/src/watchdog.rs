@@ -0,0 +1,249 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::error;
+
+use crate::{AppState, AuditAction, AuditEvent};
+
+/// 检查 worker 是否卡住的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 一个 worker 线程的心跳，watchdog 据此判断它是否卡住
+#[derive(Debug, Default)]
+pub(crate) struct Heartbeat {
+    /// 正在执行的 handler 和开始时间，流式请求为最近一次产生或读取数据的时间
+    busy: Mutex<Option<(Arc<str>, Instant)>>,
+    /// 为 true 时中断正在执行的 JS，worker 处理完后清除
+    pub(crate) interrupt: Arc<AtomicBool>,
+    /// 中断后仍未返回（如阻塞在 host op 中）时由新线程接替，旧线程返回后直接退出
+    abandoned: AtomicBool,
+    /// handler 的执行时间上限，为 None 时不限制
+    timeout: Option<Duration>,
+    /// 本次执行因超时被中断，worker 处理完后清除
+    timed_out: AtomicBool,
+}
+
+/// watchdog 发现的卡住的 worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Stall {
+    pub handler: String,
+    pub busy: Duration,
+    /// 发现时排队的请求数
+    pub queued: usize,
+    /// 中断无效，已启动新线程接替
+    pub abandoned: bool,
+}
+
+impl Heartbeat {
+    pub(crate) fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn start(&self, handler: &Arc<str>) {
+        *self.busy.lock().unwrap() = Some((handler.clone(), Instant::now()));
+    }
+
+    pub(crate) fn finish(&self) {
+        *self.busy.lock().unwrap() = None;
+    }
+
+    /// 流式 handler 产生或读取一块数据时重新计时，超时和 watchdog 只限制两次进展之间的时间
+    pub(crate) fn progress(&self) {
+        if let Some((_, since)) = self.busy.lock().unwrap().as_mut() {
+            *since = Instant::now();
+        }
+    }
+
+    /// 本次执行是否因超时被中断，不清除超时标记
+    pub(crate) fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    /// 返回本次执行是否被 watchdog 中断，同时清除中断标记
+    pub(crate) fn take_interrupt(&self) -> bool {
+        self.interrupt.swap(false, Ordering::Relaxed)
+    }
+
+    /// 返回本次执行是否因超时被中断，同时清除超时标记
+    pub(crate) fn take_timeout(&self) -> bool {
+        self.timed_out.swap(false, Ordering::Relaxed)
+    }
+
+    /// 引擎的中断回调：被 watchdog 中断，或执行时间超过 `timeout`
+    pub(crate) fn should_interrupt(&self) -> bool {
+        if self.interrupt.load(Ordering::Relaxed) {
+            return true;
+        }
+        let Some(timeout) = self.timeout else {
+            return false;
+        };
+        let busy = self.busy.lock().unwrap();
+        let expired = busy
+            .as_ref()
+            .is_some_and(|(_, since)| since.elapsed() >= timeout);
+        if expired {
+            self.timed_out.store(true, Ordering::Relaxed);
+        }
+        expired
+    }
+
+    pub(crate) fn abandoned(&self) -> bool {
+        self.abandoned.load(Ordering::Relaxed)
+    }
+
+    /// 执行超过 `limit` 时中断；中断后又过了 `limit` 仍未返回则标记为放弃
+    pub(crate) fn check(&self, limit: Duration, queued: usize) -> Option<Stall> {
+        let busy = self.busy.lock().unwrap();
+        let (handler, since) = busy.as_ref()?;
+        let elapsed = since.elapsed();
+        if elapsed < limit || self.abandoned() {
+            return None;
+        }
+        let abandoned = match self.interrupt.swap(true, Ordering::Relaxed) {
+            false => false,
+            true if elapsed >= limit * 2 => {
+                self.abandoned.store(true, Ordering::Relaxed);
+                true
+            }
+            true => return None,
+        };
+        Some(Stall {
+            handler: handler.to_string(),
+            busy: elapsed,
+            queued,
+            abandoned,
+        })
+    }
+}
+
+impl AppState {
+    /// 检查所有 tenant 的 worker，中断有排队请求时长时间没有完成的 handler，
+    /// 返回本次发现的卡住的 worker
+    pub(crate) fn check_workers(&self) -> Vec<(String, Stall)> {
+        let stalls: Vec<_> = {
+            let workers = self.workers.lock().unwrap();
+            workers
+                .iter()
+                .flat_map(|(host, handle)| {
+                    let stalls = handle.pool.check_stalled();
+                    stalls.into_iter().map(|stall| (host.clone(), stall))
+                })
+                .collect()
+        };
+        for (host, stall) in &stalls {
+            // 告警日志，按 target 过滤后接入告警
+            error!(
+                target: "dino::alert",
+                host = %host,
+                handler = %stall.handler,
+                busy_ms = stall.busy.as_millis() as u64,
+                queued = stall.queued,
+                abandoned = stall.abandoned,
+                "Worker stuck in {} for {:.1?} with {} queued request(s)",
+                stall.handler,
+                stall.busy,
+                stall.queued
+            );
+            let action = match stall.abandoned {
+                true => "replaced",
+                false => "interrupted",
+            };
+            let event = AuditEvent::new(host, "watchdog", AuditAction::WorkerRestart)
+                .with_detail(format!("{action} worker stuck in {}", stall.handler));
+            self.audit(event);
+        }
+        stalls
+    }
+}
+
+pub(crate) fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            state.check_workers();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, thread};
+
+    use anyhow::Result;
+    use dashmap::DashMap;
+
+    use super::*;
+    use crate::{ProjectConfig, SwappableAppRouter, WorkerSettings, engine::Req};
+
+    #[test]
+    fn watchdog_should_interrupt_stuck_workers() -> Result<()> {
+        let code = r#"
+        (function(){
+            async function spin() { while (true) {} }
+            async function hello() { return { status: 200, headers: {}, body: "ok" }; }
+            return { spin, hello };
+        })();
+        "#;
+        let config: ProjectConfig = serde_yaml::from_str(
+            "name: test\nroutes:\n  /spin:\n    - method: GET\n      handler: spin\n  /hello:\n    - method: GET\n      handler: hello\n",
+        )?;
+        let routers = DashMap::new();
+        routers.insert(
+            "stuck.test".to_string(),
+            SwappableAppRouter::try_new(code, config.routes)?,
+        );
+        let settings = WorkerSettings {
+            watchdog_secs: 1,
+            ..Default::default()
+        };
+        let state = AppState::with_worker_settings(
+            routers,
+            HashMap::from([("stuck.test".to_string(), settings)]),
+        );
+
+        let spin = {
+            let state = state.clone();
+            thread::spawn(move || {
+                let req = Req::builder().method("GET").url("/spin").build();
+                state.send("stuck.test".into(), "spin", req)
+            })
+        };
+        // 没有排队的请求时执行得再久也不算卡住
+        thread::sleep(Duration::from_millis(1100));
+        assert!(state.check_workers().is_empty());
+
+        let hello = {
+            let state = state.clone();
+            thread::spawn(move || {
+                let req = Req::builder().method("GET").url("/hello").build();
+                state.send("stuck.test".into(), "hello", req)
+            })
+        };
+        thread::sleep(Duration::from_millis(200));
+        let stalls = state.check_workers();
+        assert_eq!(stalls.len(), 1);
+        let (host, stall) = &stalls[0];
+        assert_eq!(
+            (host.as_str(), stall.handler.as_str()),
+            ("stuck.test", "spin")
+        );
+        assert_eq!((stall.queued, stall.abandoned), (1, false));
+
+        // 被中断的请求失败，worker 换成新的 runtime 后继续处理排队的请求
+        assert!(spin.join().unwrap().is_err());
+        let resp = hello.join().unwrap()?;
+        assert_eq!(resp.text(), Some("ok"));
+        assert!(state.check_workers().is_empty());
+        assert_eq!(state.tenants()[0].stalls, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,891 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use chrono_tz::Tz;
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender, never, select_biased};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, info_span, warn};
+
+use crate::{
+    Capabilities, Capability, Priority, StreamRequest, Timing, WorkerMessage, binding, capability,
+    cgroup,
+    engine::{self, Engine, JsWorker, Limits, Resp},
+    error::AppError,
+    host, pipe,
+    watchdog::{Heartbeat, Stall},
+};
+
+/// 连续处理这么多个高优先级请求后，先检查一次低优先级队列，避免低优先级请求饿死
+const STARVATION_LIMIT: u32 = 8;
+/// 批处理请求达到上限时，空闲 worker 每隔这么久检查一次是否有批处理名额空出
+const BATCH_POLL: Duration = Duration::from_millis(10);
+/// 求值模块期间心跳中记录的 handler 名称
+const INIT: &str = "<init>";
+/// 执行 inspector 的 eval 期间心跳中记录的 handler 名称
+const EVAL: &str = "<eval>";
+
+/// tenant worker 线程的设置：线程数的范围、扩缩容阈值，以及独占机器上的 CPU 绑定和优先级
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerSettings {
+    /// 最少的 worker 线程数
+    pub min_workers: usize,
+    /// 最多的 worker 线程数，大于 min_workers 时开启自动扩容
+    pub max_workers: usize,
+    /// 请求在队列中等待超过该时间（毫秒）时增加一个 worker
+    pub scale_up_queue_ms: u64,
+    /// 多出 min_workers 的 worker 空闲超过该时间（秒）后退出
+    pub idle_timeout_secs: u64,
+    /// 绑定到这些 CPU core，为空表示不绑定
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cpus: Vec<usize>,
+    /// 线程的 nice 值，-20 到 19，越小优先级越高，小于 0 通常需要 root 权限
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    /// 用 cgroup 限制所有 worker 线程合计使用的 CPU，以百分比表示，100 为一个 core，
+    /// 只支持 linux 上的 cgroup v2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_limit: Option<u32>,
+    /// 有请求排队时，handler 执行超过该时间（秒）没有完成则由 watchdog 中断并重建 worker，
+    /// 0 表示不检查
+    pub watchdog_secs: u64,
+    /// handler 执行超过该时间（毫秒）时中断并返回 504，不论是否有请求排队，0 表示不限制。
+    /// SSE 和流式路由限制的是两次产生或读取数据之间的时间，超时后关闭响应
+    pub timeout_ms: u64,
+    /// 每个 worker 的 JS runtime 可以使用的内存（MB），超过时 handler 返回 500，0 表示不限制。
+    /// 项目配置中设置了 `memory_limit_mb` 时以项目配置为准
+    pub memory_limit_mb: usize,
+    /// `dino:time` 的默认时区，项目配置中设置了 `timezone` 时以项目配置为准
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
+    /// 允许 handler 使用的运行时能力，默认不限制
+    #[serde(skip_serializing_if = "Capabilities::is_unrestricted")]
+    pub capabilities: Capabilities,
+}
+
+/// tenant 累计的扩缩容次数、被 watchdog 中断的次数，以及处理的请求数和占用的 CPU 时间
+#[derive(Debug, Default)]
+pub(crate) struct ScaleStats {
+    pub scale_ups: AtomicU64,
+    pub scale_downs: AtomicU64,
+    pub stalls: AtomicU64,
+    /// 包括 SSE 和流式路由的请求
+    pub requests: AtomicU64,
+    /// worker 线程执行 handler 的 CPU 时间（微秒）
+    pub cpu_us: AtomicU64,
+}
+
+/// 一个 tenant 的一组 worker 线程，共享按优先级划分的请求队列
+#[derive(Debug)]
+pub(crate) struct WorkerPool {
+    host: String,
+    code: String,
+    settings: WorkerSettings,
+    /// 按 `Priority` 的顺序：interactive、normal、batch
+    recv: [Receiver<WorkerMessage>; 3],
+    size: AtomicUsize,
+    /// 正在执行的批处理请求数
+    batch_running: AtomicUsize,
+    stats: Arc<ScaleStats>,
+    /// 各 worker 线程的心跳
+    beats: Mutex<Vec<Arc<Heartbeat>>>,
+}
+
+/// 各优先级请求队列的发送端
+#[derive(Debug, Clone)]
+pub(crate) struct Queues([Sender<WorkerMessage>; 3]);
+
+/// AppState 持有的 worker 句柄，drop 后所有 worker 处理完队列中的请求后退出
+#[derive(Debug)]
+pub(crate) struct WorkerHandle {
+    pub queues: Queues,
+    pub pool: Arc<WorkerPool>,
+}
+
+impl Default for WorkerSettings {
+    fn default() -> Self {
+        Self {
+            min_workers: 1,
+            max_workers: 1,
+            scale_up_queue_ms: 50,
+            idle_timeout_secs: 30,
+            cpus: vec![],
+            nice: None,
+            cpu_limit: None,
+            watchdog_secs: 30,
+            timeout_ms: 0,
+            memory_limit_mb: 0,
+            timezone: None,
+            capabilities: Capabilities::default(),
+        }
+    }
+}
+
+impl WorkerSettings {
+    /// 在 worker 线程中创建引擎前调用，CPU、优先级和 cgroup 设置失败只打印警告，不影响 worker 运行
+    pub(crate) fn apply(&self, host: &str) {
+        capability::set_current(self.capabilities.clone());
+        host::set_timezone(self.timezone);
+        if !self.cpus.is_empty() {
+            pin(&self.cpus);
+        }
+        if let Some(nice) = self.nice {
+            set_nice(nice);
+        }
+        if let Some(percent) = self.cpu_limit {
+            cgroup::join(host, percent);
+        }
+    }
+}
+
+impl ScaleStats {
+    /// 记录一个请求及其从 `start` 开始占用的 CPU 时间
+    fn record(&self, start: Duration) {
+        let cpu = thread_cpu_time().saturating_sub(start);
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.cpu_us
+            .fetch_add(cpu.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+impl WorkerHandle {
+    pub(crate) fn shutdown(&self) {
+        for _ in 0..self.pool.size() {
+            let _ = self.queues.send(WorkerMessage::Shutdown, Priority::Normal);
+        }
+    }
+}
+
+impl Queues {
+    pub(crate) fn send(
+        &self,
+        msg: WorkerMessage,
+        priority: Priority,
+    ) -> Result<(), crossbeam::channel::SendError<WorkerMessage>> {
+        self.0[priority as usize].send(msg)
+    }
+}
+
+impl WorkerPool {
+    /// 创建请求队列并启动 min_workers 个 worker
+    pub(crate) fn spawn(
+        host: &str,
+        code: String,
+        settings: WorkerSettings,
+        stats: Arc<ScaleStats>,
+    ) -> Result<WorkerHandle> {
+        let [(interactive, i), (normal, n), (batch, b)] =
+            std::array::from_fn(|_| crossbeam::channel::unbounded());
+        let min = settings.min_workers.max(1);
+        let pool = Arc::new(Self {
+            host: host.to_string(),
+            code,
+            settings,
+            recv: [i, n, b],
+            size: AtomicUsize::new(0),
+            batch_running: AtomicUsize::new(0),
+            stats,
+            beats: Mutex::new(vec![]),
+        });
+        for _ in 0..min {
+            pool.size.fetch_add(1, Ordering::Relaxed);
+            pool.spawn_thread()?;
+        }
+        Ok(WorkerHandle {
+            queues: Queues([interactive, normal, batch]),
+            pool,
+        })
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// 排队的请求数
+    fn queued(&self) -> usize {
+        self.recv.iter().map(|r| r.len()).sum()
+    }
+
+    /// 有请求排队时检查各 worker 是否卡住。中断无效的 worker 由新线程接替，
+    /// 新线程占用旧线程的名额，旧线程返回后直接退出
+    pub(crate) fn check_stalled(self: &Arc<Self>) -> Vec<Stall> {
+        let queued = self.queued();
+        if self.settings.watchdog_secs == 0 || queued == 0 {
+            return vec![];
+        }
+        let limit = Duration::from_secs(self.settings.watchdog_secs);
+        let beats = self.beats.lock().unwrap().clone();
+        let stalls: Vec<_> = beats
+            .iter()
+            .filter_map(|beat| beat.check(limit, queued))
+            .collect();
+        for stall in &stalls {
+            self.stats.stalls.fetch_add(1, Ordering::Relaxed);
+            if stall.abandoned
+                && let Err(e) = self.spawn_thread()
+            {
+                error!("Failed to replace stuck worker for {}: {e}", self.host);
+            }
+        }
+        stalls
+    }
+
+    /// 调用前 size 已经加 1，线程退出时减 1（空闲回收时已在 try_scale_down 中减去）
+    fn spawn_thread(self: &Arc<Self>) -> Result<()> {
+        let pool = self.clone();
+        let ret = thread::Builder::new()
+            .name(format!("worker-{}", self.host))
+            .spawn(move || {
+                pool.settings.apply(&pool.host);
+                binding::set_caller(&pool.host);
+                match pool.run() {
+                    Ok(true) => return,
+                    Ok(false) => {}
+                    Err(e) => error!("Worker for {} exited: {e:#}", pool.host),
+                }
+                pool.size.fetch_sub(1, Ordering::Relaxed);
+            });
+        if ret.is_err() {
+            self.size.fetch_sub(1, Ordering::Relaxed);
+        }
+        ret?;
+        Ok(())
+    }
+
+    /// 队列等待过长时增加一个 worker
+    fn try_scale_up(self: &Arc<Self>, queue: Duration) {
+        if queue < Duration::from_millis(self.settings.scale_up_queue_ms) {
+            return;
+        }
+        let max = self.settings.max_workers;
+        let grown = self
+            .size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < max).then_some(n + 1)
+            });
+        if let Ok(n) = grown {
+            info!(
+                "Scale up workers for {} to {} (queued {queue:?})",
+                self.host,
+                n + 1
+            );
+            self.stats.scale_ups.fetch_add(1, Ordering::Relaxed);
+            if let Err(e) = self.spawn_thread() {
+                error!("Failed to spawn worker for {}: {e}", self.host);
+            }
+        }
+    }
+
+    /// 空闲时如果多于 min_workers 则退出，返回是否应该退出
+    fn try_scale_down(&self) -> bool {
+        let min = self.settings.min_workers.max(1);
+        let shrunk = self
+            .size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n > min).then(|| n - 1)
+            });
+        if let Ok(n) = shrunk {
+            info!("Scale down workers for {} to {}", self.host, n - 1);
+            self.stats.scale_downs.fetch_add(1, Ordering::Relaxed);
+        }
+        shrunk.is_ok()
+    }
+
+    /// 取下一个请求：高优先级的队列优先；批处理请求最多占用 worker 数减一个线程，
+    /// 留出 worker 处理其他请求；连续处理 `STARVATION_LIMIT` 个请求后先检查低优先级队列
+    fn recv(
+        &self,
+        idle: Duration,
+        served: &mut u32,
+    ) -> Result<(WorkerMessage, Priority), RecvTimeoutError> {
+        let [interactive, normal, batch] = &self.recv;
+        let deadline = Instant::now() + idle;
+        loop {
+            let batch_limit = self.size().saturating_sub(1).max(1);
+            let batch_allowed = self.batch_running.load(Ordering::Relaxed) < batch_limit;
+            if *served >= STARVATION_LIMIT {
+                *served = 0;
+                if batch_allowed && let Ok(msg) = batch.try_recv() {
+                    return Ok(self.take_batch(msg));
+                }
+                if let Ok(msg) = normal.try_recv() {
+                    return Ok((msg, Priority::Normal));
+                }
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            let timeout = match batch_allowed {
+                true => left,
+                false => left.min(BATCH_POLL),
+            };
+            let never = never();
+            let (msg, priority) = select_biased! {
+                recv(interactive) -> msg => (msg, Priority::Interactive),
+                recv(normal) -> msg => (msg, Priority::Normal),
+                recv(if batch_allowed { batch } else { &never }) -> msg => (msg, Priority::Batch),
+                default(timeout) => {
+                    if Instant::now() >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    continue;
+                }
+            };
+            let msg = msg.map_err(|_| RecvTimeoutError::Disconnected)?;
+            *served += 1;
+            return Ok(match priority {
+                Priority::Batch => self.take_batch(msg),
+                _ => (msg, priority),
+            });
+        }
+    }
+
+    /// 取出的批处理请求计入 batch_running，处理完后由 `run` 减去
+    fn take_batch(&self, msg: WorkerMessage) -> (WorkerMessage, Priority) {
+        self.batch_running.fetch_add(1, Ordering::Relaxed);
+        (msg, Priority::Batch)
+    }
+
+    /// 中断回调和内存上限在求值模块之前设置，模块初始化同样受超时、watchdog 和内存上限约束
+    fn create_worker(&self, beat: &Arc<Heartbeat>) -> Result<JsWorker> {
+        let check = beat.clone();
+        let limits = Limits {
+            interrupt: Some(Box::new(move || check.should_interrupt())),
+            memory: self.settings.memory_limit_mb << 20,
+        };
+        beat.start(&Arc::from(INIT));
+        let worker = JsWorker::try_new_with(&self.code, limits);
+        beat.finish();
+        beat.take_interrupt();
+        let worker = worker.with_context(|| match beat.take_timeout() {
+            true => format!("{} worker timed out evaluating the module", JsWorker::NAME),
+            false => format!("Failed to create {} worker", JsWorker::NAME),
+        })?;
+        Ok(worker)
+    }
+
+    /// 返回 true 表示因空闲被回收，或卡住后已由新线程接替
+    fn run(self: &Arc<Self>) -> Result<bool> {
+        let timeout =
+            (self.settings.timeout_ms > 0).then(|| Duration::from_millis(self.settings.timeout_ms));
+        let beat = Arc::new(Heartbeat::new(timeout));
+        self.beats.lock().unwrap().push(beat.clone());
+        let ret = self.serve(&beat);
+        self.beats
+            .lock()
+            .unwrap()
+            .retain(|b| !Arc::ptr_eq(b, &beat));
+        ret
+    }
+
+    fn serve(self: &Arc<Self>, beat: &Arc<Heartbeat>) -> Result<bool> {
+        let mut worker = self.create_worker(beat)?;
+        let idle = Duration::from_secs(self.settings.idle_timeout_secs);
+        let mut cold = true;
+        let mut served = 0;
+        loop {
+            let (msg, priority) = match self.recv(idle, &mut served) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) if self.try_scale_down() => return Ok(true),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Ok(false),
+            };
+            let cpu = thread_cpu_time();
+            let req = match msg {
+                WorkerMessage::Request(req) => req,
+                WorkerMessage::Stream(req) => {
+                    let handler = req.handler.clone();
+                    beat.start(&handler);
+                    stream(&worker, *req, beat);
+                    beat.finish();
+                    self.stats.record(cpu);
+                    if priority == Priority::Batch {
+                        self.batch_running.fetch_sub(1, Ordering::Relaxed);
+                    }
+                    cold = false;
+                    if self.recover(beat, &handler, &mut worker, &mut cold)? {
+                        return Ok(true);
+                    }
+                    continue;
+                }
+                WorkerMessage::Pipe(req) => {
+                    let handler = req.handler.clone();
+                    beat.start(&handler);
+                    pipe::run(&worker, *req, beat);
+                    beat.finish();
+                    self.stats.record(cpu);
+                    if priority == Priority::Batch {
+                        self.batch_running.fetch_sub(1, Ordering::Relaxed);
+                    }
+                    cold = false;
+                    if self.recover(beat, &handler, &mut worker, &mut cold)? {
+                        return Ok(true);
+                    }
+                    continue;
+                }
+                WorkerMessage::Inspect(send) => {
+                    let _ = send.send(worker.inspect());
+                    continue;
+                }
+                WorkerMessage::Eval(code, send) => {
+                    let eval = Arc::from(EVAL);
+                    beat.start(&eval);
+                    let ret = worker.eval(&code);
+                    beat.finish();
+                    let _ = send.send(ret);
+                    if self.recover(beat, &eval, &mut worker, &mut cold)? {
+                        return Ok(true);
+                    }
+                    continue;
+                }
+                WorkerMessage::Shutdown => {
+                    info!("Worker shutdown");
+                    return Ok(false);
+                }
+            };
+            let span = info_span!(parent: &req.span, "js", handler = %req.handler);
+            let _enter = span.enter();
+            let queue = req.queued_at.elapsed();
+            self.try_scale_up(queue);
+            let start = Instant::now();
+            // handler 出错时丢弃 oneshot，请求方会收到错误，worker 继续处理后续请求
+            engine::set_log(req.log);
+            beat.start(&req.handler);
+            let ret = worker
+                .run_timed(&req.handler, req.req, req.replay)
+                .and_then(check_file);
+            beat.finish();
+            self.stats.record(cpu);
+            engine::set_log(None);
+            if priority == Priority::Batch {
+                self.batch_running.fetch_sub(1, Ordering::Relaxed);
+            }
+            let interrupted = beat.take_interrupt();
+            let timed_out = beat.take_timeout();
+            let out_of_memory = worker.take_out_of_memory();
+            match ret {
+                Ok((resp, serialize)) => {
+                    let timing = Timing {
+                        queue,
+                        exec: start.elapsed().saturating_sub(serialize),
+                        serialize,
+                        cold,
+                        ..Default::default()
+                    };
+                    cold = false;
+                    if let Err(e) = req.send.send(Ok((resp, timing))) {
+                        error!("Send resp to oneshot error: {}", e);
+                    }
+                }
+                // 错误中带有 JS 的调用栈，记录卡住的位置
+                Err(e) if interrupted => error!("Watchdog interrupted handler: {e:#}"),
+                Err(e) if timed_out => {
+                    warn!(
+                        "Handler {} timed out after {}ms: {e:#}",
+                        req.handler, self.settings.timeout_ms
+                    );
+                    let resp = timeout_resp();
+                    let timing = Timing {
+                        queue,
+                        exec: start.elapsed(),
+                        cold,
+                        ..Default::default()
+                    };
+                    let _ = req.send.send(Ok((resp, timing)));
+                }
+                Err(e) if out_of_memory => {
+                    error!("Handler {} ran out of memory: {e:#}", req.handler);
+                    let limit = self.settings.memory_limit_mb;
+                    let e = AppError::OutOfMemory(format!(
+                        "handler {} exceeded the memory limit of {limit}MB",
+                        req.handler
+                    ));
+                    let _ = req.send.send(Err(e));
+                }
+                Err(e) => error!("Run handler error: {e:#}"),
+            }
+            if beat.abandoned() {
+                info!("Stuck worker for {} returned and exits", self.host);
+                return Ok(true);
+            }
+            // 被中断的 runtime 状态可能不完整，换一个新的
+            if interrupted || timed_out || out_of_memory {
+                worker = self.create_worker(beat)?;
+                cold = true;
+            }
+        }
+    }
+
+    /// SSE、流式路由和 eval 结束后检查中断、超时和内存，runtime 被中断过时换一个新的。
+    /// 返回 true 表示卡住的 worker 已由新线程接替，应当退出
+    fn recover(
+        &self,
+        beat: &Arc<Heartbeat>,
+        handler: &str,
+        worker: &mut JsWorker,
+        cold: &mut bool,
+    ) -> Result<bool> {
+        let interrupted = beat.take_interrupt();
+        let timed_out = beat.take_timeout();
+        let out_of_memory = worker.take_out_of_memory();
+        if interrupted {
+            error!("Watchdog interrupted {handler}");
+        } else if timed_out {
+            warn!(
+                "{handler} made no progress for {}ms, closing it",
+                self.settings.timeout_ms
+            );
+        } else if out_of_memory {
+            error!("{handler} ran out of memory");
+        }
+        if beat.abandoned() {
+            info!("Stuck worker for {} returned and exits", self.host);
+            return Ok(true);
+        }
+        if interrupted || timed_out || out_of_memory {
+            *worker = self.create_worker(beat)?;
+            *cold = true;
+        }
+        Ok(false)
+    }
+}
+
+/// handler 超时的响应
+pub(crate) fn timeout_resp() -> Resp {
+    Resp {
+        status: 504,
+        headers: HashMap::new(),
+        body: Some("handler timed out".into()),
+        file: None,
+        compress: None,
+    }
+}
+
+/// 直接返回 `file` 的响应和 `serveFile` 一样需要 fs 能力
+fn check_file((resp, serialize): (Resp, Duration)) -> Result<(Resp, Duration)> {
+    if resp.file.is_some() && !capability::allowed(Capability::Fs) {
+        anyhow::bail!("fs is not allowed for this tenant, can't serve files");
+    }
+    Ok((resp, serialize))
+}
+
+/// 迭代 SSE handler 的事件，直到迭代结束、客户端断开或两个事件之间超时
+fn stream(worker: &JsWorker, req: StreamRequest, beat: &Heartbeat) {
+    let span = info_span!(parent: &req.span, "js", handler = %req.handler);
+    let _enter = span.enter();
+    let send = req.send;
+    let ret = worker.call_stream(&req.handler, req.req, &mut |event| {
+        beat.progress();
+        send.send(event).is_ok()
+    });
+    if let Err(e) = ret {
+        error!("Run stream handler error: {e:#}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pin(cpus: &[usize]) {
+    let ret = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for cpu in cpus {
+            libc::CPU_SET(*cpu, &mut set);
+        }
+        // pid 为 0 表示当前线程
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if ret != 0 {
+        warn!(
+            "Failed to pin worker to cpus {cpus:?}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin(cpus: &[usize]) {
+    warn!("Pinning worker to cpus {cpus:?} is only supported on linux");
+}
+
+/// 当前线程占用的 CPU 时间，其他平台上用单调时钟代替
+#[cfg(target_os = "linux")]
+fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let ret = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    if ret != 0 {
+        return Duration::ZERO;
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_cpu_time() -> Duration {
+    static EPOCH: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed()
+}
+
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) {
+    // Linux 下 setpriority 的 PRIO_PROCESS 配合线程 id 只作用于当前线程
+    let ret = unsafe {
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, tid, nice)
+    };
+    if ret != 0 {
+        warn!(
+            "Failed to set worker nice to {nice}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_nice(nice: i32) {
+    warn!("Setting worker nice to {nice} is only supported on linux");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+
+    use crate::{Reply, engine::Req};
+
+    #[test]
+    fn worker_pool_should_scale_up_and_down() -> Result<()> {
+        let code = "(function(){ async function hello(){ return { status: 200, headers: {}, body: 'ok' }; } return { hello }; })();";
+        let settings = WorkerSettings {
+            max_workers: 3,
+            scale_up_queue_ms: 0,
+            idle_timeout_secs: 1,
+            ..Default::default()
+        };
+        let stats = Arc::new(ScaleStats::default());
+        let handle = WorkerPool::spawn("scale.test", code.into(), settings, stats.clone())?;
+        assert_eq!(handle.pool.size(), 1);
+
+        for _ in 0..3 {
+            let req = Req::builder().method("GET").url("/").build();
+            let (msg, recv) = WorkerMessage::new_request(req, "hello".into(), None, None);
+            handle.queues.send(msg, Priority::Normal)?;
+            assert_eq!(recv.recv()??.0.status, 200);
+        }
+        assert_eq!(handle.pool.size(), 3);
+        assert_eq!(stats.scale_ups.load(Ordering::Relaxed), 2);
+
+        // 多出来的 worker 空闲 1 秒后退出
+        thread::sleep(Duration::from_millis(2500));
+        assert_eq!(handle.pool.size(), 1);
+        assert_eq!(stats.scale_downs.load(Ordering::Relaxed), 2);
+        handle.shutdown();
+        Ok(())
+    }
+
+    #[test]
+    fn worker_pool_should_prefer_interactive_requests() -> Result<()> {
+        // 返回开始执行的时间，每个 worker 有独立的 runtime
+        let code = r#"
+        (function(){
+            async function slow() {
+                const start = Date.now();
+                while (Date.now() - start < 300) {}
+                return { status: 200, headers: {}, body: String(start) };
+            }
+            async function fast() {
+                return { status: 200, headers: {}, body: String(Date.now()) };
+            }
+            return { slow, fast };
+        })();
+        "#;
+        let settings = WorkerSettings {
+            min_workers: 2,
+            max_workers: 2,
+            ..Default::default()
+        };
+        let stats = Arc::new(ScaleStats::default());
+        let handle = WorkerPool::spawn("priority.test", code.into(), settings, stats)?;
+        let send = |handler: &str, priority| {
+            let req = Req::builder().method("GET").url("/").build();
+            let (msg, recv) = WorkerMessage::new_request(req, handler.into(), None, None);
+            handle.queues.send(msg, priority).unwrap();
+            recv
+        };
+        let started = |recv: oneshot::Receiver<Reply>| -> Result<u64> {
+            Ok(recv.recv()??.0.text().unwrap_or_default().parse()?)
+        };
+
+        // 第一个批处理请求占用一个 worker 后，第二个要等它完成，另一个 worker 留给交互请求
+        let first = send("slow", Priority::Batch);
+        thread::sleep(Duration::from_millis(50));
+        let second = send("slow", Priority::Batch);
+        thread::sleep(Duration::from_millis(50));
+        let interactive = send("fast", Priority::Interactive);
+        let (first, second, interactive) =
+            (started(first)?, started(second)?, started(interactive)?);
+        assert!(first < interactive && interactive < second);
+        assert!(second - first >= 300);
+        handle.shutdown();
+        Ok(())
+    }
+
+    #[test]
+    fn worker_pool_should_time_out_slow_handlers() -> Result<()> {
+        let code = r#"
+        (function(){
+            async function spin() { while (true) {} }
+            async function hello() { return { status: 200, headers: {}, body: "ok" }; }
+            return { spin, hello };
+        })();
+        "#;
+        let settings = WorkerSettings {
+            timeout_ms: 200,
+            ..Default::default()
+        };
+        let stats = Arc::new(ScaleStats::default());
+        let handle = WorkerPool::spawn("timeout.test", code.into(), settings, stats)?;
+        let send = |handler: &str| {
+            let req = Req::builder().method("GET").url("/").build();
+            let (msg, recv) = WorkerMessage::new_request(req, handler.into(), None, None);
+            handle.queues.send(msg, Priority::Normal).unwrap();
+            recv
+        };
+
+        let (resp, timing) = send("spin").recv()??;
+        assert_eq!(resp.status, 504);
+        assert!(timing.exec >= Duration::from_millis(200));
+        // 超时后换成新的 worker，继续处理请求
+        let (resp, _) = send("hello").recv()??;
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.text(), Some("ok"));
+        handle.shutdown();
+        Ok(())
+    }
+
+    #[test]
+    fn worker_pool_should_time_out_stuck_streams() -> Result<()> {
+        let code = r#"
+        (function(){
+            async function* ticks() {
+                for (let i = 0; i < 3; i++) {
+                    const start = Date.now();
+                    while (Date.now() - start < 100) {}
+                    yield { data: { i } };
+                }
+                while (true) {}
+            }
+            async function hello() { return { status: 200, headers: {}, body: "ok" }; }
+            return { ticks, hello };
+        })();
+        "#;
+        let settings = WorkerSettings {
+            timeout_ms: 200,
+            ..Default::default()
+        };
+        let stats = Arc::new(ScaleStats::default());
+        let handle = WorkerPool::spawn("stream.test", code.into(), settings, stats)?;
+        let req = || Req::builder().method("GET").url("/").build();
+
+        // 每个事件之间都没有超时，合计超过 timeout_ms 也不会中断，卡住后关闭
+        let (msg, mut events) = WorkerMessage::new_stream(req(), "ticks".into());
+        handle.queues.send(msg, Priority::Normal).unwrap();
+        let start = Instant::now();
+        let mut count = 0;
+        while events.blocking_recv().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 3);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let (msg, recv) = WorkerMessage::new_request(req(), "hello".into(), None, None);
+        handle.queues.send(msg, Priority::Normal).unwrap();
+        let (resp, _) = recv.recv()??;
+        assert_eq!(resp.text(), Some("ok"));
+        handle.shutdown();
+        Ok(())
+    }
+
+    #[test]
+    fn worker_pool_should_time_out_module_init() -> Result<()> {
+        let code = r#"
+        (function(){
+            while (true) {}
+        })();
+        "#;
+        let settings = WorkerSettings {
+            timeout_ms: 200,
+            ..Default::default()
+        };
+        let stats = Arc::new(ScaleStats::default());
+        let handle = WorkerPool::spawn("init.test", code.into(), settings, stats)?;
+        // 初始化被中断后 worker 线程退出
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while handle.pool.size() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(handle.pool.size(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn worker_pool_should_limit_memory() -> Result<()> {
+        let code = r#"
+        (function(){
+            async function grow() {
+                const chunks = [];
+                while (true) chunks.push("x".repeat(1 << 20) + chunks.length);
+            }
+            async function fake() { throw new Error("out of memory"); }
+            async function hello() { return { status: 200, headers: {}, body: "ok" }; }
+            return { grow, fake, hello };
+        })();
+        "#;
+        let settings = WorkerSettings {
+            memory_limit_mb: 32,
+            ..Default::default()
+        };
+        let stats = Arc::new(ScaleStats::default());
+        let handle = WorkerPool::spawn("memory.test", code.into(), settings.clone(), stats)?;
+        let send = |handler: &str| {
+            let req = Req::builder().method("GET").url("/").build();
+            let (msg, recv) = WorkerMessage::new_request(req, handler.into(), None, None);
+            handle.queues.send(msg, Priority::Normal).unwrap();
+            recv
+        };
+
+        let err = send("grow").recv()?.unwrap_err();
+        assert!(matches!(err, AppError::OutOfMemory(_)));
+        assert_eq!(
+            err.into_response().status(),
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        let (resp, _) = send("hello").recv()??;
+        assert_eq!(resp.status, 200);
+        // 只有真正超过上限才算，handler 自己抛出的同样消息不算
+        assert!(send("fake").recv().is_err());
+        handle.shutdown();
+
+        // 模块初始化同样受内存上限约束
+        let code = r#"
+        (function(){
+            const chunks = [];
+            while (true) chunks.push("x".repeat(1 << 20) + chunks.length);
+        })();
+        "#;
+        let stats = Arc::new(ScaleStats::default());
+        let handle = WorkerPool::spawn("memory-init.test", code.into(), settings, stats)?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while handle.pool.size() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(handle.pool.size(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+const { join } = require('./join.js');
+const { upper } = require('./upper.js');
+
+exports.hello = function (name) {
+  return join(upper('hello'), name);
+};
+exports.self = function () {
+  return this === module.exports;
+};
+module.exports.version = '1.0';
@@ -0,0 +1,5 @@
+module.exports = {
+  join(a, b) {
+    return `${a}, ${b}!`;
+  },
+};
@@ -0,0 +1,3 @@
+export function upper(s) {
+  return s.toUpperCase();
+}
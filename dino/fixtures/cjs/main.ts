@@ -0,0 +1,5 @@
+import greet, { version } from './lib/greet.js';
+
+export function hello() {
+  return { message: greet.hello('dino'), version, self: greet.self() };
+}
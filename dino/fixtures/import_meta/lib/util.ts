@@ -0,0 +1,8 @@
+export function assets() {
+  return {
+    main: import.meta.main,
+    sibling: import.meta.resolve('./data.json'),
+    parent: import.meta.resolve('../static/logo.png'),
+    absolute: import.meta.resolve('https://example.com/a.js'),
+  };
+}
@@ -0,0 +1,9 @@
+import { assets } from './lib/util.ts';
+
+export function meta() {
+  return {
+    main: import.meta.main,
+    url: import.meta.url,
+    lib: assets(),
+  };
+}
@@ -0,0 +1,22 @@
+import { Buffer } from 'node:buffer';
+import { createHash, randomUUID } from 'node:crypto';
+import path from 'node:path';
+import { parse, stringify } from 'querystring';
+import { URL } from 'node:url';
+
+export function compat() {
+  const url = new URL('../b/c?x=1#top', 'https://Example.com:443/a/index.html');
+  url.searchParams.append('y', 'z w');
+  return {
+    buffer: Buffer.from('héllo').toString('base64'),
+    utf8: Buffer.from('aMOpbGxv', 'base64').toString(),
+    hash: createHash('sha256').update('hello').digest('hex'),
+    uuid: randomUUID().length,
+    path: path.join('/a/b', '../c', 'd.ts'),
+    ext: path.extname('index.d.ts'),
+    qs: parse('a=1&a=2&b=%20'),
+    qs2: stringify({ a: [1, 2], b: 'x y' }),
+    url: url.href,
+    host: url.host,
+  };
+}
@@ -0,0 +1,135 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result, bail};
+use bundler::{
+    DenoLandUrl, JSR_PREFIX, JsrSpecifier, ModuleType, Options, bundle_to_string_pretty,
+    fetch_remote,
+};
+use clap::Parser;
+use colored::Colorize;
+use url::Url;
+
+use crate::{CmdExecutor, import_map::add_import, utils::find_project_root};
+
+/// 打包后的依赖存放在项目下的这个目录
+const VENDOR_DIR: &str = "vendor";
+
+#[derive(Debug, Parser)]
+pub struct AddOpts {
+    /// URL of the module or a JSR package, e.g. `jsr:@std/path@1`
+    pub specifier: String,
+    /// Name to import the dependency with, derived from the specifier by default
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Bundle the dependency into the project instead of downloading it at build time
+    #[arg(long)]
+    pub vendor: bool,
+    /// Project directory, defaults to the project containing the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+}
+
+impl CmdExecutor for AddOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let root = find_project_root(self.project_dir.unwrap_or_else(|| ".".into()))?;
+        let options = Options::default();
+
+        let (url, default_name) = if self.specifier.starts_with(JSR_PREFIX) {
+            let spec = JsrSpecifier::parse(&self.specifier)?;
+            (spec.resolve(&options)?, Some(spec.import_name()))
+        } else {
+            let url = Url::parse(&self.specifier)
+                .ok()
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+                .with_context(|| {
+                    format!(
+                        "{} is neither an http(s) URL nor a jsr: specifier",
+                        self.specifier
+                    )
+                })?;
+            let name = default_name(&url);
+            // deno.land/x 的版本范围固定为具体版本
+            let url = match DenoLandUrl::parse(url.as_str()).filter(DenoLandUrl::is_range) {
+                Some(deno_land) => deno_land.resolve(&options)?,
+                None => url.to_string(),
+            };
+            (url, name)
+        };
+        let Some(name) = self.name.or(default_name) else {
+            bail!("can't derive a name from {url}, pass one with --name");
+        };
+
+        let target = if self.vendor {
+            let file = vendor_path(&url)?;
+            let options = Options {
+                module_type: ModuleType::Es,
+                ..options
+            };
+            let code = bundle_to_string_pretty(&url, &options)?;
+            let path = root.join(&file);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(&path, code)?;
+            format!("./{file}")
+        } else {
+            // 固定 checksum，远程内容变化时打包会失败
+            let (_, checksum) = fetch_remote(&url, &options)?;
+            format!("{url}#sha256={checksum}")
+        };
+
+        add_import(&root, &name, &target)?;
+        println!("{} {name} -> {target}", "Added".green());
+        Ok(())
+    }
+}
+
+/// 从 URL 推导导入名，如 `https://deno.land/x/oak@v12/mod.ts` 为 `oak`
+fn default_name(url: &Url) -> Option<String> {
+    let segments: Vec<_> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    let (file, dirs) = segments.split_last()?;
+    let stem = file.split('.').next().unwrap_or(file);
+    let name = match stem {
+        "mod" | "index" | "main" => dirs.last()?,
+        _ => stem,
+    };
+    let name = name.split('@').next().unwrap_or(name);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// `vendor/<host>/<path>`，打包后统一为 .js
+fn vendor_path(url: &str) -> Result<String> {
+    let url = Url::parse(url)?;
+    let host = url.host_str().context("URL has no host")?;
+    let path = PathBuf::from(url.path().trim_start_matches('/')).with_extension("js");
+    if path
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        bail!("can't vendor {url}: unexpected path");
+    }
+    Ok(format!("{VENDOR_DIR}/{host}/{}", path.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_should_derive_names_and_vendor_paths() -> Result<()> {
+        let name = |url: &str| default_name(&Url::parse(url).unwrap());
+        assert_eq!(
+            name("https://deno.land/x/oak@v12.6.1/mod.ts").as_deref(),
+            Some("oak")
+        );
+        assert_eq!(
+            name("https://example.com/lib/greet.js").as_deref(),
+            Some("greet")
+        );
+        assert_eq!(name("https://example.com/"), None);
+
+        assert_eq!(
+            vendor_path("https://jsr.io/@std/path/1.0.8/mod.ts")?,
+            "vendor/jsr.io/@std/path/1.0.8/mod.js"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use dino_server::{AuditAction, AuditLog, AuditQuery};
+
+use crate::CmdExecutor;
+
+#[derive(Debug, Parser)]
+pub struct AuditOpts {
+    /// Audit log written by `dino run --audit-log`
+    pub file: PathBuf,
+    /// Only show events of this tenant (host)
+    #[arg(long)]
+    pub tenant: Option<String>,
+    /// Only show events of this action (code-swap, config-change, worker-restart, admin)
+    #[arg(long)]
+    pub action: Option<AuditAction>,
+    /// Only show the last n events
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+}
+
+impl CmdExecutor for AuditOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let query = AuditQuery {
+            tenant: self.tenant,
+            action: self.action,
+            limit: self.limit,
+        };
+        for event in AuditLog::query_file(&self.file, &query)? {
+            let change = match (&event.before, &event.after) {
+                (Some(before), Some(after)) => format!(" {before} -> {after}"),
+                _ => String::new(),
+            };
+            let detail = event.detail.map(|d| format!(" ({d})")).unwrap_or_default();
+            println!(
+                "{} {} {:?} by {}{change}{detail}",
+                event.at, event.tenant, event.action, event.actor
+            );
+        }
+        Ok(())
+    }
+}
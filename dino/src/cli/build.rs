@@ -1,15 +1,250 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bundler::{Defines, Timings};
 use clap::Parser;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde_json::{Value, json};
+
+use crate::{
+    CmdExecutor, Diagnostic, OutputOpts,
+    utils::{BuildSettings, build_project_with, find_project_root, find_workspace_projects},
+};
 
-use crate::{CmdExecutor, utils::build_project};
+const SLOWEST_MODULES: usize = 10;
 
 #[derive(Debug, Parser)]
-pub struct BuildOpts {}
+pub struct BuildOpts {
+    /// Project directory, can be repeated to build several projects
+    #[arg(long)]
+    pub project_dir: Vec<PathBuf>,
+    /// Build every project found under the current directory
+    #[arg(long)]
+    pub workspace: bool,
+    /// Number of projects built in parallel
+    #[arg(short, long, default_value_t = default_jobs())]
+    pub jobs: usize,
+    /// Report the duration of each bundle phase and the slowest modules
+    #[arg(long)]
+    pub timings: bool,
+    /// Revalidate cached URL imports and rebuild even if the bundle is up to date
+    #[arg(long)]
+    pub reload: bool,
+    /// Replace a global with a constant at build time, e.g. `--define FEATURE_X=false`
+    #[arg(long = "define", value_name = "KEY=VALUE", value_parser = Defines::parse_pair)]
+    pub defines: Vec<(String, String)>,
+    /// Also write TypeScript declarations of the handlers to `.build/handlers.d.ts`
+    #[arg(long)]
+    pub dts: bool,
+    #[command(flatten)]
+    pub output: OutputOpts,
+}
+
+/// 一个项目的打包结果
+struct ProjectBuild {
+    root: PathBuf,
+    elapsed: Duration,
+    /// 产物路径和耗时，产物已存在时耗时为 None
+    result: Result<(PathBuf, Option<Timings>)>,
+}
 
 impl CmdExecutor for BuildOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let cur_dir = std::env::current_dir()?.display().to_string();
-        let filename = build_project(&cur_dir)?;
-        println!("Build success: {}", filename);
+        let mut roots = if self.workspace {
+            find_workspace_projects(".")?
+        } else {
+            self.project_dir
+                .into_iter()
+                .map(find_project_root)
+                .collect::<Result<Vec<_>>>()?
+        };
+        if roots.is_empty() {
+            roots.push(find_project_root(".")?);
+        }
+
+        let mut defines = Defines::default();
+        for (key, value) in self.defines {
+            defines.insert(key, value);
+        }
+        let json = self.output.is_json();
+        let settings = BuildSettings {
+            // JSON 结果总是包含耗时，并据此区分是否重新打包
+            timings: self.timings || json,
+            reload: self.reload,
+            defines,
+            dts: self.dts,
+        };
+        if let [root] = roots.as_slice()
+            && !json
+        {
+            let (filename, timings) = build_project_with(root, settings.clone())?;
+            println!("Build success: {}", filename.display());
+            if self.timings {
+                print_timings(root, timings.as_ref());
+            }
+            return Ok(());
+        }
+
+        let builds = build_projects(roots, self.jobs.max(1), &settings, json);
+        let failed = builds.iter().filter(|b| b.result.is_err()).count();
+        if json {
+            let projects: Vec<_> = builds.into_iter().map(build_json).collect();
+            self.output
+                .print(json!({ "ok": failed == 0, "projects": projects }))?;
+            // 错误已经包含在结果中，不再输出
+            if failed > 0 {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        if self.timings {
+            for build in &builds {
+                if let Ok((_, timings)) = &build.result {
+                    print_timings(&build.root, timings.as_ref());
+                }
+            }
+        }
+        if failed > 0 {
+            anyhow::bail!("{failed} project(s) failed to build");
+        }
         Ok(())
     }
 }
+
+fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(4, |n| n.get())
+}
+
+/// 用 `jobs` 个线程并行打包，每个项目一个进度条，`quiet` 时不输出进度。
+/// 返回的结果与 `roots` 的顺序一致
+fn build_projects(
+    roots: Vec<PathBuf>,
+    jobs: usize,
+    settings: &BuildSettings,
+    quiet: bool,
+) -> Vec<ProjectBuild> {
+    let progress = match quiet {
+        true => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        false => MultiProgress::new(),
+    };
+    let style = ProgressStyle::with_template("{spinner:.green} {prefix:.bold} {wide_msg}").unwrap();
+    let queue: VecDeque<_> = roots
+        .into_iter()
+        .enumerate()
+        .map(|(i, root)| {
+            let bar = progress.add(ProgressBar::new_spinner());
+            bar.set_style(style.clone());
+            bar.set_prefix(root.display().to_string());
+            bar.set_message("waiting");
+            (i, root, bar)
+        })
+        .collect();
+    let queue = Mutex::new(queue);
+    let builds = Mutex::new(vec![]);
+
+    thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| {
+                loop {
+                    let Some((i, root, bar)) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    bar.enable_steady_tick(Duration::from_millis(100));
+                    bar.set_message("building");
+                    let start = Instant::now();
+                    let result = build_project_with(&root, settings.clone());
+                    let elapsed = start.elapsed();
+                    let msg = match &result {
+                        Ok((filename, _)) => {
+                            format!("built {} in {elapsed:.2?}", filename.display())
+                        }
+                        Err(e) => format!("failed: {e:#}"),
+                    };
+                    // 非终端环境下进度条不会显示，直接输出结果
+                    if progress.is_hidden() && !quiet {
+                        println!("{}: {msg}", root.display());
+                    }
+                    bar.finish_with_message(msg);
+                    let build = ProjectBuild {
+                        root,
+                        elapsed,
+                        result,
+                    };
+                    builds.lock().unwrap().push((i, build));
+                }
+            });
+        }
+    });
+
+    let mut builds = builds.into_inner().unwrap();
+    builds.sort_by_key(|(i, _)| *i);
+    builds.into_iter().map(|(_, build)| build).collect()
+}
+
+/// 产物的文件名就是构建 hash
+fn build_json(build: ProjectBuild) -> Value {
+    let root = build.root.display().to_string();
+    let elapsed = millis(build.elapsed);
+    match build.result {
+        Ok((artifact, timings)) => json!({
+            "root": root,
+            "ok": true,
+            "artifact": artifact,
+            "config": artifact.with_extension("yml"),
+            "hash": artifact.file_stem().map(|s| s.to_string_lossy()),
+            "cached": timings.is_none(),
+            "duration_ms": elapsed,
+            "timings": timings.as_ref().map(timings_json),
+        }),
+        Err(e) => json!({
+            "root": root,
+            "ok": false,
+            "duration_ms": elapsed,
+            "error": Diagnostic::from(e).to_json(),
+        }),
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn timings_json(timings: &Timings) -> Value {
+    let phases: serde_json::Map<_, _> = timings
+        .phases
+        .iter()
+        .map(|(phase, elapsed)| (phase.to_string(), millis(*elapsed).into()))
+        .collect();
+    let slowest: Vec<_> = timings
+        .slowest_modules(SLOWEST_MODULES)
+        .into_iter()
+        .map(|(module, elapsed)| json!({ "module": module, "ms": millis(elapsed) }))
+        .collect();
+    json!({
+        "phases_ms": phases,
+        "total_ms": millis(timings.total),
+        "slowest_modules": slowest,
+    })
+}
+
+fn print_timings(root: &Path, timings: Option<&Timings>) {
+    println!("Timings for {}:", root.display());
+    let Some(timings) = timings else {
+        println!("  bundle is up to date, nothing was built");
+        return;
+    };
+    for (phase, elapsed) in &timings.phases {
+        println!("  {phase:<10} {elapsed:>10.2?}");
+    }
+    println!("  {:<10} {:>10.2?}", "total", timings.total);
+    println!("  slowest modules:");
+    for (name, elapsed) in timings.slowest_modules(SLOWEST_MODULES) {
+        println!("    {elapsed:>10.2?}  {name}");
+    }
+}
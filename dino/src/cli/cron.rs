@@ -0,0 +1,101 @@
+use std::{fs, path::PathBuf, time::Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use dino_server::engine::{Engine, JsWorker, ScheduleEvent, Trigger};
+
+use crate::{
+    CmdExecutor,
+    diagnostic::{Diagnostic, ErrorCode},
+    utils::{CONFIG_FILE, build_project, check_project, find_project_root},
+};
+
+#[derive(Debug, Parser)]
+pub struct CronOpts {
+    /// Project directory, defaults to the project containing the current directory
+    #[arg(long, global = true)]
+    pub project_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    pub cmd: CronCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CronCommand {
+    /// List the scheduled jobs declared in config.yml
+    List,
+    /// Run a scheduled job once with a synthetic schedule event
+    Run {
+        name: String,
+        /// Scheduled time passed to the handler (RFC 3339), defaults to now
+        #[arg(long)]
+        time: Option<DateTime<Utc>>,
+    },
+}
+
+impl CmdExecutor for CronOpts {
+    async fn execute(self) -> Result<()> {
+        let root = find_project_root(self.project_dir.unwrap_or_else(|| ".".into()))?;
+        let config = check_project(&root)?;
+        let (name, time) = match self.cmd {
+            CronCommand::List => {
+                for (name, job) in &config.crons {
+                    println!("{:<20} {:<20} {}", name.bold(), job.schedule, job.handler);
+                }
+                return Ok(());
+            }
+            CronCommand::Run { name, time } => (name, time),
+        };
+        let Some(job) = config.crons.get(&name) else {
+            let names: Vec<_> = config.crons.keys().map(String::as_str).collect();
+            let help = match names.is_empty() {
+                true => "declare jobs under `crons` in config.yml".to_string(),
+                false => format!("available jobs: {}", names.join(", ")),
+            };
+            return Err(Diagnostic::new(
+                ErrorCode::ConfigInvalid,
+                format!("cron job `{name}` not found"),
+            )
+            .with_file(root.join(CONFIG_FILE))
+            .with_help(help)
+            .into());
+        };
+
+        let code = fs::read_to_string(build_project(&root)?)?;
+        let worker = JsWorker::try_new(&code)?;
+        dino_server::set_timezone(config.timezone);
+        // 没有 HTTP 请求，handler 以 `scheduled` 事件触发，时间带有项目时区的偏移
+        let time = time.unwrap_or_else(Utc::now);
+        let time = match config.timezone {
+            Some(tz) => time
+                .with_timezone(&tz)
+                .to_rfc3339_opts(SecondsFormat::Secs, false),
+            None => time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+        let event = Trigger::Scheduled(ScheduleEvent {
+            name: name.clone(),
+            schedule: job.schedule.clone(),
+            time,
+        });
+
+        let start = Instant::now();
+        let resp = worker.trigger(&job.handler, event)?;
+        let elapsed = start.elapsed();
+        if let Some(body) = &resp.body {
+            println!("{}", String::from_utf8_lossy(body));
+        }
+        if !(200..300).contains(&resp.status) {
+            anyhow::bail!(
+                "cron job {name} failed with status {} in {elapsed:.2?}",
+                resp.status
+            );
+        }
+        eprintln!(
+            "{} {name} ({}) in {elapsed:.2?}",
+            "done".green(),
+            job.handler
+        );
+        Ok(())
+    }
+}
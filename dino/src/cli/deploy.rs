@@ -0,0 +1,169 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use dino_server::{DeployConfig, GitSource, Preview, ProjectConfig, parse_ttl};
+use serde_json::json;
+
+use crate::{
+    CmdExecutor, OutputOpts,
+    client::RemoteOpts,
+    utils::{CONFIG_FILE, build_project, find_project_root},
+};
+
+#[derive(Debug, Parser)]
+pub struct DeployOpts {
+    #[command(flatten)]
+    pub remote: RemoteOpts,
+    /// Tenant host, defaults to `deploy.host` in config.yml
+    pub host: Option<String>,
+    /// Let the server fetch and build `<url>[#ref]` instead of uploading a local build
+    #[arg(long, value_name = "URL[#REF]", conflicts_with = "project_dir")]
+    pub git: Option<GitSource>,
+    /// Secret of the repository's push webhook, which redeploys the tenant on push
+    #[arg(long, requires = "git")]
+    pub secret: Option<String>,
+    /// Deploy to the preview host `<name>--<host>`, the name defaults to the current git branch
+    #[arg(long, value_name = "NAME", num_args = 0..=1, conflicts_with = "git")]
+    pub preview: Option<Option<String>>,
+    /// How long the preview is kept, e.g. `30m`, `12h` or `7d`
+    #[arg(long, value_parser = parse_ttl, requires = "preview")]
+    pub ttl: Option<Duration>,
+    /// Project directory, defaults to the project containing the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+    #[command(flatten)]
+    pub output: OutputOpts,
+}
+
+impl CmdExecutor for DeployOpts {
+    async fn execute(self) -> Result<()> {
+        // 从 git 部署时不需要本地项目，但如果在项目中仍然读取其 `deploy`
+        let root = match &self.git {
+            None => Some(find_project_root(
+                self.project_dir.unwrap_or_else(|| ".".into()),
+            )?),
+            Some(_) => find_project_root(".").ok(),
+        };
+        let deploy = root
+            .as_ref()
+            .and_then(|root| ProjectConfig::load(root.join(CONFIG_FILE)).ok())
+            .and_then(|config| config.deploy)
+            .unwrap_or_default();
+        let client = remote_opts(self.remote, &deploy)?.client()?;
+        let host = self
+            .host
+            .or(deploy.host)
+            .context("no tenant host, pass it as an argument or set `deploy.host` in config.yml")?;
+        let output = self.output;
+        let Some(mut source) = self.git else {
+            let root = root.context("no project found")?;
+            let filename = build_project(&root)?;
+            let artifact = json!({
+                "artifact": filename,
+                "hash": filename.file_stem().map(|s| s.to_string_lossy()),
+            });
+            let mut body = json!({
+                "code": fs::read_to_string(&filename)?,
+                "config": fs::read_to_string(filename.with_extension("yml"))?,
+            });
+            let Some(name) = self.preview else {
+                let mut ret = client.put(&format!("/tenants/{host}"), body)?;
+                if output.is_json() {
+                    ret["build"] = artifact;
+                    return output.print(ret);
+                }
+                println!("Tenant {host} deployed");
+                return Ok(());
+            };
+            let name = match name {
+                Some(name) => name,
+                None => preview_name(&current_branch(&root)?),
+            };
+            if let Some(ttl) = self.ttl {
+                body["ttl"] = json!(ttl.as_secs());
+            }
+            let mut ret = client.put(&format!("/tenants/{host}/previews/{name}"), body)?;
+            if output.is_json() {
+                ret["build"] = artifact;
+                return output.print(ret);
+            }
+            let preview: Preview = serde_json::from_value(ret)?;
+            println!(
+                "Preview deployed to {}, expires at {}",
+                preview.host, preview.expires_at
+            );
+            return Ok(());
+        };
+
+        source.secret = self.secret;
+        let webhook = source.secret.is_some().then(|| {
+            format!(
+                "{}{}/tenants/{host}/git/webhook",
+                client.server(),
+                dino_server::ADMIN_PREFIX
+            )
+        });
+        let mut ret = client.put(&format!("/tenants/{host}/git"), json!(source))?;
+        if output.is_json() {
+            ret["webhook"] = json!(webhook);
+            return output.print(ret);
+        }
+        println!(
+            "Tenant {host} deployed from {} at {}",
+            source.url,
+            ret["commit"].as_str().unwrap_or_default()
+        );
+        if let Some(webhook) = webhook {
+            println!("Push webhook: {webhook}");
+        }
+        Ok(())
+    }
+}
+
+/// 命令行参数和环境变量没有指定的部分使用 config.yml 的 `deploy`
+fn remote_opts(mut remote: RemoteOpts, deploy: &DeployConfig) -> Result<RemoteOpts> {
+    remote.server = remote.server.or_else(|| deploy.server.clone());
+    remote.profile = remote.profile.or_else(|| deploy.profile.clone());
+    if remote.token.is_none()
+        && let Some(name) = &deploy.token_env
+    {
+        let token = std::env::var(name)
+            .with_context(|| format!("{name} from `deploy.token_env` is not set"))?;
+        remote.token = Some(token);
+    }
+    Ok(remote)
+}
+
+/// 项目所在 git 仓库当前的分支
+fn current_branch(root: &Path) -> Result<String> {
+    let head = root
+        .ancestors()
+        .map(|dir| dir.join(".git").join("HEAD"))
+        .find(|head| head.is_file())
+        .context("not in a git repository, pass a name to --preview")?;
+    let head = fs::read_to_string(head)?;
+    let branch = head
+        .trim()
+        .strip_prefix("ref: refs/heads/")
+        .context("HEAD is detached, pass a name to --preview")?;
+    Ok(branch.to_string())
+}
+
+/// 分支名中 host 不允许的字符替换为 `-`，如 `feature/Login_v2` 为 `feature-login-v2`
+fn preview_name(branch: &str) -> String {
+    let mut name = String::new();
+    for c in branch.chars().map(|c| c.to_ascii_lowercase()) {
+        match c {
+            'a'..='z' | '0'..='9' => name.push(c),
+            _ if !name.is_empty() && !name.ends_with('-') => name.push('-'),
+            _ => {}
+        }
+    }
+    name.truncate(63);
+    name.trim_end_matches('-').to_string()
+}
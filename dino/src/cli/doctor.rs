@@ -0,0 +1,299 @@
+use std::{
+    fs,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::Result;
+use bundler::{
+    CACHE_DIR, Capabilities, ImportMap, JSR_PREFIX, NodeCompat, NodeShim, Options,
+    core_module_name, run_bundle,
+};
+use clap::Parser;
+use colored::Colorize;
+use dino_server::{
+    CONFIG_VERSION, ProjectConfig,
+    engine::{Engine, JsWorker},
+};
+use regex::Regex;
+
+use crate::{
+    BUILD_DIR, CmdExecutor, DEFAULT_PORT,
+    import_map::load_import_map,
+    utils::{
+        CONFIG_FILE, SERVER_TARGET, find_project_root, get_files_with_exts, server_capabilities,
+    },
+};
+
+static IMPORT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)(?:^|;)\s*(?:import|export)\s*(?:[^'";]*?\sfrom\s*)?['"]([^'"]+)['"]"#)
+        .unwrap()
+});
+
+#[derive(Debug, Parser)]
+pub struct DoctorOpts {}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    message: String,
+    fix: Option<String>,
+}
+
+impl CmdExecutor for DoctorOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        // 找不到项目时在当前目录检查，让 config 检查给出具体问题
+        let root = find_project_root(".").unwrap_or_else(|_| PathBuf::from("."));
+        let mut checks = vec![check_config(&root), check_imports(&root)];
+        checks.extend(check_handlers(&root));
+        checks.push(check_port());
+        checks.push(check_dir("build dir", &root.join(BUILD_DIR)));
+        checks.push(check_dir("module cache", CACHE_DIR.as_path()));
+
+        for check in &checks {
+            let mark = match check.status {
+                Status::Ok => "✔".green(),
+                Status::Warn => "!".yellow(),
+                Status::Fail => "✖".red(),
+            };
+            println!("{mark} {}: {}", check.name.bold(), check.message);
+            if let Some(fix) = &check.fix {
+                println!("    {} {fix}", "fix:".cyan());
+            }
+        }
+
+        let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+        if failed > 0 {
+            anyhow::bail!("dino doctor found {failed} problem(s)");
+        }
+        Ok(())
+    }
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warn,
+            ..Self::fail(name, message, fix)
+        }
+    }
+}
+
+fn check_config(root: &Path) -> Check {
+    match ProjectConfig::load(root.join(CONFIG_FILE)) {
+        Ok(config) if config.version < CONFIG_VERSION => Check::warn(
+            "config",
+            format!(
+                "{CONFIG_FILE} uses config version {}, current is {CONFIG_VERSION}",
+                config.version
+            ),
+            "run `dino upgrade` to migrate the project",
+        ),
+        Ok(config) => Check::ok(
+            "config",
+            format!("{CONFIG_FILE} is valid ({} routes)", config.routes.len()),
+        ),
+        Err(e) => Check::fail(
+            "config",
+            format!("{CONFIG_FILE} is invalid: {e:#}"),
+            "run `dino build` for the exact location of the error",
+        ),
+    }
+}
+
+/// 打包项目并确认 config 中声明的 handler 都已导出
+fn check_handlers(root: &Path) -> Option<Check> {
+    let config = ProjectConfig::load(root.join(CONFIG_FILE)).ok()?;
+    let check = || -> Result<Vec<String>> {
+        let entry = root.join(&config.entry);
+        let options = Options {
+            capabilities: server_capabilities(),
+            target: Some(SERVER_TARGET.into()),
+            import_map: load_import_map(root)?,
+            ..Default::default()
+        };
+        let code = run_bundle(&entry.to_string_lossy(), &options)?;
+        let worker = JsWorker::try_new(&code)?;
+        let mut missing = vec![];
+        for route in config.routes.values().flatten() {
+            let ret = worker.eval(&format!("typeof handlers[{:?}]", route.handler))?;
+            if ret != "\"function\"" && !missing.contains(&route.handler) {
+                missing.push(route.handler.clone());
+            }
+        }
+        Ok(missing)
+    };
+    let check = match check() {
+        Ok(missing) if missing.is_empty() => {
+            Check::ok("handlers", "all handlers in config are exported")
+        }
+        Ok(missing) => Check::fail(
+            "handlers",
+            format!("handlers not exported: {}", missing.join(", ")),
+            format!("export these functions from {}", config.entry),
+        ),
+        Err(e) => Check::fail(
+            "handlers",
+            format!("failed to load the bundle: {e:#}"),
+            "run `dino build` to see the bundler error",
+        ),
+    };
+    Some(check)
+}
+
+fn check_imports(root: &Path) -> Check {
+    let files = match get_files_with_exts(root, &["ts", "js"]) {
+        Ok(files) => files,
+        Err(e) => return Check::fail("imports", e.to_string(), "check file permissions"),
+    };
+    let capabilities = server_capabilities();
+    let import_map = match load_import_map(root) {
+        Ok(map) => map,
+        Err(e) => return Check::fail("imports", format!("{e:#}"), "fix the JSON syntax"),
+    };
+    let mut unsupported = vec![];
+    let build_dir = root.join(BUILD_DIR);
+    for file in files.iter().filter(|f| !f.starts_with(&build_dir)) {
+        let Ok(source) = fs::read_to_string(file) else {
+            continue;
+        };
+        for specifier in find_unsupported_imports(&source, &capabilities, import_map.as_ref()) {
+            unsupported.push(format!("{} ({specifier})", file.display()));
+        }
+    }
+    if unsupported.is_empty() {
+        Check::ok(
+            "imports",
+            "all imports are supported by the dino-server runtime",
+        )
+    } else {
+        Check::fail(
+            "imports",
+            format!("unsupported imports: {}", unsupported.join(", ")),
+            format!(
+                "use relative paths (./mod.ts), full URLs or a supported core module ({})",
+                capabilities
+                    .core_modules
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )
+    }
+}
+
+fn check_port() -> Check {
+    match TcpListener::bind(("0.0.0.0", DEFAULT_PORT)) {
+        Ok(_) => Check::ok("port", format!("port {DEFAULT_PORT} is available")),
+        Err(e) => Check::warn(
+            "port",
+            format!("port {DEFAULT_PORT} is not available: {e}"),
+            "stop the process using the port before `dino run`",
+        ),
+    }
+}
+
+fn check_dir(name: &'static str, dir: &Path) -> Check {
+    let probe = dir.join(".doctor");
+    let ret = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&probe, b""))
+        .and_then(|_| fs::remove_file(&probe));
+    match ret {
+        Ok(_) => Check::ok(name, format!("{} is writable", dir.display())),
+        Err(e) => Check::fail(
+            name,
+            format!("{} is not writable: {e}", dir.display()),
+            format!("fix permissions or remove {}", dir.display()),
+        ),
+    }
+}
+
+/// 找出 bundler 无法解析的 import（裸模块名等）
+fn find_unsupported_imports(
+    source: &str,
+    capabilities: &Capabilities,
+    import_map: Option<&ImportMap>,
+) -> Vec<String> {
+    let node_compat = NodeCompat::default();
+    IMPORT_REGEX
+        .captures_iter(source)
+        .map(|c| c[1].to_string())
+        .filter(|s| {
+            let is_supported_core =
+                core_module_name(s).is_some_and(|name| capabilities.supports_module(name));
+            let is_supported_node = node_compat.shim(s).is_some_and(|shim| match shim {
+                NodeShim::Core(name) => capabilities.supports_module(name),
+                NodeShim::Polyfill => true,
+            });
+            !(s.starts_with("./")
+                || s.starts_with("../")
+                || s.starts_with('/')
+                || s.starts_with("http://")
+                || s.starts_with("https://")
+                || s.starts_with("data:")
+                || s.starts_with(JSR_PREFIX)
+                || is_supported_core
+                || is_supported_node
+                || import_map.is_some_and(|map| map.lookup(s).is_some()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_unsupported_imports_should_work() {
+        let import_map =
+            ImportMap::parse_from_json(r#"{"imports": {"@std/path": "https://jsr.io/x.ts"}}"#)
+                .unwrap();
+        let source = r#"
+import { a } from './a.ts';
+import b from "lodash";
+import "https://example.com/c.js";
+export { d } from '../d.ts';
+export * from "node:fs";
+import { join } from "path";
+import http from "node:http";
+import { existsSync } from "fs";
+import net from "net";
+import { format } from "dino:time";
+import x from "dino:nope";
+import { join as j } from "@std/path";
+import { assert } from "jsr:@std/assert@^1.0.0";
+const e = "import x from 'y'";
+"#;
+        assert_eq!(
+            find_unsupported_imports(source, &server_capabilities(), Some(&import_map)),
+            ["lodash", "node:http", "net", "dino:nope"]
+        );
+    }
+}
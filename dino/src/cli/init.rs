@@ -1,29 +1,56 @@
 use std::{fs, path::Path};
 
 use askama::Template;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use dialoguer::Input;
 use git2::Repository;
 
-use crate::CmdExecutor;
+use crate::{
+    CmdExecutor,
+    diagnostic::{Diagnostic, ErrorCode},
+};
+
+pub(crate) const TYPES_FILE: &str = "dino.d.ts";
 
 #[derive(Debug, Parser)]
-pub struct InitOpts {}
+pub struct InitOpts {
+    /// Project template
+    #[arg(long, value_enum, default_value_t)]
+    pub template: ProjectTemplate,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProjectTemplate {
+    /// An HTTP API with a single route
+    #[default]
+    Http,
+    /// Scheduled jobs declared under `crons`, see `dino cron`
+    Cron,
+}
 
 #[derive(Template)]
 #[template(path = "config.yml.j2")]
 struct ConfigFile {
     name: String,
+    cron: bool,
 }
 
 #[derive(Template)]
 #[template(path = "main.ts.j2")]
 struct MainFile {}
 
+#[derive(Template)]
+#[template(path = "cron.ts.j2")]
+struct CronMainFile {}
+
 #[derive(Template)]
 #[template(path = ".gitignore.j2")]
 struct GitignoreFile {}
 
+#[derive(Template)]
+#[template(path = "dino.d.ts.j2")]
+pub(crate) struct TypesFile {}
+
 impl CmdExecutor for InitOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let name: String = Input::new().with_prompt("Project name").interact_text()?;
@@ -31,25 +58,86 @@ impl CmdExecutor for InitOpts {
         // if current dir is empty then init project, otherwise create new dir and init project
         let cur = Path::new(".");
         if fs::read_dir(cur)?.next().is_none() {
-            init_project(&name, cur)?;
+            init_project(&name, cur, self.template)?;
         } else {
             let new_dir = cur.join(&name);
-            init_project(&name, &new_dir)?;
+            if new_dir.exists() {
+                return Err(Diagnostic::new(
+                    ErrorCode::InitFailed,
+                    format!("directory `{name}` already exists"),
+                )
+                .with_file(&new_dir)
+                .with_help("choose another project name or run `dino init` in an empty directory")
+                .into());
+            }
+            init_project(&name, &new_dir, self.template)?;
         }
 
         Ok(())
     }
 }
 
-fn init_project(name: &str, path: &Path) -> anyhow::Result<()> {
+fn init_project(name: &str, path: &Path, template: ProjectTemplate) -> anyhow::Result<()> {
     Repository::init(path)?;
 
+    let cron = template == ProjectTemplate::Cron;
     let config = ConfigFile {
         name: name.to_string(),
+        cron,
+    };
+    let main = match cron {
+        true => CronMainFile {}.render()?,
+        false => MainFile {}.render()?,
     };
     fs::write(path.join("config.yml"), config.render()?)?;
-    fs::write(path.join("main.ts"), MainFile {}.render()?)?;
+    fs::write(path.join("main.ts"), main)?;
     fs::write(path.join(".gitignore"), GitignoreFile {}.render()?)?;
+    fs::write(path.join(TYPES_FILE), TypesFile {}.render()?)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use dino_server::{
+        ProjectConfig,
+        engine::{Engine, JsWorker, ScheduleEvent, Trigger},
+    };
+
+    use super::*;
+
+    #[test]
+    fn cron_template_should_declare_jobs() -> anyhow::Result<()> {
+        let config = ConfigFile {
+            name: "jobs".into(),
+            cron: true,
+        };
+        let config: ProjectConfig = serde_yaml::from_str(&config.render()?)?;
+        assert_eq!(config.routes.keys().collect::<Vec<_>>(), ["/api/health"]);
+        let job = &config.crons["cleanup"];
+        assert_eq!(job.schedule, "0 * * * *");
+
+        // 模板的 handler 使用 ES module 导出，包成 worker 需要的形式
+        let code = CronMainFile {}.render()?.replace(
+            "export { cleanup, weeklyReport, health };",
+            "return { cleanup, weeklyReport, health };",
+        );
+        let worker = JsWorker::try_new(&format!("(function(){{ {code} }})();"))?;
+        let event = Trigger::Scheduled(ScheduleEvent {
+            name: "cleanup".into(),
+            schedule: job.schedule.clone(),
+            time: "2025-01-01T00:00:00Z".into(),
+        });
+        let resp = worker.trigger(&job.handler, event)?;
+        assert_eq!(resp.text(), Some(r#"{"job":"cleanup","removed":0}"#));
+
+        let config = ConfigFile {
+            name: "api".into(),
+            cron: false,
+        };
+        let config: ProjectConfig = serde_yaml::from_str(&config.render()?)?;
+        assert!(config.crons.is_empty());
+        assert!(config.routes.contains_key("/api/hello"));
+        Ok(())
+    }
+}
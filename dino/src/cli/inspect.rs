@@ -0,0 +1,64 @@
+use clap::Parser;
+use colored::Colorize;
+use dino_server::engine::Inspection;
+
+use crate::{CmdExecutor, OutputOpts, client::RemoteOpts};
+
+#[derive(Debug, Parser)]
+pub struct InspectOpts {
+    #[command(flatten)]
+    pub remote: RemoteOpts,
+    /// Tenant host
+    pub host: String,
+    #[command(flatten)]
+    pub output: OutputOpts,
+}
+
+impl CmdExecutor for InspectOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let client = self.remote.client()?;
+        let ret = client.get(&format!("/tenants/{}/inspect", self.host))?;
+        if self.output.is_json() {
+            return self.output.print(ret);
+        }
+        let worker: Inspection = serde_json::from_value(ret["worker"].clone())?;
+        let memory = &worker.memory;
+        let jobs = match worker.pending_jobs {
+            true => "pending".yellow(),
+            false => "none".green(),
+        };
+        println!("host:     {}", self.host);
+        println!("engine:   {}", worker.engine);
+        println!(
+            "memory:   {} used, {} allocated, {} binary",
+            size(memory.used_size),
+            size(memory.malloc_size),
+            size(memory.binary_size)
+        );
+        println!(
+            "objects:  {} objects, {} strings, {} functions, {} arrays",
+            memory.objects, memory.strings, memory.functions, memory.arrays
+        );
+        println!("jobs:     {jobs}");
+        println!(
+            "handlers: {} ({})",
+            worker.handlers.join(", "),
+            worker.handlers.len()
+        );
+        println!(
+            "globals:  {} ({})",
+            worker.globals.join(", "),
+            worker.globals.len()
+        );
+        Ok(())
+    }
+}
+
+/// 以 KiB/MiB 显示字节数
+fn size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
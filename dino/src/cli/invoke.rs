@@ -0,0 +1,67 @@
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+use dino_server::InvokeEvent;
+use serde_json::json;
+
+use crate::{CmdExecutor, client::RemoteOpts};
+
+#[derive(Debug, Parser)]
+pub struct InvokeOpts {
+    #[command(flatten)]
+    pub remote: RemoteOpts,
+    /// Tenant host
+    pub host: String,
+    /// Route path, may include a query string
+    pub path: String,
+    /// HTTP method
+    #[arg(short = 'X', long, default_value = "GET")]
+    pub method: String,
+    /// Request body
+    #[arg(short, long)]
+    pub data: Option<String>,
+    /// Stream the handler's output while it runs
+    #[arg(long)]
+    pub tail: bool,
+}
+
+impl CmdExecutor for InvokeOpts {
+    async fn execute(self) -> Result<()> {
+        let client = self.remote.client()?;
+        let body = json!({ "method": self.method, "path": self.path, "body": self.data });
+        let reader = client.stream(&format!("/tenants/{}/invoke", self.host), body)?;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line)? {
+                InvokeEvent::Start { handler } if self.tail => {
+                    eprintln!("{} {handler}", "invoke".dimmed());
+                }
+                InvokeEvent::Log { message } if self.tail => {
+                    eprintln!("{} {message}", "log".cyan());
+                }
+                InvokeEvent::Start { .. } | InvokeEvent::Log { .. } => {}
+                InvokeEvent::Result {
+                    status,
+                    body,
+                    duration_ms,
+                    ..
+                } => {
+                    if self.tail {
+                        eprintln!("{} {status} in {duration_ms:.1}ms", "done".green());
+                    }
+                    // 二进制 body 原样输出，便于重定向到文件
+                    let mut stdout = std::io::stdout().lock();
+                    stdout.write_all(&body.unwrap_or_default())?;
+                    writeln!(stdout)?;
+                }
+                InvokeEvent::Error { message } => anyhow::bail!("invoke failed: {message}"),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use clap::Parser;
+use dialoguer::Password;
+
+use crate::{
+    CmdExecutor,
+    client::AdminClient,
+    credentials::{Credentials, Profile},
+};
+
+#[derive(Debug, Parser)]
+pub struct LoginOpts {
+    /// Server URL
+    #[arg(long)]
+    pub server: String,
+    /// Admin API token, prompted for if omitted
+    #[arg(long, env = "DINO_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+    /// Profile name
+    #[arg(long, default_value = "default")]
+    pub profile: String,
+    /// Store the token in the system keyring instead of the credentials file
+    #[arg(long)]
+    pub keyring: bool,
+    /// Make this the default profile
+    #[arg(long)]
+    pub set_default: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct LogoutOpts {
+    /// Profile name
+    #[arg(long, default_value = "default")]
+    pub profile: String,
+}
+
+impl CmdExecutor for LoginOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let token = match self.token {
+            Some(token) => token,
+            None => Password::new().with_prompt("Token").interact()?,
+        };
+
+        // 保存前确认 token 可用
+        let client = AdminClient::new(&self.server, Some(token.clone()));
+        client.get("/tenants")?;
+
+        let mut credentials = Credentials::load()?;
+        let profile = Profile {
+            server: self.server.clone(),
+            token: None,
+            keyring: self.keyring,
+        };
+        credentials.insert(&self.profile, profile, &token)?;
+        if self.set_default {
+            credentials.default = Some(self.profile.clone());
+        }
+        credentials.save()?;
+        println!("Logged in to {} as profile {}", self.server, self.profile);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for LogoutOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut credentials = Credentials::load()?;
+        match credentials.remove(&self.profile)? {
+            Some(profile) => {
+                credentials.save()?;
+                println!(
+                    "Logged out of {} (profile {})",
+                    profile.server, self.profile
+                );
+            }
+            None => println!("Profile {} not found", self.profile),
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,30 @@
-use clap::{Parser, command};
+use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-pub use self::{build::*, init::*, run::*};
+use crate::OutputFormat;
 
+pub use self::{
+    add::*, audit::*, build::*, cron::*, deploy::*, doctor::*, init::*, inspect::*, invoke::*,
+    login::*, reload::*, repl::*, replay::*, run::*, tenant::*, test::*, upgrade::*,
+};
+
+mod add;
+mod audit;
 mod build;
+mod cron;
+mod deploy;
+mod doctor;
 mod init;
+mod inspect;
+mod invoke;
+mod login;
+mod reload;
+mod repl;
+mod replay;
 mod run;
+mod tenant;
+mod test;
+mod upgrade;
 
 #[derive(Debug, Parser)]
 #[command(name = "dino", version, author, about, long_about = None)]
@@ -23,4 +42,77 @@ pub enum SubCommand {
     Build(BuildOpts),
     #[command(name = "run", about = "Run the project")]
     Run(RunOpts),
+    #[command(
+        name = "repl",
+        about = "Start an interactive session with the project loaded"
+    )]
+    Repl(ReplOpts),
+    #[command(
+        name = "doctor",
+        about = "Check the project and environment for problems"
+    )]
+    Doctor(DoctorOpts),
+    #[command(
+        name = "upgrade",
+        about = "Migrate the project to the current config version"
+    )]
+    Upgrade(UpgradeOpts),
+    #[command(
+        name = "replay",
+        about = "Re-run requests recorded by `dino run --record`"
+    )]
+    Replay(ReplayOpts),
+    #[command(
+        name = "invoke",
+        about = "Invoke a route on a dino server through the admin API"
+    )]
+    Invoke(InvokeOpts),
+    #[command(
+        name = "inspect",
+        about = "Show the runtime state of a tenant's worker on a dino server"
+    )]
+    Inspect(InspectOpts),
+    #[command(
+        name = "test",
+        about = "Run the project's `*.test.ts` files in the JS worker"
+    )]
+    Test(TestOpts),
+    #[command(name = "cron", about = "Run the project's scheduled jobs locally")]
+    Cron(CronOpts),
+    #[command(name = "add", about = "Add a remote dependency to the import map")]
+    Add(AddOpts),
+    #[command(name = "audit", about = "Query the audit log of a dino server")]
+    Audit(AuditOpts),
+    #[command(
+        name = "deploy",
+        about = "Deploy the project or a git repository to a remote dino server"
+    )]
+    Deploy(DeployOpts),
+    #[command(name = "tenant", about = "Manage tenants on a remote dino server")]
+    Tenant(TenantOpts),
+    #[command(name = "login", about = "Save credentials for a remote dino server")]
+    Login(LoginOpts),
+    #[command(
+        name = "reload",
+        about = "Rebuild the project and push it into the running `dino run`"
+    )]
+    Reload(ReloadOpts),
+    #[command(
+        name = "logout",
+        about = "Remove saved credentials of a remote dino server"
+    )]
+    Logout(LogoutOpts),
+}
+
+impl SubCommand {
+    /// 支持 `--output` 的命令选择的输出格式，其它命令总是文本
+    pub fn output(&self) -> OutputFormat {
+        match self {
+            Self::Build(opts) => opts.output.output,
+            Self::Deploy(opts) => opts.output.output,
+            Self::Tenant(opts) => opts.output.output,
+            Self::Inspect(opts) => opts.output.output,
+            _ => OutputFormat::Text,
+        }
+    }
 }
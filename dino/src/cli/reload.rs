@@ -0,0 +1,35 @@
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+
+use crate::{
+    CmdExecutor,
+    control::{self, ControlRequest},
+    utils::{BuildSettings, build_project_with, find_project_root},
+};
+
+#[derive(Debug, Parser)]
+pub struct ReloadOpts {
+    /// Project directory, defaults to the project containing the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+}
+
+impl CmdExecutor for ReloadOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let root = find_project_root(self.project_dir.unwrap_or_else(|| ".".into()))?;
+        let (filename, _) = build_project_with(&root, BuildSettings::default())?;
+        let user = std::env::var("USER").unwrap_or_else(|_| "unknown".into());
+        let req = ControlRequest::Swap {
+            code: fs::read_to_string(&filename)?,
+            config: fs::read_to_string(filename.with_extension("yml"))?,
+            actor: format!("{user} (dino reload)"),
+        };
+        let resp = control::send(&root, &req)?;
+        if !resp.ok {
+            anyhow::bail!("reload failed: {}", resp.message);
+        }
+        println!("Reloaded {}", filename.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use std::fs;
+
+use clap::Parser;
+use colored::Colorize;
+use dialoguer::{BasicHistory, Input};
+use dino_server::engine::{Engine, JsWorker};
+
+use crate::{
+    CmdExecutor,
+    utils::{build_project, find_project_root},
+};
+
+#[derive(Debug, Parser)]
+pub struct ReplOpts {}
+
+impl CmdExecutor for ReplOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let root = find_project_root(".")?;
+        let filename = build_project(&root)?;
+        let code = fs::read_to_string(filename)?;
+        let worker = JsWorker::try_new(&code)?;
+
+        println!("Dino repl, project handlers are available as `handlers`.");
+        println!("Type `.exit` or press Ctrl-D to quit.");
+
+        let mut history = BasicHistory::new().max_entries(100).no_duplicates(true);
+        // Ctrl-D / Ctrl-C 会返回错误，直接退出
+        while let Ok(line) = Input::<String>::new()
+            .with_prompt(">")
+            .history_with(&mut history)
+            .interact_text()
+        {
+            let line = line.trim();
+            if line == ".exit" {
+                break;
+            }
+            match worker.eval(line) {
+                Ok(ret) => println!("{ret}"),
+                Err(e) => eprintln!("{} {e}", "Uncaught".red()),
+            }
+        }
+        Ok(())
+    }
+}
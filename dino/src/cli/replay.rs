@@ -0,0 +1,61 @@
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use colored::Colorize;
+use dino_server::{
+    ReplayRecord,
+    engine::{Engine, JsWorker},
+};
+
+use crate::{
+    CmdExecutor,
+    utils::{build_project, find_project_root},
+};
+
+#[derive(Debug, Parser)]
+pub struct ReplayOpts {
+    /// Record file written by `dino run --record`
+    pub file: PathBuf,
+    /// Only replay the n-th request (starting from 0)
+    #[arg(long)]
+    pub index: Option<usize>,
+    /// Project directory, defaults to the project containing the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+}
+
+impl CmdExecutor for ReplayOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let root = find_project_root(self.project_dir.unwrap_or_else(|| ".".into()))?;
+        let filename = build_project(&root)?;
+        let code = fs::read_to_string(filename)?;
+        let worker = JsWorker::try_new(&code)?;
+
+        let records = ReplayRecord::load(&self.file)?;
+        let mut diverged = 0;
+        for (i, record) in records.into_iter().enumerate() {
+            if self.index.is_some_and(|index| index != i) {
+                continue;
+            }
+            let title = format!(
+                "#{i} {} {} -> {}",
+                record.req.method, record.req.url, record.handler
+            );
+            // 使用记录时的种子和时间重新执行，结果应当与记录一致
+            let resp = worker.run_with_replay(&record.handler, record.req, Some(record.replay))?;
+            if resp.status == record.status && resp.body == record.body {
+                println!("{} {title} ({})", "✔".green(), resp.status);
+            } else {
+                diverged += 1;
+                println!("{} {title}", "✖".red());
+                println!("    recorded: {} {:?}", record.status, record.body);
+                println!("    replayed: {} {:?}", resp.status, resp.body);
+            }
+        }
+
+        if diverged > 0 {
+            anyhow::bail!("{diverged} request(s) diverged from the record");
+        }
+        Ok(())
+    }
+}
@@ -1,59 +1,239 @@
 use anyhow::Result;
+use bundler::Defines;
 use clap::Parser;
 use notify::RecursiveMode;
 use notify_debouncer_mini::{DebounceEventResult, new_debouncer};
-use std::{fs, path::Path, time::Duration};
-use tokio::sync::mpsc::channel;
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpListener, sync::mpsc::channel};
 use tokio_stream::{StreamExt, wrappers::ReceiverStream};
-use tracing::{info, level_filters::LevelFilter, warn};
-use tracing_subscriber::{Layer as _, fmt::Layer, layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::{error, info, warn};
 
-use crate::{CmdExecutor, utils::build_project};
-use dino_server::{ProjectConfig, SwappableAppRouter, TenantRouter, start_server};
+use crate::{
+    CmdExecutor, DEFAULT_PORT, control,
+    diagnostic::{Diagnostic, ErrorCode},
+    discovery::{DEV_FILE, DevServer},
+    utils::{BuildSettings, build_project_with, find_project_root, is_project_source},
+};
+use dino_server::{
+    AdminConfig, AppState, AuditLog, DEFAULT_INSPECT_ADDR, DEV_STATUS_PATH, ObjectStore,
+    ProjectConfig, Recorder, ServerOptions, SwappableAppRouter, TenantRouter, WatcherHealth,
+    start_server_on,
+};
 
+/// dev server 中对象的存储目录，重启后状态仍然保留
+const OBJECTS_DIR: &str = ".dino/objects";
+const UPLOADS_DIR: &str = ".dino/uploads";
+/// `serveFile` 读取的文件放在其中的 `localhost` 目录下
+const FILES_DIR: &str = ".dino/files";
 const MONITOR_FS_INTERVAL: Duration = Duration::from_secs(10);
+/// 文件监听任务退出后重启的等待时间，连续失败时加倍，不超过上限
+const WATCH_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_WATCH_RESTART_DELAY: Duration = Duration::from_secs(30);
+/// 运行超过该时长后退出的任务不算连续失败
+const WATCH_STABLE_AFTER: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Parser)]
-pub struct RunOpts {}
+pub struct RunOpts {
+    /// Project directory, defaults to the project containing the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+    /// Port to listen on, 0 picks a free port; the actual port is written to .dino/dev.json
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    pub port: u16,
+    /// Record requests with their random seed and time to a file for `dino replay`
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+    /// Append code swaps, config changes and worker restarts to an audit log
+    #[arg(long)]
+    pub audit_log: Option<PathBuf>,
+    /// Enable the admin API under /_admin with the API tokens in this file
+    #[arg(long)]
+    pub admin_config: Option<PathBuf>,
+    /// Serve the admin API on this address instead of the server port
+    #[arg(long, value_name = "ADDR", requires = "admin_config")]
+    pub admin_addr: Option<SocketAddr>,
+    /// Don't watch files, use `dino reload` to push changes instead
+    #[arg(long)]
+    pub no_watch: bool,
+    /// Replace a global with a constant at build time, e.g. `--define FEATURE_X=false`
+    #[arg(long = "define", value_name = "KEY=VALUE", value_parser = Defines::parse_pair)]
+    pub defines: Vec<(String, String)>,
+    /// Accept Chrome DevTools connections, e.g. from chrome://inspect or an IDE, to evaluate
+    /// code in the running worker and see its output; breakpoints are not supported
+    #[arg(
+        long,
+        value_name = "ADDR",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_INSPECT_ADDR
+    )]
+    pub inspect: Option<SocketAddr>,
+}
 
 impl CmdExecutor for RunOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let layer = Layer::new().with_filter(LevelFilter::INFO);
-        tracing_subscriber::registry().with(layer).init();
-
-        let (code, config) = get_code_and_config()?;
+        let root = find_project_root(self.project_dir.unwrap_or_else(|| ".".into()))?;
+        let mut defines = Defines::default();
+        for (key, value) in self.defines {
+            defines.insert(key, value);
+        }
+        let (code, config) = get_code_and_config(&root, &defines)?;
+        config.logging.init()?;
 
+        let (timezone, routing) = (config.timezone, config.routing);
+        let memory_limit = config.memory_limit_mb;
         let router = SwappableAppRouter::try_new(&code, config.routes)?;
 
-        tokio::spawn(async_watch(".", router.clone()));
+        let object_store = ObjectStore::new(root.join(OBJECTS_DIR));
+        let upload_dir = root.join(UPLOADS_DIR);
+        let files_dir = root.join(FILES_DIR);
+        let control_root = root.clone();
+        let discovery_root = root.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve(&control_root, "localhost").await {
+                warn!("control socket error: {e:#}");
+            }
+        });
+        if !self.no_watch {
+            tokio::spawn(supervise_watch(root, router.clone(), defines));
+        }
+
+        let options = ServerOptions {
+            recorder: self.record.map(Recorder::try_new).transpose()?,
+            audit: self.audit_log.map(AuditLog::try_new).transpose()?,
+            admin: self.admin_config.map(AdminConfig::load).transpose()?,
+            admin_addr: self.admin_addr,
+            server_timing: true,
+            dev: true,
+            check_contracts: true,
+            object_store: Some(object_store),
+            upload_dir: Some(upload_dir),
+            files_dir: Some(files_dir),
+            inspect: self.inspect,
+            ..Default::default()
+        };
+        let port = self.port;
+        let listener = TcpListener::bind(("0.0.0.0", port)).await.map_err(|e| {
+            Diagnostic::new(ErrorCode::ServerFailed, "failed to run dev server")
+                .with_help(format!(
+                    "make sure port {port} is not used by another process, or use `--port 0`"
+                ))
+                .with_source(&anyhow::Error::from(e))
+        })?;
+        let dev = DevServer {
+            port: listener.local_addr()?.port(),
+            pid: std::process::id(),
+            hosts: vec!["localhost".to_string()],
+        };
+        dev.write(&discovery_root)?;
+        println!("Dev server running at {} ({DEV_FILE})", dev.url());
 
-        start_server(
-            8888,
-            vec![TenantRouter::new("localhost".to_string(), router)],
+        start_server_on(
+            listener,
+            vec![
+                TenantRouter::new("localhost".to_string(), router)
+                    .with_timezone(timezone)
+                    .with_memory_limit(memory_limit)
+                    .with_routing(routing),
+            ],
+            options,
         )
-        .await?;
+        .await
+        .map_err(|e| {
+            Diagnostic::new(ErrorCode::ServerFailed, "failed to run dev server").with_source(&e)
+        })?;
         Ok(())
     }
 }
 
-fn get_code_and_config() -> Result<(String, ProjectConfig)> {
-    let filename = build_project(".")?;
-    let config = filename.replace(".mjs", ".yml");
+fn get_code_and_config(root: &Path, defines: &Defines) -> Result<(String, ProjectConfig)> {
+    let settings = BuildSettings {
+        defines: defines.clone(),
+        ..Default::default()
+    };
+    let (filename, _) = build_project_with(root, settings)?;
+    let config = filename.with_extension("yml");
     let code = fs::read_to_string(filename)?;
-    let config = ProjectConfig::load(config)?;
+    let config =
+        ProjectConfig::load(&config).map_err(|e| Diagnostic::config_invalid(&config, &e))?;
     Ok((code, config))
 }
 
-async fn async_watch(path: impl AsRef<Path>, router: SwappableAppRouter) -> Result<()> {
+/// 文件监听触发的变更记在当前系统用户名下
+fn watcher_actor() -> String {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".into());
+    format!("{user} (file watcher)")
+}
+
+fn reload(root: &Path, router: &SwappableAppRouter, defines: &Defines) -> Result<()> {
+    let (code, config) = get_code_and_config(root, defines)?;
+    info!("reload code and config");
+
+    // 通过 state 替换代码，以便重启 worker 并记录审计日志
+    match AppState::get_current() {
+        Some(state) => {
+            state.swap_with_config("localhost", code, &config, &watcher_actor())?;
+            info!("worker updated successfully");
+        }
+        None => router.swap(code, config.routes)?,
+    }
+    Ok(())
+}
+
+/// 运行文件监听任务，任务出错或 panic 后记录日志并重启，状态显示在 dev 状态接口中
+async fn supervise_watch(root: PathBuf, router: SwappableAppRouter, defines: Defines) {
+    // 等服务器启动后再开始，以便状态接口能看到监听任务
+    while AppState::get_current().is_none() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut health = WatcherHealth::default();
+    let mut delay = WATCH_RESTART_DELAY;
+    loop {
+        health.running = true;
+        report_watcher(&health);
+        let start = Instant::now();
+        let ret = tokio::spawn(async_watch(root.clone(), router.clone(), defines.clone())).await;
+        let error = match ret {
+            Ok(Ok(())) => "file watcher stopped".to_string(),
+            Ok(Err(e)) => format!("{e:#}"),
+            Err(e) => format!("file watcher panicked: {e}"),
+        };
+        if start.elapsed() > WATCH_STABLE_AFTER {
+            delay = WATCH_RESTART_DELAY;
+        }
+        health.running = false;
+        health.restarts += 1;
+        health.last_error = Some(error.clone());
+        report_watcher(&health);
+        error!(
+            "{error}, hot reload paused, restarting the watcher in {}s",
+            delay.as_secs()
+        );
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_WATCH_RESTART_DELAY);
+    }
+}
+
+fn report_watcher(health: &WatcherHealth) {
+    if let Some(state) = AppState::get_current() {
+        state.set_watcher_health("localhost", health.clone());
+    }
+}
+
+async fn async_watch(root: PathBuf, router: SwappableAppRouter, defines: Defines) -> Result<()> {
     let (tx, rx) = channel(1);
 
     let mut debouncer = new_debouncer(MONITOR_FS_INTERVAL, move |res: DebounceEventResult| {
         tx.blocking_send(res).unwrap();
     })?;
 
-    debouncer
-        .watcher()
-        .watch(path.as_ref(), RecursiveMode::Recursive)?;
+    debouncer.watcher().watch(&root, RecursiveMode::Recursive)?;
 
     let mut stream = ReceiverStream::new(rx);
 
@@ -62,24 +242,22 @@ async fn async_watch(path: impl AsRef<Path>, router: SwappableAppRouter) -> Resu
             Ok(events) => {
                 let mut need_reload = false;
                 for event in events {
-                    let path = event.path;
-                    let ext = path.extension().unwrap_or_default();
-                    if path.ends_with("config.yml") || ext == "ts" || ext == "js" {
-                        info!("file changed: {}", path.display());
+                    if is_project_source(&root, &event.path) {
+                        info!("file changed: {}", event.path.display());
                         need_reload = true;
                         break;
                     }
                 }
                 if need_reload {
-                    let (code, config) = get_code_and_config()?;
-                    info!("reload code and config");
-                    router.swap(code, config.routes)?;
-
-                    // 更新所有 worker
-                    let state = dino_server::AppState::get_current();
-                    if let Some(state) = state {
-                        state.update_worker("localhost")?;
-                        info!("worker updated successfully");
+                    // 构建失败时继续使用上一次成功的代码，下次文件变化时重试
+                    let ret = reload(&root, &router, &defines);
+                    if let Err(e) = &ret {
+                        error!(
+                            "reload failed, still serving the last good build (see {DEV_STATUS_PATH}): {e:#}"
+                        );
+                    }
+                    if let Some(state) = AppState::get_current() {
+                        state.set_build_error("localhost", ret.err().map(|e| format!("{e:#}")));
                     }
                 }
             }
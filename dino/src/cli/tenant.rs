@@ -0,0 +1,156 @@
+use std::{fs, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use dino_server::{BundleVersion, TenantStatus};
+use serde_json::json;
+
+use crate::{
+    CmdExecutor, OutputOpts,
+    client::RemoteOpts,
+    utils::{build_project, find_project_root},
+};
+
+#[derive(Debug, Parser)]
+pub struct TenantOpts {
+    #[command(flatten)]
+    pub remote: RemoteOpts,
+    #[command(flatten)]
+    pub output: OutputOpts,
+    #[command(subcommand)]
+    pub cmd: TenantCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TenantCommand {
+    /// List tenants on the server
+    List,
+    /// Show the deployed version of a tenant
+    Status { host: String },
+    /// Build the project and add it as a new tenant
+    Add {
+        host: String,
+        /// Project directory, defaults to the project containing the current directory
+        #[arg(long)]
+        project_dir: Option<PathBuf>,
+    },
+    /// Remove a tenant and stop its worker
+    Remove { host: String },
+    /// List the deployed versions a tenant can roll back to
+    Versions { host: String },
+    /// Roll a tenant back to its previous deployment, or switch to a kept version
+    Rollback {
+        host: String,
+        /// Version listed by `dino tenant versions`
+        version: Option<u64>,
+    },
+}
+
+impl CmdExecutor for TenantOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let client = self.remote.client()?;
+        let output = self.output;
+        match self.cmd {
+            TenantCommand::List => {
+                let ret = client.get("/tenants")?;
+                if output.is_json() {
+                    return output.print(ret);
+                }
+                let tenants: Vec<TenantStatus> = serde_json::from_value(ret["tenants"].clone())?;
+                println!(
+                    "{:<30} {:<16} {:<16} {:>7}",
+                    "HOST".bold(),
+                    "CODE".bold(),
+                    "ROUTES".bold(),
+                    "HISTORY".bold()
+                );
+                for t in tenants {
+                    println!(
+                        "{:<30} {:<16} {:<16} {:>7}",
+                        t.host, t.code, t.routes, t.history
+                    );
+                }
+            }
+            TenantCommand::Status { host } => {
+                let ret = client.get(&format!("/tenants/{host}"))?;
+                if output.is_json() {
+                    return output.print(ret);
+                }
+                let t: TenantStatus = serde_json::from_value(ret)?;
+                let worker = match t.worker_running {
+                    true => "running".green(),
+                    false => "stopped".red(),
+                };
+                println!("host:    {}", t.host);
+                println!("code:    {}", t.code);
+                println!("routes:  {}", t.routes);
+                println!("version: {}", t.version);
+                println!("history: {} version(s)", t.history);
+                println!(
+                    "worker:  {worker}, {} thread(s), scaled up {} / down {} time(s), {} stall(s)",
+                    t.workers, t.scale_ups, t.scale_downs, t.stalls
+                );
+                println!(
+                    "egress:  {} byte(s) sent, {} byte(s) received",
+                    t.egress_sent, t.egress_received
+                );
+            }
+            TenantCommand::Add { host, project_dir } => {
+                let root = find_project_root(project_dir.unwrap_or_else(|| ".".into()))?;
+                let filename = build_project(&root)?;
+                let body = json!({
+                    "code": fs::read_to_string(&filename)?,
+                    "config": fs::read_to_string(filename.with_extension("yml"))?,
+                });
+                let mut ret = client.post(&format!("/tenants/{host}"), Some(body))?;
+                if output.is_json() {
+                    ret["build"] = json!({
+                        "artifact": filename,
+                        "hash": filename.file_stem().map(|s| s.to_string_lossy()),
+                    });
+                    return output.print(ret);
+                }
+                println!("Tenant {host} added");
+            }
+            TenantCommand::Remove { host } => {
+                let ret = client.delete(&format!("/tenants/{host}"))?;
+                if output.is_json() {
+                    return output.print(ret);
+                }
+                println!("Tenant {host} removed");
+            }
+            TenantCommand::Versions { host } => {
+                let ret = client.get(&format!("/tenants/{host}/versions"))?;
+                if output.is_json() {
+                    return output.print(ret);
+                }
+                let current = ret["current"].as_u64().unwrap_or_default();
+                let versions: Vec<BundleVersion> = serde_json::from_value(ret["versions"].clone())?;
+                println!(
+                    "  {:>7} {:<16} {:<16} {:<24} {}",
+                    "VERSION".bold(),
+                    "CODE".bold(),
+                    "ROUTES".bold(),
+                    "DEPLOYED".bold(),
+                    "ACTOR".bold()
+                );
+                for v in versions.iter().rev() {
+                    let mark = if v.version == current { "*" } else { " " };
+                    println!(
+                        "{mark} {:>7} {:<16} {:<16} {:<24} {}",
+                        v.version, v.code, v.routes, v.deployed_at, v.actor
+                    );
+                }
+            }
+            TenantCommand::Rollback { host, version } => {
+                let body = version.map(|version| json!({ "version": version }));
+                let ret = client.post(&format!("/tenants/{host}/rollback"), body)?;
+                if output.is_json() {
+                    return output.print(ret);
+                }
+                println!("Tenant {host} rolled back to version {}", ret["version"]);
+            }
+        }
+        Ok(())
+    }
+}
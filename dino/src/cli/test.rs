@@ -0,0 +1,196 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::Result;
+use bundler::{Defines, Options, run_bundle};
+use clap::Parser;
+use colored::Colorize;
+use dino_server::engine::{Engine, JsWorker};
+
+use crate::{
+    CmdExecutor, Diagnostic,
+    diagnostic::ErrorCode,
+    import_map::load_import_map,
+    utils::{
+        SERVER_TARGET, check_project, find_project_root, get_files_with_exts, normalize_path,
+        server_capabilities,
+    },
+};
+
+/// 测试文件的后缀
+const TEST_SUFFIXES: [&str; 2] = [".test.ts", ".test.js"];
+
+#[derive(Debug, Parser)]
+pub struct TestOpts {
+    /// Test files to run, defaults to every `*.test.ts` and `*.test.js` in the project
+    pub files: Vec<PathBuf>,
+    /// Project directory, defaults to the project containing the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+    /// Only run tests whose name contains this string
+    #[arg(long)]
+    pub filter: Option<String>,
+}
+
+/// 一个测试的结果，失败时带有 JS 的错误信息
+#[derive(Debug)]
+struct TestResult {
+    name: String,
+    error: Option<String>,
+}
+
+impl CmdExecutor for TestOpts {
+    async fn execute(self) -> Result<()> {
+        let root = find_project_root(self.project_dir.unwrap_or_else(|| ".".into()))?;
+        let config = check_project(&root)?;
+        let files = match self.files.is_empty() {
+            true => find_test_files(&root)?,
+            false => self
+                .files
+                .iter()
+                .map(normalize_path)
+                .collect::<Result<_>>()?,
+        };
+        if files.is_empty() {
+            println!("no test files found");
+            return Ok(());
+        }
+        // 测试在 worker 中执行，时区和服务器上一致
+        dino_server::set_timezone(config.timezone);
+
+        let start = Instant::now();
+        let (mut passed, mut failed) = (0, vec![]);
+        for file in files {
+            let rel = file.strip_prefix(&root).unwrap_or(&file).to_path_buf();
+            println!("{} {}", "running".bold(), rel.display());
+            for result in run_file(&root, &file, self.filter.as_deref())? {
+                match result.error {
+                    None => {
+                        println!("  {} {}", result.name, "ok".green());
+                        passed += 1;
+                    }
+                    Some(e) => {
+                        println!("  {} {}", result.name, "FAILED".red());
+                        failed.push((format!("{}::{}", rel.display(), result.name), e));
+                    }
+                }
+            }
+        }
+
+        for (name, error) in &failed {
+            eprintln!("\n{} {name}\n{}", "failure:".red().bold(), error.trim_end());
+        }
+        let summary = format!(
+            "{passed} passed; {} failed; finished in {:.2?}",
+            failed.len(),
+            start.elapsed()
+        );
+        if !failed.is_empty() {
+            anyhow::bail!("test result: {summary}");
+        }
+        println!("\ntest result: {} {summary}", "ok".green());
+        Ok(())
+    }
+}
+
+/// 项目中所有测试文件，跳过隐藏目录和 node_modules
+fn find_test_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let files = get_files_with_exts(root, &["ts", "js"])?
+        .into_iter()
+        .filter(|file| {
+            let rel = file.strip_prefix(root).unwrap_or(file);
+            let name = rel.to_string_lossy();
+            TEST_SUFFIXES.iter().any(|s| name.ends_with(s))
+                && !rel.components().any(|c| {
+                    let c = c.as_os_str().to_string_lossy();
+                    c.starts_with('.') || c == "node_modules"
+                })
+        })
+        .collect();
+    Ok(files)
+}
+
+/// 打包测试文件并在 worker 中依次执行导出的函数，函数抛出异常（或返回的
+/// Promise 被拒绝）时测试失败
+fn run_file(root: &Path, file: &Path, filter: Option<&str>) -> Result<Vec<TestResult>> {
+    let options = Options {
+        capabilities: server_capabilities(),
+        defines: Defines::from_env(),
+        target: Some(SERVER_TARGET.into()),
+        import_map: load_import_map(root)?,
+        ..Default::default()
+    };
+    let code = run_bundle(&file.to_string_lossy(), &options).map_err(|e| {
+        Diagnostic::new(ErrorCode::BundleFailed, "failed to bundle test file")
+            .with_file(file)
+            .with_source(&e)
+    })?;
+    let worker = JsWorker::try_new(&code)?;
+    let mut results = vec![];
+    for name in worker.inspect()?.handlers {
+        if filter.is_some_and(|f| !name.contains(f)) {
+            continue;
+        }
+        let test = format!("handlers[{}]", serde_json::to_string(&name)?);
+        if worker.eval(&format!("typeof {test}"))? != r#""function""# {
+            continue;
+        }
+        let error = worker
+            .eval(&format!("{test}()"))
+            .err()
+            .map(|e| format!("{e:#}"));
+        results.push(TestResult { name, error });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_files_should_run_exported_functions() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("dino-test-{}", std::process::id()));
+        fs::create_dir_all(root.join(".build"))?;
+        fs::write(
+            root.join("main.ts"),
+            "export function hello() { return { status: 200, body: 'hi' }; }",
+        )?;
+        fs::write(
+            root.join("main.test.ts"),
+            r#"import { hello } from "./main.ts";
+export function helloReturnsOk() {
+  if (hello().status !== 200) throw new Error("bad status");
+}
+export async function helloFails() {
+  throw new Error("expected failure");
+}
+export const value = 1;
+"#,
+        )?;
+        fs::write(root.join(".build/skip.test.ts"), "")?;
+        let root = normalize_path(&root)?;
+
+        let files = find_test_files(&root)?;
+        assert_eq!(files, [root.join("main.test.ts")]);
+        let results = run_file(&root, &files[0], None)?;
+        let names: Vec<_> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["helloFails", "helloReturnsOk"]);
+        assert!(
+            results[0]
+                .error
+                .as_ref()
+                .unwrap()
+                .contains("expected failure")
+        );
+        assert!(results[1].error.is_none());
+        assert_eq!(run_file(&root, &files[0], Some("Ok"))?.len(), 1);
+
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
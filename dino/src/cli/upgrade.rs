@@ -0,0 +1,191 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use askama::Template;
+use clap::Parser;
+use colored::Colorize;
+use dino_server::CONFIG_VERSION;
+use serde_yaml::{Mapping, Value};
+use similar::{ChangeTag, TextDiff};
+
+use super::init::{TYPES_FILE, TypesFile};
+use crate::{
+    CmdExecutor,
+    utils::{CONFIG_FILE, find_project_root},
+};
+
+/// 迁移直接修改配置文本，保留注释和格式
+type Migration = fn(&str) -> String;
+
+/// (目标版本, 迁移函数)，按版本顺序执行
+const MIGRATIONS: &[(u32, Migration)] = &[(1, migrate_v1)];
+
+#[derive(Debug, Parser)]
+pub struct UpgradeOpts {
+    /// Print the changes without writing any file
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl CmdExecutor for UpgradeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let root = find_project_root(".")?;
+        let old_config =
+            fs::read_to_string(root.join(CONFIG_FILE)).context("Failed to read config file")?;
+        let new_config = upgrade_config(&old_config)?;
+        let old_types = fs::read_to_string(root.join(TYPES_FILE)).unwrap_or_default();
+        let new_types = TypesFile {}.render()?;
+
+        let files = [
+            (CONFIG_FILE, old_config, new_config),
+            (TYPES_FILE, old_types, new_types),
+        ];
+        let mut changed = false;
+        for (name, old, new) in files.iter().filter(|(_, old, new)| old != new) {
+            changed = true;
+            if self.dry_run {
+                print_diff(name, old, new);
+            } else {
+                fs::write(root.join(name), new)?;
+                println!("{} {name}", "Upgraded".green());
+            }
+        }
+
+        if !changed {
+            println!("Project is up to date (config version {CONFIG_VERSION})");
+        }
+        Ok(())
+    }
+}
+
+/// 把配置迁移到 `CONFIG_VERSION`，已经是最新版本时原样返回
+fn upgrade_config(content: &str) -> Result<String> {
+    let config: Mapping = serde_yaml::from_str(content)?;
+    let version = config
+        .get("version")
+        .and_then(Value::as_u64)
+        .unwrap_or_default() as u32;
+    if version > CONFIG_VERSION {
+        anyhow::bail!(
+            "config version {version} is newer than supported version {CONFIG_VERSION}, please upgrade dino"
+        );
+    }
+    if version == CONFIG_VERSION {
+        return Ok(content.to_string());
+    }
+
+    let mut content = content.to_string();
+    for (target, migrate) in MIGRATIONS.iter().filter(|(v, _)| *v > version) {
+        content = set_version(&migrate(&content), *target);
+    }
+    serde_yaml::from_str::<Mapping>(&content).context("upgraded config is not valid YAML")?;
+    Ok(content)
+}
+
+/// 修改顶层的 `version`，没有时加在文档开头（`---` 之后）
+fn set_version(content: &str, version: u32) -> String {
+    let line = format!("version: {version}\n");
+    let mut lines: Vec<_> = content.split_inclusive('\n').map(String::from).collect();
+    if let Some(old) = lines.iter_mut().find(|l| l.starts_with("version:")) {
+        *old = line;
+    } else {
+        let at = usize::from(lines.first().is_some_and(|l| l.trim_end() == "---"));
+        lines.insert(at, line);
+    }
+    lines.concat()
+}
+
+/// v1: 引入 version 字段，method 统一为大写
+fn migrate_v1(content: &str) -> String {
+    content
+        .split_inclusive('\n')
+        .map(uppercase_methods)
+        .collect()
+}
+
+/// 把一行中 `method:` 的值改为大写，块和流式写法都支持，注释不变
+fn uppercase_methods(line: &str) -> String {
+    let (code, comment) = match line.find(" #") {
+        Some(i) => line.split_at(i),
+        None => (line, ""),
+    };
+    let mut out = String::with_capacity(line.len());
+    let mut rest = code;
+    while let Some(i) = rest.find("method:") {
+        let key_start = rest[..i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| " {,-".contains(c));
+        let (head, tail) = rest.split_at(i + "method:".len());
+        out.push_str(head);
+        let end = tail.find([',', '}']).unwrap_or(tail.len());
+        match key_start {
+            true => out.push_str(&tail[..end].to_ascii_uppercase()),
+            false => out.push_str(&tail[..end]),
+        }
+        rest = &tail[end..];
+    }
+    out.push_str(rest);
+    out.push_str(comment);
+    out
+}
+
+fn print_diff(name: &str, old: &str, new: &str) {
+    println!("{}", format!("--- {name}\n+++ {name} (upgraded)").bold());
+    for change in TextDiff::from_lines(old, new).iter_all_changes() {
+        let line = match change.tag() {
+            ChangeTag::Delete => format!("-{change}").red(),
+            ChangeTag::Insert => format!("+{change}").green(),
+            ChangeTag::Equal => format!(" {change}").normal(),
+        };
+        print!("{line}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dino_server::ProjectConfig;
+
+    #[test]
+    fn upgrade_config_should_work() -> Result<()> {
+        let old = r#"---
+# legacy project
+name: legacy
+routes:
+  # say hello
+  /api/hello:
+    - method: get # lower case
+      handler: hello
+  /api/bye: [{ method: "post", handler: bye }]
+"#;
+        let new = upgrade_config(old)?;
+        assert_eq!(
+            new,
+            r#"---
+version: 1
+# legacy project
+name: legacy
+routes:
+  # say hello
+  /api/hello:
+    - method: GET # lower case
+      handler: hello
+  /api/bye: [{ method: "POST", handler: bye }]
+"#
+        );
+
+        let config: ProjectConfig = serde_yaml::from_str(&new)?;
+        assert_eq!(config.version, CONFIG_VERSION);
+        // 已经是最新版本则不做修改
+        assert_eq!(upgrade_config(&new)?, new);
+
+        // 已有的 version 字段原地修改
+        let old = "name: legacy\nversion: 0\nroutes: {}\n";
+        assert_eq!(
+            upgrade_config(old)?,
+            "name: legacy\nversion: 1\nroutes: {}\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use std::io::{BufRead, BufReader};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use serde_json::Value;
+
+use crate::{DEFAULT_PORT, credentials::Credentials, discovery::DevServer};
+
+/// 连接远程 dino server 管理接口的参数，未指定时使用 `dino login` 保存的 profile
+#[derive(Debug, Clone, Args)]
+pub struct RemoteOpts {
+    /// Server URL
+    #[arg(long, env = "DINO_SERVER", global = true)]
+    pub server: Option<String>,
+    /// Admin API token
+    #[arg(long, env = "DINO_TOKEN", global = true, hide_env_values = true)]
+    pub token: Option<String>,
+    /// Profile saved by `dino login`, defaults to the default profile
+    #[arg(long, env = "DINO_PROFILE", global = true)]
+    pub profile: Option<String>,
+}
+
+/// 管理接口的 HTTP 客户端
+pub struct AdminClient {
+    server: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl RemoteOpts {
+    /// `--server`/`--token` 优先，其次是 profile，最后是本地的 dev server，
+    /// 端口来自当前项目的 `.dino/dev.json`，没有时使用默认端口
+    pub fn client(&self) -> Result<AdminClient> {
+        let credentials = Credentials::load()?;
+        let profile = credentials.profile(self.profile.as_deref());
+        if let Some(name) = &self.profile
+            && profile.is_none()
+        {
+            anyhow::bail!("profile {name} not found, run `dino login --profile {name}` first");
+        }
+
+        // 显式指定了其它 server 时不使用 profile 的 token
+        let profile = profile.filter(|(_, p)| self.server.as_ref().is_none_or(|s| *s == p.server));
+        let server = match (&self.server, profile) {
+            (Some(server), _) => server.clone(),
+            (None, Some((_, p))) => p.server.clone(),
+            (None, None) => DevServer::find()
+                .map_or_else(|| format!("http://localhost:{DEFAULT_PORT}"), |d| d.url()),
+        };
+        let token = match (&self.token, profile) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some((name, p))) => p.token(name)?,
+            (None, None) => None,
+        };
+        Ok(AdminClient::new(server, token))
+    }
+}
+
+impl AdminClient {
+    pub fn new(server: impl Into<String>, token: Option<String>) -> Self {
+        Self {
+            server: server.into().trim_end_matches('/').to_string(),
+            token,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    pub fn get(&self, path: &str) -> Result<Value> {
+        self.send("GET", path, None)
+    }
+
+    pub fn post(&self, path: &str, body: Option<Value>) -> Result<Value> {
+        self.send("POST", path, body)
+    }
+
+    pub fn put(&self, path: &str, body: Value) -> Result<Value> {
+        self.send("PUT", path, Some(body))
+    }
+
+    pub fn delete(&self, path: &str) -> Result<Value> {
+        self.send("DELETE", path, None)
+    }
+
+    /// POST 并按行读取流式响应
+    pub fn stream(&self, path: &str, body: Value) -> Result<impl BufRead + use<>> {
+        let resp = self.request("POST", path, Some(body))?;
+        Ok(BufReader::new(resp.into_reader()))
+    }
+
+    fn send(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value> {
+        Ok(self.request(method, path, body)?.into_json()?)
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> Result<ureq::Response> {
+        let url = format!("{}{}{path}", self.server, dino_server::ADMIN_PREFIX);
+        let mut req = self.agent.request(method, &url);
+        if let Some(token) = &self.token {
+            req = req.set("Authorization", &format!("Bearer {token}"));
+        }
+        let ret = match body {
+            Some(body) => req.send_json(body),
+            None => req.call(),
+        };
+        match ret {
+            Ok(resp) => Ok(resp),
+            Err(ureq::Error::Status(status, resp)) => {
+                let msg = resp.into_string().unwrap_or_default();
+                anyhow::bail!("{method} {url} failed ({status}): {msg}")
+            }
+            Err(e) => Err(e).with_context(|| format!("failed to connect to {}", self.server)),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// 本地 dev server 的控制 socket，相对于项目根目录
+pub const CONTROL_SOCKET: &str = ".dino/control.sock";
+
+/// 发送给 dev server 的命令，每行一个 JSON
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// 替换代码和配置
+    Swap {
+        code: String,
+        config: String,
+        actor: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    pub message: String,
+}
+
+pub fn socket_path(root: &Path) -> PathBuf {
+    root.join(CONTROL_SOCKET)
+}
+
+impl ControlResponse {
+    fn from_result(ret: Result<String>) -> Self {
+        match ret {
+            Ok(message) => Self { ok: true, message },
+            Err(e) => Self {
+                ok: false,
+                message: format!("{e:#}"),
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+        path::Path,
+    };
+
+    use anyhow::{Context, Result};
+    use dino_server::{AppState, ProjectConfig};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader},
+        net::UnixListener,
+    };
+    use tracing::{info, warn};
+
+    use super::{ControlRequest, ControlResponse, socket_path};
+
+    /// 监听控制 socket，处理 `dino reload` 等命令
+    pub async fn serve(root: &Path, host: &str) -> Result<()> {
+        let path = socket_path(root);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // 上次异常退出可能留下 socket 文件
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("failed to bind control socket {}", path.display()))?;
+        info!("Control socket: {}", path.display());
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = AsyncBufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await? {
+                let resp = ControlResponse::from_result(handle(&line, host));
+                if !resp.ok {
+                    warn!("control command failed: {}", resp.message);
+                }
+                let mut out = serde_json::to_string(&resp)?;
+                out.push('\n');
+                writer.write_all(out.as_bytes()).await?;
+            }
+        }
+    }
+
+    fn handle(line: &str, host: &str) -> Result<String> {
+        let state = AppState::get_current().context("server is not ready")?;
+        match serde_json::from_str(line)? {
+            ControlRequest::Swap {
+                code,
+                config,
+                actor,
+            } => {
+                let config: ProjectConfig = config.parse()?;
+                state.swap_with_config(host, code, &config, &actor)?;
+                info!("code and config reloaded by {actor}");
+                Ok("reloaded".into())
+            }
+        }
+    }
+
+    /// 向运行中的 dev server 发送命令
+    pub fn send(root: &Path, req: &ControlRequest) -> Result<ControlResponse> {
+        let path = socket_path(root);
+        let mut stream = UnixStream::connect(&path).with_context(|| {
+            format!(
+                "failed to connect to {}, is `dino run` running?",
+                path.display()
+            )
+        })?;
+        writeln!(stream, "{}", serde_json::to_string(req)?)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::path::Path;
+
+    use anyhow::Result;
+
+    use super::{ControlRequest, ControlResponse};
+
+    pub async fn serve(_root: &Path, _host: &str) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn send(_root: &Path, _req: &ControlRequest) -> Result<ControlResponse> {
+        anyhow::bail!("the control socket is only supported on unix")
+    }
+}
+
+pub use imp::{send, serve};
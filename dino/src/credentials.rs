@@ -0,0 +1,151 @@
+use std::{collections::BTreeMap, env, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// 覆盖配置目录的环境变量
+pub const CONFIG_DIR_ENV: &str = "DINO_CONFIG_DIR";
+const CREDENTIALS_FILE: &str = "credentials.yml";
+const KEYRING_SERVICE: &str = "dino";
+
+/// 一个远程 server 的登录信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub server: String,
+    /// token 保存在系统 keyring 时为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub keyring: bool,
+}
+
+/// 保存在配置目录中的所有 profile
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Credentials {
+    /// 未指定 `--profile` 时使用的 profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+pub fn config_dir() -> Result<PathBuf> {
+    if let Ok(dir) = env::var(CONFIG_DIR_ENV) {
+        return Ok(dir.into());
+    }
+    Ok(dirs::config_dir()
+        .context("can not find the config directory")?
+        .join("dino"))
+}
+
+impl Credentials {
+    pub fn load() -> Result<Self> {
+        let path = config_dir()?.join(CREDENTIALS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_yaml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = config_dir()?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(CREDENTIALS_FILE);
+        fs::write(&path, serde_yaml::to_string(self)?)?;
+        // token 可能明文保存，只允许当前用户读写
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// 返回指定的 profile，未指定时使用默认 profile
+    pub fn profile(&self, name: Option<&str>) -> Option<(&str, &Profile)> {
+        let name = name.or(self.default.as_deref())?;
+        self.profiles
+            .get_key_value(name)
+            .map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// 保存 profile，token 在 `profile.keyring` 为 true 时写入系统 keyring
+    pub fn insert(&mut self, name: &str, mut profile: Profile, token: &str) -> Result<()> {
+        if profile.keyring {
+            keyring_entry(name)?.set_password(token)?;
+            profile.token = None;
+        } else {
+            profile.token = Some(token.to_string());
+        }
+        if self.default.is_none() {
+            self.default = Some(name.to_string());
+        }
+        self.profiles.insert(name.to_string(), profile);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Option<Profile>> {
+        let profile = self.profiles.remove(name);
+        if let Some(profile) = &profile
+            && profile.keyring
+        {
+            // keyring 中可能已经没有该条目，忽略错误
+            let _ = keyring_entry(name)?.delete_credential();
+        }
+        if self.default.as_deref() == Some(name) {
+            self.default = self.profiles.keys().next().cloned();
+        }
+        Ok(profile)
+    }
+}
+
+impl Profile {
+    /// 返回 token，必要时从系统 keyring 读取
+    pub fn token(&self, name: &str) -> Result<Option<String>> {
+        if !self.keyring {
+            return Ok(self.token.clone());
+        }
+        let token = keyring_entry(name)?
+            .get_password()
+            .with_context(|| format!("failed to read token of profile {name} from keyring"))?;
+        Ok(Some(token))
+    }
+}
+
+fn keyring_entry(profile: &str) -> Result<keyring::Entry> {
+    Ok(keyring::Entry::new(KEYRING_SERVICE, profile)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_should_manage_profiles() -> Result<()> {
+        let mut credentials = Credentials::default();
+        let profile = |server: &str| Profile {
+            server: server.into(),
+            token: None,
+            keyring: false,
+        };
+        credentials.insert("prod", profile("https://prod"), "t1")?;
+        credentials.insert("dev", profile("http://localhost:8888"), "t2")?;
+
+        let (name, prod) = credentials.profile(None).unwrap();
+        assert_eq!(name, "prod");
+        assert_eq!(prod.token("prod")?.as_deref(), Some("t1"));
+        assert_eq!(
+            credentials.profile(Some("dev")).unwrap().1.server,
+            "http://localhost:8888"
+        );
+        assert!(credentials.profile(Some("nope")).is_none());
+
+        let yaml = serde_yaml::to_string(&credentials)?;
+        let credentials: Credentials = serde_yaml::from_str(&yaml)?;
+        let mut credentials = credentials;
+        credentials.remove("prod")?;
+        assert_eq!(credentials.default.as_deref(), Some("dev"));
+        Ok(())
+    }
+}
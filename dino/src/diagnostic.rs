@@ -0,0 +1,219 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use colored::Colorize;
+use serde_json::{Value, json};
+
+/// 面向用户的错误码，输出形如 `error[D002]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Unknown,
+    ConfigNotFound,
+    ConfigInvalid,
+    EntryNotFound,
+    BundleFailed,
+    InitFailed,
+    ServerFailed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// 带错误码、出错位置和修复建议的错误
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub code: ErrorCode,
+    pub message: String,
+    pub location: Option<Location>,
+    pub help: Option<String>,
+    pub causes: Vec<String>,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Unknown => "D000",
+            ErrorCode::ConfigNotFound => "D001",
+            ErrorCode::ConfigInvalid => "D002",
+            ErrorCode::EntryNotFound => "D003",
+            ErrorCode::BundleFailed => "D004",
+            ErrorCode::InitFailed => "D005",
+            ErrorCode::ServerFailed => "D006",
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            location: None,
+            help: None,
+            causes: vec![],
+        }
+    }
+
+    pub fn with_file(mut self, file: impl AsRef<Path>) -> Self {
+        self.location = Some(Location {
+            file: file.as_ref().to_path_buf(),
+            line: 0,
+            column: 0,
+        });
+        self
+    }
+
+    pub fn with_location(mut self, file: impl AsRef<Path>, line: usize, column: usize) -> Self {
+        self.location = Some(Location {
+            file: file.as_ref().to_path_buf(),
+            line,
+            column,
+        });
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// 记录底层错误链，渲染时作为 `caused by` 输出
+    pub fn with_source(mut self, err: &anyhow::Error) -> Self {
+        self.causes = err.chain().map(|e| e.to_string()).collect();
+        self
+    }
+
+    /// 配置文件解析错误，尽可能从 serde_yaml 的错误中取出行列号
+    pub fn config_invalid(file: impl AsRef<Path>, err: &anyhow::Error) -> Self {
+        let diag = Self::new(ErrorCode::ConfigInvalid, "invalid project config")
+            .with_help("check the config against the template generated by `dino init`")
+            .with_source(err);
+        let location = err
+            .chain()
+            .find_map(|e| e.downcast_ref::<serde_yaml::Error>())
+            .and_then(|e| e.location());
+        match location {
+            Some(loc) => diag.with_location(file, loc.line(), loc.column()),
+            None => diag.with_file(file),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// `--output json` 时输出的错误
+    pub fn to_json(&self) -> Value {
+        let location = self
+            .location
+            .as_ref()
+            .map(|loc| json!({ "file": loc.file, "line": loc.line, "column": loc.column }));
+        json!({
+            "code": self.code.as_str(),
+            "message": self.message,
+            "location": location,
+            "help": self.help,
+            "causes": self.causes,
+        })
+    }
+}
+
+impl From<anyhow::Error> for Diagnostic {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Diagnostic>() {
+            Ok(diag) => diag,
+            Err(err) => Diagnostic::new(ErrorCode::Unknown, err.to_string()).with_source(&err),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {}",
+            format!("error[{}]", self.code.as_str()).red().bold(),
+            self.message.bold()
+        )?;
+
+        if let Some(loc) = &self.location {
+            if loc.line == 0 {
+                writeln!(f, "  {} {}", "-->".blue(), loc.file.display())?;
+            } else {
+                writeln!(
+                    f,
+                    "  {} {}:{}:{}",
+                    "-->".blue(),
+                    loc.file.display(),
+                    loc.line,
+                    loc.column
+                )?;
+                let line = fs::read_to_string(&loc.file)
+                    .ok()
+                    .and_then(|s| s.lines().nth(loc.line - 1).map(|l| l.to_string()));
+                if let Some(line) = line {
+                    let gutter = loc.line.to_string();
+                    let pad = " ".repeat(gutter.len());
+                    writeln!(f, "  {pad} {}", "|".blue())?;
+                    writeln!(f, "  {} {} {line}", gutter.blue(), "|".blue())?;
+                    writeln!(
+                        f,
+                        "  {pad} {} {}{}",
+                        "|".blue(),
+                        " ".repeat(loc.column.saturating_sub(1)),
+                        "^".red().bold()
+                    )?;
+                }
+            }
+        }
+
+        // 第一条通常和 message 重复，跳过
+        for cause in self.causes.iter().filter(|c| **c != self.message) {
+            writeln!(f, "  {} {cause}", "caused by:".yellow())?;
+        }
+
+        if let Some(help) = &self.help {
+            writeln!(f, "  {} {help}", "= help:".cyan())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dino_server::ProjectConfig;
+
+    #[test]
+    fn config_invalid_should_have_location() {
+        colored::control::set_override(false);
+        let err = ProjectConfig::load("fixtures/invalid_config.yml").unwrap_err();
+        let diag = Diagnostic::config_invalid("fixtures/invalid_config.yml", &err);
+        assert_eq!(diag.code, ErrorCode::ConfigInvalid);
+        let loc = diag.location.as_ref().unwrap();
+        assert_eq!(loc.line, 5);
+
+        let output = diag.to_string();
+        assert!(output.starts_with("error[D002]: invalid project config"));
+        assert!(output.contains("--> fixtures/invalid_config.yml:5:"));
+        assert!(output.contains("= help:"));
+    }
+
+    #[test]
+    fn to_json_should_include_code_and_location() {
+        let diag = Diagnostic::new(ErrorCode::EntryNotFound, "entry file not found")
+            .with_location("main.ts", 3, 7)
+            .with_help("create main.ts");
+        let json = diag.to_json();
+        assert_eq!(json["code"], "D003");
+        assert_eq!(json["location"]["line"], 3);
+        assert_eq!(json["help"], "create main.ts");
+        assert_eq!(json["causes"], serde_json::json!([]));
+    }
+}
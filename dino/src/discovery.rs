@@ -0,0 +1,71 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::find_project_root;
+
+/// 运行中的 dev server 写入的发现文件，相对于项目根目录
+pub const DEV_FILE: &str = ".dino/dev.json";
+
+/// `dino run` 实际监听的端口等信息，供 `dino invoke` 和编辑器插件找到 dev server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DevServer {
+    pub port: u16,
+    pub pid: u32,
+    /// dev server 上的 tenant
+    pub hosts: Vec<String>,
+}
+
+impl DevServer {
+    pub fn url(&self) -> String {
+        format!("http://localhost:{}", self.port)
+    }
+
+    pub fn write(&self, root: &Path) -> Result<()> {
+        let path = root.join(DEV_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(DEV_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        let server = serde_json::from_str(&content)
+            .with_context(|| format!("invalid discovery file {}", path.display()))?;
+        Ok(Some(server))
+    }
+
+    /// 当前目录所在项目的 dev server，不在项目中或没有运行过时返回 None
+    pub fn find() -> Option<Self> {
+        let root = find_project_root(".").ok()?;
+        Self::load(&root).ok().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dev_server_should_round_trip() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("dino-dev-{}", std::process::id()));
+        assert_eq!(DevServer::load(&root)?, None);
+        let server = DevServer {
+            port: 41234,
+            pid: 42,
+            hosts: vec!["localhost".into()],
+        };
+        server.write(&root)?;
+        assert_eq!(DevServer::load(&root)?, Some(server.clone()));
+        assert_eq!(server.url(), "http://localhost:41234");
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
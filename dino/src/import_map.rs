@@ -0,0 +1,88 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use bundler::ImportMap;
+use serde_json::{Map, Value, json};
+
+/// 项目根目录下的 import map，由 `dino add` 维护，打包时用来解析裸模块名
+pub const IMPORT_MAP_FILE: &str = "import_map.json";
+
+/// 读取项目的 import map，`./` 开头的目标按项目根目录而不是当前目录解析
+pub fn load_import_map(root: &Path) -> Result<Option<ImportMap>> {
+    let path = root.join(IMPORT_MAP_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let mut json = read(&path)?;
+    if let Some(imports) = json["imports"].as_object_mut() {
+        for target in imports.values_mut() {
+            if let Some(rel) = target.as_str().and_then(|t| t.strip_prefix("./")) {
+                *target = root.join(rel).to_string_lossy().into_owned().into();
+            }
+        }
+    }
+    let map = ImportMap::parse_from_json(&json.to_string())
+        .with_context(|| format!("invalid {}", path.display()))?;
+    Ok(Some(map))
+}
+
+/// 添加或替换 `name` 的映射，保留文件中的其他内容
+pub fn add_import(root: &Path, name: &str, target: &str) -> Result<()> {
+    let path = root.join(IMPORT_MAP_FILE);
+    let mut json = match path.is_file() {
+        true => read(&path)?,
+        false => json!({ "imports": {} }),
+    };
+    let imports = json
+        .as_object_mut()
+        .context("import map must be an object")?
+        .entry("imports")
+        .or_insert_with(|| Value::Object(Map::new()));
+    imports
+        .as_object_mut()
+        .context("import map's \"imports\" must be an object")?
+        .insert(name.to_string(), target.into());
+    fs::write(&path, serde_json::to_string_pretty(&json)? + "\n")?;
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Value> {
+    let text = fs::read_to_string(path)?;
+    serde_json::from_str(&text).with_context(|| format!("invalid {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_map_should_add_and_resolve_from_root() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("dino-import-map-{}", std::process::id()));
+        fs::create_dir_all(&root)?;
+        assert!(load_import_map(&root)?.is_none());
+
+        add_import(
+            &root,
+            "@std/path",
+            "https://jsr.io/@std/path/1.0.8/mod.ts#sha256=ab",
+        )?;
+        add_import(&root, "greet", "./vendor/example.com/greet.js")?;
+        add_import(&root, "greet", "./vendor/example.com/greet2.js")?;
+
+        let map = load_import_map(&root)?.unwrap();
+        assert_eq!(
+            map.lookup("@std/path").as_deref(),
+            Some("https://jsr.io/@std/path/1.0.8/mod.ts#sha256=ab")
+        );
+        assert_eq!(
+            map.lookup("greet"),
+            Some(
+                root.join("vendor/example.com/greet2.js")
+                    .to_string_lossy()
+                    .into()
+            )
+        );
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
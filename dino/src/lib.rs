@@ -1,11 +1,21 @@
 use cli::*;
 use enum_dispatch::enum_dispatch;
 mod cli;
+mod client;
+mod control;
+mod credentials;
+mod diagnostic;
+mod discovery;
+mod import_map;
+mod output;
 mod utils;
 
 pub use cli::Opts;
+pub use diagnostic::{Diagnostic, ErrorCode};
+pub use output::{OutputFormat, OutputOpts};
 
 pub const BUILD_DIR: &str = ".build";
+pub const DEFAULT_PORT: u16 = 8888;
 
 #[allow(async_fn_in_trait)]
 #[enum_dispatch]
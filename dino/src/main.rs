@@ -1,9 +1,19 @@
 use clap::Parser;
-use dino::{CmdExecutor, Opts};
+use dino::{CmdExecutor, Diagnostic, Opts, OutputFormat};
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() {
     let opts = Opts::parse();
-    opts.cmd.execute().await?;
-    Ok(())
+    let output = opts.cmd.output();
+    if let Err(e) = opts.cmd.execute().await {
+        let diag = Diagnostic::from(e);
+        match output {
+            OutputFormat::Json => {
+                let ret = serde_json::json!({ "ok": false, "error": diag.to_json() });
+                println!("{ret:#}");
+            }
+            OutputFormat::Text => eprint!("{diag}"),
+        }
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use serde_json::Value;
+
+/// 命令结果的输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// `--output` 参数，json 模式下命令只向 stdout 输出一个 JSON 文档，
+/// 失败时为 `{"ok": false, "error": ...}`
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct OutputOpts {
+    /// Output format, `json` prints a single machine-readable result to stdout
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+}
+
+impl OutputOpts {
+    pub fn is_json(&self) -> bool {
+        self.output == OutputFormat::Json
+    }
+
+    /// 输出结果，`value` 为对象且没有 `ok` 时加上 `"ok": true`
+    pub fn print(&self, mut value: Value) -> Result<()> {
+        if let Some(obj) = value.as_object_mut() {
+            obj.entry("ok").or_insert(true.into());
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        Ok(())
+    }
+}
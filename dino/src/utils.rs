@@ -1,5 +1,7 @@
 use anyhow::Result;
-use bundler::{Options, run_bundle};
+use bundler::{
+    Capabilities, Defines, Options, Timings, emit_declarations, run_bundle, run_bundle_with_timings,
+};
 use std::{
     collections::BTreeSet,
     fs::{self, File},
@@ -7,11 +9,25 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use glob::glob;
+use dino_server::{HOST_GLOBALS, ProjectConfig, host_modules};
+use glob::{Pattern, glob};
+
+use crate::{
+    BUILD_DIR,
+    diagnostic::{Diagnostic, ErrorCode},
+    import_map::load_import_map,
+};
 
-use crate::BUILD_DIR;
+pub(crate) const CONFIG_FILE: &str = "config.yml";
+/// 入口导出的 handler 的类型声明，文件名固定，方便其他项目引用
+pub(crate) const DECLARATIONS_FILE: &str = "handlers.d.ts";
 
-pub fn get_files_with_exts(dir: &str, exts: &[&str]) -> Result<BTreeSet<PathBuf>> {
+pub fn get_files_with_exts(dir: impl AsRef<Path>, exts: &[&str]) -> Result<BTreeSet<PathBuf>> {
+    // 转义目录中的 glob 特殊字符，Windows 下统一使用 `/` 作为分隔符
+    let mut dir = Pattern::escape(&dir.as_ref().to_string_lossy());
+    if cfg!(windows) {
+        dir = dir.replace('\\', "/");
+    }
     let mut files = BTreeSet::new();
     for ext in exts {
         let rule = format!("{dir}/**/*.{ext}");
@@ -21,12 +37,21 @@ pub fn get_files_with_exts(dir: &str, exts: &[&str]) -> Result<BTreeSet<PathBuf>
     Ok(files)
 }
 
-pub fn calc_project_hash(dir: &str) -> Result<String> {
-    calc_hash_for_files(dir, &["ts", "js", "json"], 16)
+/// 只计算根目录下的 config.yml（只修改日志配置等也会重新生成）和 `.build`、隐藏目录以外的
+/// ts/js/json 文件，`.build` 中的产物不影响 hash
+pub fn calc_project_hash(dir: impl AsRef<Path>) -> Result<String> {
+    let dir = dir.as_ref();
+    let files = get_files_with_exts(dir, &["ts", "js", "json", "yml"])?
+        .into_iter()
+        .filter(|file| {
+            let json = file.extension() == Some("json".as_ref());
+            is_project_source(dir, file)
+                || json && file.strip_prefix(dir).is_ok_and(|rel| !is_hidden(rel))
+        });
+    hash_files(files, 16)
 }
 
-pub fn calc_hash_for_files(dir: &str, exts: &[&str], len: usize) -> Result<String> {
-    let files = get_files_with_exts(dir, exts)?;
+fn hash_files(files: impl IntoIterator<Item = PathBuf>, len: usize) -> Result<String> {
     let mut hasher = blake3::Hasher::new();
     for file in files {
         hasher.update_reader(File::open(file)?)?;
@@ -36,29 +61,194 @@ pub fn calc_hash_for_files(dir: &str, exts: &[&str], len: usize) -> Result<Strin
     Ok(hash)
 }
 
-pub fn build_project(dir: &str) -> Result<String> {
-    let hash = calc_project_hash(dir)?;
-    fs::create_dir_all(BUILD_DIR)?;
-    let filename = format!("{}/{}.mjs", BUILD_DIR, hash);
-    let dst = Path::new(&filename);
-    if dst.exists() {
-        return Ok(filename);
+/// 返回规范化后的绝对路径，去掉 Windows 下 canonicalize 产生的 `\\?\` 前缀
+pub fn normalize_path(path: impl AsRef<Path>) -> Result<PathBuf> {
+    let path = fs::canonicalize(path)?;
+    let path = match path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        Some(p) if !p.starts_with("UNC") => PathBuf::from(p),
+        _ => path,
+    };
+    Ok(path)
+}
+
+/// 从 `start` 开始向上查找包含 config.yml 的目录作为项目根目录
+pub fn find_project_root(start: impl AsRef<Path>) -> Result<PathBuf> {
+    let start = normalize_path(start)?;
+    match start
+        .ancestors()
+        .find(|dir| dir.join(CONFIG_FILE).is_file())
+    {
+        Some(root) => Ok(root.to_path_buf()),
+        None => Err(
+            Diagnostic::new(ErrorCode::ConfigNotFound, "project config not found")
+                .with_file(start.join(CONFIG_FILE))
+                .with_help("run `dino init` to create a project, or run dino inside a project")
+                .into(),
+        ),
     }
+}
 
-    let content = run_bundle("main.ts", &Options::default())?;
-    fs::write(dst, content)?;
+/// 查找 `dir` 下所有项目（包含 config.yml 的目录），跳过隐藏目录
+pub fn find_workspace_projects(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = normalize_path(dir)?;
+    let projects = get_files_with_exts(&dir, &["yml"])?
+        .into_iter()
+        .filter(|file| {
+            let rel = file.strip_prefix(&dir).unwrap_or(file);
+            rel.file_name() == Some(CONFIG_FILE.as_ref()) && !is_hidden(rel)
+        })
+        .filter_map(|file| file.parent().map(Path::to_path_buf))
+        .collect();
+    Ok(projects)
+}
 
-    let config = format!("{}/{}.yml", BUILD_DIR, hash);
-    let mut dst = File::create(&config)?;
-    let mut src = File::open("config.yml")?;
-    io::copy(&mut src, &mut dst)?;
+fn is_hidden(path: &Path) -> bool {
+    path.components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+}
 
-    Ok(filename)
+/// 文件变化是否需要重新打包：项目根目录下的 config.yml，或 .build 以外的 ts/js 文件
+pub fn is_project_source(root: &Path, path: &Path) -> bool {
+    let Ok(rel) = path.strip_prefix(root) else {
+        return false;
+    };
+    if rel == Path::new(CONFIG_FILE) {
+        return true;
+    }
+    let ext = rel.extension().unwrap_or_default();
+    !is_hidden(rel) && (ext == "ts" || ext == "js")
+}
+
+/// 打包 `root` 下的项目，返回生成的 .mjs 路径，配置文件会复制为同名 .yml
+pub fn build_project(root: &Path) -> Result<PathBuf> {
+    Ok(build_project_with(root, BuildSettings::default())?.0)
+}
+
+/// dino-server 的打包目标，`foo.ts` 存在 `foo.server.ts` 时使用后者
+pub const SERVER_TARGET: &str = "server";
+
+/// dino-server worker 的能力清单，打包时据此拒绝不支持的核心模块
+pub fn server_capabilities() -> Capabilities {
+    Capabilities {
+        runtime: "dino-server".into(),
+        core_modules: host_modules(),
+        globals: HOST_GLOBALS.iter().map(|g| g.to_string()).collect(),
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BuildSettings {
+    /// 返回打包各阶段的耗时
+    pub timings: bool,
+    /// 重新验证远程依赖，并忽略已有的打包产物
+    pub reload: bool,
+    /// `--define` 指定的编译期常量，覆盖环境变量中的 DINO_DEFINE_*
+    pub defines: Defines,
+    /// 同时生成 handler 的类型声明 `.build/handlers.d.ts`
+    pub dts: bool,
+}
+
+/// 按 `settings` 打包项目，产物已存在（未重新打包）时耗时为 None
+pub fn build_project_with(
+    root: &Path,
+    settings: BuildSettings,
+) -> Result<(PathBuf, Option<Timings>)> {
+    let config = check_project(root)?;
+
+    let mut defines = Defines::from_env();
+    defines.extend(settings.defines);
+    let hash = build_hash(root, &defines)?;
+    let build_dir = root.join(BUILD_DIR);
+    fs::create_dir_all(&build_dir)?;
+    let entry = root.join(&config.entry);
+    if settings.dts {
+        let declarations = emit_declarations(&entry.to_string_lossy()).map_err(|e| {
+            Diagnostic::new(ErrorCode::BundleFailed, "failed to generate declarations")
+                .with_file(&entry)
+                .with_source(&e)
+        })?;
+        fs::write(build_dir.join(DECLARATIONS_FILE), declarations)?;
+    }
+    let dst = build_dir.join(format!("{hash}.mjs"));
+    if dst.exists() && !settings.reload {
+        return Ok((dst, None));
+    }
+
+    let entry_str = entry.to_string_lossy();
+    let options = Options {
+        skip_cache: settings.reload,
+        capabilities: server_capabilities(),
+        defines,
+        target: Some(SERVER_TARGET.into()),
+        import_map: load_import_map(root)?,
+        ..Default::default()
+    };
+    let ret = if settings.timings {
+        run_bundle_with_timings(&entry_str, &options).map(|(c, t)| (c, Some(t)))
+    } else {
+        run_bundle(&entry_str, &options).map(|c| (c, None))
+    };
+    let (content, timings) = ret.map_err(|e| {
+        Diagnostic::new(ErrorCode::BundleFailed, "failed to bundle project")
+            .with_file(&entry)
+            .with_help("fix the errors above, imports must be relative paths, URLs or supported core modules")
+            .with_source(&e)
+    })?;
+    fs::write(&dst, content)?;
+
+    let mut config = File::create(dst.with_extension("yml"))?;
+    let mut src = File::open(root.join(CONFIG_FILE))?;
+    io::copy(&mut src, &mut config)?;
+
+    Ok((dst, timings))
+}
+
+/// 产物的文件名，不同的 defines 会生成不同的产物
+fn build_hash(root: &Path, defines: &Defines) -> Result<String> {
+    let hash = calc_project_hash(root)?;
+    if defines.is_empty() {
+        return Ok(hash);
+    }
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(hash.as_bytes());
+    for (key, value) in defines.sorted() {
+        hasher.update(format!("{key}={value}\n").as_bytes());
+    }
+    let mut hash = hasher.finalize().to_string();
+    hash.truncate(16);
+    Ok(hash)
+}
+
+/// 在打包前检查入口文件和配置文件
+pub(crate) fn check_project(root: &Path) -> Result<ProjectConfig, Diagnostic> {
+    let config = root.join(CONFIG_FILE);
+    if !config.is_file() {
+        return Err(
+            Diagnostic::new(ErrorCode::ConfigNotFound, "project config not found")
+                .with_file(&config)
+                .with_help("run `dino init` to create a project, or run dino in the project root"),
+        );
+    }
+    let config =
+        ProjectConfig::load(&config).map_err(|e| Diagnostic::config_invalid(&config, &e))?;
+    let entry = root.join(&config.entry);
+    if !entry.is_file() {
+        return Err(
+            Diagnostic::new(ErrorCode::EntryNotFound, "entry file not found")
+                .with_file(&entry)
+                .with_help(format!(
+                    "create `{}` exporting your handlers, or set `entry` in {CONFIG_FILE}",
+                    config.entry
+                )),
+        );
+    }
+    Ok(config)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use dino_server::engine::Engine;
 
     #[test]
     fn get_files_with_exts_should_work() -> Result<()> {
@@ -76,8 +266,124 @@ mod tests {
     }
 
     #[test]
-    fn calc_hash_for_files_should_work() -> Result<()> {
-        let hash = calc_hash_for_files("fixtures/prj", &["ts", "js", "json"], 12)?;
+    fn is_project_source_should_work() {
+        let root = Path::new("/prj");
+        assert!(is_project_source(root, Path::new("/prj/config.yml")));
+        assert!(is_project_source(root, Path::new("/prj/main.ts")));
+        assert!(is_project_source(root, Path::new("/prj/lib/util.js")));
+        assert!(!is_project_source(root, Path::new("/prj/lib/config.yml")));
+        assert!(!is_project_source(root, Path::new("/prj/.build/abc.mjs")));
+        assert!(!is_project_source(root, Path::new("/prj/.cache/lib.ts")));
+        assert!(!is_project_source(root, Path::new("/other/main.ts")));
+    }
+
+    #[test]
+    fn import_meta_should_work_in_worker() -> Result<()> {
+        let options = Options {
+            capabilities: server_capabilities(),
+            ..Default::default()
+        };
+        let code = run_bundle("fixtures/import_meta/main.ts", &options)?;
+        let worker = dino_server::engine::JsWorker::try_new(&code)?;
+        let ret: serde_json::Value = serde_json::from_str(&worker.eval("handlers.meta()")?)?;
+
+        let dir = normalize_path("fixtures/import_meta")?;
+        let url = |path: &str| format!("file://{}/{path}", dir.display());
+        assert_eq!(ret["main"], true);
+        assert_eq!(ret["url"], url("main.ts"));
+        assert_eq!(ret["lib"]["main"], false);
+        assert_eq!(ret["lib"]["sibling"], url("lib/data.json"));
+        assert_eq!(ret["lib"]["parent"], url("static/logo.png"));
+        assert_eq!(ret["lib"]["absolute"], "https://example.com/a.js");
+        Ok(())
+    }
+
+    #[test]
+    fn commonjs_modules_should_work_in_worker() -> Result<()> {
+        let options = Options {
+            capabilities: server_capabilities(),
+            ..Default::default()
+        };
+        let code = run_bundle("fixtures/cjs/main.ts", &options)?;
+        let worker = dino_server::engine::JsWorker::try_new(&code)?;
+        let ret = worker.eval("handlers.hello()")?;
+        assert_eq!(
+            ret,
+            r#"{"message":"HELLO, dino!","version":"1.0","self":true}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn node_builtins_should_work_in_worker() -> Result<()> {
+        let options = Options {
+            capabilities: server_capabilities(),
+            ..Default::default()
+        };
+        let code = run_bundle("fixtures/node/main.ts", &options)?;
+        let worker = dino_server::engine::JsWorker::try_new(&code)?;
+        let ret: serde_json::Value = serde_json::from_str(&worker.eval("handlers.compat()")?)?;
+        assert_eq!(
+            ret,
+            serde_json::json!({
+                "buffer": "aMOpbGxv",
+                "utf8": "héllo",
+                "hash": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+                "uuid": 36,
+                "path": "/a/c/d.ts",
+                "ext": ".ts",
+                "qs": { "a": ["1", "2"], "b": " " },
+                "qs2": "a=1&a=2&b=x%20y",
+                "url": "https://example.com/b/c?x=1&y=z+w#top",
+                "host": "example.com",
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn find_workspace_projects_should_work() -> Result<()> {
+        let projects = find_workspace_projects("fixtures")?;
+        assert_eq!(projects.len(), 1);
+        assert!(projects[0].ends_with("dino/fixtures/prj"));
+        Ok(())
+    }
+
+    #[test]
+    fn find_project_root_should_work() -> Result<()> {
+        let root = find_project_root("fixtures/prj/test1")?;
+        assert!(root.ends_with("dino/fixtures/prj"));
+        Ok(())
+    }
+
+    #[test]
+    fn calc_project_hash_should_ignore_build_output() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("dino-hash-{}", std::process::id()));
+        fs::create_dir_all(root.join("lib"))?;
+        fs::write(root.join(CONFIG_FILE), "name: demo\n")?;
+        fs::write(root.join("main.ts"), "export const a = 1;")?;
+        let hash = calc_project_hash(&root)?;
+
+        // 打包产物和隐藏目录中的文件不影响 hash
+        fs::create_dir_all(root.join(BUILD_DIR))?;
+        fs::write(
+            root.join(BUILD_DIR).join(format!("{hash}.yml")),
+            "name: demo\n",
+        )?;
+        fs::write(root.join(BUILD_DIR).join(format!("{hash}.mjs")), "")?;
+        fs::write(root.join("lib/config.yml"), "name: other\n")?;
+        assert_eq!(calc_project_hash(&root)?, hash);
+
+        fs::write(root.join(CONFIG_FILE), "name: demo\nlogging: {}\n")?;
+        assert_ne!(calc_project_hash(&root)?, hash);
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn hash_files_should_work() -> Result<()> {
+        let files = get_files_with_exts("fixtures/prj", &["ts", "js", "json"])?;
+        let hash = hash_files(files, 12)?;
         assert_eq!(hash, "af1349b9f5f9");
         Ok(())
     }
@@ -0,0 +1,23 @@
+#![no_main]
+
+use std::sync::LazyLock;
+
+use arbitrary::Arbitrary;
+use dino_server::{SwappableAppRouter, fuzzing};
+use libfuzzer_sys::fuzz_target;
+
+static ROUTER: LazyLock<SwappableAppRouter> = LazyLock::new(|| fuzzing::router().unwrap());
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    method: &'a [u8],
+    uri: &'a str,
+    body: &'a [u8],
+}
+
+fuzz_target!(|input: Input| {
+    if let Ok(req) = fuzzing::assemble(&ROUTER, input.method, input.uri, input.body) {
+        // 组装出的 Req 一定能传给 handler
+        fuzzing::marshal_req(req).unwrap();
+    }
+});
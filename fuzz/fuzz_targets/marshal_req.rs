@@ -0,0 +1,32 @@
+#![no_main]
+
+use std::collections::HashMap;
+
+use arbitrary::Arbitrary;
+use dino_server::{engine::Req, fuzzing};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    headers: HashMap<String, String>,
+    query: HashMap<String, String>,
+    params: HashMap<String, String>,
+    body: Option<String>,
+    url: String,
+    method: String,
+}
+
+fuzz_target!(|input: Input| {
+    let req = Req::builder()
+        .headers(input.headers)
+        .query(input.query)
+        .params(input.params)
+        .body(input.body.clone())
+        .url(input.url.clone())
+        .method(input.method.clone())
+        .build();
+    let json = fuzzing::marshal_req(req).unwrap();
+    // 字符串字段在 JS 中保持不变
+    let req: Req = serde_json::from_str(&json).unwrap();
+    assert_eq!((req.url, req.method, req.body), (input.url, input.method, input.body));
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use dino_server::fuzzing;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|json: &str| {
+    let _ = fuzzing::marshal_resp(json);
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use std::sync::LazyLock;
+
+use arbitrary::Arbitrary;
+use dino_server::{SwappableAppRouter, fuzzing};
+use libfuzzer_sys::fuzz_target;
+
+static ROUTER: LazyLock<SwappableAppRouter> = LazyLock::new(|| fuzzing::router().unwrap());
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    method: &'a [u8],
+    path: &'a str,
+}
+
+fuzz_target!(|input: Input| {
+    let _ = fuzzing::match_route(&ROUTER, input.method, input.path);
+});